@@ -12,5 +12,5 @@ fn main() {
     let module = kicad_parse_gen::read_module(&name).unwrap();
     let s = kicad_parse_gen::footprint::module_to_string(&module, 0).unwrap();
     println!("{}", module.name);
-    println!("{}", s)
+    print!("{}", s)
 }
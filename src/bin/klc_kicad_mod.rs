@@ -9,6 +9,18 @@ use std::path::PathBuf;
 use std::env;
 
 use kicad::checkfix::CheckFix;
+use kicad::footprint::library::FootprintLibrary;
+use kicad::footprint::Module;
+
+fn check(module: &Module, config: &kicad::checkfix::Config) {
+    let checkres = module.check(config);
+    if !checkres.is_empty() {
+        info!("Module {}", module.name);
+        for v in checkres {
+            v.dump_on_logger(1);
+        }
+    }
+}
 
 fn main() {
     env::set_var("RUST_LOG", "debug");
@@ -18,13 +30,17 @@ fn main() {
     args.next();
     let name = args.next().unwrap();
     let name = PathBuf::from(name);
-    let module = kicad::read_module(&name).unwrap();
     let config = kicad::checkfix::Config::klc();
-    let checkres = module.check(&config);
-    if !checkres.is_empty() {
-        info!("Module {}", module.name);
-        for v in checkres {
-            v.dump_on_logger(1);
+    // a .pretty directory checks all of its footprints
+    if name.is_dir() {
+        let library = FootprintLibrary::open(&name).unwrap();
+        for (file, module) in library.check_names().unwrap() {
+            info!("Module {} is in {}.kicad_mod", module, file);
+        }
+        for module in library.modules().unwrap() {
+            check(&module.unwrap(), &config)
         }
+    } else {
+        check(&kicad::read_module(&name).unwrap(), &config)
     }
 }
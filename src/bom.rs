@@ -0,0 +1,297 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+// bill of materials generation from a schematic
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use reference_ord;
+use schematic::{Component, Schematic};
+use symbol_lib::SymbolLib;
+use KicadError;
+
+// fields that are not grouped on as custom fields
+const STANDARD_FIELDS: [&str; 4] = ["Reference", "Value", "Footprint", "UserDocLink"];
+
+// components are grouped on value, footprint and custom fields
+type GroupKey = (String, String, Vec<(String, String)>);
+
+/// a line of a bill of materials: a group of identical components
+#[derive(Debug, Clone, PartialEq)]
+pub struct BomLine {
+    /// the references of the components, ordered
+    pub references: Vec<String>,
+    /// value
+    pub value: String,
+    /// footprint
+    pub footprint: String,
+    /// custom fields
+    pub fields: BTreeMap<String, String>,
+}
+
+impl BomLine {
+    /// the number of components in the line
+    pub fn quantity(&self) -> usize {
+        self.references.len()
+    }
+
+    fn prefix(&self) -> String {
+        match self.references.first() {
+            Some(r) => r.chars().take_while(|c| c.is_alphabetic()).collect(),
+            None => String::new(),
+        }
+    }
+}
+
+/// a bill of materials
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bom {
+    /// the lines, ordered by their first reference
+    pub lines: Vec<BomLine>,
+}
+
+impl Bom {
+    /// create a bill of materials for a list of components; components with
+    /// a reference starting with `#` like power symbols are left out
+    pub fn new(components: &[Component]) -> Bom {
+        let mut groups: BTreeMap<GroupKey, BomLine> = BTreeMap::new();
+        for component in components {
+            if component.reference.starts_with('#') {
+                continue;
+            }
+            let hash = component.fields_hash();
+            let value = hash.get("Value").cloned().unwrap_or_default();
+            let footprint = hash.get("Footprint").cloned().unwrap_or_default();
+            let fields: BTreeMap<String, String> = hash.into_iter()
+                .filter(|(name, _)| !STANDARD_FIELDS.contains(&&name[..]))
+                .collect();
+            let key = (
+                value.clone(),
+                footprint.clone(),
+                fields.clone().into_iter().collect(),
+            );
+            let line = groups.entry(key).or_insert_with(|| BomLine {
+                references: vec![],
+                value,
+                footprint,
+                fields,
+            });
+            // units of a multi-unit component share the reference
+            if !line.references.contains(&component.reference) {
+                line.references.push(component.reference.clone())
+            }
+        }
+        let mut lines: Vec<BomLine> = groups.into_values().collect();
+        for line in &mut lines {
+            line.references.sort_by_key(|r| (reference_ord(r), r.clone()));
+        }
+        lines.sort_by_key(|l| (reference_ord(&l.references[0]), l.references[0].clone()));
+        Bom { lines }
+    }
+
+    /// create a bill of materials for a list of components, also leaving out
+    /// the components that are power symbols in the symbol library
+    pub fn new_with_symbols(components: &[Component], lib: &SymbolLib) -> Bom {
+        let components: Vec<Component> = components
+            .iter()
+            .filter(|c| {
+                // the library name prefix is replaced by an underscore in cache libraries
+                let cache_name = c.name.replace(':', "_");
+                let power = lib.find(|s| s.name == c.name || s.name == cache_name)
+                    .map(|s| s.is_power)
+                    .unwrap_or(false);
+                !power
+            })
+            .cloned()
+            .collect();
+        Bom::new(&components)
+    }
+
+    /// create a bill of materials for a schematic including its sub-sheets;
+    /// a sheet used more than once counts its components for every instance
+    pub fn from_schematic(schematic: &Schematic) -> Result<Bom, KicadError> {
+        let components: Vec<Component> = schematic
+            .component_instances()?
+            .into_iter()
+            .map(|instance| {
+                let mut c = instance.component;
+                c.update_reference(instance.reference);
+                c
+            })
+            .collect();
+        Ok(Bom::new(&components))
+    }
+
+    /// the names of the custom fields used in the bill of materials
+    pub fn field_names(&self) -> Vec<String> {
+        let mut v: Vec<String> = vec![];
+        for line in &self.lines {
+            for name in line.fields.keys() {
+                if !v.contains(name) {
+                    v.push(name.clone())
+                }
+            }
+        }
+        v.sort();
+        v
+    }
+
+    /// generate a CSV bill of materials
+    pub fn to_csv(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+        let names = self.field_names();
+        let mut s = String::new();
+        let mut header = vec!["Quantity", "References", "Value", "Footprint"]
+            .into_iter()
+            .map(quote)
+            .collect::<Vec<_>>();
+        header.extend(names.iter().map(|n| quote(n)));
+        writeln!(s, "{}", header.join(",")).unwrap();
+        for line in &self.lines {
+            let mut v = vec![
+                format!("{}", line.quantity()),
+                quote(&line.references.join(" ")),
+                quote(&line.value),
+                quote(&line.footprint),
+            ];
+            for name in &names {
+                v.push(quote(line.fields.get(name).map(|x| &x[..]).unwrap_or("")))
+            }
+            writeln!(s, "{}", v.join(",")).unwrap();
+        }
+        s
+    }
+
+    /// generate a JSON bill of materials
+    pub fn to_json(&self) -> String {
+        let mut s = String::new();
+        s.push_str("[\n");
+        for (i, line) in self.lines.iter().enumerate() {
+            let references: Vec<String> = line.references.iter().map(|r| json_string(r)).collect();
+            let fields: Vec<String> = line.fields
+                .iter()
+                .map(|(k, v)| format!("{}: {}", json_string(k), json_string(v)))
+                .collect();
+            write!(
+                s,
+                "  {{\"quantity\": {}, \"references\": [{}], \"value\": {}, \"footprint\": {}, \"fields\": {{{}}}}}",
+                line.quantity(),
+                references.join(", "),
+                json_string(&line.value),
+                json_string(&line.footprint),
+                fields.join(", ")
+            ).unwrap();
+            if i + 1 < self.lines.len() {
+                s.push(',')
+            }
+            s.push('\n')
+        }
+        s.push_str("]\n");
+        s
+    }
+
+    /// generate a text report, grouped by reference prefix
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        let mut prefix = None;
+        for line in &self.lines {
+            let p = line.prefix();
+            if prefix.as_ref() != Some(&p) {
+                if prefix.is_some() {
+                    s.push('\n')
+                }
+                writeln!(s, "{}:", p).unwrap();
+                prefix = Some(p);
+            }
+            write!(
+                s,
+                "  {:>3}x {} {}",
+                line.quantity(),
+                line.value,
+                line.footprint
+            ).unwrap();
+            for (k, v) in &line.fields {
+                write!(s, " {}={}", k, v).unwrap();
+            }
+            writeln!(s).unwrap();
+            writeln!(s, "       {}", line.references.join(", ")).unwrap();
+        }
+        s
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut r = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => r.push_str("\\\""),
+            '\\' => r.push_str("\\\\"),
+            '\n' => r.push_str("\\n"),
+            '\t' => r.push_str("\\t"),
+            c if (c as u32) < 0x20 => r.push_str(&format!("\\u{:04x}", c as u32)),
+            c => r.push(c),
+        }
+    }
+    r.push('"');
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use schematic;
+    use schematic::ComponentField;
+    use test_util;
+
+    fn component(reference: &str, value: &str, footprint: &str, mpn: Option<&str>) -> Component {
+        let mut c = Component::default();
+        c.update_reference(reference.into());
+        let mut fields = vec![
+            ComponentField::new_from(0, "Reference".into(), reference.into(), 0.0, 0.0),
+            ComponentField::new_from(1, "Value".into(), value.into(), 0.0, 0.0),
+            ComponentField::new_from(2, "Footprint".into(), footprint.into(), 0.0, 0.0),
+        ];
+        if let Some(mpn) = mpn {
+            fields.push(ComponentField::new_from(4, "MPN".into(), mpn.into(), 0.0, 0.0))
+        }
+        c.fields = fields;
+        c
+    }
+
+    #[test]
+    fn bom_grouping() {
+        let components = vec![
+            component("R10", "10k", "R_0603", None),
+            component("R2", "10k", "R_0603", None),
+            component("R3", "10k", "R_0603", Some("RC0603")),
+            component("C1", "100n", "C_0603", None),
+            component("#PWR01", "GND", "", None),
+            component("U1", "LM358", "SOIC-8", None),
+            component("U1", "LM358", "SOIC-8", None),
+        ];
+        let bom = Bom::new(&components);
+        assert_eq!(bom.lines.len(), 4);
+        assert_eq!(bom.lines[0].references, vec!["C1"]);
+        assert_eq!(bom.lines[1].references, vec!["R2", "R10"]);
+        assert_eq!(bom.lines[2].references, vec!["R3"]);
+        assert_eq!(bom.lines[3].quantity(), 1);
+        let csv = bom.to_csv();
+        assert!(csv.starts_with("\"Quantity\",\"References\",\"Value\",\"Footprint\",\"MPN\"\n"));
+        assert!(csv.contains("2,\"R2 R10\",\"10k\",\"R_0603\",\"\"\n"));
+        assert!(bom.to_json().contains("\"fields\": {\"MPN\": \"RC0603\"}"));
+        assert!(bom.to_text().starts_with("C:\n"));
+    }
+
+    #[test]
+    fn bom_from_hierarchy() {
+        let dir = test_util::legacy_hierarchy("bom_from_hierarchy");
+        let sch = schematic::parse_file(&dir.join("root.sch")).unwrap();
+        let bom = Bom::from_schematic(&sch).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(bom.lines.len(), 1);
+        assert_eq!(bom.lines[0].quantity(), 3);
+        assert_eq!(bom.lines[0].references, vec!["R1", "R2", "R3"]);
+    }
+}
@@ -0,0 +1,124 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! expansion of the variables in the URIs of library tables
+//!
+//! a variable like `${KISYSMOD}` is looked up in the variables of the
+//! context first, then in the environment. `KIPRJMOD` is the project
+//! directory when the context has one.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use shellexpand;
+use {str_error, KicadError};
+
+/// the variables to expand library URIs with
+#[derive(Debug, Clone, Default)]
+pub struct ExpandContext {
+    /// the project directory, the value of `KIPRJMOD`
+    pub project_dir: Option<PathBuf>,
+    /// variables that take precedence over the environment, like the path
+    /// variables configured in Kicad
+    pub vars: HashMap<String, String>,
+}
+
+impl ExpandContext {
+    /// a context for a project; `path` is the project directory or a file in it
+    pub fn for_project(path: &Path) -> ExpandContext {
+        let dir = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(path)
+        };
+        ExpandContext {
+            project_dir: Some(PathBuf::from(dir)),
+            vars: HashMap::new(),
+        }
+    }
+
+    /// set a variable
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_string(), value.to_string());
+    }
+
+    /// get the value of a variable
+    pub fn get(&self, name: &str) -> Option<String> {
+        if name == "KIPRJMOD" {
+            if let Some(ref dir) = self.project_dir {
+                return Some(dir.to_string_lossy().into_owned());
+            }
+        }
+        if let Some(value) = self.vars.get(name) {
+            return Some(value.clone());
+        }
+        env::var(name).ok()
+    }
+
+    // expand what can be expanded, unknown variables are left as they are
+    fn expand_partial(&self, s: &str) -> (String, Vec<String>) {
+        let unresolved = RefCell::new(vec![]);
+        let s = shellexpand::tilde(s);
+        let s = shellexpand::env_with_context_no_errors(&s, |name| {
+            let value = self.get(name);
+            if value.is_none() {
+                let mut unresolved = unresolved.borrow_mut();
+                if !unresolved.iter().any(|n| n == name) {
+                    unresolved.push(name.to_string())
+                }
+            }
+            value
+        }).into_owned();
+        (s, unresolved.into_inner())
+    }
+
+    /// the variables in `s` that have no value
+    pub fn unresolved(&self, s: &str) -> Vec<String> {
+        self.expand_partial(s).1
+    }
+
+    /// expand the variables and a leading `~` in `s`; fails with the names
+    /// of the variables that have no value
+    pub fn expand(&self, s: &str) -> Result<String, KicadError> {
+        match self.expand_partial(s) {
+            (s, ref unresolved) if unresolved.is_empty() => Ok(s),
+            (_, unresolved) => str_error(format!(
+                "unresolved variables in {}: {}",
+                s,
+                unresolved.join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use expand::ExpandContext;
+
+    #[test]
+    fn expand() {
+        let mut context = ExpandContext::for_project(Path::new("/work/board/board.pro"));
+        context.set("KISYSMOD", "/usr/share/kicad/modules");
+        assert_eq!(
+            context.expand("${KIPRJMOD}/lib.pretty").unwrap(),
+            "/work/board/lib.pretty"
+        );
+        assert_eq!(
+            context.expand("$KISYSMOD/Resistors_SMD.pretty").unwrap(),
+            "/usr/share/kicad/modules/Resistors_SMD.pretty"
+        );
+        let s = "${KICAD_PARSE_GEN_A}/${KIPRJMOD}/${KICAD_PARSE_GEN_B}/${KICAD_PARSE_GEN_A}";
+        assert_eq!(
+            context.unresolved(s),
+            vec!["KICAD_PARSE_GEN_A", "KICAD_PARSE_GEN_B"]
+        );
+        let e = format!("{}", context.expand(s).unwrap_err());
+        assert!(e.contains("KICAD_PARSE_GEN_A, KICAD_PARSE_GEN_B"));
+        // the environment is the fallback
+        let path = ExpandContext::default().expand("${PATH}").unwrap();
+        assert_eq!(path, ::std::env::var("PATH").unwrap());
+    }
+}
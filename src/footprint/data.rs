@@ -8,8 +8,11 @@ pub use layout::NetName;
 
 use checkfix::{CheckFix, CheckFixData, Config};
 
+use Sexp;
 use symbolic_expressions::SexpError;
 
+use geometry::{arc_points, arc_points_through, convex_hull, grow, Point, Shape};
+
 /// implement to allow a Module and it's sub Element be flippable
 pub trait Flip {
     /// flip me
@@ -29,6 +32,8 @@ pub struct Module {
     pub name: String,
     /// elements contained within the Kicad Module
     pub elements: Vec<Element>,
+    /// written as `footprint` like Kicad 6 and later do, instead of `module`
+    pub footprint: bool,
 }
 
 trait Named {
@@ -41,6 +46,7 @@ impl Module {
         Module {
             name: name,
             elements: vec![],
+            footprint: false,
         }
     }
     /// append an Element to a Module
@@ -118,7 +124,7 @@ impl Module {
     pub fn has_smd_attr(&self) -> bool {
         for element in &self.elements {
             if let Element::Attr(ref attr) = *element {
-                if attr.as_str() == "smd" {
+                if attr.split_whitespace().any(|flag| flag == "smd") {
                     return true;
                 }
             }
@@ -132,9 +138,9 @@ impl Module {
     }
 
     /// check if a Module has a tstamp Element and return it
-    pub fn get_tstamp(&self) -> Option<i64> {
+    pub fn get_tstamp(&self) -> Option<&String> {
         for element in &self.elements {
-            if let Element::TStamp(stamp) = *element {
+            if let Element::TStamp(ref stamp) = *element {
                 return Some(stamp);
             }
         }
@@ -235,6 +241,130 @@ impl Module {
         }
         v
     }
+
+    /// replace the `FpLine`s on F.CrtYd and B.CrtYd by a courtyard around the
+    /// pads and the Fab graphics, grown by `clearance` and with its corners
+    /// rounded outward to `grid`; returns the bound of the new courtyard
+    pub fn generate_courtyard(
+        &mut self,
+        shape: CourtyardShape,
+        clearance: f64,
+        grid: f64,
+    ) -> Bound {
+        let points = self.courtyard_points();
+        if points.is_empty() {
+            return Bound::default();
+        }
+        let outline = match shape {
+            CourtyardShape::Box => {
+                let mut b = Bound::default();
+                for p in &points {
+                    b.update(&Bound::new(p.x, p.y, p.x, p.y))
+                }
+                let (x1, y1) = (b.x1 - clearance, b.y1 - clearance);
+                let (x2, y2) = (b.x2 + clearance, b.y2 + clearance);
+                vec![
+                    Point::new(x1, y1),
+                    Point::new(x2, y1),
+                    Point::new(x2, y2),
+                    Point::new(x1, y2),
+                ]
+            }
+            CourtyardShape::Hull => grow(&convex_hull(&points), clearance),
+        };
+        // round away from the middle, so the courtyard only gets bigger
+        let n = outline.len() as f64;
+        let cx = outline.iter().map(|p| p.x).sum::<f64>() / n;
+        let cy = outline.iter().map(|p| p.y).sum::<f64>() / n;
+        let snap = |v: f64, up: bool| {
+            if grid <= 0.0 {
+                return v;
+            }
+            let v = ((v / grid) * 1e6).round() / 1e6;
+            let v = if up { v.ceil() } else { v.floor() };
+            ((v * grid) * 1e6).round() / 1e6
+        };
+        let outline: Vec<Point> = outline
+            .iter()
+            .map(|p| Point::new(snap(p.x, p.x > cx), snap(p.y, p.y > cy)))
+            .collect();
+        let outline = convex_hull(&outline);
+
+        let mut index = None;
+        let mut i = 0;
+        while i < self.elements.len() {
+            let courtyard = match self.elements[i] {
+                Element::FpLine(ref line) => line.layer.t == LayerType::CrtYd,
+                _ => false,
+            };
+            if courtyard {
+                index = index.or(Some(i));
+                self.elements.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        let index = index
+            .or_else(|| self.elements.iter().position(|e| e.is_pad()))
+            .unwrap_or(self.elements.len());
+        let side = if self.is_front() {
+            LayerSide::Front
+        } else {
+            LayerSide::Back
+        };
+        let mut bound = Bound::default();
+        for (i, a) in outline.iter().enumerate() {
+            let b = &outline[(i + 1) % outline.len()];
+            let mut line = FpLine::make(a.x, a.y, b.x, b.y, LayerType::CrtYd, 0.05);
+            line.layer.side = side.clone();
+            self.elements.insert(index + i, Element::FpLine(line));
+            bound.update(&Bound::new(a.x, a.y, a.x, a.y));
+        }
+        bound
+    }
+
+    // the outlines of the pads and the points of the Fab graphics
+    fn courtyard_points(&self) -> Vec<Point> {
+        let mut v = vec![];
+        let point = |xy: &Xy| Point::new(xy.x, xy.y);
+        for element in &self.elements {
+            match *element {
+                Element::Pad(ref pad) => v.extend(Shape::pad(pad, |x, y| (x, y)).outline()),
+                Element::FpLine(ref line) if line.is_fab() => {
+                    v.push(point(&line.start));
+                    v.push(point(&line.end));
+                }
+                Element::FpPoly(ref poly) if poly.is_fab() => {
+                    v.extend(poly.pts.elements.iter().map(&point))
+                }
+                Element::FpCircle(ref circle) if circle.is_fab() => {
+                    let (dx, dy) = (circle.end.x - circle.center.x, circle.end.y - circle.center.y);
+                    let radius = (dx * dx + dy * dy).sqrt();
+                    v.extend(Shape::circle(point(&circle.center), radius).outline())
+                }
+                Element::FpArc(ref arc) if arc.is_fab() => match arc.mid {
+                    Some(ref mid) => v.extend(arc_points_through(
+                        &point(&arc.start),
+                        &point(mid),
+                        &point(&arc.end),
+                    )),
+                    // a Kicad 5 arc has its center as start and its start as end
+                    None => v.extend(arc_points(&point(&arc.start), &point(&arc.end), arc.angle)),
+                },
+                _ => (),
+            }
+        }
+        v
+    }
+}
+
+/// the shape of a courtyard made by `Module::generate_courtyard`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CourtyardShape {
+    /// a rectangle
+    Box,
+    /// the convex hull, for footprints that are not rectangular
+    Hull,
 }
 
 impl BoundingBox for Module {
@@ -283,6 +413,7 @@ impl Rotate for Module {
 
 /// elements that can be found in a Module
 #[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Element {
     /// solder mask margin
     SolderMaskMargin(f64),
@@ -292,7 +423,7 @@ pub enum Element {
     Descr(String),
     /// Tags element
     Tags(String),
-    /// Attr element
+    /// Attr element, its flags separated by spaces
     Attr(String),
     /// text
     FpText(FpText),
@@ -308,8 +439,8 @@ pub enum Element {
     FpArc(FpArc),
     /// edited time stamp
     TEdit(i64),
-    /// time stamp
-    TStamp(i64),
+    /// time stamp, hexadecimal or a uuid since Kicad 6
+    TStamp(String),
     /// Path element
     Path(String),
     /// location of module in layout
@@ -320,6 +451,10 @@ pub enum Element {
     Clearance(f64),
     /// is the module locked
     Locked,
+    /// property with a name and a value, like the sheet of the module
+    Property(String, String),
+    /// other (uninterpreted symbolic-expression)
+    Other(Sexp),
 }
 
 impl BoundingBox for Element {
@@ -342,6 +477,8 @@ impl BoundingBox for Element {
             Element::Clearance(_) |
             Element::Tags(_) |
             Element::Locked |
+            Element::Property(_, _) |
+            Element::Other(_) |
             Element::TStamp(_) => Bound::default(),
         }
     }
@@ -368,6 +505,8 @@ impl Named for Element {
             Element::Tags(_) => "Tags",
             Element::Attr(_) => "Attr",
             Element::Locked => "Locked",
+            Element::Property(_, _) => "Property",
+            Element::Other(_) => "Other",
         }
     }
 }
@@ -392,6 +531,8 @@ impl Flip for Element {
             Element::Clearance(_) |
             Element::Tags(_) |
             Element::Attr(_) |
+            Element::Property(_, _) |
+            Element::Other(_) |
             Element::Locked => (),
         }
     }
@@ -417,6 +558,8 @@ impl Rotate for Element {
             Element::Clearance(_) |
             Element::Tags(_) |
             Element::Attr(_) |
+            Element::Property(_, _) |
+            Element::Other(_) |
             Element::Locked => (),
         }
     }
@@ -442,6 +585,8 @@ impl Element {
             Element::Clearance(_) |
             Element::Tags(_) |
             Element::Attr(_) |
+            Element::Property(_, _) |
+            Element::Other(_) |
             Element::Locked => false,
         }
     }
@@ -465,6 +610,8 @@ impl Element {
             Element::Clearance(_) |
             Element::Tags(_) |
             Element::Attr(_) |
+            Element::Property(_, _) |
+            Element::Other(_) |
             Element::Locked => false,
         }
     }
@@ -493,6 +640,11 @@ pub struct FpText {
     pub effects: Effects,
     /// is it a hidden text
     pub hide: bool,
+    /// time stamp
+    pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the text expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Flip for FpText {
@@ -560,6 +712,8 @@ impl FpText {
             layer: Layer::default(),
             effects: Effects::default(),
             hide: false,
+            tstamp: None,
+            other: vec![],
         }
     }
     /// set the text effects of the text
@@ -636,25 +790,31 @@ pub struct Font {
 pub struct Effects {
     /// the font used
     pub font: Font,
-    /// the text justification
-    pub justify: Option<Justify>,
+    /// the text justification, like `left` or `left mirror`
+    pub justify: Vec<Justify>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the effects expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Flip for Effects {
     fn flip(&mut self) {
-        self.justify = match self.justify {
-            None => Some(Justify::Mirror),
-            Some(_) => None,
+        match self.justify.iter().position(|j| *j == Justify::Mirror) {
+            Some(i) => {
+                self.justify.remove(i);
+            }
+            None => self.justify.push(Justify::Mirror),
         }
     }
 }
 
 impl Effects {
     /// create a text effects element from font and justification
-    pub fn from_font(font: Font, justify: Option<Justify>) -> Effects {
+    pub fn from_font(font: Font, justify: Vec<Justify>) -> Effects {
         Effects {
             font: font,
             justify: justify,
+            other: vec![],
         }
     }
 }
@@ -668,6 +828,10 @@ pub enum Justify {
     Left,
     /// the text is right-justified
     Right,
+    /// the text is top-justified
+    Top,
+    /// the text is bottom-justified
+    Bottom,
 }
 
 /// the type of X-Y element
@@ -685,6 +849,8 @@ pub enum XyType {
     Center,
     /// rectangular delta
     RectDelta,
+    /// middle point of an arc
+    Mid,
 }
 
 impl Default for XyType {
@@ -816,6 +982,8 @@ pub enum PadShape {
     Oval,
     /// trapezoid
     Trapezoid, // TODO
+    /// rectangular with rounded corners
+    RoundRect,
 }
 
 impl PadShape {
@@ -826,6 +994,7 @@ impl PadShape {
             "circle" => Ok(PadShape::Circle),
             "oval" => Ok(PadShape::Oval),
             "trapezoid" => Ok(PadShape::Trapezoid),
+            "roundrect" => Ok(PadShape::RoundRect),
             x => Err(format!("unknown PadShape: {}", x).into()),
         }
     }
@@ -854,6 +1023,62 @@ pub enum LayerSide {
     In1,
     /// Inner layer 2
     In2,
+    /// Inner layer 3
+    In3,
+    /// Inner layer 4
+    In4,
+    /// Inner layer 5
+    In5,
+    /// Inner layer 6
+    In6,
+    /// Inner layer 7
+    In7,
+    /// Inner layer 8
+    In8,
+    /// Inner layer 9
+    In9,
+    /// Inner layer 10
+    In10,
+    /// Inner layer 11
+    In11,
+    /// Inner layer 12
+    In12,
+    /// Inner layer 13
+    In13,
+    /// Inner layer 14
+    In14,
+    /// Inner layer 15
+    In15,
+    /// Inner layer 16
+    In16,
+    /// Inner layer 17
+    In17,
+    /// Inner layer 18
+    In18,
+    /// Inner layer 19
+    In19,
+    /// Inner layer 20
+    In20,
+    /// Inner layer 21
+    In21,
+    /// Inner layer 22
+    In22,
+    /// Inner layer 23
+    In23,
+    /// Inner layer 24
+    In24,
+    /// Inner layer 25
+    In25,
+    /// Inner layer 26
+    In26,
+    /// Inner layer 27
+    In27,
+    /// Inner layer 28
+    In28,
+    /// Inner layer 29
+    In29,
+    /// Inner layer 30
+    In30,
     /// no side
     None,
 }
@@ -869,6 +1094,116 @@ impl Flip for LayerSide {
     }
 }
 
+impl LayerSide {
+    /// create an inner layer side from its index (1 for In1 up to 30 for In30)
+    pub fn inner(index: i64) -> Option<LayerSide> {
+        match index {
+            1 => Some(LayerSide::In1),
+            2 => Some(LayerSide::In2),
+            3 => Some(LayerSide::In3),
+            4 => Some(LayerSide::In4),
+            5 => Some(LayerSide::In5),
+            6 => Some(LayerSide::In6),
+            7 => Some(LayerSide::In7),
+            8 => Some(LayerSide::In8),
+            9 => Some(LayerSide::In9),
+            10 => Some(LayerSide::In10),
+            11 => Some(LayerSide::In11),
+            12 => Some(LayerSide::In12),
+            13 => Some(LayerSide::In13),
+            14 => Some(LayerSide::In14),
+            15 => Some(LayerSide::In15),
+            16 => Some(LayerSide::In16),
+            17 => Some(LayerSide::In17),
+            18 => Some(LayerSide::In18),
+            19 => Some(LayerSide::In19),
+            20 => Some(LayerSide::In20),
+            21 => Some(LayerSide::In21),
+            22 => Some(LayerSide::In22),
+            23 => Some(LayerSide::In23),
+            24 => Some(LayerSide::In24),
+            25 => Some(LayerSide::In25),
+            26 => Some(LayerSide::In26),
+            27 => Some(LayerSide::In27),
+            28 => Some(LayerSide::In28),
+            29 => Some(LayerSide::In29),
+            30 => Some(LayerSide::In30),
+            _ => None,
+        }
+    }
+
+    /// get the index of an inner layer side (1 for In1 up to 30 for In30)
+    pub fn inner_index(&self) -> Option<i64> {
+        match *self {
+            LayerSide::In1 => Some(1),
+            LayerSide::In2 => Some(2),
+            LayerSide::In3 => Some(3),
+            LayerSide::In4 => Some(4),
+            LayerSide::In5 => Some(5),
+            LayerSide::In6 => Some(6),
+            LayerSide::In7 => Some(7),
+            LayerSide::In8 => Some(8),
+            LayerSide::In9 => Some(9),
+            LayerSide::In10 => Some(10),
+            LayerSide::In11 => Some(11),
+            LayerSide::In12 => Some(12),
+            LayerSide::In13 => Some(13),
+            LayerSide::In14 => Some(14),
+            LayerSide::In15 => Some(15),
+            LayerSide::In16 => Some(16),
+            LayerSide::In17 => Some(17),
+            LayerSide::In18 => Some(18),
+            LayerSide::In19 => Some(19),
+            LayerSide::In20 => Some(20),
+            LayerSide::In21 => Some(21),
+            LayerSide::In22 => Some(22),
+            LayerSide::In23 => Some(23),
+            LayerSide::In24 => Some(24),
+            LayerSide::In25 => Some(25),
+            LayerSide::In26 => Some(26),
+            LayerSide::In27 => Some(27),
+            LayerSide::In28 => Some(28),
+            LayerSide::In29 => Some(29),
+            LayerSide::In30 => Some(30),
+            _ => None,
+        }
+    }
+
+    /// get the Kicad copper layer number of the side:
+    /// 0 for front, 1 to 30 for the inner layers and 31 for back
+    pub fn copper_num(&self) -> Option<i64> {
+        match *self {
+            LayerSide::Front => Some(0),
+            LayerSide::Back => Some(31),
+            ref x => x.inner_index(),
+        }
+    }
+
+    /// create a layer side from a Kicad copper layer number
+    pub fn from_copper_num(num: i64) -> Option<LayerSide> {
+        match num {
+            0 => Some(LayerSide::Front),
+            31 => Some(LayerSide::Back),
+            x => LayerSide::inner(x),
+        }
+    }
+
+    /// flip the side in a board with `copper_layers` copper layers;
+    /// unlike `Flip` this also mirrors the inner layers in the stack. An
+    /// inner layer that isn't in the stack is left unchanged
+    pub fn flip_in_stack(&mut self, copper_layers: i64) {
+        if let Some(index) = self.inner_index() {
+            if index < copper_layers - 1 {
+                if let Some(side) = LayerSide::inner(copper_layers - 1 - index) {
+                    *self = side;
+                }
+            }
+        } else {
+            self.flip()
+        }
+    }
+}
+
 impl Default for LayerSide {
     fn default() -> LayerSide {
         LayerSide::Front
@@ -923,6 +1258,13 @@ impl Flip for Layer {
     }
 }
 
+impl Layer {
+    /// flip the layer in a board with `copper_layers` copper layers
+    pub fn flip_in_stack(&mut self, copper_layers: i64) {
+        self.side.flip_in_stack(copper_layers)
+    }
+}
+
 impl PartialEq for Layer {
     fn eq(&self, other: &Layer) -> bool {
         self.t == other.t
@@ -943,10 +1285,16 @@ impl Layer {
                 "Eco1" => LayerSide::Eco1,
                 "Eco2" => LayerSide::Eco2,
                 "Edge" => LayerSide::Edge,
-                "In1" => LayerSide::In1,
-                "In2" => LayerSide::In2,
                 "*" => LayerSide::Both,
-                x => return Err(format!("unknown layer side {}", x).into()),
+                x => {
+                    let inner = x.strip_prefix("In")
+                        .and_then(|index| index.parse::<i64>().ok())
+                        .and_then(LayerSide::inner);
+                    match inner {
+                        Some(side) => side,
+                        None => return Err(format!("unknown layer side {}", x).into()),
+                    }
+                }
             };
             sp[1]
         } else {
@@ -1001,6 +1349,8 @@ pub struct Pad {
     pub t: PadType,
     /// shape
     pub shape: PadShape,
+    /// is the pad locked, Kicad 6 and later
+    pub locked: bool,
     /// size
     pub size: Xy,
     /// offset
@@ -1023,6 +1373,17 @@ pub struct Pad {
     pub clearance: Option<f64>,
     /// thermal gap
     pub thermal_gap: Option<f64>,
+    /// corner radius of a rounded rectangle, relative to the smallest side
+    pub roundrect_rratio: Option<f64>,
+    /// function of the pin of the symbol connected to the pad
+    pub pinfunction: Option<String>,
+    /// electrical type of the pin of the symbol connected to the pad
+    pub pintype: Option<String>,
+    /// time stamp
+    pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the pad expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Flip for Pad {
@@ -1079,6 +1440,12 @@ impl PartialEq for Pad {
         if self.thermal_gap != other.thermal_gap {
             return false;
         }
+        if self.roundrect_rratio != other.roundrect_rratio {
+            return false;
+        }
+        if self.other != other.other {
+            return false;
+        }
         true
     }
 }
@@ -1090,6 +1457,7 @@ impl Pad {
             name: name,
             t: t,
             shape: shape,
+            locked: false,
             size: Xy::new_empty(XyType::Size),
             rect_delta: None,
             at: At::default(),
@@ -1101,6 +1469,11 @@ impl Pad {
             solder_mask_margin: None,
             clearance: None,
             thermal_gap: None,
+            roundrect_rratio: None,
+            pinfunction: None,
+            pintype: None,
+            tstamp: None,
+            other: vec![],
         }
     }
 
@@ -1157,6 +1530,10 @@ pub struct FpPoly {
     pub width: f64,
     /// layer
     pub layer: Layer,
+    /// fill, `none` or `solid`
+    pub fill: Option<String>,
+    /// time stamp
+    pub tstamp: Option<String>,
 }
 
 impl Flip for FpPoly {
@@ -1195,6 +1572,13 @@ pub struct FpLine {
     pub layer: Layer,
     /// width
     pub width: f64,
+    /// line style; Kicad 7 writes the width in a stroke with a type
+    pub stroke: Option<String>,
+    /// time stamp
+    pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the line expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Flip for FpLine {
@@ -1212,6 +1596,9 @@ impl Default for FpLine {
             end: Xy::new_empty(XyType::End),
             layer: Layer::default(),
             width: 0.0,
+            stroke: None,
+            tstamp: None,
+            other: vec![],
         }
     }
 }
@@ -1223,7 +1610,7 @@ impl BoundingBox for FpLine {
 }
 
 impl FpLine {
-    fn make(x1: f64, y1: f64, x2: f64, y2: f64, t: LayerType, width: f64) -> FpLine {
+    pub(crate) fn make(x1: f64, y1: f64, x2: f64, y2: f64, t: LayerType, width: f64) -> FpLine {
         let mut line1 = FpLine::default();
         line1.start.x = x1;
         line1.start.y = y1;
@@ -1250,6 +1637,10 @@ pub struct FpCircle {
     pub layer: Layer,
     /// width
     pub width: f64,
+    /// fill, `none` or `solid`
+    pub fill: Option<String>,
+    /// time stamp
+    pub tstamp: Option<String>,
 }
 
 impl Default for FpCircle {
@@ -1259,6 +1650,8 @@ impl Default for FpCircle {
             end: Xy::new_empty(XyType::End),
             layer: Layer::default(),
             width: 0.0,
+            fill: None,
+            tstamp: None,
         }
     }
 }
@@ -1292,11 +1685,14 @@ impl FpCircle {
     }
 }
 
-/// an arc
+/// an arc, either with a center as `start` and an angle or since Kicad 6
+/// with a start, middle and end point
 #[derive(Debug, Clone, PartialEq)]
 pub struct FpArc {
     /// start point
     pub start: Xy,
+    /// middle point
+    pub mid: Option<Xy>,
     /// end point
     pub end: Xy,
     /// angle
@@ -1305,11 +1701,21 @@ pub struct FpArc {
     pub layer: Layer,
     /// width
     pub width: f64,
+    /// line style; Kicad 7 writes the width in a stroke with a type
+    pub stroke: Option<String>,
+    /// time stamp
+    pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the arc expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Flip for FpArc {
     fn flip(&mut self) {
         self.start.flip();
+        if let Some(ref mut mid) = self.mid {
+            mid.flip()
+        }
         self.end.flip();
         self.layer.flip()
     }
@@ -1318,7 +1724,12 @@ impl Flip for FpArc {
 impl BoundingBox for FpArc {
     fn bounding_box(&self) -> Bound {
         // perhaps not correct
-        Bound::new(self.start.x, self.start.y, self.end.x, self.end.y)
+        let mut b = Bound::new(self.start.x, self.start.y, self.end.x, self.end.y);
+        if let Some(ref mid) = self.mid {
+            b.swap_if_needed();
+            b.update(&Bound::new(mid.x, mid.y, mid.x, mid.y))
+        }
+        b
     }
 }
 
@@ -1326,10 +1737,14 @@ impl Default for FpArc {
     fn default() -> FpArc {
         FpArc {
             start: Xy::new_empty(XyType::Start),
+            mid: None,
             end: Xy::new_empty(XyType::End),
             angle: 0.0,
             layer: Layer::default(),
             width: 0.0,
+            stroke: None,
+            tstamp: None,
+            other: vec![],
         }
     }
 }
@@ -1360,6 +1775,9 @@ pub struct Model {
     pub scale: Xyz,
     /// rotation
     pub rotate: Xyz,
+    /// the location is an `offset` in mm like Kicad 6 and later write it,
+    /// instead of an `at` in inch
+    pub offset: bool,
 }
 
 /// a 3D X-Y-Z coordinate
@@ -1689,8 +2107,10 @@ impl CheckFix for Module {
                     value: "%R".into(),
                     at: At::new(0.0, 0.0, 0.0),
                     layer: Layer::from_string("F.Fab").unwrap(),
-                    effects: Effects::from_font(font, None),
+                    effects: Effects::from_font(font, vec![]),
                     hide: false,
+                    tstamp: None,
+                    other: vec![],
                 };
                 self.elements.push(Element::FpText(ref2));
             };
@@ -1811,4 +2231,105 @@ mod test {
         assert!((bound.x1 + 2.95).abs() < EPSILON);
         assert!((bound.y1 + 2.95).abs() < EPSILON);
     }
+
+    fn courtyard(module: &Module) -> Vec<(f64, f64, f64, f64)> {
+        module
+            .lines()
+            .into_iter()
+            .filter(|l| l.layer.t == LayerType::CrtYd)
+            .map(|l| (l.start.x, l.start.y, l.end.x, l.end.y))
+            .collect()
+    }
+
+    #[test]
+    fn generate_courtyard() {
+        let mut module = Module::new("SOT-23".into());
+        module.append(Element::Layer(Layer::from_string("F.Cu").unwrap()));
+        let old = FpLine::make(-5.0, -5.0, 5.0, -5.0, LayerType::CrtYd, 0.05);
+        module.append(Element::FpLine(old));
+        module.append(Element::FpLine(
+            FpLine::make(-0.7, -1.52, 0.7, -1.52, LayerType::Fab, 0.1),
+        ));
+        module.append(Element::FpLine(
+            FpLine::make(-0.7, 1.52, 0.7, 1.52, LayerType::Fab, 0.1),
+        ));
+        for &(name, x, y) in &[("1", -1.0, -0.95), ("2", -1.0, 0.95), ("3", 1.0, 0.0)] {
+            let mut pad = Pad::new(name.into(), PadType::Smd, PadShape::Rect);
+            pad.at = At::new(x, y, 0.0);
+            pad.size = Xy::new(0.9, 0.8, XyType::Size);
+            module.append(Element::Pad(pad));
+        }
+
+        let bound = module.generate_courtyard(CourtyardShape::Box, 0.25, 0.05);
+        assert_eq!((bound.x1, bound.y1, bound.x2, bound.y2), (-1.7, -1.8, 1.7, 1.8));
+        assert_eq!(
+            courtyard(&module),
+            vec![
+                (-1.7, -1.8, 1.7, -1.8),
+                (1.7, -1.8, 1.7, 1.8),
+                (1.7, 1.8, -1.7, 1.8),
+                (-1.7, 1.8, -1.7, -1.8),
+            ]
+        );
+        // the courtyard takes the place of the old one
+        match module.elements[1] {
+            Element::FpLine(ref line) => assert_eq!(line.layer.t, LayerType::CrtYd),
+            ref e => panic!("unexpected {:?}", e),
+        }
+
+        // the hull cuts the corners next to pad 3 and stays on the grid
+        module.generate_courtyard(CourtyardShape::Hull, 0.25, 0.05);
+        let hull = courtyard(&module);
+        assert!(hull.len() > 4);
+        let on_grid = |v: f64| ((v / 0.05).round() * 0.05 - v).abs() < 1e-9;
+        for &(x1, y1, _, _) in &hull {
+            assert!(on_grid(x1) && on_grid(y1));
+            assert!((x1, y1) != (1.7, -1.8) && (x1, y1) != (1.7, 1.8));
+            assert!(x1.abs() >= 0.7 + 0.25 || y1.abs() >= 1.52 + 0.25);
+        }
+        for pad in module.pads() {
+            let b = pad.bounding_box();
+            for &(x, y) in &[(b.x1, b.y1), (b.x2, b.y1), (b.x2, b.y2), (b.x1, b.y2)] {
+                // every corner of a pad is at least the clearance inside
+                for &(x1, y1, x2, y2) in &hull {
+                    let (dx, dy) = (x2 - x1, y2 - y1);
+                    let side = (dx * (y - y1) - dy * (x - x1)) / (dx * dx + dy * dy).sqrt();
+                    assert!(side >= 0.25 - 1e-9, "{:?} too close to {:?}", (x, y), (x1, y1));
+                }
+            }
+        }
+        assert_eq!(
+            module.lines().iter().filter(|l| l.layer.t == LayerType::CrtYd).count(),
+            hull.len()
+        );
+
+        // a module on the back gets its courtyard on B.CrtYd
+        module.flip();
+        module.generate_courtyard(CourtyardShape::Box, 0.25, 0.05);
+        for line in module.lines() {
+            if line.layer.t == LayerType::CrtYd {
+                assert_eq!(line.layer.side, LayerSide::Back);
+            }
+        }
+    }
+
+    #[test]
+    fn inner_layers() {
+        let layer = Layer::from_string("In17.Cu").unwrap();
+        assert_eq!(layer.side, LayerSide::In17);
+        assert_eq!(format!("{}", layer), "In17.Cu");
+        assert_eq!(layer.side.copper_num(), Some(17));
+        assert_eq!(LayerSide::from_copper_num(31), Some(LayerSide::Back));
+        assert!(Layer::from_string("In31.Cu").is_err());
+        let mut side = LayerSide::In1;
+        side.flip_in_stack(6);
+        assert_eq!(side, LayerSide::In4);
+        side.flip();
+        assert_eq!(side, LayerSide::In4);
+        let mut side = LayerSide::In5;
+        side.flip_in_stack(4);
+        assert_eq!(side, LayerSide::In5);
+        side.flip_in_stack(2);
+        assert_eq!(side, LayerSide::In5);
+    }
 }
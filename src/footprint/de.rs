@@ -4,6 +4,7 @@ use Sexp;
 use footprint;
 use footprint::data::*;
 use wrap;
+use sexpr::{name_of, parse_stroke};
 use symbolic_expressions::iteratom::*;
 use symbolic_expressions::SexpError;
 
@@ -17,6 +18,11 @@ enum Part {
     Clearance(f64),
     ThermalGap(f64),
     ZoneConnect(i64),
+    RoundrectRratio(f64),
+    PinFunction(String),
+    PinType(String),
+    TStamp(String),
+    Other,
 }
 
 struct Offset(f64, f64);
@@ -50,25 +56,42 @@ impl FromSexp for Layer {
     }
 }
 
+// (effects (font (size 1 1) (thickness 0.15)) (justify left mirror))
 impl FromSexp for Effects {
     fn from_sexp(s: &Sexp) -> Result<Effects, SexpError> {
-        let mut i = IterAtom::new(s, "effects")?;
-        let font = i.t("font")?;
-        let justify = i.maybe_t();
-        i.close(Effects::from_font(font, justify))
+        let i = IterAtom::new(s, "effects")?;
+        let mut font = None;
+        let mut effects = Effects::default();
+        for (n, e) in i.iter.enumerate() {
+            match name_of(e) {
+                "font" => font = Some(from_sexp(e)?),
+                "justify" => effects.justify = Justification::from_sexp(e)?.0,
+                _ => effects.other.push((n + 1, e.clone())),
+            }
+        }
+        effects.font = font.ok_or("effects: missing font")?;
+        Ok(effects)
     }
 }
 
-impl FromSexp for Justify {
-    fn from_sexp(s: &Sexp) -> Result<Justify, SexpError> {
-        let mut i = IterAtom::new(s, "justify")?;
-        let s = i.s("mirror")?;
-        match &s[..] {
-            "mirror" => Ok(Justify::Mirror),
-            "left" => Ok(Justify::Left),
-            "right" => Ok(Justify::Right),
-            _ => Err(format!("unknown justify: {}", s).into()),
-        }
+struct Justification(Vec<Justify>);
+
+impl FromSexp for Justification {
+    fn from_sexp(s: &Sexp) -> Result<Justification, SexpError> {
+        let i = IterAtom::new(s, "justify")?;
+        let mut v = vec![];
+        for e in i.iter {
+            let j = e.string()?;
+            v.push(match &j[..] {
+                "mirror" => Justify::Mirror,
+                "left" => Justify::Left,
+                "right" => Justify::Right,
+                "top" => Justify::Top,
+                "bottom" => Justify::Bottom,
+                _ => return Err(format!("unknown justify: {}", j).into()),
+            })
+        }
+        Ok(Justification(v))
     }
 }
 
@@ -116,6 +139,18 @@ where
     Ok(make(f))
 }
 
+fn parse_part_string<F>(e: &Sexp, make: F) -> Result<Part, SexpError>
+where
+    F: Fn(String) -> Part,
+{
+    let v = e.list()?;
+    if v.len() < 2 {
+        return Err(format!("not enough elements in {}", e).into());
+    }
+    let s = v[1].string()?;
+    Ok(make(s.clone()))
+}
+
 fn parse_part_int<F>(e: &Sexp, make: F) -> Result<Part, SexpError>
 where
     F: Fn(i64) -> Part,
@@ -138,6 +173,7 @@ impl FromSexp for Xy {
             "size" => Ok(XyType::Size),
             "center" => Ok(XyType::Center),
             "rect_delta" => Ok(XyType::RectDelta),
+            "mid" => Ok(XyType::Mid),
             x => Err(format!("unknown XyType {}", x).into()),
         };
         let t = t?;
@@ -204,7 +240,11 @@ impl FromSexp for Part {
             "clearance" => parse_part_float(s, Part::Clearance),
             "thermal_gap" => parse_part_float(s, Part::ThermalGap),
             "zone_connect" => parse_part_int(s, Part::ZoneConnect),
-            x => Err(format!("unknown part {}", x).into()),
+            "roundrect_rratio" => parse_part_float(s, Part::RoundrectRratio),
+            "pinfunction" => parse_part_string(s, Part::PinFunction),
+            "pintype" => parse_part_string(s, Part::PinType),
+            "tstamp" => parse_part_string(s, Part::TStamp),
+            _ => Ok(Part::Other),
         }
     }
 }
@@ -216,19 +256,18 @@ impl FromSexp for FpText {
         let name = i.s("name")?;
         let value = i.s("value")?;
         let mut fp = FpText::new(name, value);
-        if let Some(at) = i.maybe_t::<footprint::At>() {
-            fp.at = at;
-        }
-        if let Some(layer) = i.maybe_t::<footprint::Layer>() {
-            fp.layer = layer;
-        }
-        if i.maybe_literal_s("hide").is_some() {
-            fp.hide = true
-        }
-        if let Some(effects) = i.maybe_t::<footprint::Effects>() {
-            fp.effects = effects;
+        let first = s.list()?.len() - i.iter.len();
+        for (n, e) in i.iter.enumerate() {
+            match (e, name_of(e)) {
+                (Sexp::String(h), _) if h == "hide" => fp.hide = true,
+                (_, "at") => fp.at = from_sexp(e)?,
+                (_, "layer") => fp.layer = from_sexp(e)?,
+                (_, "effects") => fp.effects = from_sexp(e)?,
+                (_, "tstamp") => fp.tstamp = Some(e.named_value_s("tstamp")?),
+                _ => fp.other.push((first + n, e.clone())),
+            }
         }
-        i.close(fp)
+        Ok(fp)
     }
 }
 
@@ -243,6 +282,7 @@ impl FromSexp for Pad {
         let shape = PadShape::from_string(&shape)?;
         let mut pad = Pad::new(name, t, shape);
         // println!("{}", pad);
+        pad.locked = i.maybe_literal_s("locked").is_some();
         if let Some(at) = i.maybe_t::<At>() {
             pad.at = at;
         }
@@ -258,8 +298,12 @@ impl FromSexp for Pad {
         if let Some(layers) = i.maybe_t::<Layers>() {
             pad.layers = layers;
         }
-        let parts = i.vec()?;
-        for part in parts {
+        let first = s.list()?.len() - i.iter.len();
+        for (n, e) in i.iter.enumerate() {
+            let part = match *e {
+                Sexp::List(_) => Part::from_sexp(e)?,
+                _ => Part::Other,
+            };
             match part {
                 Part::Net(n) => pad.set_net(n),
                 Part::SolderPasteMargin(n) => pad.solder_paste_margin = Some(n),
@@ -267,7 +311,11 @@ impl FromSexp for Pad {
                 Part::Clearance(n) => pad.clearance = Some(n),
                 Part::ThermalGap(n) => pad.thermal_gap = Some(n),
                 Part::ZoneConnect(n) => pad.zone_connect = Some(n),
-                ref x => return Err(format!("pad: unknown {:?}", x).into()),
+                Part::RoundrectRratio(n) => pad.roundrect_rratio = Some(n),
+                Part::PinFunction(n) => pad.pinfunction = Some(n),
+                Part::PinType(n) => pad.pintype = Some(n),
+                Part::TStamp(n) => pad.tstamp = Some(n),
+                Part::Thickness(_) | Part::Other => pad.other.push((first + n, e.clone())),
             }
         }
         Ok(pad)
@@ -288,32 +336,34 @@ impl FromSexp for FpPoly {
         if let Some(width) = i.maybe_f_in_list("width") {
             fp_poly.width = width;
         }
+        fp_poly.fill = i.maybe_s_in_list("fill");
+        fp_poly.tstamp = i.maybe_s_in_list("tstamp");
         i.close(fp_poly)
     }
 }
 
 // (fp_line (start -1.5 -1.5) (end -1.5 1.5) (layer F.SilkS) (width 0.1))
+// (fp_line (start -1.5 -1.5) (end -1.5 1.5) (stroke (width 0.1) (type solid)) (layer "F.SilkS"))
 impl FromSexp for FpLine {
     fn from_sexp(s: &Sexp) -> Result<FpLine, SexpError> {
-        let mut i = IterAtom::new(s, "fp_line")?;
+        let i = IterAtom::new(s, "fp_line")?;
         let mut fp_line = FpLine::default();
-        if let Some(xy) = i.maybe_t::<Xy>() {
-            if xy.t == XyType::Start {
-                fp_line.start = xy;
-            }
-        }
-        if let Some(xy) = i.maybe_t::<Xy>() {
-            if xy.t == XyType::End {
-                fp_line.end = xy;
+        for (n, e) in i.iter.enumerate() {
+            match name_of(e) {
+                "start" => fp_line.start = from_sexp(e)?,
+                "end" => fp_line.end = from_sexp(e)?,
+                "layer" => fp_line.layer = from_sexp(e)?,
+                "width" => fp_line.width = e.named_value_f("width")?,
+                "stroke" => {
+                    let (width, t) = parse_stroke(e)?;
+                    fp_line.width = width;
+                    fp_line.stroke = Some(t)
+                }
+                "tstamp" => fp_line.tstamp = Some(e.named_value_s("tstamp")?),
+                _ => fp_line.other.push((n + 1, e.clone())),
             }
         }
-        if let Some(layer) = i.maybe_t::<Layer>() {
-            fp_line.layer = layer;
-        }
-        if let Some(width) = i.maybe_f_in_list("width") {
-            fp_line.width = width;
-        }
-        i.close(fp_line)
+        Ok(fp_line)
     }
 }
 
@@ -338,47 +388,65 @@ impl FromSexp for FpCircle {
         if let Some(width) = i.maybe_f_in_list("width") {
             fp_circle.width = width;
         }
+        fp_circle.fill = i.maybe_s_in_list("fill");
+        fp_circle.tstamp = i.maybe_s_in_list("tstamp");
         i.close(fp_circle)
     }
 }
 
 // (fp_arc (start 4.15 4.25) (end 5.15 4.25) (angle 86.6) (layer F.SilkS) (width 0.1))
+// (fp_arc (start 4.15 4.25) (mid 4.8 4.5) (end 5.15 4.25) (layer "F.SilkS") (width 0.1) (tstamp ...))
 impl FromSexp for FpArc {
     fn from_sexp(s: &Sexp) -> Result<FpArc, SexpError> {
-        let mut i = IterAtom::new(s, "fp_arc")?;
+        let i = IterAtom::new(s, "fp_arc")?;
         let mut fp_arc = FpArc::default();
-        if let Some(xy) = i.maybe_t::<Xy>() {
-            if xy.t == XyType::Start {
-                fp_arc.start = xy;
-            }
-        }
-        if let Some(xy) = i.maybe_t::<Xy>() {
-            if xy.t == XyType::End {
-                fp_arc.end = xy;
+        for (n, e) in i.iter.enumerate() {
+            match name_of(e) {
+                "start" => fp_arc.start = from_sexp(e)?,
+                "mid" => fp_arc.mid = Some(from_sexp(e)?),
+                "end" => fp_arc.end = from_sexp(e)?,
+                "angle" => fp_arc.angle = e.named_value_f("angle")?,
+                "layer" => fp_arc.layer = from_sexp(e)?,
+                "width" => fp_arc.width = e.named_value_f("width")?,
+                "stroke" => {
+                    let (width, t) = parse_stroke(e)?;
+                    fp_arc.width = width;
+                    fp_arc.stroke = Some(t)
+                }
+                "tstamp" => fp_arc.tstamp = Some(e.named_value_s("tstamp")?),
+                _ => fp_arc.other.push((n + 1, e.clone())),
             }
         }
-        if let Some(angle) = i.maybe_f_in_list("angle") {
-            fp_arc.angle = angle;
-        }
-        if let Some(layer) = i.maybe_t::<Layer>() {
-            fp_arc.layer = layer;
-        }
-        if let Some(width) = i.maybe_f_in_list("width") {
-            fp_arc.width = width;
-        }
-        i.close(fp_arc)
+        Ok(fp_arc)
+    }
+}
+
+struct ModelOffset(Xyz);
+
+impl FromSexp for ModelOffset {
+    fn from_sexp(s: &Sexp) -> Result<ModelOffset, SexpError> {
+        let mut i = IterAtom::new(s, "offset")?;
+        let xyz = ModelOffset(i.t("xyz")?);
+        i.close(xyz)
     }
 }
 
 // (model C_0603J.wrl (at (xyz 0 0 0)) (scale (xyz 1 1 1)) (rotate (xyz 0 0 0)))
+// (model C_0603J.wrl (offset (xyz 0 0 0)) (scale (xyz 1 1 1)) (rotate (xyz 0 0 0)))
 impl FromSexp for Model {
     fn from_sexp(s: &Sexp) -> Result<Model, SexpError> {
         let mut i = IterAtom::new(s, "model")?;
+        let name = i.s("name")?;
+        let (at, offset) = match i.maybe_t::<ModelOffset>() {
+            Some(offset) => (offset.0, true),
+            None => (i.t_in_list("at")?, false),
+        };
         let model = Model {
-            name: i.s("name")?,
-            at: i.t_in_list("at")?,
+            name,
+            at,
             scale: i.t_in_list("scale")?,
             rotate: i.t_in_list("rotate")?,
+            offset,
         };
         i.close(model)
     }
@@ -387,21 +455,40 @@ impl FromSexp for Model {
 fn parse_string_element(s: &Sexp) -> Result<String, SexpError> {
     let name = s.list_name()?;
     let mut i = IterAtom::new(s, name)?;
-    Ok(i.s("element")?)
+    let e = i.s("element")?;
+    i.close(e)
+}
+
+// (attr smd) (attr through_hole exclude_from_bom)
+fn parse_attr(s: &Sexp) -> Result<String, SexpError> {
+    let mut i = IterAtom::new(s, "attr")?;
+    let mut flags = vec![i.s("flag")?];
+    while let Some(flag) = i.maybe_s() {
+        flags.push(flag)
+    }
+    i.close(flags.join(" "))
 }
 
 fn parse_float_element(s: &Sexp) -> Result<f64, SexpError> {
     let name = s.list_name()?;
     let mut i = IterAtom::new(s, name)?;
-    Ok(i.f("element")?)
+    let e = i.f("element")?;
+    i.close(e)
+}
+
+fn parse_property(s: &Sexp) -> Result<Element, SexpError> {
+    let mut i = IterAtom::new(s, "property")?;
+    let name = i.s("name")?;
+    let value = i.s("value")?;
+    i.close(Element::Property(name, value))
 }
 
 fn parse_stamp_element(s: &Sexp) -> Result<i64, SexpError> {
     let name = s.list_name()?;
     let mut i = IterAtom::new(s, name)?;
-    let s = i.s("element")?;
-    let i = i64::from_str_radix(&s, 16)?;
-    Ok(i)
+    let e = i.s("element")?;
+    let e = i64::from_str_radix(&e, 16)?;
+    i.close(e)
 }
 
 impl FromSexp for Element {
@@ -409,7 +496,7 @@ impl FromSexp for Element {
         match *s {
             Sexp::String(ref s) => match &s[..] {
                 "locked" => Ok(Element::Locked),
-                _ => Err(format!("unknown element in module: {}", s).into()),
+                _ => Ok(Element::Other(Sexp::String(s.clone()))),
             },
             Sexp::List(_) => {
                 let name = s.list_name()?;
@@ -418,7 +505,7 @@ impl FromSexp for Element {
                     "layer" => wrap(s, from_sexp, Element::Layer),
                     "descr" => wrap(s, parse_string_element, Element::Descr),
                     "tags" => wrap(s, parse_string_element, Element::Tags),
-                    "attr" => wrap(s, parse_string_element, Element::Attr),
+                    "attr" => wrap(s, parse_attr, Element::Attr),
                     "fp_text" => wrap(s, from_sexp, Element::FpText),
                     "pad" => wrap(s, from_sexp, Element::Pad),
                     "fp_poly" => wrap(s, from_sexp, Element::FpPoly),
@@ -426,12 +513,14 @@ impl FromSexp for Element {
                     "fp_circle" => wrap(s, from_sexp, Element::FpCircle),
                     "fp_arc" => wrap(s, from_sexp, Element::FpArc),
                     "tedit" => wrap(s, parse_stamp_element, Element::TEdit),
-                    "tstamp" => wrap(s, parse_stamp_element, Element::TStamp),
+                    "tstamp" => wrap(s, parse_string_element, Element::TStamp),
                     "path" => wrap(s, parse_string_element, Element::Path),
                     "at" => wrap(s, from_sexp, Element::At),
                     "model" => wrap(s, from_sexp, Element::Model),
                     "clearance" => wrap(s, parse_float_element, Element::Clearance),
-                    _ => Err(format!("unknown element in module: {}", name).into()),
+                    "property" => parse_property(s),
+                    // unknown elements are kept as they are
+                    _ => Ok(Element::Other(s.clone())),
                 }
             }
             Sexp::Empty => unreachable!(),
@@ -441,9 +530,11 @@ impl FromSexp for Element {
 
 impl FromSexp for Module {
     fn from_sexp(s: &Sexp) -> Result<Module, SexpError> {
-        let mut i = IterAtom::new(s, "module")?;
+        let footprint = s.list_name()? == "footprint";
+        let mut i = IterAtom::new(s, if footprint { "footprint" } else { "module" })?;
         let name = i.s("name")?;
         let mut module = Module::new(name);
+        module.footprint = footprint;
         for e in i.iter {
             let el = from_sexp(e)?;
             module.append(el)
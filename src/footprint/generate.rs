@@ -0,0 +1,660 @@
+// (c) 2017 Productize SPRL <joost@productize.be>
+
+//! parametric footprint generators
+//!
+//! the generators build complete footprints for standard packages from the
+//! dimensions in a datasheet, all in mm: the pads, the body on F.Fab, a
+//! silkscreen outline clear of the pads, a courtyard and the reference and
+//! value texts. Pad 1 is at the top left and the pads are numbered
+//! counter-clockwise like the Kicad libraries do. SMD footprints are centered
+//! on the origin, through-hole footprints have pad 1 on the origin.
+
+use checkfix::Config;
+use footprint::data::*;
+use Bound;
+
+const FAB_WIDTH: f64 = 0.1;
+const SILK_WIDTH: f64 = 0.12;
+// distance from the body to the silkscreen outline
+const SILK_OFFSET: f64 = 0.11;
+// distance from the center of a silkscreen line to a pad
+const SILK_PAD_CLEARANCE: f64 = 0.2 + SILK_WIDTH / 2.0;
+// distance between the silkscreen outline and the pad 1 mark next to it
+const MARK_OFFSET: f64 = 0.3;
+const CRTYD_GRID: f64 = 0.01;
+
+/// a package with a row of pads on two sides, like SOIC, SSOP, TSSOP and DFN
+#[derive(Debug, Clone)]
+pub struct Dual {
+    /// name of the footprint
+    pub name: String,
+    /// number of pads on both sides together, not counting an exposed pad;
+    /// it must be even
+    pub pins: usize,
+    /// distance between the pads in a row
+    pub pitch: f64,
+    /// distance between the centers of the two rows
+    pub span: f64,
+    /// size of a pad across the row
+    pub pad_length: f64,
+    /// size of a pad along the row
+    pub pad_width: f64,
+    /// size of the body across the rows
+    pub body_width: f64,
+    /// size of the body along the rows
+    pub body_length: f64,
+    /// size of the exposed pad in the middle, numbered after the other pads
+    pub exposed_pad: Option<(f64, f64)>,
+}
+
+/// a package with a row of pads on four sides, like QFP and QFN
+#[derive(Debug, Clone)]
+pub struct Quad {
+    /// name of the footprint
+    pub name: String,
+    /// number of pads on the top and on the bottom side
+    pub pins_x: usize,
+    /// number of pads on the left and on the right side
+    pub pins_y: usize,
+    /// distance between the pads in a row
+    pub pitch: f64,
+    /// distance between the centers of the left and the right row
+    pub span_x: f64,
+    /// distance between the centers of the top and the bottom row
+    pub span_y: f64,
+    /// size of a pad across the row
+    pub pad_length: f64,
+    /// size of a pad along the row
+    pub pad_width: f64,
+    /// size of the body in x
+    pub body_width: f64,
+    /// size of the body in y
+    pub body_length: f64,
+    /// size of the exposed pad in the middle, numbered after the other pads
+    pub exposed_pad: Option<(f64, f64)>,
+}
+
+/// a ball grid array
+#[derive(Debug, Clone)]
+pub struct Bga {
+    /// name of the footprint
+    pub name: String,
+    /// number of rows, named A, B, C, ... from the top
+    pub rows: usize,
+    /// number of columns, numbered from 1 on the left
+    pub columns: usize,
+    /// distance between the balls
+    pub pitch: f64,
+    /// diameter of a pad
+    pub pad_diameter: f64,
+    /// size of the body in x
+    pub body_width: f64,
+    /// size of the body in y
+    pub body_length: f64,
+}
+
+/// a two terminal chip like a resistor or a capacitor
+#[derive(Debug, Clone)]
+pub struct Chip {
+    /// name of the footprint
+    pub name: String,
+    /// distance between the centers of the pads
+    pub span: f64,
+    /// size of a pad in x
+    pub pad_length: f64,
+    /// size of a pad in y
+    pub pad_width: f64,
+    /// size of the body in x
+    pub body_length: f64,
+    /// size of the body in y
+    pub body_width: f64,
+}
+
+/// a straight through-hole pin header
+#[derive(Debug, Clone)]
+pub struct PinHeader {
+    /// name of the footprint
+    pub name: String,
+    /// number of rows, pads are numbered across the rows first
+    pub rows: usize,
+    /// number of pins in a row
+    pub pins: usize,
+    /// distance between the pins
+    pub pitch: f64,
+    /// drill diameter
+    pub drill: f64,
+    /// pad diameter
+    pub pad_diameter: f64,
+}
+
+/// generate a footprint for a package with pads on two sides
+///
+/// panics when the number of pins is odd
+pub fn dual(p: &Dual) -> Module {
+    let first = -(((p.pins / 2) as f64) - 1.0) * p.pitch / 2.0;
+    let mut pads = dual_pads(p.pins, p.pitch, p.span, p.pad_length, p.pad_width);
+    if let Some((w, h)) = p.exposed_pad {
+        pads.push(smd_pad(p.pins + 1, 0.0, 0.0, w, h))
+    }
+    let (bx, by) = (p.body_width / 2.0, p.body_length / 2.0);
+    let body = Bound::new(-bx, -by, bx, by);
+    let mut graphics = fab_body(&body, true);
+    // silkscreen above and below the body, clear of the rows of pads
+    let sx = bx + SILK_OFFSET;
+    let sy = (by + SILK_OFFSET).max(-first + p.pad_width / 2.0 + SILK_PAD_CLEARANCE);
+    graphics.push(line(-sx, sy, sx, sy, LayerType::SilkS, SILK_WIDTH));
+    // the top line continues to the outside of pad 1 to mark it
+    let outer = (p.span + p.pad_length) / 2.0;
+    graphics.push(line(-outer.max(sx), -sy, sx, -sy, LayerType::SilkS, SILK_WIDTH));
+    let descr = format!(
+        "{} pins, pitch {} mm, body {} x {} mm",
+        p.pins,
+        p.pitch,
+        p.body_width,
+        p.body_length
+    );
+    assemble(&p.name, descr, true, 0.25, pads, graphics)
+}
+
+/// generate a footprint for a package with pads on four sides
+pub fn quad(p: &Quad) -> Module {
+    let first_x = -((p.pins_x as f64) - 1.0) * p.pitch / 2.0;
+    let first_y = -((p.pins_y as f64) - 1.0) * p.pitch / 2.0;
+    let (cx, cy) = (p.span_x / 2.0, p.span_y / 2.0);
+    let (l, w) = (p.pad_length, p.pad_width);
+    let mut pads = vec![];
+    for i in 0..p.pins_y {
+        let y = first_y + i as f64 * p.pitch;
+        let n = pads.len() + 1;
+        pads.push(smd_pad(n, -cx, y, l, w));
+    }
+    for i in 0..p.pins_x {
+        let x = first_x + i as f64 * p.pitch;
+        let n = pads.len() + 1;
+        pads.push(smd_pad(n, x, cy, w, l));
+    }
+    for i in 0..p.pins_y {
+        let y = -(first_y + i as f64 * p.pitch);
+        let n = pads.len() + 1;
+        pads.push(smd_pad(n, cx, y, l, w));
+    }
+    for i in 0..p.pins_x {
+        let x = -(first_x + i as f64 * p.pitch);
+        let n = pads.len() + 1;
+        pads.push(smd_pad(n, x, -cy, w, l));
+    }
+    if let Some((w, h)) = p.exposed_pad {
+        let n = pads.len() + 1;
+        pads.push(smd_pad(n, 0.0, 0.0, w, h))
+    }
+    let (bx, by) = (p.body_width / 2.0, p.body_length / 2.0);
+    let body = Bound::new(-bx, -by, bx, by);
+    let mut graphics = fab_body(&body, true);
+    // silkscreen corners that stop before the first pads of the sides
+    let (sx, sy) = (bx + SILK_OFFSET, by + SILK_OFFSET);
+    let stop_x = -first_x + w / 2.0 + SILK_PAD_CLEARANCE;
+    let stop_y = -first_y + w / 2.0 + SILK_PAD_CLEARANCE;
+    for &(mx, my) in &[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        let (x, y) = (mx * sx, my * sy);
+        if sx > stop_x && sy >= stop_y {
+            graphics.push(line(x, y, mx * stop_x, y, LayerType::SilkS, SILK_WIDTH));
+        }
+        if sy > stop_y && sx >= stop_x {
+            graphics.push(line(x, y, x, my * stop_y, LayerType::SilkS, SILK_WIDTH));
+        }
+    }
+    // pad 1 is marked by a line above it to the outside of the pad
+    let outer = cx + l / 2.0;
+    graphics.push(line(-sx.min(outer), -stop_y, -outer, -stop_y, LayerType::SilkS, SILK_WIDTH));
+    let descr = format!(
+        "{} pins, pitch {} mm, body {} x {} mm",
+        2 * (p.pins_x + p.pins_y),
+        p.pitch,
+        p.body_width,
+        p.body_length
+    );
+    assemble(&p.name, descr, true, 0.25, pads, graphics)
+}
+
+/// generate a footprint for a ball grid array
+pub fn bga(p: &Bga) -> Module {
+    let first_x = -((p.columns as f64) - 1.0) * p.pitch / 2.0;
+    let first_y = -((p.rows as f64) - 1.0) * p.pitch / 2.0;
+    let mut pads = vec![];
+    for row in 0..p.rows {
+        for column in 0..p.columns {
+            let mut pad = smd_pad(
+                0,
+                first_x + column as f64 * p.pitch,
+                first_y + row as f64 * p.pitch,
+                p.pad_diameter,
+                p.pad_diameter,
+            );
+            pad.name = format!("{}{}", bga_row(row), column + 1);
+            pad.shape = PadShape::Circle;
+            pads.push(pad)
+        }
+    }
+    let (bx, by) = (p.body_width / 2.0, p.body_length / 2.0);
+    let body = Bound::new(-bx, -by, bx, by);
+    let mut graphics = fab_body(&body, true);
+    let sx = (bx + SILK_OFFSET).max(-first_x + p.pad_diameter / 2.0 + SILK_PAD_CLEARANCE);
+    let sy = (by + SILK_OFFSET).max(-first_y + p.pad_diameter / 2.0 + SILK_PAD_CLEARANCE);
+    graphics.extend(silk_outline(sx, sy));
+    let descr = format!(
+        "{} x {} balls, pitch {} mm, body {} x {} mm",
+        p.columns,
+        p.rows,
+        p.pitch,
+        p.body_width,
+        p.body_length
+    );
+    assemble(&p.name, descr, true, 1.0, pads, graphics)
+}
+
+/// generate a footprint for a chip resistor or capacitor
+pub fn chip(p: &Chip) -> Module {
+    let pads = vec![
+        smd_pad(1, -p.span / 2.0, 0.0, p.pad_length, p.pad_width),
+        smd_pad(2, p.span / 2.0, 0.0, p.pad_length, p.pad_width),
+    ];
+    let (bx, by) = (p.body_length / 2.0, p.body_width / 2.0);
+    let body = Bound::new(-bx, -by, bx, by);
+    let mut graphics = fab_body(&body, false);
+    // silkscreen lines above and below the body, between the pads if the
+    // body is not wider than the pads
+    let mut sy = by + SILK_OFFSET;
+    let pad_y = p.pad_width / 2.0 + SILK_PAD_CLEARANCE;
+    let between = (p.span - p.pad_length) / 2.0 - SILK_PAD_CLEARANCE;
+    let sx = if sy >= pad_y {
+        bx
+    } else if between > 0.0 {
+        between
+    } else {
+        sy = pad_y;
+        bx
+    };
+    graphics.push(line(-sx, -sy, sx, -sy, LayerType::SilkS, SILK_WIDTH));
+    graphics.push(line(-sx, sy, sx, sy, LayerType::SilkS, SILK_WIDTH));
+    // smaller than 0603 gets a smaller courtyard
+    let clearance = if p.body_length < 1.6 { 0.15 } else { 0.25 };
+    let descr = format!("chip, body {} x {} mm", p.body_length, p.body_width);
+    assemble(&p.name, descr, true, clearance, pads, graphics)
+}
+
+/// generate a footprint for a straight pin header
+pub fn pin_header(p: &PinHeader) -> Module {
+    let mut pads = vec![];
+    for i in 0..p.pins {
+        for row in 0..p.rows {
+            let n = pads.len() + 1;
+            let shape = if n == 1 {
+                PadShape::Rect
+            } else {
+                PadShape::Oval
+            };
+            let mut pad = Pad::new(format!("{}", n), PadType::Pth, shape);
+            pad.at = At::new(round(row as f64 * p.pitch), round(i as f64 * p.pitch), 0.0);
+            pad.size = Xy::new(p.pad_diameter, p.pad_diameter, XyType::Size);
+            pad.set_drill(Drill {
+                width: p.drill,
+                height: p.drill,
+                ..Drill::default()
+            });
+            for layer in &["*.Cu", "*.Mask"] {
+                pad.layers.append(Layer::from_string(layer).unwrap())
+            }
+            pads.push(pad)
+        }
+    }
+    let half = p.pitch / 2.0;
+    let x2 = (p.rows as f64 - 1.0) * p.pitch + half;
+    let y2 = (p.pins as f64 - 1.0) * p.pitch + half;
+    let body = Bound::new(-half, -half, x2, y2);
+    let mut graphics = fab_body(&body, true);
+    let offset = SILK_OFFSET.max(p.pad_diameter / 2.0 + SILK_PAD_CLEARANCE - half);
+    let (cx, cy) = ((x2 - half) / 2.0, (y2 - half) / 2.0);
+    let mut silk = silk_outline(cx + half + offset, cy + half + offset);
+    for e in &mut silk {
+        if let Element::FpLine(ref mut line) = *e {
+            line.start.x = round(line.start.x + cx);
+            line.start.y = round(line.start.y + cy);
+            line.end.x = round(line.end.x + cx);
+            line.end.y = round(line.end.y + cy);
+        }
+    }
+    graphics.extend(silk);
+    let descr = format!(
+        "pin header, {} x {:02} pins, pitch {} mm",
+        p.rows,
+        p.pins,
+        p.pitch
+    );
+    assemble(&p.name, descr, false, 0.5, pads, graphics)
+}
+
+/// the name of a row of a ball grid array: A to Y, then AA to AY, BA, ...
+/// leaving out I, O, Q, S, X and Z
+pub fn bga_row(row: usize) -> String {
+    const LETTERS: &[u8] = b"ABCDEFGHJKLMNPRTUVWY";
+    let n = LETTERS.len();
+    let mut s = vec![];
+    let mut row = row;
+    loop {
+        s.insert(0, LETTERS[row % n]);
+        if row < n {
+            break;
+        }
+        row = row / n - 1;
+    }
+    String::from_utf8(s).unwrap()
+}
+
+// the pads of two rows, numbered down the left row and up the right row
+pub(crate) fn dual_pads(pins: usize, pitch: f64, span: f64, length: f64, width: f64) -> Vec<Pad> {
+    assert!(pins.is_multiple_of(2), "{} pins can't be split over two rows", pins);
+    let per_row = pins / 2;
+    let first = -((per_row as f64) - 1.0) * pitch / 2.0;
+    let mut pads = vec![];
+    for i in 0..per_row {
+        let y = first + i as f64 * pitch;
+        pads.push(smd_pad(i + 1, -span / 2.0, y, length, width));
+    }
+    for i in 0..per_row {
+        let y = -(first + i as f64 * pitch);
+        pads.push(smd_pad(per_row + i + 1, span / 2.0, y, length, width));
+    }
+    pads
+}
+
+// round away the noise of the floating point calculations
+fn round(x: f64) -> f64 {
+    (x * 1e6).round() / 1e6
+}
+
+fn line(x1: f64, y1: f64, x2: f64, y2: f64, t: LayerType, width: f64) -> Element {
+    Element::FpLine(FpLine::make(
+        round(x1),
+        round(y1),
+        round(x2),
+        round(y2),
+        t,
+        width,
+    ))
+}
+
+// a closed outline through the points
+fn outline(points: &[(f64, f64)], t: &LayerType, width: f64) -> Vec<Element> {
+    let mut v = vec![];
+    for (i, &(x1, y1)) in points.iter().enumerate() {
+        let (x2, y2) = points[(i + 1) % points.len()];
+        v.push(line(x1, y1, x2, y2, t.clone(), width))
+    }
+    v
+}
+
+// the body on F.Fab, with the corner of pad 1 chamfered for a polarized part
+fn fab_body(body: &Bound, polarized: bool) -> Vec<Element> {
+    let (x1, y1, x2, y2) = (body.x1, body.y1, body.x2, body.y2);
+    if polarized {
+        let c = (0.25 * body.width().min(body.height())).min(1.0);
+        let points = [(x1 + c, y1), (x2, y1), (x2, y2), (x1, y2), (x1, y1 + c)];
+        outline(&points, &LayerType::Fab, FAB_WIDTH)
+    } else {
+        let points = [(x1, y1), (x2, y1), (x2, y2), (x1, y2)];
+        outline(&points, &LayerType::Fab, FAB_WIDTH)
+    }
+}
+
+// a silkscreen rectangle around the center with pad 1 marked by an angle
+// outside of the top left corner
+fn silk_outline(sx: f64, sy: f64) -> Vec<Element> {
+    let points = [(-sx, -sy), (sx, -sy), (sx, sy), (-sx, sy)];
+    let mut v = outline(&points, &LayerType::SilkS, SILK_WIDTH);
+    let (mx, my) = (-sx - MARK_OFFSET, -sy - MARK_OFFSET);
+    let size = sx.min(sy).min(1.0);
+    v.push(line(mx, my + size, mx, my, LayerType::SilkS, SILK_WIDTH));
+    v.push(line(mx, my, mx + size, my, LayerType::SilkS, SILK_WIDTH));
+    v
+}
+
+pub(crate) fn smd_pad(n: usize, x: f64, y: f64, w: f64, h: f64) -> Pad {
+    let mut pad = Pad::new(format!("{}", n), PadType::Smd, PadShape::Rect);
+    pad.at = At::new(round(x), round(y), 0.0);
+    pad.size = Xy::new(round(w), round(h), XyType::Size);
+    for layer in &["F.Cu", "F.Paste", "F.Mask"] {
+        pad.layers.append(Layer::from_string(layer).unwrap())
+    }
+    pad
+}
+
+fn text(name: &str, value: &str, x: f64, y: f64, layer: &str) -> Element {
+    let config = Config::klc();
+    let font = Font {
+        size: Xy::new(config.m.font_size, config.m.font_size, XyType::Size),
+        thickness: config.m.font_thickness,
+        italic: false,
+    };
+    let mut text = FpText::new(name.into(), value.into());
+    text.at = At::new(round(x), round(y), 0.0);
+    text.set_layer(&Layer::from_string(layer).unwrap());
+    text.set_effects(&Effects::from_font(font, vec![]));
+    Element::FpText(text)
+}
+
+// put a footprint together: the texts, the graphics, the pads and a
+// courtyard around the body and the pads on the grid
+fn assemble(
+    name: &str,
+    descr: String,
+    smd: bool,
+    clearance: f64,
+    pads: Vec<Pad>,
+    graphics: Vec<Element>,
+) -> Module {
+    let mut module = Module::new(name.into());
+    module.append(Element::Layer(Layer::from_string("F.Cu").unwrap()));
+    module.append(Element::Descr(descr));
+    if smd {
+        module.append(Element::Attr("smd".into()));
+    }
+    let texts = module.elements.len();
+    module.elements.extend(graphics);
+    for pad in pads {
+        module.append(Element::Pad(pad))
+    }
+    let b = module.generate_courtyard(CourtyardShape::Box, clearance, CRTYD_GRID);
+    let (cx, cy) = ((b.x1 + b.x2) / 2.0, (b.y1 + b.y2) / 2.0);
+    let reference = text("reference", "REF**", cx, b.y1 - 1.0, "F.SilkS");
+    let value = text("value", name, cx, b.y2 + 1.0, "F.Fab");
+    let user = text("user", "%R", cx, cy, "F.Fab");
+    module.elements.splice(texts..texts, vec![reference, value, user]);
+    module
+}
+
+#[cfg(test)]
+mod test {
+    use checkfix::{CheckFix, Config};
+    use footprint::generate::*;
+    use footprint::{module_to_string, parse, Element, LayerType, Module};
+    use {Bound, BoundingBox};
+
+    // the module passes the checks, survives a round trip and has nothing
+    // on the silkscreen that touches a pad
+    fn verify(module: &Module) {
+        let check = module.check(&Config::klc());
+        assert!(check.is_empty(), "{}: {:?}", module.name, check);
+        let s = module_to_string(module, 0).unwrap();
+        assert_eq!(module_to_string(&parse(&s).unwrap(), 0).unwrap(), s);
+        let pads: Vec<Bound> = module.pads().iter().map(|p| p.bounding_box()).collect();
+        let mut silk = 0;
+        for line in module.lines() {
+            if line.layer.t != LayerType::SilkS {
+                continue;
+            }
+            silk += 1;
+            let b = line.bounding_box();
+            let (x1, x2) = (b.x1.min(b.x2), b.x1.max(b.x2));
+            let (y1, y2) = (b.y1.min(b.y2), b.y1.max(b.y2));
+            for pad in &pads {
+                let dx = (pad.x1 - x2).max(x1 - pad.x2);
+                let dy = (pad.y1 - y2).max(y1 - pad.y2);
+                assert!(dx.max(dy) > 0.2, "{}: silk {:?} on a pad", module.name, b);
+            }
+        }
+        assert!(silk > 0)
+    }
+
+    fn pad_at(module: &Module, name: &str) -> (f64, f64) {
+        let pad = module.pads().into_iter().find(|p| p.name == name).unwrap();
+        (pad.at.x, pad.at.y)
+    }
+
+    #[test]
+    fn soic() {
+        let module = dual(&Dual {
+            name: "SOIC-8_3.9x4.9mm_P1.27mm".into(),
+            pins: 8,
+            pitch: 1.27,
+            span: 4.95,
+            pad_length: 1.95,
+            pad_width: 0.6,
+            body_width: 3.9,
+            body_length: 4.9,
+            exposed_pad: None,
+        });
+        verify(&module);
+        assert_eq!(module.pads().len(), 8);
+        assert_eq!(pad_at(&module, "1"), (-2.475, -1.905));
+        assert_eq!(pad_at(&module, "4"), (-2.475, 1.905));
+        assert_eq!(pad_at(&module, "5"), (2.475, 1.905));
+        assert_eq!(pad_at(&module, "8"), (2.475, -1.905));
+    }
+
+    #[test]
+    #[should_panic(expected = "7 pins")]
+    fn dual_odd_pins() {
+        dual_pads(7, 1.27, 4.95, 1.95, 0.6);
+    }
+
+    #[test]
+    fn dfn() {
+        let module = dual(&Dual {
+            name: "DFN-8-1EP_3x3mm_P0.65mm_EP1.55x2.4mm".into(),
+            pins: 8,
+            pitch: 0.65,
+            span: 2.9,
+            pad_length: 0.6,
+            pad_width: 0.35,
+            body_width: 3.0,
+            body_length: 3.0,
+            exposed_pad: Some((1.55, 2.4)),
+        });
+        verify(&module);
+        assert_eq!(pad_at(&module, "9"), (0.0, 0.0));
+    }
+
+    #[test]
+    fn qfp_qfn() {
+        let qfp = quad(&Quad {
+            name: "LQFP-44_10x10mm_P0.8mm".into(),
+            pins_x: 11,
+            pins_y: 11,
+            pitch: 0.8,
+            span_x: 11.4,
+            span_y: 11.4,
+            pad_length: 1.5,
+            pad_width: 0.55,
+            body_width: 10.0,
+            body_length: 10.0,
+            exposed_pad: None,
+        });
+        verify(&qfp);
+        assert_eq!(pad_at(&qfp, "1"), (-5.7, -4.0));
+        assert_eq!(pad_at(&qfp, "12"), (-4.0, 5.7));
+        assert_eq!(pad_at(&qfp, "23"), (5.7, 4.0));
+        assert_eq!(pad_at(&qfp, "44"), (-4.0, -5.7));
+        let qfn = quad(&Quad {
+            name: "QFN-20-1EP_4x4mm_P0.5mm_EP2.6x2.6mm".into(),
+            pins_x: 5,
+            pins_y: 5,
+            pitch: 0.5,
+            span_x: 3.9,
+            span_y: 3.9,
+            pad_length: 0.8,
+            pad_width: 0.25,
+            body_width: 4.0,
+            body_length: 4.0,
+            exposed_pad: Some((2.6, 2.6)),
+        });
+        verify(&qfn);
+        assert_eq!(qfn.pads().len(), 21);
+    }
+
+    #[test]
+    fn ball_grid() {
+        let module = bga(&Bga {
+            name: "BGA-64_9x9mm_Layout8x8_P0.8mm".into(),
+            rows: 8,
+            columns: 8,
+            pitch: 0.8,
+            pad_diameter: 0.4,
+            body_width: 9.0,
+            body_length: 9.0,
+        });
+        verify(&module);
+        assert_eq!(pad_at(&module, "A1"), (-2.8, -2.8));
+        assert_eq!(pad_at(&module, "H8"), (2.8, 2.8));
+        assert_eq!(bga_row(19), "Y");
+        assert_eq!(bga_row(20), "AA");
+        assert_eq!(bga_row(41), "BB");
+    }
+
+    #[test]
+    fn chips() {
+        let r0603 = chip(&Chip {
+            name: "R_0603_1608Metric".into(),
+            span: 1.55,
+            pad_length: 0.8,
+            pad_width: 0.95,
+            body_length: 1.6,
+            body_width: 0.8,
+        });
+        verify(&r0603);
+        let c1206 = chip(&Chip {
+            name: "C_1206_3216Metric".into(),
+            span: 2.8,
+            pad_length: 1.15,
+            pad_width: 1.8,
+            body_length: 3.2,
+            body_width: 1.6,
+        });
+        verify(&c1206);
+    }
+
+    #[test]
+    fn headers() {
+        let module = pin_header(&PinHeader {
+            name: "PinHeader_2x05_P2.54mm_Vertical".into(),
+            rows: 2,
+            pins: 5,
+            pitch: 2.54,
+            drill: 1.0,
+            pad_diameter: 1.7,
+        });
+        verify(&module);
+        assert!(!module.has_smd_attr());
+        assert_eq!(pad_at(&module, "1"), (0.0, 0.0));
+        assert_eq!(pad_at(&module, "2"), (2.54, 0.0));
+        assert_eq!(pad_at(&module, "10"), (2.54, 10.16));
+        let crtyd = module
+            .elements
+            .iter()
+            .filter(|e| match **e {
+                Element::FpLine(ref l) => l.layer.t == LayerType::CrtYd,
+                _ => false,
+            })
+            .count();
+        assert_eq!(crtyd, 4);
+    }
+}
@@ -0,0 +1,366 @@
+// (c) 2017 Productize SPRL <joost@productize.be>
+
+//! IPC-7351 land pattern calculator
+//!
+//! the pads are derived from the dimensions and tolerances of the leads in a
+//! datasheet and the solder fillet goals of the density level:
+//!
+//! * `Zmax = Lmin + 2 toe + sqrt(CL² + F² + P²)`, outside of the pads
+//! * `Gmin = Smax - 2 heel - sqrt(CS² + F² + P²)`, inside of the pads
+//! * `Xmax = Wmin + 2 side + sqrt(CW² + F² + P²)`, width of the pads
+//!
+//! where `C` is the tolerance of a dimension, `F` the fabrication and `P` the
+//! placement tolerance. The tolerance of the distance between the leads `S`
+//! is the root mean square of the tolerances it is made of. `Z` and `X` are
+//! rounded up and `G` is rounded down to 0.05 mm.
+
+use footprint::data::*;
+use footprint::generate::{dual_pads, smd_pad, Chip, Dual};
+
+const ROUND_OFF: f64 = 0.05;
+
+/// a dimension from a datasheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dim {
+    /// minimum
+    pub min: f64,
+    /// maximum
+    pub max: f64,
+}
+
+impl Dim {
+    /// create a dimension from its minimum and maximum
+    pub fn new(min: f64, max: f64) -> Dim {
+        Dim { min, max }
+    }
+
+    /// create a dimension from a nominal value and a tolerance on both sides
+    pub fn nominal(nominal: f64, tolerance: f64) -> Dim {
+        Dim::new(nominal - tolerance, nominal + tolerance)
+    }
+
+    /// the tolerance, the difference between the maximum and the minimum
+    pub fn tolerance(&self) -> f64 {
+        self.max - self.min
+    }
+}
+
+/// the leads of a component, across two rows of leads or across the
+/// terminals of a chip
+#[derive(Debug, Clone)]
+pub struct Leads {
+    /// distance between the outsides of the leads, `L`; the body length of
+    /// a chip
+    pub span: Dim,
+    /// length of a lead that can be soldered, `T`
+    pub length: Dim,
+    /// width of a lead, `W`; the body width of a chip
+    pub width: Dim,
+    /// distance between the leads in a row, 0 for a chip
+    pub pitch: f64,
+}
+
+/// density level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Density {
+    /// most land protrusion, for low density boards and wave soldering
+    A,
+    /// nominal land protrusion, for most boards
+    B,
+    /// least land protrusion, for high density boards
+    C,
+}
+
+/// the kind of leads, which decides the solder fillet goals
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LeadType {
+    /// gull wing leads, like SOIC, SSOP and QFP
+    GullWing,
+    /// rectangular end caps of chip resistors and capacitors of 0603 and up
+    Chip,
+    /// leads on the bottom of the body, like QFN and DFN
+    NoLead,
+}
+
+/// the solder fillet goals and the courtyard excess
+#[derive(Debug, Clone, PartialEq)]
+pub struct Goals {
+    /// fillet at the outside of the lead
+    pub toe: f64,
+    /// fillet at the inside of the lead
+    pub heel: f64,
+    /// fillet at the sides of the lead
+    pub side: f64,
+    /// distance from the pads and the body to the courtyard
+    pub courtyard: f64,
+}
+
+impl LeadType {
+    /// the solder fillet goals of the IPC-7351B tables at a density level
+    pub fn goals(&self, density: Density, pitch: f64) -> Goals {
+        // fine pitch gull wings get less side fillet
+        let fine = pitch <= 0.625;
+        let (toe, heel, side) = match (*self, density) {
+            (LeadType::GullWing, Density::A) if fine => (0.55, 0.45, 0.01),
+            (LeadType::GullWing, Density::B) if fine => (0.35, 0.35, -0.02),
+            (LeadType::GullWing, Density::C) if fine => (0.15, 0.25, -0.04),
+            (LeadType::GullWing, Density::A) => (0.55, 0.45, 0.05),
+            (LeadType::GullWing, Density::B) => (0.35, 0.35, 0.03),
+            (LeadType::GullWing, Density::C) => (0.15, 0.25, 0.01),
+            (LeadType::Chip, Density::A) => (0.55, -0.05, 0.05),
+            (LeadType::Chip, Density::B) => (0.35, -0.05, 0.0),
+            (LeadType::Chip, Density::C) => (0.15, -0.05, -0.05),
+            (LeadType::NoLead, Density::A) => (0.4, 0.0, -0.04),
+            (LeadType::NoLead, Density::B) => (0.3, 0.0, -0.04),
+            (LeadType::NoLead, Density::C) => (0.2, 0.0, -0.04),
+        };
+        let courtyard = match density {
+            Density::A => 0.5,
+            Density::B => 0.25,
+            Density::C => 0.1,
+        };
+        Goals {
+            toe,
+            heel,
+            side,
+            courtyard,
+        }
+    }
+}
+
+/// the manufacturing tolerances of the board
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tolerances {
+    /// fabrication tolerance of the pads, `F`
+    pub fabrication: f64,
+    /// placement tolerance of the component, `P`
+    pub placement: f64,
+}
+
+impl Default for Tolerances {
+    fn default() -> Tolerances {
+        Tolerances {
+            fabrication: 0.05,
+            placement: 0.025,
+        }
+    }
+}
+
+/// a calculated land pattern of two rows of pads
+#[derive(Debug, Clone, PartialEq)]
+pub struct LandPattern {
+    /// distance between the outsides of the pads, `Z`
+    pub z: f64,
+    /// distance between the insides of the pads, `G`
+    pub g: f64,
+    /// width of the pads, `X`
+    pub x: f64,
+    /// distance between the pads in a row
+    pub pitch: f64,
+    /// distance from the pads and the body to the courtyard
+    pub courtyard: f64,
+}
+
+impl LandPattern {
+    /// calculate the land pattern for leads of a kind at a density level
+    pub fn calculate(
+        leads: &Leads,
+        lead_type: LeadType,
+        density: Density,
+        tolerances: &Tolerances,
+    ) -> LandPattern {
+        LandPattern::with_goals(
+            leads,
+            &lead_type.goals(density, leads.pitch),
+            tolerances,
+        )
+    }
+
+    /// calculate the land pattern for leads with custom fillet goals
+    pub fn with_goals(leads: &Leads, goals: &Goals, tolerances: &Tolerances) -> LandPattern {
+        let f = tolerances.fabrication;
+        let p = tolerances.placement;
+        let rss = |c: f64| (c * c + f * f + p * p).sqrt();
+        let cl = leads.span.tolerance();
+        let ct = leads.length.tolerance();
+        // the tolerance of the distance between the leads by root mean square
+        // instead of adding up the worst cases
+        let s_min = leads.span.min - 2.0 * leads.length.max;
+        let s_max = leads.span.max - 2.0 * leads.length.min;
+        let cs = (cl * cl + 2.0 * ct * ct).sqrt();
+        let s_max = s_max - (s_max - s_min - cs) / 2.0;
+        let z = leads.span.min + 2.0 * goals.toe + rss(cl);
+        let g = s_max - 2.0 * goals.heel - rss(cs);
+        let x = leads.width.min + 2.0 * goals.side + rss(leads.width.tolerance());
+        LandPattern {
+            z: round_up(z),
+            g: round_down(g),
+            x: round_up(x),
+            pitch: leads.pitch,
+            courtyard: goals.courtyard,
+        }
+    }
+
+    /// size of a pad across the row
+    pub fn pad_length(&self) -> f64 {
+        round((self.z - self.g) / 2.0)
+    }
+
+    /// size of a pad along the row
+    pub fn pad_width(&self) -> f64 {
+        self.x
+    }
+
+    /// distance between the centers of the two rows of pads
+    pub fn span(&self) -> f64 {
+        round((self.z + self.g) / 2.0)
+    }
+
+    /// a pad of the land pattern on the origin, oriented for the left or
+    /// the right row
+    pub fn pad(&self, name: &str) -> Pad {
+        let mut pad = smd_pad(0, 0.0, 0.0, self.pad_length(), self.pad_width());
+        pad.name = name.into();
+        pad
+    }
+
+    /// the pads of two rows with `pins` pads together, numbered down the left
+    /// row and up the right row; panics when `pins` is odd
+    pub fn pads(&self, pins: usize) -> Vec<Pad> {
+        dual_pads(
+            pins,
+            self.pitch,
+            self.span(),
+            self.pad_length(),
+            self.pad_width(),
+        )
+    }
+
+    /// the parameters of a package with pads on two sides with this land
+    /// pattern, for `generate::dual`
+    pub fn dual(&self, name: &str, pins: usize, body_width: f64, body_length: f64) -> Dual {
+        Dual {
+            name: name.into(),
+            pins,
+            pitch: self.pitch,
+            span: self.span(),
+            pad_length: self.pad_length(),
+            pad_width: self.pad_width(),
+            body_width,
+            body_length,
+            exposed_pad: None,
+        }
+    }
+
+    /// the parameters of a chip with this land pattern, for `generate::chip`
+    pub fn chip(&self, name: &str, body_length: f64, body_width: f64) -> Chip {
+        Chip {
+            name: name.into(),
+            span: self.span(),
+            pad_length: self.pad_length(),
+            pad_width: self.pad_width(),
+            body_length,
+            body_width,
+        }
+    }
+}
+
+// round away the noise of the floating point calculations
+fn round(x: f64) -> f64 {
+    (x * 1e6).round() / 1e6
+}
+
+fn round_up(x: f64) -> f64 {
+    round(round(x / ROUND_OFF).ceil() * ROUND_OFF)
+}
+
+fn round_down(x: f64) -> f64 {
+    round(round(x / ROUND_OFF).floor() * ROUND_OFF)
+}
+
+#[cfg(test)]
+mod test {
+    use footprint::generate;
+    use footprint::ipc7351::*;
+
+    fn soic8() -> Leads {
+        Leads {
+            span: Dim::new(5.8, 6.2),
+            length: Dim::new(0.4, 1.27),
+            width: Dim::new(0.31, 0.51),
+            pitch: 1.27,
+        }
+    }
+
+    #[test]
+    fn gull_wing() {
+        let tolerances = Tolerances::default();
+        let b = LandPattern::calculate(&soic8(), LeadType::GullWing, Density::B, &tolerances);
+        assert_eq!((b.z, b.g, b.x), (6.95, 2.95, 0.6));
+        assert_eq!((b.span(), b.pad_length(), b.pad_width()), (4.95, 2.0, 0.6));
+        assert_eq!(b.courtyard, 0.25);
+        // more density is smaller pads
+        let a = LandPattern::calculate(&soic8(), LeadType::GullWing, Density::A, &tolerances);
+        let c = LandPattern::calculate(&soic8(), LeadType::GullWing, Density::C, &tolerances);
+        assert!(a.z > b.z && b.z > c.z);
+        assert!(a.g < b.g && b.g < c.g);
+        assert!(a.x > b.x && b.x > c.x);
+
+        let pads = b.pads(8);
+        assert_eq!(pads.len(), 8);
+        assert_eq!((pads[0].at.x, pads[0].at.y), (-2.475, -1.905));
+        assert_eq!((pads[7].at.x, pads[7].at.y), (2.475, -1.905));
+        assert_eq!((pads[7].size.x, pads[7].size.y), (2.0, 0.6));
+        let module = generate::dual(&b.dual("SOIC-8_3.9x4.9mm_P1.27mm", 8, 3.9, 4.9));
+        assert_eq!(module.pads()[0], &pads[0]);
+    }
+
+    #[test]
+    fn fine_pitch_goals() {
+        let side = |density, pitch| LeadType::GullWing.goals(density, pitch).side;
+        assert_eq!(side(Density::A, 0.5), 0.01);
+        assert_eq!(side(Density::B, 0.5), -0.02);
+        assert_eq!(side(Density::C, 0.5), -0.04);
+        assert_eq!(side(Density::A, 0.65), 0.05);
+        assert_eq!(side(Density::B, 0.65), 0.03);
+        assert_eq!(side(Density::C, 0.65), 0.01);
+        // only the side fillet depends on the pitch
+        let fine = LeadType::GullWing.goals(Density::B, 0.625);
+        let wide = LeadType::GullWing.goals(Density::B, 1.27);
+        assert_eq!((fine.toe, fine.heel), (wide.toe, wide.heel));
+        assert_eq!(LeadType::Chip.goals(Density::B, 0.5).side, 0.0);
+    }
+
+    #[test]
+    fn fine_pitch() {
+        let leads = Leads {
+            pitch: 0.5,
+            ..soic8()
+        };
+        let tolerances = Tolerances::default();
+        let wide = LandPattern::calculate(&soic8(), LeadType::GullWing, Density::B, &tolerances);
+        let fine = LandPattern::calculate(&leads, LeadType::GullWing, Density::B, &tolerances);
+        assert!(fine.x < wide.x);
+        assert_eq!(fine.pitch, 0.5);
+    }
+
+    #[test]
+    fn chip() {
+        // a 0805 resistor
+        let leads = Leads {
+            span: Dim::nominal(2.0, 0.1),
+            length: Dim::nominal(0.4, 0.2),
+            width: Dim::nominal(1.25, 0.1),
+            pitch: 0.0,
+        };
+        let tolerances = Tolerances::default();
+        let b = LandPattern::calculate(&leads, LeadType::Chip, Density::B, &tolerances);
+        assert!(b.g > 0.0 && b.g < 2.0 - 2.0 * 0.2);
+        assert!(b.z > 2.1);
+        let pad = b.pad("1");
+        assert_eq!(pad.size.x, b.pad_length());
+        assert_eq!(pad.size.y, b.x);
+        let chip = b.chip("R_0805_2012Metric", 2.0, 1.25);
+        assert_eq!(chip.span, b.span());
+    }
+}
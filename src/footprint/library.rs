@@ -0,0 +1,256 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! footprint libraries
+//!
+//! a footprint library is a `.pretty` directory with a `.kicad_mod` file for
+//! every footprint, the file is named after the footprint.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use footprint::Module;
+use {read_module, str_error, write_module, KicadError};
+
+/// a footprint library, a `.pretty` directory
+#[derive(Debug, Clone)]
+pub struct FootprintLibrary {
+    /// the directory
+    pub path: PathBuf,
+}
+
+impl FootprintLibrary {
+    /// open an existing footprint library
+    pub fn open(path: &Path) -> Result<FootprintLibrary, KicadError> {
+        if !path.is_dir() {
+            return str_error(format!("not a footprint library: {}", path.display()));
+        }
+        Ok(FootprintLibrary {
+            path: PathBuf::from(path),
+        })
+    }
+
+    /// open a footprint library, creating the directory if it doesn't exist
+    pub fn create(path: &Path) -> Result<FootprintLibrary, KicadError> {
+        fs::create_dir_all(path)?;
+        FootprintLibrary::open(path)
+    }
+
+    /// the name of the library, the directory name without `.pretty`
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// the file of a footprint, see `footprint_name` for the names that
+    /// are allowed
+    pub fn file_name(&self, name: &str) -> Result<PathBuf, KicadError> {
+        Ok(self.path.join(format!("{}.kicad_mod", footprint_name(name)?)))
+    }
+
+    /// check if the library has a footprint
+    pub fn contains(&self, name: &str) -> bool {
+        match self.file_name(name) {
+            Ok(file) => file.is_file(),
+            Err(_) => false,
+        }
+    }
+
+    /// the names of the footprints, read from the directory while iterating
+    /// and in no particular order
+    pub fn names(&self) -> Result<Names, KicadError> {
+        Ok(Names {
+            dir: fs::read_dir(&self.path)?,
+        })
+    }
+
+    /// the footprints, every footprint is only loaded when iterating gets to it
+    pub fn modules(&self) -> Result<Modules<'_>, KicadError> {
+        Ok(Modules {
+            library: self,
+            names: self.names()?,
+        })
+    }
+
+    /// load a footprint
+    pub fn load(&self, name: &str) -> Result<Module, KicadError> {
+        if !self.contains(name) {
+            return str_error(format!("no footprint {} in {}", name, self.path.display()));
+        }
+        read_module(&self.file_name(name)?)
+    }
+
+    /// write a footprint to the file named after it, replacing the footprint
+    /// if it is already in the library; a `Lib:` prefix is left out of the
+    /// name
+    pub fn save(&self, module: &Module) -> Result<(), KicadError> {
+        let name = footprint_name(&module.name)?;
+        if name == module.name {
+            return write_module(module, &self.file_name(name)?);
+        }
+        let mut module = module.clone();
+        module.name = name.to_string();
+        write_module(&module, &self.file_name(name)?)
+    }
+
+    /// add a footprint that is not yet in the library
+    pub fn add(&self, module: &Module) -> Result<(), KicadError> {
+        if self.contains(footprint_name(&module.name)?) {
+            return str_error(format!(
+                "footprint {} already in {}",
+                module.name,
+                self.path.display()
+            ));
+        }
+        self.save(module)
+    }
+
+    /// rename a footprint, both the file and the name in the file
+    pub fn rename(&self, old: &str, new: &str) -> Result<(), KicadError> {
+        let mut module = self.load(old)?;
+        module.name = footprint_name(new)?.to_string();
+        self.add(&module)?;
+        fs::remove_file(self.file_name(old)?)?;
+        Ok(())
+    }
+
+    /// delete a footprint
+    pub fn delete(&self, name: &str) -> Result<(), KicadError> {
+        if !self.contains(name) {
+            return str_error(format!("no footprint {} in {}", name, self.path.display()));
+        }
+        fs::remove_file(self.file_name(name)?)?;
+        Ok(())
+    }
+
+    /// check that every footprint is named like its file; returns the file
+    /// name and the footprint name of the ones that aren't
+    pub fn check_names(&self) -> Result<Vec<(String, String)>, KicadError> {
+        let mut v = vec![];
+        for name in self.names()? {
+            let name = name?;
+            let module = self.load(&name)?;
+            if module.name != name {
+                v.push((name, module.name))
+            }
+        }
+        v.sort();
+        Ok(v)
+    }
+}
+
+/// the name of a footprint in a library: without the `Lib:` prefix of a
+/// footprint in a layout, and an error for a name that is not a file name
+pub fn footprint_name(name: &str) -> Result<&str, KicadError> {
+    let name = match name.rfind(':') {
+        Some(i) => &name[i + 1..],
+        None => name,
+    };
+    if name.is_empty() || name == "." || name == ".." || name.contains(&['/', '\\'][..]) {
+        return str_error(format!("invalid footprint name: {}", name));
+    }
+    Ok(name)
+}
+
+/// iterator over the names of the footprints of a library
+#[derive(Debug)]
+pub struct Names {
+    dir: fs::ReadDir,
+}
+
+impl Iterator for Names {
+    type Item = Result<String, KicadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let path = match self.dir.next()? {
+                Ok(entry) => entry.path(),
+                Err(e) => return Some(Err(e.into())),
+            };
+            if path.extension() != Some("kicad_mod".as_ref()) {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                return Some(Ok(name.to_string()));
+            }
+        }
+    }
+}
+
+/// iterator over the footprints of a library
+#[derive(Debug)]
+pub struct Modules<'a> {
+    library: &'a FootprintLibrary,
+    names: Names,
+}
+
+impl<'a> Iterator for Modules<'a> {
+    type Item = Result<Module, KicadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.names.next()?;
+        Some(name.and_then(|name| self.library.load(&name)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use footprint::library::FootprintLibrary;
+    use footprint::Module;
+    use write_file;
+
+    #[test]
+    fn library() {
+        let dir = env::temp_dir().join(format!("kicad_parse_gen_{}.pretty", process::id()));
+        let library = FootprintLibrary::create(&dir).unwrap();
+        assert_eq!(library.name(), format!("kicad_parse_gen_{}", process::id()));
+        library.add(&Module::new("R_0603".into())).unwrap();
+        library.add(&Module::new("C_0603".into())).unwrap();
+        assert!(library.add(&Module::new("C_0603".into())).is_err());
+        write_file(library.path.join("README.txt"), "not a footprint").unwrap();
+
+        let mut names: Vec<String> = library.names().unwrap().map(|n| n.unwrap()).collect();
+        names.sort();
+        assert_eq!(names, vec!["C_0603", "R_0603"]);
+        assert_eq!(library.modules().unwrap().count(), 2);
+        assert_eq!(library.load("R_0603").unwrap().name, "R_0603");
+
+        library.rename("R_0603", "R_0805").unwrap();
+        assert!(!library.contains("R_0603"));
+        assert_eq!(library.load("R_0805").unwrap().name, "R_0805");
+        assert!(library.check_names().unwrap().is_empty());
+
+        // a footprint in the wrong file
+        let wrong = library.load("C_0603").unwrap();
+        let (from, to) = (library.file_name("C_0603"), library.file_name("C_0402"));
+        fs::rename(from.unwrap(), to.unwrap()).unwrap();
+        assert_eq!(
+            library.check_names().unwrap(),
+            vec![("C_0402".to_string(), wrong.name)]
+        );
+
+        // a layout footprint goes in without its library prefix
+        library.add(&Module::new("Lib:R_0402".into())).unwrap();
+        assert_eq!(library.load("R_0402").unwrap().name, "R_0402");
+        assert!(library.contains("Lib:R_0402"));
+        // names that are not a file in the library
+        for name in &["../R_0603", "sub/R_0603", "..\\R_0603", "..", "Lib:"] {
+            assert!(library.add(&Module::new(name.to_string())).is_err());
+            assert!(library.file_name(name).is_err());
+            assert!(library.rename("R_0402", name).is_err());
+        }
+        assert!(library.contains("R_0402"));
+        assert!(!dir.parent().unwrap().join("R_0603.kicad_mod").exists());
+        library.delete("R_0402").unwrap();
+
+        library.delete("C_0402").unwrap();
+        assert!(library.delete("C_0402").is_err());
+        assert!(library.load("C_0402").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
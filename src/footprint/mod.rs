@@ -10,6 +10,7 @@ use std::result;
 use symbolic_expressions;
 use symbolic_expressions::IntoSexp;
 use formatter::KicadFormatter;
+use sexpr::requote_board;
 use KicadError;
 
 // pub use footprint;
@@ -18,8 +19,13 @@ pub use footprint::data::*;
 /// convert a Kicad Module (footprint) to a String
 pub fn module_to_string(module: &Module, indent_level: i64) -> Result<String, KicadError> {
     let formatter = KicadFormatter::new(indent_level);
-    symbolic_expressions::ser::to_string_with_formatter(&module.into_sexp(), formatter)
-        .map_err(From::from)
+    let mut sexp = module.into_sexp();
+    if module.footprint {
+        sexp = requote_board(&sexp)
+    }
+    let mut s = symbolic_expressions::ser::to_string_with_formatter(&sexp, formatter)?;
+    s.push('\n');
+    Ok(s)
 }
 
 impl fmt::Display for Layer {
@@ -32,10 +38,12 @@ impl fmt::Display for Layer {
             LayerSide::Eco1 => write!(f, "Eco1."),
             LayerSide::Eco2 => write!(f, "Eco2."),
             LayerSide::Edge => write!(f, "Edge."),
-            LayerSide::In1 => write!(f, "In1."),
-            LayerSide::In2 => write!(f, "In2."),
             LayerSide::Both => write!(f, "*."),
             LayerSide::None => Ok(()),
+            ref x => match x.inner_index() {
+                Some(index) => write!(f, "In{}.", index),
+                None => Ok(()),
+            },
         }?;
         match self.t {
             LayerType::Cu => write!(f, "Cu"),
@@ -60,6 +68,13 @@ pub fn parse(s: &str) -> Result<Module, KicadError> {
     Ok(s)
 }
 
+/// footprint libraries, `.pretty` directories
+pub mod library;
+/// parametric footprint generators for standard packages
+pub mod generate;
+/// IPC-7351 land pattern calculator
+pub mod ipc7351;
+
 mod data;
 mod ser;
 mod de;
@@ -3,11 +3,12 @@
 use Sexp;
 use symbolic_expressions::IntoSexp;
 use footprint::data::*;
+use sexpr::{insert_other, push_width};
 use std::f64;
 
 impl IntoSexp for Module {
     fn into_sexp(&self) -> Sexp {
-        let mut v = Sexp::start("module");
+        let mut v = Sexp::start(if self.footprint { "footprint" } else { "module" });
         v.push(&self.name);
         for e in &self.elements {
             v.push(e.into_sexp())
@@ -23,7 +24,13 @@ impl IntoSexp for Element {
             Element::Layer(ref s) => ("layer", s).into(),
             Element::Descr(ref s) => ("descr", s).into(),
             Element::Tags(ref s) => ("tags", s).into(),
-            Element::Attr(ref s) => ("attr", s).into(),
+            Element::Attr(ref s) => {
+                let mut v = Sexp::start("attr");
+                for flag in s.split_whitespace() {
+                    v.push(flag)
+                }
+                v
+            }
             Element::FpText(ref p) => p.into_sexp(),
             Element::Pad(ref pad) => pad.into_sexp(),
             Element::FpPoly(ref p) => p.into_sexp(),
@@ -31,12 +38,19 @@ impl IntoSexp for Element {
             Element::FpCircle(ref p) => p.into_sexp(),
             Element::FpArc(ref p) => p.into_sexp(),
             Element::TEdit(ref p) => ("tedit", &format!("{:X}", p)).into(),
-            Element::TStamp(ref p) => ("tstamp", &format!("{:X}", p)).into(),
+            Element::TStamp(ref p) => ("tstamp", p).into(),
             Element::Path(ref p) => ("path", p).into(),
             Element::At(ref p) => p.into_sexp(),
             Element::Model(ref p) => p.into_sexp(),
             Element::Clearance(ref s) => ("clearance", s).into(),
             Element::Locked => "locked".into(),
+            Element::Property(ref name, ref value) => {
+                let mut v = Sexp::start("property");
+                v.push(name);
+                v.push(value);
+                v
+            }
+            Element::Other(ref s) => s.clone(),
         }
     }
 }
@@ -52,6 +66,10 @@ impl IntoSexp for FpText {
             v.push("hide");
         }
         v.push(self.effects.into_sexp());
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp));
+        }
+        insert_other(&mut v, &self.other);
         v
     }
 }
@@ -89,23 +107,24 @@ impl IntoSexp for Effects {
     fn into_sexp(&self) -> Sexp {
         let mut v = Sexp::start("effects");
         v.push(self.font.into_sexp());
-        if let Some(ref j) = self.justify {
-            v.push(j.into_sexp())
+        if !self.justify.is_empty() {
+            let mut j = Sexp::start("justify");
+            for justify in &self.justify {
+                j.push(match *justify {
+                    Justify::Mirror => "mirror",
+                    Justify::Left => "left",
+                    Justify::Right => "right",
+                    Justify::Top => "top",
+                    Justify::Bottom => "bottom",
+                })
+            }
+            v.push(j)
         }
+        insert_other(&mut v, &self.other);
         v
     }
 }
 
-impl IntoSexp for Justify {
-    fn into_sexp(&self) -> Sexp {
-        match *self {
-            Justify::Mirror => ("justify", &"mirror").into(),
-            Justify::Left => ("justify", &"left").into(),
-            Justify::Right => ("justify", &"right").into(),
-        }
-    }
-}
-
 impl IntoSexp for Xy {
     fn into_sexp(&self) -> Sexp {
         let mut v = Sexp::start(match self.t {
@@ -115,6 +134,7 @@ impl IntoSexp for Xy {
             XyType::Size => "size",
             XyType::Center => "center",
             XyType::RectDelta => "rect_delta",
+            XyType::Mid => "mid",
         });
         v.push(self.x);
         v.push(self.y);
@@ -172,6 +192,7 @@ impl IntoSexp for PadShape {
             PadShape::Circle => "circle",
             PadShape::Oval => "oval",
             PadShape::Trapezoid => "trapezoid",
+            PadShape::RoundRect => "roundrect",
         }.into()
     }
 }
@@ -198,6 +219,9 @@ impl IntoSexp for Pad {
         v.push(&self.name);
         v.push(self.t.into_sexp());
         v.push(self.shape.into_sexp());
+        if self.locked {
+            v.push("locked");
+        }
         v.push(self.at.into_sexp());
         v.push(self.size.into_sexp());
         if let Some(ref drill) = self.drill {
@@ -207,9 +231,18 @@ impl IntoSexp for Pad {
             v.push(rect_delta.into_sexp());
         }
         v.push(self.layers.into_sexp());
+        if let Some(ref rratio) = self.roundrect_rratio {
+            v.push(("roundrect_rratio", rratio));
+        }
         if let Some(ref net) = self.net {
             v.push(net.into_sexp());
         }
+        if let Some(ref pinfunction) = self.pinfunction {
+            v.push(("pinfunction", pinfunction));
+        }
+        if let Some(ref pintype) = self.pintype {
+            v.push(("pintype", pintype));
+        }
         if let Some(ref zc) = self.zone_connect {
             v.push(("zone_connect", zc));
         }
@@ -225,6 +258,11 @@ impl IntoSexp for Pad {
         if let Some(ref spm) = self.thermal_gap {
             v.push(("thermal_gap", spm));
         }
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp));
+        }
+        // the other expressions go back where they were
+        insert_other(&mut v, &self.other);
         v
     }
 }
@@ -235,6 +273,12 @@ impl IntoSexp for FpPoly {
         v.push(self.pts.into_sexp());
         v.push(("layer", &self.layer));
         v.push(("width", &self.width));
+        if let Some(ref fill) = self.fill {
+            v.push(("fill", fill));
+        }
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp));
+        }
         v
     }
 }
@@ -244,8 +288,11 @@ impl IntoSexp for FpLine {
         let mut v = Sexp::start("fp_line");
         v.push(self.start.into_sexp());
         v.push(self.end.into_sexp());
-        v.push(("layer", &self.layer));
-        v.push(("width", &self.width));
+        push_width(&mut v, self.width, &self.stroke, ("layer", &self.layer).into());
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp));
+        }
+        insert_other(&mut v, &self.other);
         v
     }
 }
@@ -257,6 +304,12 @@ impl IntoSexp for FpCircle {
         v.push(self.end.into_sexp());
         v.push(("layer", &self.layer));
         v.push(("width", &self.width));
+        if let Some(ref fill) = self.fill {
+            v.push(("fill", fill));
+        }
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp));
+        }
         v
     }
 }
@@ -265,10 +318,21 @@ impl IntoSexp for FpArc {
     fn into_sexp(&self) -> Sexp {
         let mut v = Sexp::start("fp_arc");
         v.push(self.start.into_sexp());
-        v.push(self.end.into_sexp());
-        v.push(("angle", &self.angle));
-        v.push(("layer", &self.layer));
-        v.push(("width", &self.width));
+        match self.mid {
+            Some(ref mid) => {
+                v.push(mid.into_sexp());
+                v.push(self.end.into_sexp());
+            }
+            None => {
+                v.push(self.end.into_sexp());
+                v.push(("angle", &self.angle));
+            }
+        }
+        push_width(&mut v, self.width, &self.stroke, ("layer", &self.layer).into());
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp));
+        }
+        insert_other(&mut v, &self.other);
         v
     }
 }
@@ -287,7 +351,7 @@ impl IntoSexp for Model {
     fn into_sexp(&self) -> Sexp {
         let mut v = Sexp::start("model");
         v.push(&self.name);
-        v.push(("at", self.at.into_sexp()));
+        v.push((if self.offset { "offset" } else { "at" }, self.at.into_sexp()));
         v.push(("scale", self.scale.into_sexp()));
         v.push(("rotate", self.rotate.into_sexp()));
         v
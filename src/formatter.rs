@@ -43,13 +43,22 @@ impl Indent {
 }
 
 
+// the graphic elements of boards and footprints that have a line width
+const LINES: &[&str] = &[
+    "fp_line", "fp_arc", "fp_circle", "fp_rect", "fp_poly", "gr_line", "gr_arc", "gr_circle",
+    "gr_rect", "gr_poly",
+];
+
 pub struct KicadFormatter {
     indent: i64,
     stack: Vec<Option<(String, Option<Indent>)>>,
     ind: Vec<u8>,
     pts_xy_count: i64,
-    seen_module: bool,
-    seen_segment: bool,
+    section: Option<&'static str>,
+    // a Kicad 6 or later board
+    kicad6: bool,
+    // the name of the previous element of a schematic
+    previous: Option<String>,
 }
 
 impl KicadFormatter {
@@ -59,8 +68,9 @@ impl KicadFormatter {
             stack: vec![],
             ind: vec![b' ', b' '], // two spaces
             pts_xy_count: 0,
-            seen_module: false,
-            seen_segment: false,
+            section: None,
+            kicad6: false,
+            previous: None,
         }
     }
 
@@ -102,11 +112,11 @@ impl KicadFormatter {
     }
 
     fn want_indent_fp_lib_table(&self, _ele: &str) -> Option<Indent> {
-        if !self.is("fp_lib_table") {
+        if !self.is("fp_lib_table") && !self.is("sym_lib_table") {
             return None;
         }
         let mut indent = Indent::default();
-        if self.parent_is("fp_lib_table") {
+        if self.parent_is("fp_lib_table") || self.parent_is("sym_lib_table") {
             indent.before();
         }
         Some(indent)
@@ -118,16 +128,17 @@ impl KicadFormatter {
         // }
         let mut indent = Indent::default();
         indent.before();
-        if self.parent_is("module") {
+        if self.parent_is("module") || self.parent_is("footprint") {
+            // everything but the first line gets a line of its own, Kicad 6
+            // puts the time stamps on the second line
             match ele {
-                "at" | "descr" | "fp_line" | "fp_poly" | "pad" | "path" | "fp_circle" | "attr" => {
-                    return Some(indent)
-                }
+                "tedit" if self.parent_is("footprint") => return Some(indent),
+                "layer" | "tedit" | "tstamp" => (),
                 "model" | "fp_text" | "gr_text" => {
                     indent.close_on_new_line();
                     return Some(indent);
                 }
-                _ => (),
+                _ => return Some(indent),
             }
         }
         if self.parent_is("dimension") {
@@ -137,7 +148,21 @@ impl KicadFormatter {
             }
         }
         if self.parent_is("fp_text") || self.parent_is("gr_text") {
-            if let "effects" = ele {
+            match ele {
+                "effects" => return Some(indent),
+                "tstamp" if self.parent_is("fp_text") && self.is("footprint") => {
+                    return Some(indent)
+                }
+                _ => (),
+            }
+        }
+        // Kicad 7 puts the stroke of a line on a line of its own
+        if ele == "stroke" && LINES.iter().any(|l| self.parent_is(l)) {
+            return Some(indent);
+        }
+        if self.kicad6 && self.parent_is("gr_poly") {
+            if let "pts" = ele {
+                indent.close_on_new_line();
                 return Some(indent);
             }
         }
@@ -147,7 +172,8 @@ impl KicadFormatter {
                 if self.pts_xy_count > 0 && self.pts_xy_count % wrap == 0 {
                     return Some(indent);
                 } else if self.pts_xy_count == 0
-                    && (self.is("polygon") || self.is("filled_polygon"))
+                    && (self.is("polygon") || self.is("filled_polygon") ||
+                        (self.kicad6 && self.is("gr_poly")))
                 {
                     return Some(indent);
                 }
@@ -155,7 +181,7 @@ impl KicadFormatter {
         }
         if self.parent_is("model") {
             match ele {
-                "at" | "scale" | "rotate" => return Some(indent),
+                "at" | "offset" | "scale" | "rotate" => return Some(indent),
                 _ => (),
             }
         }
@@ -175,31 +201,39 @@ impl KicadFormatter {
         indent.before();
         if self.parent_is("kicad_pcb") {
             match ele {
-                "page" => {
+                "page" | "paper" => {
                     indent.before_double();
                     return Some(indent);
                 }
-                "net" | "gr_circle" | "gr_line" | "gr_arc" | "segment" | "via" => {
-                    return Some(indent)
-                }
+                "net" | "gr_circle" | "gr_line" | "gr_arc" | "gr_poly" | "gr_rect" | "segment" |
+                "arc" | "via" => return Some(indent),
                 "layers" | "gr_text" | "dimension" | "zone" => {
                     indent.close_on_new_line();
                     return Some(indent);
                 }
                 "setup" => {
                     indent.before_double();
-                    indent.close_on_new_line();
                     indent.newline_after_closing();
+                    indent.close_on_new_line();
                     return Some(indent);
                 }
-                "general" | "net_class" | "module" => {
+                "general" | "net_class" | "module" | "footprint" => {
                     indent.before_double();
                     indent.close_on_new_line();
                     return Some(indent);
                 }
-                _ => (),
+                "title_block" => {
+                    indent.close_on_new_line();
+                    indent.newline_after_closing();
+                    return Some(indent);
+                }
+                "version" | "host" | "generator" => (),
+                _ => return Some(indent),
             }
         }
+        if self.parent_is("title_block") {
+            return Some(indent);
+        }
         if self.parent_is("general") {
             return Some(indent);
         }
@@ -207,6 +241,14 @@ impl KicadFormatter {
             return Some(indent);
         }
         if self.parent_is("setup") {
+            match ele {
+                "stackup" => indent.close_on_new_line(),
+                "pcbplotparams" if self.kicad6 => indent.close_on_new_line(),
+                _ => (),
+            }
+            return Some(indent);
+        }
+        if self.parent_is("stackup") {
             return Some(indent);
         }
         if self.parent_is("pcbplotparams") {
@@ -247,6 +289,114 @@ impl KicadFormatter {
         None
     }
 
+    fn want_indent_schematic(&self, ele: &str) -> Option<Indent> {
+        if !self.is("kicad_sch") && !self.is("kicad_symbol_lib") {
+            return None;
+        }
+        let mut indent = Indent::default();
+        indent.before();
+        if self.parent_is("kicad_symbol_lib") {
+            match ele {
+                "version" | "generator" => return None,
+                _ => indent.close_on_new_line(),
+            }
+            return Some(indent);
+        }
+        if self.parent_is("kicad_sch") {
+            match ele {
+                "version" | "generator" => return None,
+                "uuid" | "paper" => indent.before_double(),
+                "title_block" | "lib_symbols" | "symbol" | "sheet" | "sheet_instances" |
+                "symbol_instances" => {
+                    indent.before_double();
+                    indent.close_on_new_line();
+                }
+                "no_connect" => (),
+                _ => indent.close_on_new_line(),
+            }
+            return Some(indent);
+        }
+        if self.parent_is("name") || self.parent_is("number") {
+            return None;
+        }
+        let in_lib = self.is("lib_symbols") || self.is("kicad_symbol_lib");
+        // the pins of a placed symbol only have a uuid
+        let placed_pin = self.parent_is("pin") && !self.is("sheet") && !in_lib;
+        match ele {
+            "uuid" if placed_pin || self.parent_is("no_connect") => None,
+            "in_bom" if self.parent_is("symbol") && !in_lib => Some(indent),
+            "uuid" | "stroke" | "fill" | "effects" | "title" | "date" | "rev" | "company" |
+            "comment" | "reference" | "name" | "number" | "alternate" => Some(indent),
+            "pin" if !self.is("sheet") && !in_lib => Some(indent),
+            "pts" if in_lib && self.parent_is("polyline") => Some(indent),
+            "path" if self.parent_is("sheet_instances") => Some(indent),
+            "property" | "pin" | "symbol" | "rectangle" | "polyline" | "circle" | "arc" |
+            "text" | "instances" | "project" | "path" => {
+                indent.close_on_new_line();
+                Some(indent)
+            }
+            _ => None,
+        }
+    }
+
+    fn want_indent_netlist(&self, ele: &str) -> Option<Indent> {
+        if !self.is("export") {
+            return None;
+        }
+        let mut indent = Indent::default();
+        indent.before();
+        if self.parent_is("export") {
+            match ele {
+                "version" => return None,
+                _ => return Some(indent),
+            }
+        }
+        if self.parent_is("comp") {
+            match ele {
+                "ref" => return None,
+                _ => return Some(indent),
+            }
+        }
+        if self.parent_is("libpart") {
+            match ele {
+                "lib" | "part" => return None,
+                _ => return Some(indent),
+            }
+        }
+        if self.parent_is("library") {
+            if let "uri" = ele {
+                return Some(indent);
+            }
+        }
+        if self.parent_is("net") {
+            if let "node" = ele {
+                return Some(indent);
+            }
+        }
+        if self.parent_is("sheet") {
+            if let "title_block" = ele {
+                return Some(indent);
+            }
+        }
+        for parent in &[
+            "design",
+            "title_block",
+            "components",
+            "fields",
+            "libparts",
+            "aliases",
+            "footprints",
+            "pins",
+            "libraries",
+            "nets",
+        ] {
+            if self.parent_is(parent) {
+                return Some(indent);
+            }
+        }
+        None
+    }
+
     fn want_indent(&self, value: &Sexp) -> Option<Indent> {
         let first = match *value {
             Sexp::List(ref l) => {
@@ -259,6 +409,10 @@ impl KicadFormatter {
             Sexp::String(ref l) => Sexp::String(l.clone()),
         };
         if let Sexp::String(ref ele) = first {
+            let i = self.want_indent_schematic(ele);
+            if i.is_some() {
+                return i;
+            }
             let i = self.want_indent_module(ele);
             if i.is_some() {
                 return i;
@@ -271,11 +425,27 @@ impl KicadFormatter {
             if i.is_some() {
                 return i;
             }
+            let i = self.want_indent_netlist(ele);
+            if i.is_some() {
+                return i;
+            }
         }
         None
     }
 }
 
+// kicad writes a board in sections, the modules, the drawings and the
+// tracks are each followed by an empty line
+fn board_section(ele: &str) -> Option<&'static str> {
+    match ele {
+        "module" | "footprint" => Some("module"),
+        "gr_text" | "gr_line" | "gr_arc" | "gr_circle" | "gr_curve" | "gr_poly" | "gr_rect" |
+        "dimension" | "target" => Some("drawing"),
+        "segment" | "arc" | "via" => Some("track"),
+        _ => None,
+    }
+}
+
 impl Formatter for KicadFormatter {
     fn open<W>(&mut self, writer: &mut W, value: Option<&Sexp>) -> Result<(), SexpError>
     where
@@ -290,23 +460,33 @@ impl Formatter for KicadFormatter {
         }
 
         // special handling: ugly :(
-        // write an extra newline before the first element after the last module
-        if let "module" = &ele[..] {
-            self.seen_module = true;
-        } else if self.parent_is("kicad_pcb") && self.seen_module {
-            self.seen_module = false;
-            self.indent(writer, 1)?;
+        // write an extra newline after the modules, the drawings and the tracks
+        // and for Kicad 6 after the nets, unless the next element already
+        // starts after an empty line
+        let exp = Sexp::String(ele.clone());
+        if self.parent_is("kicad_pcb") {
+            if ele == "generator" {
+                self.kicad6 = true;
+            }
+            let section = match board_section(&ele) {
+                None if self.kicad6 && ele == "net" => Some("net"),
+                section => section,
+            };
+            let empty_line = self.want_indent(&exp).is_some_and(|i| i.newline_before > 1);
+            if self.section.is_some() && section != self.section && !empty_line {
+                self.indent(writer, 1)?;
+            }
+            self.section = section;
         }
-
-        // write an extra newline before the first segment
-        if !self.seen_segment {
-            if let "segment" = &ele[..] {
-                self.seen_segment = true;
+        // and between the groups of elements of the same kind in a schematic
+        if self.parent_is("kicad_sch") {
+            let single = self.want_indent(&exp).is_some_and(|i| i.newline_before == 1);
+            if single && self.previous.as_ref().is_some_and(|p| *p != ele) {
                 self.indent(writer, 1)?;
             }
+            self.previous = Some(ele.clone());
         }
 
-        let exp = Sexp::String(ele.clone());
         let want_indent = self.want_indent(&exp);
         if let Some(ref want_indent) = want_indent {
             self.indent += 1;
@@ -361,7 +541,7 @@ impl Formatter for KicadFormatter {
                 }
                 // special handling of toplevel module...
                 // which doesn't work, because it is not indented
-                if &s == "module" && self.stack.is_empty() {
+                if (&s == "module" || &s == "footprint") && self.stack.is_empty() {
                     writer.write_all(b"\n")?;
                 }
                 writer.write_all(b")")?;
@@ -370,9 +550,14 @@ impl Formatter for KicadFormatter {
                 }
                 return Ok(());
             } else if self.stack.is_empty()
-                && (&s == "module" || &s == "kicad_pcb" || &s == "fp_lib_table")
+                && (&s == "module" || &s == "footprint" || &s == "kicad_pcb" ||
+                    &s == "fp_lib_table" || &s == "sym_lib_table" ||
+                    &s == "kicad_sch" || &s == "kicad_symbol_lib")
             {
                 writer.write_all(b"\n")?;
+                if &s == "kicad_pcb" && self.section.is_some() {
+                    writer.write_all(b"\n")?;
+                }
             }
         }
         writer.write_all(b")")?;
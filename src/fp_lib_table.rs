@@ -7,8 +7,11 @@ use symbolic_expressions;
 use symbolic_expressions::{IntoSexp, Sexp, SexpError};
 use formatter::KicadFormatter;
 use symbolic_expressions::iteratom::*;
-use shellexpand;
 use error::KicadError;
+use expand::ExpandContext;
+use footprint::library::FootprintLibrary;
+use footprint::Module;
+use std::path::Path;
 
 /// a fp-lib-table
 #[derive(Debug, Clone)]
@@ -32,11 +35,72 @@ pub struct Lib {
     pub descr: String,
 }
 
+impl FpLibTable {
+    /// chain the table over another one like a project table over the global
+    /// table, the libraries of this table take precedence
+    pub fn chain(&self, global: &FpLibTable) -> FpLibTable {
+        let mut libs = self.libs.clone();
+        for lib in &global.libs {
+            if self.get(&lib.name).is_none() {
+                libs.push(lib.clone())
+            }
+        }
+        FpLibTable { libs }
+    }
+
+    /// get a library entry by its nickname
+    pub fn get(&self, nickname: &str) -> Option<&Lib> {
+        self.libs.iter().find(|l| l.name == nickname)
+    }
+
+    /// open the footprint library of a nickname, expanding its URI with the
+    /// environment
+    pub fn library(&self, nickname: &str) -> Result<FootprintLibrary, KicadError> {
+        self.library_with(nickname, &ExpandContext::default())
+    }
+
+    /// open the footprint library of a nickname, expanding its URI in a context
+    pub fn library_with(
+        &self,
+        nickname: &str,
+        context: &ExpandContext,
+    ) -> Result<FootprintLibrary, KicadError> {
+        let lib = match self.get(nickname) {
+            Some(lib) => lib,
+            None => return Err(format!("library {} not found in fp-lib-table", nickname).into()),
+        };
+        if !lib.type_.eq_ignore_ascii_case("kicad") {
+            let e = format!("library {} of type {} is not supported", nickname, lib.type_);
+            return Err(e.into());
+        }
+        FootprintLibrary::open(Path::new(&lib.expand_uri(context)?))
+    }
+
+    /// load a footprint from a `Lib:Name` identifier like the ones of the
+    /// footprint field of a component or of a module in a layout
+    pub fn resolve(&self, id: &str) -> Result<Module, KicadError> {
+        self.resolve_with(id, &ExpandContext::default())
+    }
+
+    /// load a footprint from a `Lib:Name` identifier, expanding the URI of
+    /// the library in a context
+    pub fn resolve_with(&self, id: &str, context: &ExpandContext) -> Result<Module, KicadError> {
+        match id.find(':') {
+            Some(i) => self.library_with(&id[..i], context)?.load(&id[i + 1..]),
+            None => Err(format!("footprint {} has no library", id).into()),
+        }
+    }
+}
+
 impl Lib {
     /// return the URI with environment variables substituted
     pub fn get_expanded_uri(&self) -> Result<String, KicadError> {
-        let s = shellexpand::full(&self.uri)?;
-        Ok(s.into())
+        self.expand_uri(&ExpandContext::default())
+    }
+
+    /// return the URI with the variables of a context substituted
+    pub fn expand_uri(&self, context: &ExpandContext) -> Result<String, KicadError> {
+        context.expand(&self.uri)
     }
 }
 
@@ -101,3 +165,59 @@ pub fn to_string(fp_lib_table: &FpLibTable, indent_level: i64) -> Result<String,
     symbolic_expressions::ser::to_string_with_formatter(&fp_lib_table.into_sexp(), formatter)
         .map_err(From::from)
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use footprint::library::FootprintLibrary;
+    use footprint::{Element, Module};
+    use expand::ExpandContext;
+    use fp_lib_table::{parse, FpLibTable};
+    use test_util;
+
+    fn table_of(libs: &[(&str, &str)]) -> FpLibTable {
+        let mut s = String::from("(fp_lib_table\n");
+        for &(name, uri) in libs {
+            s.push_str(&format!(
+                "  (lib (name {})(type KiCad)(uri {})(options \"\")(descr \"\"))\n",
+                name, uri
+            ));
+        }
+        s.push(')');
+        parse(&s).unwrap()
+    }
+
+    #[test]
+    fn resolve() {
+        let dir = test_util::temp_dir("resolve");
+        let project = FootprintLibrary::create(&dir.join("project.pretty")).unwrap();
+        let global = FootprintLibrary::create(&dir.join("global.pretty")).unwrap();
+        let mut module = Module::new("R_0603".into());
+        module.elements.push(Element::Descr("project".into()));
+        project.add(&module).unwrap();
+        global.add(&Module::new("R_0603".into())).unwrap();
+        global.add(&Module::new("C_0603".into())).unwrap();
+
+        let project_table = table_of(&[("Passives", &project.path.to_string_lossy())]);
+        let global_table = table_of(&[
+            ("Passives", &global.path.to_string_lossy()),
+            ("Capacitors", &global.path.to_string_lossy()),
+        ]);
+        let table = project_table.chain(&global_table);
+        assert_eq!(table.libs.len(), 2);
+        // the project library shadows the global one
+        let r = table.resolve("Passives:R_0603").unwrap();
+        assert!(r.elements.contains(&Element::Descr("project".into())));
+        assert_eq!(table.resolve("Capacitors:C_0603").unwrap().name, "C_0603");
+        assert!(table.resolve("Passives:C_0603").is_err());
+        assert!(table.resolve("Missing:R_0603").is_err());
+        assert!(table.resolve("R_0603").is_err());
+
+        // a project library relative to the project directory
+        let project_table = table_of(&[("Passives", "${KIPRJMOD}/project.pretty")]);
+        let context = ExpandContext::for_project(&dir);
+        assert!(project_table.resolve_with("Passives:R_0603", &context).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
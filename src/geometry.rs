@@ -0,0 +1,440 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+// geometry of copper shapes, shared by the layout checks and generators
+
+use footprint::{At, Pad, PadShape, Pts};
+use Bound;
+
+// number of segments of a polygon around a circle or along an arc
+const ROUND_SEGMENTS: usize = 16;
+
+/// a point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    /// x coordinate
+    pub x: f64,
+    /// y coordinate
+    pub y: f64,
+}
+
+impl Point {
+    /// create a point
+    pub fn new(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+}
+
+/// rotate a coordinate the way Kicad does, with the rotation in degrees
+/// counter-clockwise as seen on screen (y pointing down)
+pub fn rotate(x: f64, y: f64, rot: f64) -> (f64, f64) {
+    if rot == 0.0 {
+        return (x, y);
+    }
+    let (sin, cos) = rot.to_radians().sin_cos();
+    (x * cos + y * sin, y * cos - x * sin)
+}
+
+/// a copper shape
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// a line from start to end with round ends; a circle when start equals end
+    Round {
+        /// start of the line
+        start: Point,
+        /// end of the line
+        end: Point,
+        /// half of the width of the line
+        radius: f64,
+    },
+    /// a closed polygon
+    Polygon(Vec<Point>),
+}
+
+impl Shape {
+    /// create a circle
+    pub fn circle(center: Point, radius: f64) -> Shape {
+        Shape::Round {
+            start: center,
+            end: center,
+            radius,
+        }
+    }
+
+    /// create a track of a certain width
+    pub fn track(start: Point, end: Point, width: f64) -> Shape {
+        Shape::Round {
+            start,
+            end,
+            radius: width / 2.0,
+        }
+    }
+
+    /// create a polygon from a list of X-Y coordinates
+    pub fn polygon(pts: &Pts) -> Shape {
+        Shape::Polygon(pts.elements.iter().map(|p| Point::new(p.x, p.y)).collect())
+    }
+
+    /// create the shape of a pad placed on a certain location
+    /// `position` maps a coordinate relative to the module to an absolute one
+    pub fn pad<F>(pad: &Pad, position: F) -> Shape
+    where
+        F: Fn(f64, f64) -> (f64, f64),
+    {
+        let center = pad_center(pad, position);
+        let place = |x: f64, y: f64| {
+            let (x, y) = rotate(x, y, pad.at.rot);
+            Point::new(center.x + x, center.y + y)
+        };
+        let hx = pad.size.x / 2.0;
+        let hy = pad.size.y / 2.0;
+        match pad.shape {
+            PadShape::Circle => Shape::circle(center, hx),
+            PadShape::Oval => {
+                if hx > hy {
+                    Shape::Round {
+                        start: place(hy - hx, 0.0),
+                        end: place(hx - hy, 0.0),
+                        radius: hy,
+                    }
+                } else {
+                    Shape::Round {
+                        start: place(0.0, hx - hy),
+                        end: place(0.0, hy - hx),
+                        radius: hx,
+                    }
+                }
+            }
+            // the rounded corners only make a rounded rectangle smaller
+            PadShape::Rect | PadShape::RoundRect => Shape::Polygon(vec![
+                place(-hx, hy),
+                place(-hx, -hy),
+                place(hx, -hy),
+                place(hx, hy),
+            ]),
+            PadShape::Trapezoid => {
+                let (dx, dy) = match pad.rect_delta {
+                    Some(ref delta) => (delta.x / 2.0, delta.y / 2.0),
+                    None => (0.0, 0.0),
+                };
+                Shape::Polygon(vec![
+                    place(-hx - dy, hy + dx),
+                    place(-hx + dy, -hy - dx),
+                    place(hx - dy, -hy + dx),
+                    place(hx + dy, hy - dx),
+                ])
+            }
+        }
+    }
+
+    /// create the shape of a rounded rectangle pad placed on a certain
+    /// location with the corners rounded by `radius`; `pad` keeps them sharp
+    pub fn rounded_pad<F>(pad: &Pad, radius: f64, position: F) -> Shape
+    where
+        F: Fn(f64, f64) -> (f64, f64),
+    {
+        let center = pad_center(pad, position);
+        let hx = pad.size.x / 2.0;
+        let hy = pad.size.y / 2.0;
+        let r = radius.max(0.0).min(hx).min(hy);
+        let n = ROUND_SEGMENTS / 4;
+        let mut v = vec![];
+        // a quarter circle per corner, starting at the bottom left
+        for i in 0..4 {
+            let start = (90.0 * (i + 1) as f64).to_radians();
+            let (sin, cos) = (start + ::std::f64::consts::FRAC_PI_4).sin_cos();
+            let (cx, cy) = ((hx - r) * cos.signum(), (hy - r) * sin.signum());
+            for j in 0..n + 1 {
+                let a = start + ::std::f64::consts::FRAC_PI_2 * j as f64 / n as f64;
+                let (x, y) = rotate(cx + r * a.cos(), cy + r * a.sin(), pad.at.rot);
+                v.push(Point::new(center.x + x, center.y + y))
+            }
+        }
+        Shape::Polygon(v)
+    }
+
+    /// calculate the bounding box of the shape
+    pub fn bound(&self) -> Bound {
+        match *self {
+            Shape::Round {
+                ref start,
+                ref end,
+                radius,
+            } => Bound::new(
+                start.x.min(end.x) - radius,
+                start.y.min(end.y) - radius,
+                start.x.max(end.x) + radius,
+                start.y.max(end.y) + radius,
+            ),
+            Shape::Polygon(ref points) => {
+                let mut b = Bound::default();
+                for p in points {
+                    b.update(&Bound::new(p.x, p.y, p.x, p.y));
+                }
+                b
+            }
+        }
+    }
+
+    /// points around the outline of the shape; the round ends of a line
+    /// are approximated by a polygon just around them
+    pub fn outline(&self) -> Vec<Point> {
+        match *self {
+            Shape::Round {
+                ref start,
+                ref end,
+                radius,
+            } => {
+                // the sides of the polygon touch the circle at the top,
+                // bottom, left and right
+                let n = ROUND_SEGMENTS as f64;
+                let r = radius / (::std::f64::consts::PI / n).cos();
+                let mut v = vec![];
+                for c in &[start, end] {
+                    for i in 0..ROUND_SEGMENTS {
+                        let a = (i as f64 + 0.5) * 2.0 * ::std::f64::consts::PI / n;
+                        v.push(Point::new(c.x + r * a.cos(), c.y + r * a.sin()))
+                    }
+                }
+                v
+            }
+            Shape::Polygon(ref points) => points.clone(),
+        }
+    }
+
+    /// calculate the distance between the edges of two shapes;
+    /// this is 0.0 when the shapes touch or overlap
+    pub fn distance(&self, other: &Shape) -> f64 {
+        match (self, other) {
+            (
+                Shape::Round {
+                    start: s1,
+                    end: e1,
+                    radius: r1,
+                },
+                Shape::Round {
+                    start: s2,
+                    end: e2,
+                    radius: r2,
+                },
+            ) => (segment_distance(s1, e1, s2, e2) - r1 - r2).max(0.0),
+            (Shape::Round { start, end, radius }, Shape::Polygon(points))
+            | (Shape::Polygon(points), Shape::Round { start, end, radius }) => {
+                if inside(start, points) {
+                    return 0.0;
+                }
+                (polygon_distance(start, end, points) - radius).max(0.0)
+            }
+            (Shape::Polygon(p1), Shape::Polygon(p2)) => {
+                if p1.is_empty() || p2.is_empty() {
+                    return f64::MAX;
+                }
+                if inside(&p1[0], p2) || inside(&p2[0], p1) {
+                    return 0.0;
+                }
+                let mut d = f64::MAX;
+                for (a, b) in edges(p1) {
+                    d = d.min(polygon_distance(a, b, p2));
+                }
+                d
+            }
+        }
+    }
+}
+
+/// points on an arc around a center from a start point over an angle in
+/// degrees, clockwise as seen on screen like a Kicad 5 arc
+pub fn arc_points(center: &Point, start: &Point, angle: f64) -> Vec<Point> {
+    let (dx, dy) = (start.x - center.x, start.y - center.y);
+    (0..ROUND_SEGMENTS + 1)
+        .map(|i| {
+            let (x, y) = rotate(dx, dy, -angle * i as f64 / ROUND_SEGMENTS as f64);
+            Point::new(center.x + x, center.y + y)
+        })
+        .collect()
+}
+
+/// points on an arc from a start point through a middle point to an end
+/// point like a Kicad 6 arc
+pub fn arc_points_through(start: &Point, mid: &Point, end: &Point) -> Vec<Point> {
+    let d = 2.0 * cross(start, mid, end);
+    if d.abs() < 1e-9 {
+        // a straight line
+        return vec![*start, *mid, *end];
+    }
+    // the center of the circle through the three points
+    let (bx, by) = (mid.x - start.x, mid.y - start.y);
+    let (cx, cy) = (end.x - start.x, end.y - start.y);
+    let (b2, c2) = (bx * bx + by * by, cx * cx + cy * cy);
+    let center = Point::new(
+        start.x + (cy * b2 - by * c2) / d,
+        start.y + (bx * c2 - cx * b2) / d,
+    );
+    let angle = |p: &Point| (p.y - center.y).atan2(p.x - center.x);
+    let turn = |a: f64| {
+        let full = 2.0 * ::std::f64::consts::PI;
+        ((a % full) + full) % full
+    };
+    let a = angle(start);
+    let sweep = turn(angle(end) - a);
+    // go the other way around when the middle point is not on the way
+    let sweep = if turn(angle(mid) - a) < sweep {
+        sweep
+    } else {
+        sweep - 2.0 * ::std::f64::consts::PI
+    };
+    let r = ((start.x - center.x).powi(2) + (start.y - center.y).powi(2)).sqrt();
+    (0..ROUND_SEGMENTS + 1)
+        .map(|i| {
+            let a = a + sweep * i as f64 / ROUND_SEGMENTS as f64;
+            Point::new(center.x + r * a.cos(), center.y + r * a.sin())
+        })
+        .collect()
+}
+
+/// the convex hull of points, without points on the edges
+pub fn convex_hull(points: &[Point]) -> Vec<Point> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| {
+        a.x
+            .partial_cmp(&b.x)
+            .unwrap()
+            .then(a.y.partial_cmp(&b.y).unwrap())
+    });
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    // the lower and the upper part by Andrew's monotone chain
+    let mut hull: Vec<Point> = vec![];
+    for pass in 0..2 {
+        let start = hull.len();
+        for p in &points {
+            while hull.len() >= start + 2
+                && cross(&hull[hull.len() - 2], &hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(*p)
+        }
+        hull.pop();
+        if pass == 0 {
+            points.reverse()
+        }
+    }
+    hull
+}
+
+/// grow a convex polygon from `convex_hull` by a distance, the corners stay
+/// sharp
+pub fn grow(polygon: &[Point], distance: f64) -> Vec<Point> {
+    let n = polygon.len();
+    if n < 3 {
+        return polygon.to_vec();
+    }
+    // the outward normals of the edges
+    let normals: Vec<(f64, f64)> = edges(polygon)
+        .into_iter()
+        .map(|(a, b)| {
+            let (dx, dy) = (b.x - a.x, b.y - a.y);
+            let len = (dx * dx + dy * dy).sqrt();
+            (dy / len, -dx / len)
+        })
+        .collect();
+    (0..n)
+        .map(|i| {
+            // the corner where the moved edges before and after meet
+            let (n1x, n1y) = normals[(i + n - 1) % n];
+            let (n2x, n2y) = normals[i];
+            let p = &polygon[i];
+            let d1 = n1x * p.x + n1y * p.y + distance;
+            let d2 = n2x * p.x + n2y * p.y + distance;
+            let det = n1x * n2y - n1y * n2x;
+            Point::new((d1 * n2y - d2 * n1y) / det, (n1x * d2 - n2x * d1) / det)
+        })
+        .collect()
+}
+
+/// check if two bounds overlap when one of them is grown by a margin
+pub fn bounds_overlap(a: &Bound, b: &Bound, margin: f64) -> bool {
+    a.x1 - margin <= b.x2 && b.x1 <= a.x2 + margin && a.y1 - margin <= b.y2
+        && b.y1 <= a.y2 + margin
+}
+
+// the center of the copper of a pad, which is moved by the drill offset
+fn pad_center<F>(pad: &Pad, position: F) -> Point
+where
+    F: Fn(f64, f64) -> (f64, f64),
+{
+    let (ox, oy) = match pad.drill {
+        Some(ref drill) => rotate(drill.offset_x, drill.offset_y, pad.at.rot),
+        None => (0.0, 0.0),
+    };
+    let (cx, cy) = position(pad.at.x, pad.at.y);
+    Point::new(cx + ox, cy + oy)
+}
+
+/// the absolute position of a pad in a module placed at a location
+pub fn module_position(at: &At, x: f64, y: f64) -> (f64, f64) {
+    let (x, y) = rotate(x, y, at.rot);
+    (at.x + x, at.y + y)
+}
+
+fn edges(points: &[Point]) -> Vec<(&Point, &Point)> {
+    let n = points.len();
+    (0..n).map(|i| (&points[i], &points[(i + 1) % n])).collect()
+}
+
+fn inside(p: &Point, points: &[Point]) -> bool {
+    let mut result = false;
+    for (a, b) in edges(points) {
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            result = !result;
+        }
+    }
+    result
+}
+
+fn polygon_distance(start: &Point, end: &Point, points: &[Point]) -> f64 {
+    let mut d = f64::MAX;
+    for (a, b) in edges(points) {
+        d = d.min(segment_distance(start, end, a, b));
+    }
+    d
+}
+
+fn point_segment_distance(p: &Point, a: &Point, b: &Point) -> f64 {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 == 0.0 {
+        0.0
+    } else {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / len2).clamp(0.0, 1.0)
+    };
+    let x = a.x + t * dx - p.x;
+    let y = a.y + t * dy - p.y;
+    (x * x + y * y).sqrt()
+}
+
+fn cross(o: &Point, a: &Point, b: &Point) -> f64 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+fn segments_cross(a1: &Point, a2: &Point, b1: &Point, b2: &Point) -> bool {
+    let d1 = cross(b1, b2, a1);
+    let d2 = cross(b1, b2, a2);
+    let d3 = cross(a1, a2, b1);
+    let d4 = cross(a1, a2, b2);
+    ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+}
+
+fn segment_distance(a1: &Point, a2: &Point, b1: &Point, b2: &Point) -> f64 {
+    if segments_cross(a1, a2, b1, b2) {
+        return 0.0;
+    }
+    point_segment_distance(a1, b1, b2)
+        .min(point_segment_distance(a2, b1, b2))
+        .min(point_segment_distance(b1, a1, a2))
+        .min(point_segment_distance(b2, a1, a2))
+}
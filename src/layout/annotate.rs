@@ -0,0 +1,716 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! forward and back annotation between a layout and its schematic
+//!
+//! forward annotation updates a layout from a netlist: modules are paired
+//! with the components of the netlist by their `path` timestamps, falling
+//! back to the reference. Missing modules are loaded through an
+//! fp-lib-table and placed at the origin, modules without a component are
+//! removed unless they are locked.
+//!
+//! back annotation pushes the references, values and footprints of the
+//! modules into the schematic components with the same `path`, across all
+//! sheets of the schematic.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use expand::ExpandContext;
+use footprint;
+use footprint::{Flip, Rotate};
+use fp_lib_table::FpLibTable;
+use layout::{Element, Layout, Net, NetName};
+use netlist::{self, Netlist};
+use schematic::{self, Component, Schematic};
+use symbol_lib::SymbolLib;
+use {write_schematic, KicadError};
+
+/// a change made to a layout
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// a module was added for a component
+    Added(String),
+    /// a module without component was removed
+    Removed(String),
+    /// the reference of a module changed from the first to the second
+    Reference(String, String),
+    /// the value of a module changed
+    Value(String, String, String),
+    /// the footprint of a module was replaced
+    Footprint(String, String, String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::Added(ref r) => write!(f, "added {}", r),
+            Change::Removed(ref r) => write!(f, "removed {}", r),
+            Change::Reference(ref old, ref new) => write!(f, "renamed {} to {}", old, new),
+            Change::Value(ref r, ref old, ref new) => {
+                write!(f, "changed value of {} from {} to {}", r, old, new)
+            }
+            Change::Footprint(ref r, ref old, ref new) => {
+                write!(f, "changed footprint of {} from {} to {}", r, old, new)
+            }
+        }
+    }
+}
+
+/// update a layout to match a netlist, loading new footprints through
+/// the fp-lib-table with its URIs expanded from the environment
+pub fn forward(
+    layout: &mut Layout,
+    netlist: &Netlist,
+    table: &FpLibTable,
+) -> Result<Vec<Change>, KicadError> {
+    forward_with(layout, netlist, table, &ExpandContext::default())
+}
+
+/// update a layout to match a netlist, loading new footprints through
+/// the fp-lib-table with its URIs expanded in a context, like the one of
+/// the project for `${KIPRJMOD}`
+pub fn forward_with(
+    layout: &mut Layout,
+    netlist: &Netlist,
+    table: &FpLibTable,
+    context: &ExpandContext,
+) -> Result<Vec<Change>, KicadError> {
+    update(layout, netlist, |name| table.resolve_with(name, context))
+}
+
+/// update a layout to match a schematic including its sub-sheets
+pub fn forward_from_schematic(
+    layout: &mut Layout,
+    schematic: &Schematic,
+    libs: &[&SymbolLib],
+    table: &FpLibTable,
+    context: &ExpandContext,
+) -> Result<Vec<Change>, KicadError> {
+    let netlist = Netlist::from_schematic(schematic, libs)?;
+    forward_with(layout, &netlist, table, context)
+}
+
+/// update the schematic in `filename` and its sub-sheets from the modules
+/// of a layout; the changed sheet files are written back
+pub fn back(layout: &Layout, filename: &Path) -> Result<Vec<Change>, KicadError> {
+    let (mut sheets, instances) = schematic::load_hierarchy(filename)?;
+    let (changes, mut touched) = back_annotate(layout, &mut sheets, &instances);
+    if schematic::kicad_sch::update_symbol_instances(&mut sheets)? && !touched.contains(&0) {
+        touched.push(0)
+    }
+    for i in touched {
+        if let Some(ref name) = sheets[i].filename {
+            write_schematic(&sheets[i], name)?
+        }
+    }
+    Ok(changes)
+}
+
+fn update<F>(layout: &mut Layout, netlist: &Netlist, load: F) -> Result<Vec<Change>, KicadError>
+where
+    F: Fn(&str) -> Result<footprint::Module, KicadError>,
+{
+    let mut changes = vec![];
+    let mut placed = vec![false; netlist.components.len()];
+    // the layout only changes when every footprint could be loaded
+    let mut elements = vec![];
+    for element in layout.elements.iter().cloned() {
+        let module = match element {
+            Element::Module(module) => module,
+            e => {
+                elements.push(e);
+                continue;
+            }
+        };
+        let reference = module.get_reference().cloned().unwrap_or_default();
+        let i = match pair(netlist, &placed, &module, &reference) {
+            Some(i) => i,
+            None => {
+                if module.elements.contains(&footprint::Element::Locked) {
+                    elements.push(Element::Module(module))
+                } else {
+                    changes.push(Change::Removed(reference))
+                }
+                continue;
+            }
+        };
+        placed[i] = true;
+        let component = &netlist.components[i];
+        let footprint = component.footprint.clone().unwrap_or_default();
+        let mut module = if !footprint.is_empty() && footprint != module.name {
+            changes.push(Change::Footprint(
+                component.reference.clone(),
+                module.name.clone(),
+                footprint.clone(),
+            ));
+            let mut new = place(load(&footprint)?, &footprint, &module);
+            set_text(&mut new, "reference", &reference);
+            set_text(&mut new, "value", &get_value(&module));
+            new
+        } else {
+            module
+        };
+        if reference != component.reference {
+            changes.push(Change::Reference(reference, component.reference.clone()));
+            set_text(&mut module, "reference", &component.reference);
+        }
+        let value = get_value(&module);
+        if value != component.value {
+            let new = component.value.clone();
+            changes.push(Change::Value(component.reference.clone(), value, new));
+            set_text(&mut module, "value", &component.value);
+        }
+        if let Some(path) = component.path() {
+            set_path(&mut module, &path);
+        }
+        elements.push(Element::Module(module))
+    }
+    for (i, component) in netlist.components.iter().enumerate() {
+        if placed[i] {
+            continue;
+        }
+        let footprint = match component.footprint {
+            Some(ref footprint) => footprint,
+            None => {
+                return Err(format!("component {} has no footprint", component.reference).into())
+            }
+        };
+        let mut module = load(footprint)?;
+        module.name = footprint.clone();
+        add_at(&mut module);
+        set_text(&mut module, "reference", &component.reference);
+        set_text(&mut module, "value", &component.value);
+        if let Some(path) = component.path() {
+            set_path(&mut module, &path);
+        }
+        changes.push(Change::Added(component.reference.clone()));
+        elements.push(Element::Module(module))
+    }
+    layout.elements = elements;
+    update_nets(layout, netlist);
+    Ok(changes)
+}
+
+// find the unplaced component for a module, first by path then by reference
+fn pair(
+    netlist: &Netlist,
+    placed: &[bool],
+    module: &footprint::Module,
+    reference: &str,
+) -> Option<usize> {
+    let free = |i: &usize| !placed[*i];
+    let by_path = get_path(module).and_then(|path| {
+        (0..netlist.components.len())
+            .filter(&free)
+            .find(|&i| netlist.components[i].path().as_ref().map(|p| &p[..]) == Some(path))
+    });
+    by_path.or_else(|| {
+        (0..netlist.components.len())
+            .filter(&free)
+            .find(|&i| netlist.components[i].reference == reference)
+    })
+}
+
+// a library module placed like an existing module
+fn place(mut new: footprint::Module, name: &str, old: &footprint::Module) -> footprint::Module {
+    new.name = name.to_string();
+    new.footprint = old.footprint;
+    add_at(&mut new);
+    if !old.is_front() {
+        new.flip()
+    }
+    new.rotate(old.get_rotation());
+    let (x, y) = old.at();
+    new.adjust_at(x, y);
+    for element in &old.elements {
+        match *element {
+            footprint::Element::Path(_) |
+            footprint::Element::TStamp(_) |
+            footprint::Element::Property(_, _) |
+            footprint::Element::Locked => new.elements.push(element.clone()),
+            _ => (),
+        }
+    }
+    new
+}
+
+// library modules have no location, a module on a layout always has one
+fn add_at(module: &mut footprint::Module) {
+    if module.elements.iter().any(|e| matches!(*e, footprint::Element::At(_))) {
+        return;
+    }
+    let i = module
+        .elements
+        .iter()
+        .rposition(|e| matches!(*e, footprint::Element::Layer(_) | footprint::Element::TEdit(_)))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    module.elements.insert(i, footprint::Element::At(footprint::At::new(0.0, 0.0, 0.0)))
+}
+
+fn get_value(module: &footprint::Module) -> String {
+    module
+        .get_value_text()
+        .map(|t| t.value.clone())
+        .unwrap_or_default()
+}
+
+fn set_text(module: &mut footprint::Module, which: &'static str, value: &str) {
+    if let Some(text) = module.get_text_mut(which) {
+        text.value = value.to_string()
+    }
+}
+
+fn set_path(module: &mut footprint::Module, path: &str) {
+    for element in &mut module.elements {
+        if let footprint::Element::Path(ref mut p) = *element {
+            *p = path.to_string();
+            return;
+        }
+    }
+    module.elements.push(footprint::Element::Path(path.to_string()))
+}
+
+fn get_path(module: &footprint::Module) -> Option<&str> {
+    module
+        .elements
+        .iter()
+        .filter_map(|e| match *e {
+            footprint::Element::Path(ref p) => Some(&p[..]),
+            _ => None,
+        })
+        .next()
+}
+
+// update the components of all sheet instances from the modules paired by
+// path; returns the changes and the indices of the changed sheet files
+fn back_annotate(
+    layout: &Layout,
+    sheets: &mut [Schematic],
+    instances: &[schematic::SheetInstance],
+) -> (Vec<Change>, Vec<usize>) {
+    let modules: HashMap<&str, &footprint::Module> = layout
+        .elements
+        .iter()
+        .filter_map(|e| match *e {
+            Element::Module(ref m) => get_path(m).map(|p| (p, m)),
+            _ => None,
+        })
+        .collect();
+    // only one unit of a multi-unit component has a module, the other
+    // units follow it by reference
+    let mut paired = HashMap::new();
+    for &(ref stamp_path, file) in instances {
+        for element in &sheets[file].elements {
+            if let schematic::Element::Component(ref c) = *element {
+                if let Some(path) = component_path(c, stamp_path) {
+                    if let Some(module) = modules.get(&path[..]) {
+                        paired.insert(instance_reference(c, &path), *module);
+                    }
+                }
+            }
+        }
+    }
+    let mut changes = vec![];
+    let mut touched = vec![];
+    for &(ref stamp_path, file) in instances {
+        for element in &mut sheets[file].elements {
+            let c = match *element {
+                schematic::Element::Component(ref mut c) => c,
+                _ => continue,
+            };
+            let path = match component_path(c, stamp_path) {
+                Some(path) => path,
+                None => continue,
+            };
+            let module = match paired.get(&instance_reference(c, &path)) {
+                Some(module) => *module,
+                None => continue,
+            };
+            let c_changes = update_component(c, &path, module);
+            if c_changes.is_empty() {
+                continue;
+            }
+            if !touched.contains(&file) {
+                touched.push(file)
+            }
+            if get_path(module) == Some(&path[..]) {
+                changes.extend(c_changes)
+            }
+        }
+    }
+    (changes, touched)
+}
+
+// the path of a component like it is used in a layout, power symbols have none
+fn component_path(c: &Component, stamp_path: &str) -> Option<String> {
+    if c.reference.starts_with('#') {
+        return None;
+    }
+    c.timestamp().map(|stamp| format!("{}/{}", stamp_path, stamp))
+}
+
+fn instance_reference(c: &Component, path: &str) -> String {
+    c.reference_for_path(path)
+        .unwrap_or_else(|| c.reference.clone())
+}
+
+fn update_component(c: &mut Component, path: &str, module: &footprint::Module) -> Vec<Change> {
+    let mut changes = vec![];
+    let old = instance_reference(c, path);
+    let reference = module.get_reference().cloned().unwrap_or_else(|| old.clone());
+    if reference != old {
+        c.update_reference_for_path(path, &reference);
+        if c.reference == old {
+            c.update_reference(reference.clone())
+        }
+        changes.push(Change::Reference(old, reference.clone()))
+    }
+    let value = get_value(module);
+    if let Some(old) = c.get_field_value("Value") {
+        if old != value {
+            c.update_field("Value", &value);
+            changes.push(Change::Value(reference.clone(), old, value))
+        }
+    }
+    if let Some(old) = c.get_field_value("Footprint") {
+        if old != module.name {
+            c.update_field("Footprint", &module.name);
+            changes.push(Change::Footprint(reference, old, module.name.clone()))
+        }
+    }
+    changes
+}
+
+// replace the net table by the nets of the netlist and renumber everything
+// that refers to a net
+fn update_nets(layout: &mut Layout, netlist: &Netlist) {
+    let mut nets = netlist.nets.clone();
+    nets.sort_by_key(|net| net.code);
+    let new_nums: HashMap<&str, i64> = nets.iter()
+        .enumerate()
+        .map(|(i, net)| (&net.name[..], i as i64 + 1))
+        .collect();
+    let old_names: HashMap<i64, String> = layout
+        .nets()
+        .into_iter()
+        .map(|net| (net.num, net.name.0.clone()))
+        .collect();
+    let renumber = |num: i64| {
+        old_names
+            .get(&num)
+            .and_then(|name| new_nums.get(&name[..]))
+            .cloned()
+            .unwrap_or(0)
+    };
+    let mut pins: HashMap<(&str, &str), &netlist::Net> = HashMap::new();
+    for net in &nets {
+        for node in &net.nodes {
+            pins.insert((&node.reference, &node.pin), net);
+        }
+    }
+    let position = layout
+        .elements
+        .iter()
+        .position(|e| matches!(*e, Element::Net(_) | Element::NetClass(_)))
+        .unwrap_or(0);
+    layout.elements.retain(|e| !matches!(*e, Element::Net(_)));
+    let mut table = vec![
+        Element::Net(Net {
+            num: 0,
+            name: "".into(),
+        }),
+    ];
+    for (i, net) in nets.iter().enumerate() {
+        table.push(Element::Net(Net {
+            num: i as i64 + 1,
+            name: net.name.as_str().into(),
+        }))
+    }
+    let count = table.len() as i64;
+    let position = position.min(layout.elements.len());
+    layout.elements.splice(position..position, table);
+    let mut modules = 0;
+    for element in &mut layout.elements {
+        match *element {
+            Element::Module(ref mut module) => {
+                modules += 1;
+                let reference = module.get_reference().cloned().unwrap_or_default();
+                for e in &mut module.elements {
+                    if let footprint::Element::Pad(ref mut pad) = *e {
+                        let net = pins.get(&(&reference[..], &pad.name[..]));
+                        pad.net = net.map(|net| footprint::Net {
+                            num: new_nums[&net.name[..]],
+                            name: net.name.as_str().into(),
+                        });
+                    }
+                }
+            }
+            Element::Segment(ref mut segment) => segment.net = renumber(segment.net),
+            Element::Arc(ref mut arc) => arc.net = renumber(arc.net),
+            Element::Via(ref mut via) => via.net = renumber(via.net),
+            Element::Zone(ref mut zone) => {
+                zone.net = renumber(zone.net);
+                if zone.net == 0 {
+                    zone.net_name = NetName::default()
+                }
+            }
+            Element::NetClass(ref mut nc) => {
+                nc.nets.retain(|name| new_nums.contains_key(&name.0[..]))
+            }
+            _ => (),
+        }
+    }
+    // nets that are not in a netclass go in the default netclass
+    let unassigned: Vec<NetName> = nets.iter()
+        .map(|net| NetName::from(net.name.as_str()))
+        .filter(|name| !layout.netclasses().iter().any(|nc| nc.nets.contains(name)))
+        .collect();
+    for element in &mut layout.elements {
+        if let Element::NetClass(ref mut nc) = *element {
+            if nc.name == "Default" {
+                nc.nets.extend(unassigned.iter().cloned());
+                break;
+            }
+        }
+    }
+    layout.general.nets = count;
+    layout.general.modules = modules;
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use expand::ExpandContext;
+    use footprint;
+    use footprint::library::FootprintLibrary;
+    use fp_lib_table;
+    use layout;
+    use layout::annotate::{back_annotate, forward_with, update, Change};
+    use netlist;
+    use schematic;
+    use test_util;
+
+    const BOARD: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (layers (0 F.Cu signal) (31 B.Cu signal))
+  (net 0 "") (net 1 OLD)
+  (net_class Default "" (clearance 0.2) (trace_width 0.25) (via_dia 0.6) (via_drill 0.4)
+    (uvia_dia 0.3) (uvia_drill 0.1) (add_net OLD))
+  (module Lib:R_0603 (layer F.Cu) (at 10 10) (path /5B000001)
+    (fp_text reference R9 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 1k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15))))
+    (pad 1 smd rect (at -1 0) (size 1 1) (layers F.Cu) (net 1 OLD))
+    (pad 2 smd rect (at 1 0) (size 1 1) (layers F.Cu)))
+  (module Lib:R_0603 (layer F.Cu) (at 20 10)
+    (fp_text reference R5 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 1k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+  (segment (start 0 10) (end 9 10) (width 0.25) (layer F.Cu) (net 1))
+  (arc (start 0 10) (mid 4.5 12) (end 9 10) (width 0.25) (layer F.Cu) (net 1))
+)"#;
+
+    const FOOTPRINT: &str = r#"(module R_0805 (layer F.Cu) (tedit 5A000000)
+  (fp_text reference REF** (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+  (fp_text value R_0805 (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15))))
+  (pad 1 smd rect (at -1 0) (size 1 1.2) (layers F.Cu))
+  (pad 2 smd rect (at 1 0) (size 1 1.2) (layers F.Cu))
+)"#;
+
+    const NETLIST: &str = r#"(export (version D)
+  (components
+    (comp (ref R1)
+      (value 10k)
+      (footprint Lib:R_0805)
+      (tstamp 5B000001))
+    (comp (ref R2)
+      (value 1k)
+      (footprint Lib:R_0805)
+      (tstamp 5B000002)))
+  (nets
+    (net (code 1) (name GND)
+      (node (ref R1) (pin 2))
+      (node (ref R2) (pin 2)))
+    (net (code 2) (name OLD)
+      (node (ref R1) (pin 1)))))"#;
+
+    #[test]
+    fn forward_annotation() {
+        let mut layout = layout::parse(BOARD).unwrap();
+        let netlist = netlist::parse(NETLIST).unwrap();
+        let changes = update(&mut layout, &netlist, |_| footprint::parse(FOOTPRINT)).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Footprint("R1".into(), "Lib:R_0603".into(), "Lib:R_0805".into()),
+                Change::Reference("R9".into(), "R1".into()),
+                Change::Value("R1".into(), "1k".into(), "10k".into()),
+                Change::Removed("R5".into()),
+                Change::Added("R2".into()),
+            ]
+        );
+        let nets: Vec<(i64, &str)> = layout.nets().iter().map(|n| (n.num, &n.name.0[..])).collect();
+        assert_eq!(nets, vec![(0, ""), (1, "GND"), (2, "OLD")]);
+        let r1 = layout.get_module("R1").unwrap();
+        assert_eq!(r1.at(), (10.0, 10.0));
+        let pads = r1.pads();
+        assert_eq!(pads[0].net.as_ref().map(|n| n.num), Some(2));
+        assert_eq!(pads[1].net.as_ref().map(|n| &n.name.0[..]), Some("GND"));
+        let r2 = layout.get_module("R2").unwrap();
+        assert_eq!(r2.name, "Lib:R_0805");
+        assert_eq!(r2.get_value_text().unwrap().value, "1k");
+        // the tracks keep their net by name
+        let track_nets: Vec<i64> = layout.elements.iter().filter_map(|e| match *e {
+            layout::Element::Segment(ref s) => Some(s.net),
+            layout::Element::Arc(ref a) => Some(a.net),
+            _ => None,
+        }).collect();
+        assert_eq!(track_nets, vec![2, 2]);
+        assert_eq!(layout.netclasses()[0].nets.len(), 2);
+    }
+
+    #[test]
+    fn forward_annotation_in_project() {
+        let dir = test_util::temp_dir("forward_annotation_in_project");
+        let library = FootprintLibrary::create(&dir.join("Lib.pretty")).unwrap();
+        library.add(&footprint::parse(FOOTPRINT).unwrap()).unwrap();
+        let table = fp_lib_table::parse(
+            "(fp_lib_table
+  (lib (name Lib)(type KiCad)(uri ${KIPRJMOD}/Lib.pretty)(options \"\")(descr \"\"))
+)",
+        ).unwrap();
+        let mut layout = layout::parse(BOARD).unwrap();
+        let netlist = netlist::parse(NETLIST).unwrap();
+        let context = ExpandContext::for_project(&dir);
+        let changes = forward_with(&mut layout, &netlist, &table, &context).unwrap();
+        assert!(changes.contains(&Change::Added("R2".into())));
+        assert_eq!(layout.get_module("R2").unwrap().name, "Lib:R_0805");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn forward_annotation_fails() {
+        let mut layout = layout::parse(BOARD).unwrap();
+        let before = layout::layout_to_string(&layout, 0).unwrap();
+        let netlist = netlist::parse(NETLIST).unwrap();
+        let result = update(&mut layout, &netlist, |name| {
+            Err(format!("no footprint {}", name).into())
+        });
+        assert!(result.is_err());
+        assert_eq!(layout::layout_to_string(&layout, 0).unwrap(), before);
+    }
+
+    const ROOT: &str = r##"EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R1
+U 1 1 5B000001
+P 1000 1000
+F 0 "R1" V 1080 1000 50  0000 C CNN
+F 1 "1k" V 1000 1000 50  0000 C CNN
+F 2 "Lib:R_0603" V 930 1000 50  0001 C CNN
+	1    1000 1000
+	1    0    0    -1  
+$EndComp
+$Comp
+L OPAMP U1
+U 1 1 5B000003
+P 2000 1000
+F 0 "U1" H 2000 1200 50  0000 C CNN
+F 1 "LM358" H 2000 800 50  0000 C CNN
+F 2 "Lib:SOIC-8" H 2000 1000 50  0001 C CNN
+	1    2000 1000
+	1    0    0    -1  
+$EndComp
+$Comp
+L OPAMP U1
+U 2 1 5B000004
+P 2000 2000
+F 0 "U1" H 2000 2200 50  0000 C CNN
+F 1 "LM358" H 2000 1800 50  0000 C CNN
+F 2 "Lib:SOIC-8" H 2000 2000 50  0001 C CNN
+	2    2000 2000
+	1    0    0    -1  
+$EndComp
+$Sheet
+S 3000 700 500 500
+U 5A000001
+F0 "Sub" 60
+F1 "sub.sch" 60
+$EndSheet
+$EndSCHEMATC
+"##;
+
+    const SUB: &str = r##"EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R2
+U 1 1 5B000002
+P 1000 1000
+AR Path="/5A000001/5B000002" Ref="R2"  Part="1" 
+AR Path="/5A000002/5B000002" Ref="R3"  Part="1" 
+F 0 "R2" V 1080 1000 50  0000 C CNN
+F 1 "1k" V 1000 1000 50  0000 C CNN
+F 2 "Lib:R_0603" V 930 1000 50  0001 C CNN
+	1    1000 1000
+	1    0    0    -1  
+$EndComp
+$EndSCHEMATC
+"##;
+
+    const PLACED: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (layers (0 F.Cu signal) (31 B.Cu signal))
+  (module Lib:R_0805 (layer F.Cu) (at 10 10) (path /5B000001)
+    (fp_text reference R10 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 4k7 (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+  (module Lib:SOIC-8 (layer F.Cu) (at 20 10) (path /5B000003)
+    (fp_text reference U5 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value LM358 (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+  (module Lib:R_0603 (layer F.Cu) (at 30 10) (path /5A000001/5B000002)
+    (fp_text reference R2 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 1k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+  (module Lib:R_0603 (layer F.Cu) (at 40 10) (path /5A000002/5B000002)
+    (fp_text reference R4 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 1k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+)"#;
+
+    #[test]
+    fn back_annotation() {
+        let layout = layout::parse(PLACED).unwrap();
+        let mut sheets = vec![
+            schematic::parse_str(ROOT).unwrap(),
+            schematic::parse_str(SUB).unwrap(),
+        ];
+        let instances = vec![
+            (String::new(), 0),
+            ("/5A000001".to_string(), 1),
+            ("/5A000002".to_string(), 1),
+        ];
+        let (changes, touched) = back_annotate(&layout, &mut sheets, &instances);
+        assert_eq!(
+            changes,
+            vec![
+                Change::Reference("R1".into(), "R10".into()),
+                Change::Value("R10".into(), "1k".into(), "4k7".into()),
+                Change::Footprint("R10".into(), "Lib:R_0603".into(), "Lib:R_0805".into()),
+                Change::Reference("U1".into(), "U5".into()),
+                Change::Reference("R3".into(), "R4".into()),
+            ]
+        );
+        assert_eq!(touched, vec![0, 1]);
+        let root = sheets[0].components();
+        assert_eq!(root[0].reference, "R10");
+        assert_eq!(root[0].get_field_value("Reference"), Some("R10".into()));
+        assert_eq!(root[0].get_field_value("Value"), Some("4k7".into()));
+        assert_eq!(root[0].get_field_value("Footprint"), Some("Lib:R_0805".into()));
+        // the second unit follows the first one
+        assert_eq!(root[2].reference, "U5");
+        // only the annotation of the second instance of the sheet changes
+        let sub = sheets[1].components();
+        assert_eq!(sub[0].reference, "R2");
+        assert_eq!(sub[0].reference_for_path("/5A000001/5B000002"), Some("R2".into()));
+        assert_eq!(sub[0].reference_for_path("/5A000002/5B000002"), Some("R4".into()));
+    }
+}
@@ -0,0 +1,348 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! physical copper connectivity of a layout
+//!
+//! tracks, vias, pads and zone fills that touch on a common copper layer
+//! are merged into islands; an island is a piece of copper as it will be on
+//! the manufactured board.
+
+use std::collections::BTreeMap;
+
+use footprint;
+use geometry::{bounds_overlap, module_position, Point, Shape};
+use util::DisjointSet;
+use layout::{Element, Layout, Net};
+use Bound;
+
+/// a copper item of a layout
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    /// a segment or arc track, with its index in the layout elements
+    Segment(usize),
+    /// a via, with its index in the layout elements
+    Via(usize),
+    /// a pad, with the reference of its module and the pad name
+    Pad(String, String),
+    /// a filled polygon of a zone, with the index of the zone in the
+    /// layout elements and the index of the polygon
+    Zone(usize, usize),
+}
+
+/// a piece of physically connected copper
+#[derive(Clone, Debug, Default)]
+pub struct Island {
+    /// the copper items in the island
+    pub items: Vec<Item>,
+    /// the numbers of the nets of the pads in the island
+    pub nets: Vec<i64>,
+}
+
+/// a net of which the pads are spread over several islands
+#[derive(Clone, Debug, PartialEq)]
+pub struct Open {
+    /// the net
+    pub net: Net,
+    /// the indices of the islands containing pads of the net
+    pub islands: Vec<usize>,
+}
+
+/// an island connecting pads of different nets
+#[derive(Clone, Debug, PartialEq)]
+pub struct Short {
+    /// the index of the island
+    pub island: usize,
+    /// the nets that are shorted
+    pub nets: Vec<Net>,
+}
+
+/// the copper connectivity of a layout
+#[derive(Clone, Debug, Default)]
+pub struct Connectivity {
+    /// the copper islands
+    pub islands: Vec<Island>,
+    /// the names of the nets by number
+    names: BTreeMap<i64, Net>,
+}
+
+impl Connectivity {
+    /// calculate the connectivity of a layout
+    pub fn new(layout: &Layout) -> Connectivity {
+        let copper = Copper::new(layout);
+        let items = copper.items(layout);
+        let mut sets = DisjointSet::new(items.len());
+        let bounds: Vec<Bound> = items.iter().map(|i| i.shape.bound()).collect();
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        order.sort_by(|a, b| bounds[*a].x1.partial_cmp(&bounds[*b].x1).unwrap());
+        for (n, &i) in order.iter().enumerate() {
+            for &j in &order[n + 1..] {
+                if bounds[j].x1 > bounds[i].x2 + EPSILON {
+                    break;
+                }
+                if items[i].layers & items[j].layers == 0 {
+                    continue;
+                }
+                if !bounds_overlap(&bounds[i], &bounds[j], EPSILON) {
+                    continue;
+                }
+                if items[i].shape.distance(&items[j].shape) <= EPSILON {
+                    sets.join(i, j)
+                }
+            }
+        }
+        let mut islands: BTreeMap<usize, Island> = BTreeMap::new();
+        let names = layout
+            .nets()
+            .into_iter()
+            .map(|net| (net.num, net.clone()))
+            .collect();
+        for (i, item) in items.into_iter().enumerate() {
+            let island = islands.entry(sets.find(i)).or_default();
+            if let Item::Pad(_, _) = item.item {
+                if item.net != 0 && !island.nets.contains(&item.net) {
+                    island.nets.push(item.net)
+                }
+            }
+            island.items.push(item.item);
+        }
+        let mut islands: Vec<Island> = islands.into_values().collect();
+        for island in &mut islands {
+            island.nets.sort()
+        }
+        Connectivity { islands, names }
+    }
+
+    /// get the nets of which the pads are not all connected
+    pub fn opens(&self) -> Vec<Open> {
+        let mut nets: BTreeMap<i64, Vec<usize>> = BTreeMap::new();
+        for (i, island) in self.islands.iter().enumerate() {
+            for net in &island.nets {
+                nets.entry(*net).or_default().push(i)
+            }
+        }
+        nets.into_iter()
+            .filter(|(_, islands)| islands.len() > 1)
+            .map(|(num, islands)| Open {
+                net: self.net(num),
+                islands,
+            })
+            .collect()
+    }
+
+    /// get the islands that connect pads of different nets
+    pub fn shorts(&self) -> Vec<Short> {
+        self.islands
+            .iter()
+            .enumerate()
+            .filter(|&(_, island)| island.nets.len() > 1)
+            .map(|(i, island)| Short {
+                island: i,
+                nets: island.nets.iter().map(|num| self.net(*num)).collect(),
+            })
+            .collect()
+    }
+
+    /// get the net with a certain number
+    pub fn net(&self, num: i64) -> Net {
+        match self.names.get(&num) {
+            Some(net) => net.clone(),
+            None => Net {
+                num,
+                name: "".into(),
+            },
+        }
+    }
+}
+
+impl Layout {
+    /// calculate the copper connectivity of the layout
+    pub fn connectivity(&self) -> Connectivity {
+        Connectivity::new(self)
+    }
+}
+
+// items closer than this are considered to be touching
+pub(crate) const EPSILON: f64 = 1e-6;
+
+// a copper item together with its shape, copper layers and net
+pub(crate) struct CopperItem {
+    pub item: Item,
+    pub shape: Shape,
+    pub layers: u32,
+    pub net: i64,
+    // the local clearance of a pad
+    pub clearance: Option<f64>,
+}
+
+// the copper layers of the board, as a bit mask on the Kicad layer number
+pub(crate) struct Copper {
+    board: u32,
+}
+
+impl Copper {
+    pub fn new(layout: &Layout) -> Copper {
+        let mut board = 0;
+        for layer in layout.copper_layers() {
+            if let Some(num) = layer.layer.side.copper_num() {
+                board |= 1 << num
+            }
+        }
+        if board == 0 {
+            board = 1 | 1 << 31;
+        }
+        Copper { board }
+    }
+
+    // the mask of a single layer, `*.Cu` being all copper layers
+    pub fn layer(&self, layer: &footprint::Layer) -> u32 {
+        if layer.t != footprint::LayerType::Cu {
+            return 0;
+        }
+        match layer.side {
+            footprint::LayerSide::Both => self.board,
+            ref side => match side.copper_num() {
+                Some(num) => (1 << num) & self.board,
+                None => 0,
+            },
+        }
+    }
+
+    // the mask of the layers of a pad
+    pub fn layers(&self, layers: &footprint::Layers) -> u32 {
+        layers.layers.iter().fold(0, |acc, l| acc | self.layer(l))
+    }
+
+    // the mask of all layers between the outer layers of a via
+    pub fn span(&self, layers: &footprint::Layers) -> u32 {
+        let nums: Vec<i64> = layers
+            .layers
+            .iter()
+            .filter_map(|l| l.side.copper_num())
+            .collect();
+        let first = nums.iter().min().cloned().unwrap_or(0);
+        let last = nums.iter().max().cloned().unwrap_or(31);
+        (first..last + 1).fold(0, |acc, num| acc | 1 << num) & self.board
+    }
+
+    pub fn items(&self, layout: &Layout) -> Vec<CopperItem> {
+        let mut items = vec![];
+        for (i, element) in layout.elements.iter().enumerate() {
+            match *element {
+                Element::Segment(ref segment) => items.push(CopperItem {
+                    item: Item::Segment(i),
+                    shape: Shape::track(
+                        Point::new(segment.start.x, segment.start.y),
+                        Point::new(segment.end.x, segment.end.y),
+                        segment.width,
+                    ),
+                    layers: self.layer(&segment.layer),
+                    net: segment.net,
+                    clearance: None,
+                }),
+                // an arc track is taken as the two chords through its middle
+                Element::Arc(ref arc) => {
+                    let chords = [(&arc.start, &arc.mid), (&arc.mid, &arc.end)];
+                    for &(a, b) in &chords {
+                        let (a, b) = (Point::new(a.x, a.y), Point::new(b.x, b.y));
+                        items.push(CopperItem {
+                            item: Item::Segment(i),
+                            shape: Shape::track(a, b, arc.width),
+                            layers: self.layer(&arc.layer),
+                            net: arc.net,
+                            clearance: None,
+                        })
+                    }
+                }
+                Element::Via(ref via) => items.push(CopperItem {
+                    item: Item::Via(i),
+                    shape: Shape::circle(Point::new(via.at.x, via.at.y), via.size / 2.0),
+                    layers: self.span(&via.layers),
+                    net: via.net,
+                    clearance: None,
+                }),
+                Element::Zone(ref zone) => {
+                    for (j, polygon) in zone.filled_polygons.iter().enumerate() {
+                        items.push(CopperItem {
+                            item: Item::Zone(i, j),
+                            shape: Shape::polygon(&polygon.pts),
+                            layers: self.layer(&zone.layer),
+                            net: zone.net,
+                            clearance: None,
+                        })
+                    }
+                }
+                Element::Module(ref module) => {
+                    let (x, y) = module.at();
+                    let at = footprint::At::new(x, y, module.get_rotation());
+                    let reference = module.get_reference().cloned().unwrap_or_default();
+                    for pad in module.pads() {
+                        // non-plated holes do not connect copper
+                        if pad.t == footprint::PadType::NpPth {
+                            continue;
+                        }
+                        items.push(CopperItem {
+                            item: Item::Pad(reference.clone(), pad.name.clone()),
+                            shape: Shape::pad(pad, |x, y| module_position(&at, x, y)),
+                            layers: self.layers(&pad.layers),
+                            net: pad.net.as_ref().map(|net| net.num).unwrap_or(0),
+                            clearance: pad.clearance,
+                        })
+                    }
+                }
+                Element::Net(_) |
+                Element::NetClass(_) |
+                Element::GrText(_) |
+                Element::GrLine(_) |
+                Element::GrArc(_) |
+                Element::GrCircle(_) |
+                Element::GrPoly(_) |
+                Element::GrRect(_) |
+                Element::Dimension(_) |
+                Element::Other(_) => (),
+            }
+        }
+        items.retain(|item| item.layers != 0);
+        items
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use layout;
+
+    const BOARD: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (layers (0 F.Cu signal) (31 B.Cu signal))
+  (net 0 "") (net 1 A) (net 2 B)
+  (module R (layer F.Cu) (at 10 10)
+    (fp_text reference R1 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (pad 1 smd rect (at -1 0) (size 1 1) (layers F.Cu) (net 1 A))
+    (pad 2 smd rect (at 1 0) (size 1 1) (layers F.Cu) (net 2 B)))
+  (module R (layer F.Cu) (at 20 10 90)
+    (fp_text reference R2 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (pad 1 thru_hole circle (at -1 0) (size 1 1) (drill 0.5) (layers *.Cu) (net 1 A))
+    (pad 2 thru_hole circle (at 1 0) (size 1 1) (drill 0.5) (layers *.Cu) (net 2 B)))
+  (segment (start 9 10) (end 9 14) (width 0.25) (layer F.Cu) (net 1))
+  (segment (start 9 14) (end 15 14) (width 0.25) (layer F.Cu) (net 1))
+  (via (at 15 14) (size 0.6) (drill 0.3) (layers F.Cu B.Cu) (net 1))
+  (segment (start 15 14) (end 20 11) (width 0.25) (layer B.Cu) (net 1))
+)"#;
+
+    #[test]
+    fn connected_board() {
+        let layout = layout::parse(BOARD).unwrap();
+        let connectivity = layout.connectivity();
+        assert!(connectivity.shorts().is_empty());
+        let opens = connectivity.opens();
+        assert_eq!(opens.len(), 1);
+        assert_eq!(opens[0].net.name.0, "B");
+    }
+
+    #[test]
+    fn shorted_board() {
+        let short = "(segment (start 9 10) (end 11 10) (width 0.25) (layer F.Cu) (net 1))";
+        let board = BOARD.replace("(via", &format!("{}\n  (via", short));
+        let layout = layout::parse(&board).unwrap();
+        let shorts = layout.connectivity().shorts();
+        assert_eq!(shorts.len(), 1);
+        assert_eq!(shorts[0].nets.len(), 2);
+    }
+}
@@ -0,0 +1,403 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! consistency between a schematic and its layout
+//!
+//! components and modules are paired by reference. The nets of the pads
+//! are compared with the connectivity of the schematic by grouping: the
+//! net names of both don't have to match, but pads that share a net in
+//! the schematic have to share a net in the layout and the other way around.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use checkfix::CheckFixData;
+use footprint;
+use layout::Layout;
+use schematic::connectivity::Connectivity;
+use schematic::{Component, ComponentInstance, Schematic};
+use symbol_lib::SymbolLib;
+use KicadError;
+
+/// consistency section
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    /// pairing of components and modules
+    Components,
+    /// connectivity of the pads
+    Nets,
+}
+
+impl From<Section> for i64 {
+    fn from(section: Section) -> i64 {
+        match section {
+            Section::Components => 1,
+            Section::Nets => 2,
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Section::Components => "Components",
+            Section::Nets => "Nets",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// consistency rule
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    /// every component has a module
+    MissingModule,
+    /// every module has a component
+    MissingComponent,
+    /// the value of a module matches its component
+    Value,
+    /// the footprint of a module matches its component
+    Footprint,
+    /// the net of a pad matches the schematic
+    PadNet,
+}
+
+impl Rule {
+    /// the section of the rule
+    pub fn section(&self) -> Section {
+        match *self {
+            Rule::MissingModule | Rule::MissingComponent | Rule::Value | Rule::Footprint => {
+                Section::Components
+            }
+            Rule::PadNet => Section::Nets,
+        }
+    }
+}
+
+impl From<Rule> for i64 {
+    fn from(rule: Rule) -> i64 {
+        match rule {
+            Rule::MissingModule | Rule::PadNet => 1,
+            Rule::MissingComponent => 2,
+            Rule::Value => 3,
+            Rule::Footprint => 4,
+        }
+    }
+}
+
+/// check a layout against its schematic including the sub-sheets; the
+/// symbols of the components are looked up in the libraries
+pub fn check(
+    schematic: &Schematic,
+    layout: &Layout,
+    libs: &[&SymbolLib],
+) -> Result<Vec<CheckFixData>, KicadError> {
+    let components = schematic.component_instances()?;
+    let connectivity = schematic.connectivity(libs)?;
+    let mut v = check_components(&components, layout);
+    v.append(&mut check_nets(&components, &connectivity, layout));
+    Ok(v)
+}
+
+fn report(rule: Rule, item: &str, message: String) -> CheckFixData {
+    CheckFixData::new(rule.section().into(), rule.into(), item, message)
+}
+
+// the components by the reference of their sheet instance without power
+// symbols; the units of a multi-unit component are one component
+fn by_reference(components: &[ComponentInstance]) -> HashMap<&str, &Component> {
+    components
+        .iter()
+        .filter(|c| !c.reference.starts_with('#'))
+        .map(|c| (&c.reference[..], &c.component))
+        .collect()
+}
+
+fn check_components(components: &[ComponentInstance], layout: &Layout) -> Vec<CheckFixData> {
+    let mut v = vec![];
+    let by_reference = by_reference(components);
+    let modules = layout.get_modules();
+    let mut references = vec![];
+    for module in &modules {
+        let reference = match module.get_reference() {
+            Some(reference) => reference,
+            None => continue,
+        };
+        references.push(&reference[..]);
+        let component = match by_reference.get(&reference[..]) {
+            Some(component) => component,
+            None => {
+                v.push(report(
+                    Rule::MissingComponent,
+                    reference,
+                    "module has no component in the schematic".into(),
+                ));
+                continue;
+            }
+        };
+        let value = module.get_value_text().map(|t| &t.value[..]).unwrap_or("");
+        if let Some(expected) = component.get_field_value("Value") {
+            if value != expected {
+                v.push(report(
+                    Rule::Value,
+                    reference,
+                    format!("value {} differs from {} in the schematic", value, expected),
+                ))
+            }
+        }
+        if let Some(expected) = component.get_field_value("Footprint") {
+            if !expected.is_empty() && module.name != expected {
+                v.push(report(
+                    Rule::Footprint,
+                    reference,
+                    format!(
+                        "footprint {} differs from {} in the schematic",
+                        module.name,
+                        expected
+                    ),
+                ))
+            }
+        }
+    }
+    let mut missing: Vec<&str> = by_reference
+        .keys()
+        .filter(|r| !references.contains(r))
+        .cloned()
+        .collect();
+    missing.sort();
+    for reference in missing {
+        v.push(report(
+            Rule::MissingModule,
+            reference,
+            "component has no module in the layout".into(),
+        ))
+    }
+    v
+}
+
+fn pad_net(pad: &footprint::Pad) -> Option<&str> {
+    match pad.net {
+        Some(ref net) if net.num != 0 => Some(&net.name.0[..]),
+        _ => None,
+    }
+}
+
+fn check_nets(
+    components: &[ComponentInstance],
+    connectivity: &Connectivity,
+    layout: &Layout,
+) -> Vec<CheckFixData> {
+    let mut v = vec![];
+    let by_reference = by_reference(components);
+    let mut pins = HashMap::new();
+    for (name, nodes) in &connectivity.nets {
+        for node in nodes {
+            pins.insert((&node.reference[..], &node.pin[..]), (&name[..], nodes.len()));
+        }
+    }
+    // the first net seen for a net of the other side
+    let mut to_layout: HashMap<&str, &str> = HashMap::new();
+    let mut to_schematic: HashMap<&str, &str> = HashMap::new();
+    for module in layout.get_modules() {
+        let reference = match module.get_reference() {
+            Some(reference) if by_reference.contains_key(&reference[..]) => reference,
+            _ => continue,
+        };
+        for pad in module.pads() {
+            if pad.name.is_empty() {
+                continue;
+            }
+            let item = format!("{}-{}", reference, pad.name);
+            let expected = pins.get(&(&reference[..], &pad.name[..]));
+            match (expected, pad_net(pad)) {
+                (None, None) => (),
+                // a pin that connects to nothing doesn't need a net
+                (Some(&(_, 1)), None) => (),
+                (Some(&(name, _)), None) => v.push(report(
+                    Rule::PadNet,
+                    &item,
+                    format!("pad has no net but is on {} in the schematic", name),
+                )),
+                (None, Some(net)) => v.push(report(
+                    Rule::PadNet,
+                    &item,
+                    format!("pad is on {} but not connected in the schematic", net),
+                )),
+                (Some(&(name, _)), Some(net)) => {
+                    let l = *to_layout.entry(name).or_insert(net);
+                    let s = *to_schematic.entry(net).or_insert(name);
+                    if l != net {
+                        v.push(report(
+                            Rule::PadNet,
+                            &item,
+                            format!("pad is on {} but {} is on {} in the schematic", net, l, name),
+                        ))
+                    } else if s != name {
+                        v.push(report(
+                            Rule::PadNet,
+                            &item,
+                            format!("pad is on {} which joins {} and {} of the schematic",
+                                    net, s, name),
+                        ))
+                    }
+                }
+            }
+        }
+    }
+    v
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use checkfix::CheckFixData;
+    use layout;
+    use layout::consistency::check;
+    use schematic;
+    use symbol_lib;
+    use test_util;
+
+    const LIB: &str = r##"EESchema-LIBRARY Version 2.3
+#encoding utf-8
+#
+# R
+#
+DEF R R 0 0 N Y 1 F N
+F0 "R" 80 0 50 V V C CNN
+F1 "R" 0 0 50 V V C CNN
+DRAW
+X ~ 1 0 150 50 D 50 50 1 1 P
+X ~ 2 0 -150 50 U 50 50 1 1 P
+ENDDRAW
+ENDDEF
+#
+#End Library
+"##;
+
+    // R1, R2 and R3 in series with touching pins, R4 has no module
+    const SCHEMATIC: &str = r##"EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R1
+U 1 1 5B000001
+P 1000 1000
+F 0 "R1" V 1080 1000 50  0000 C CNN
+F 1 "1k" V 1000 1000 50  0000 C CNN
+F 2 "Lib:R_0603" V 930 1000 50  0001 C CNN
+	1    1000 1000
+	1    0    0    -1
+$EndComp
+$Comp
+L R R2
+U 1 1 5B000002
+P 1000 1300
+F 0 "R2" V 1080 1300 50  0000 C CNN
+F 1 "1k" V 1000 1300 50  0000 C CNN
+F 2 "Lib:R_0603" V 930 1300 50  0001 C CNN
+	1    1000 1300
+	1    0    0    -1
+$EndComp
+$Comp
+L R R3
+U 1 1 5B000003
+P 1000 1600
+F 0 "R3" V 1080 1600 50  0000 C CNN
+F 1 "1k" V 1000 1600 50  0000 C CNN
+F 2 "Lib:R_0603" V 930 1600 50  0001 C CNN
+	1    1000 1600
+	1    0    0    -1
+$EndComp
+$Comp
+L R R4
+U 1 1 5B000004
+P 2000 1000
+F 0 "R4" V 2080 1000 50  0000 C CNN
+F 1 "1k" V 2000 1000 50  0000 C CNN
+F 2 "Lib:R_0603" V 1930 1000 50  0001 C CNN
+	1    2000 1000
+	1    0    0    -1
+$EndComp
+$EndSCHEMATC
+"##;
+
+    const LAYOUT: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (layers (0 F.Cu signal) (31 B.Cu signal))
+  (net 0 "") (net 1 A) (net 2 B)
+  (module Lib:R_0603 (layer F.Cu) (at 10 10)
+    (fp_text reference R1 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 1k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15))))
+    (pad 1 smd rect (at -1 0) (size 1 1) (layers F.Cu))
+    (pad 2 smd rect (at 1 0) (size 1 1) (layers F.Cu) (net 1 A)))
+  (module Lib:R_0805 (layer F.Cu) (at 20 10)
+    (fp_text reference R2 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 10k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15))))
+    (pad 1 smd rect (at -1 0) (size 1 1) (layers F.Cu) (net 1 A))
+    (pad 2 smd rect (at 1 0) (size 1 1) (layers F.Cu) (net 1 A)))
+  (module Lib:R_0603 (layer F.Cu) (at 30 10)
+    (fp_text reference R3 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 1k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15))))
+    (pad 1 smd rect (at -1 0) (size 1 1) (layers F.Cu) (net 2 B))
+    (pad 2 smd rect (at 1 0) (size 1 1) (layers F.Cu)))
+  (module Lib:R_0603 (layer F.Cu) (at 40 10)
+    (fp_text reference R9 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 1k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+)"#;
+
+    fn item(data: &CheckFixData) -> (i64, i64, String) {
+        match *data {
+            CheckFixData::Item(ref item) => (item.section, item.rule, item.item.clone()),
+            CheckFixData::More(_) => panic!("unexpected nested data"),
+        }
+    }
+
+    #[test]
+    fn schematic_layout_consistency() {
+        let lib = symbol_lib::parse_str(LIB).unwrap();
+        let schematic = schematic::parse_str(SCHEMATIC).unwrap();
+        let layout = layout::parse(LAYOUT).unwrap();
+        let v = check(&schematic, &layout, &[&lib]).unwrap();
+        let items: Vec<(i64, i64, String)> = v.iter().map(item).collect();
+        assert_eq!(
+            items,
+            vec![
+                (1, 3, "R2".to_string()),
+                (1, 4, "R2".to_string()),
+                (1, 2, "R9".to_string()),
+                (1, 1, "R4".to_string()),
+                // R2 shorts both nets
+                (2, 1, "R2-2".to_string()),
+                (2, 1, "R3-1".to_string()),
+            ]
+        );
+    }
+
+    const HIERARCHICAL_LAYOUT: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (layers (0 F.Cu signal) (31 B.Cu signal))
+  (net 0 "")
+  (module Lib:R_0603 (layer F.Cu) (at 10 10)
+    (fp_text reference R1 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 10k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+  (module Lib:R_0603 (layer F.Cu) (at 20 10)
+    (fp_text reference R2 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 10k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+  (module Lib:R_0603 (layer F.Cu) (at 30 10)
+    (fp_text reference R3 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 10k (at 0 1) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+  (module Lib:R_0603 (layer F.Cu) (at 40 10)
+    (fp_text reference R4 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15)))))
+)"#;
+
+    #[test]
+    fn hierarchical_consistency() {
+        let dir = test_util::legacy_hierarchy("hierarchical_consistency");
+        let lib = symbol_lib::parse_str(LIB).unwrap();
+        let schematic = schematic::parse_file(&dir.join("root.sch")).unwrap();
+        let layout = layout::parse(HIERARCHICAL_LAYOUT).unwrap();
+        let v = check(&schematic, &layout, &[&lib]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let items: Vec<(i64, i64, String)> = v.iter().map(item).collect();
+        // each instance of the sub-sheet has its module
+        assert_eq!(items, vec![(1, 2, "R4".to_string())]);
+    }
+}
@@ -8,6 +8,10 @@ use layout::{Adjust, Bound, BoundingBox};
 use std::{fmt, result};
 use KicadError;
 
+// the version of the file format of Kicad 5, later versions are written by
+// Kicad 6 and up
+const KICAD5_VERSION: i64 = 20171130;
+
 /// a Kicad layout
 #[derive(Debug)]
 pub struct Layout {
@@ -19,6 +23,9 @@ pub struct Layout {
     pub general: General,
     /// page size
     pub page: String,
+    /// other (uninterpreted symbolic-expressions) before the layers, like
+    /// the title block
+    pub header: Vec<Sexp>,
     /// setup information
     pub setup: Setup,
     /// layers
@@ -44,10 +51,16 @@ pub enum Element {
     GrArc(GrArc),
     /// circle
     GrCircle(GrCircle),
+    /// polygon
+    GrPoly(GrPoly),
+    /// rectangle
+    GrRect(GrRect),
     /// dimension
     Dimension(Dimension),
     /// segment
     Segment(Segment),
+    /// arc track
+    Arc(Arc),
     /// via
     Via(Via),
     // FilledPolygon,
@@ -76,6 +89,9 @@ pub struct Zone {
     pub connect_pads: ConnectPads,
     /// minimum thickness
     pub min_thickness: f64,
+    /// if the filled polygons are drawn with the minimum thickness, Kicad 6
+    /// and later say no when the polygons are the exact copper
+    pub filled_areas_thickness: Option<bool>,
     /// keepout
     pub keepout: Option<Keepout>,
     /// fill
@@ -83,7 +99,7 @@ pub struct Zone {
     /// polygons
     pub polygons: Vec<footprint::Pts>,
     /// filled polygons
-    pub filled_polygons: Vec<footprint::Pts>,
+    pub filled_polygons: Vec<FilledPolygon>,
     /// filled segments
     pub fill_segments: Option<footprint::Pts>,
     /// other (uninterpreted symbolic-expressions)
@@ -96,7 +112,7 @@ impl Adjust for Zone {
             p.adjust(x, y)
         }
         for p in &mut self.filled_polygons {
-            p.adjust(x, y)
+            p.pts.adjust(x, y)
         }
         for p in &mut self.fill_segments {
             p.adjust(x, y)
@@ -111,7 +127,7 @@ impl BoundingBox for Zone {
             b.update(&p.bounding_box());
         }
         for p in &self.filled_polygons {
-            b.update(&p.bounding_box());
+            b.update(&p.pts.bounding_box());
         }
         for p in &self.fill_segments {
             b.update(&p.bounding_box());
@@ -130,6 +146,18 @@ pub struct Hatch {
     pub pitch: f64,
 }
 
+/// a filled polygon of a zone
+//  (filled_polygon (layer "F.Cu") (island) (pts (xy 0 0) (xy 1 0) (xy 1 1)))
+#[derive(Clone, Debug, Default)]
+pub struct FilledPolygon {
+    /// layer, only named by Kicad 6 and later
+    pub layer: Option<footprint::Layer>,
+    /// if it is an island that isn't connected to the rest of the zone
+    pub island: bool,
+    /// points
+    pub pts: footprint::Pts,
+}
+
 /// a zone connect pads
 #[derive(Clone, Debug, Default)]
 pub struct ConnectPads {
@@ -149,6 +177,8 @@ pub struct Keepout {
     pub vias: bool,
     /// copperpour
     pub copperpour: bool,
+    /// other (uninterpreted symbolic-expressions)
+    pub other: Vec<Sexp>,
 }
 
 /// fill of a zone
@@ -157,10 +187,10 @@ pub struct Keepout {
 pub struct Fill {
     /// if it is filled (default no)
     pub filled: bool,
-    /// if it is segment mode
-    pub segment: bool,
-    /// number of arc segments
-    pub arc_segments: i64,
+    /// fill mode, like segment or hatch
+    pub mode: Option<String>,
+    /// number of arc segments, not written by Kicad 6 and later
+    pub arc_segments: Option<i64>,
     /// thermal relief gap
     pub thermal_gap: f64,
     /// thermal relief copper bridge
@@ -169,6 +199,8 @@ pub struct Fill {
     pub smoothing: Option<String>,
     /// corner radius
     pub corner_radius: f64,
+    /// other (uninterpreted symbolic-expressions)
+    pub other: Vec<Sexp>,
 }
 
 /// build host info
@@ -201,6 +233,8 @@ pub struct General {
     pub modules: i64,
     /// number of nets
     pub nets: i64,
+    /// other (uninterpreted symbolic-expressions)
+    pub other: Vec<Sexp>,
 }
 
 /// area
@@ -227,6 +261,29 @@ pub struct Layer {
     pub layer_type: LayerType,
     /// if the layer is shown
     pub hide: bool,
+    /// name given by the user, like `F.Silkscreen` for `F.SilkS`
+    pub user_name: Option<String>,
+}
+
+impl Layer {
+    /// create a signal layer entry for a copper layer side, numbered like Kicad does
+    pub fn new_copper(side: footprint::LayerSide) -> Option<Layer> {
+        side.copper_num().map(|num| Layer {
+            num,
+            layer: footprint::Layer {
+                side,
+                t: footprint::LayerType::Cu,
+            },
+            layer_type: LayerType::Signal,
+            hide: false,
+            user_name: None,
+        })
+    }
+
+    /// check if the layer is a copper layer
+    pub fn is_copper(&self) -> bool {
+        self.layer.t == footprint::LayerType::Cu
+    }
 }
 
 /// layer type
@@ -247,10 +304,44 @@ pub enum LayerType {
 /// setup elements of the layout
 #[derive(Clone, Debug, Default)]
 pub struct Setup {
+    /// the board stackup, Kicad 6 and later
+    pub stackup: Option<Stackup>,
     /// the setup elements
     pub elements: Vec<SetupElement>,
     /// the pcb plot elements
     pub pcbplotparams: Vec<SetupElement>,
+    /// other (uninterpreted symbolic-expressions)
+    pub other: Vec<Sexp>,
+}
+
+/// the stackup of the board, from the top to the bottom
+// (stackup (layer "F.Cu" (type "copper") (thickness 0.035)) ... (copper_finish "None"))
+#[derive(Clone, Debug, Default)]
+pub struct Stackup {
+    /// the layers
+    pub layers: Vec<StackupLayer>,
+    /// other (uninterpreted symbolic-expressions)
+    pub other: Vec<Sexp>,
+}
+
+/// a layer of the stackup
+// (layer "dielectric 1" (type "core") (thickness 1.51) (material "FR4") (epsilon_r 4.5) (loss_tangent 0.02))
+#[derive(Clone, Debug, Default)]
+pub struct StackupLayer {
+    /// name, a board layer like `F.Cu` or a dielectric like `dielectric 1`
+    pub name: String,
+    /// type, like `copper` or `core`
+    pub t: String,
+    /// thickness
+    pub thickness: Option<f64>,
+    /// material
+    pub material: Option<String>,
+    /// relative permittivity
+    pub epsilon_r: Option<f64>,
+    /// loss tangent
+    pub loss_tangent: Option<f64>,
+    /// other (uninterpreted symbolic-expressions)
+    pub other: Vec<Sexp>,
 }
 
 /// a generic setup element
@@ -331,6 +422,8 @@ pub struct NetClass {
     pub diff_pair_width: Option<f64>,
     /// associated nets
     pub nets: Vec<NetName>,
+    /// other (uninterpreted symbolic-expressions)
+    pub other: Vec<Sexp>,
 }
 
 /// text
@@ -346,6 +439,9 @@ pub struct GrText {
     pub effects: footprint::Effects,
     /// timestamp
     pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the text expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Adjust for GrText {
@@ -374,8 +470,13 @@ pub struct GrLine {
     pub layer: footprint::Layer,
     /// width
     pub width: f64,
+    /// line style; Kicad 7 writes the width in a stroke with a type
+    pub stroke: Option<String>,
     /// time stamp
     pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the line expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Adjust for GrLine {
@@ -395,11 +496,14 @@ impl BoundingBox for GrLine {
     }
 }
 
-/// arc
+/// arc, either with a center as `start` and an angle or since Kicad 6 with
+/// a start, middle and end point
 #[derive(Clone, Debug, Default)]
 pub struct GrArc {
     /// start point
     pub start: footprint::Xy,
+    /// middle point
+    pub mid: Option<footprint::Xy>,
     /// end point
     pub end: footprint::Xy,
     /// angle
@@ -408,13 +512,21 @@ pub struct GrArc {
     pub layer: footprint::Layer,
     /// width
     pub width: f64,
+    /// line style; Kicad 7 writes the width in a stroke with a type
+    pub stroke: Option<String>,
     /// timestamp
     pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the arc expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Adjust for GrArc {
     fn adjust(&mut self, x: f64, y: f64) {
         self.start.adjust(x, y);
+        if let Some(ref mut mid) = self.mid {
+            mid.adjust(x, y)
+        }
         self.end.adjust(x, y);
     }
 }
@@ -425,7 +537,11 @@ impl BoundingBox for GrArc {
         let y1 = self.start.y.min(self.end.y);
         let x2 = self.start.x.max(self.end.x);
         let y2 = self.start.y.max(self.end.y);
-        Bound::new(x1, y1, x2, y2)
+        let mut b = Bound::new(x1, y1, x2, y2);
+        if let Some(ref mid) = self.mid {
+            b.update(&Bound::new(mid.x, mid.y, mid.x, mid.y))
+        }
+        b
     }
 }
 
@@ -441,8 +557,15 @@ pub struct GrCircle {
     pub layer: footprint::Layer,
     /// width
     pub width: f64,
+    /// line style; Kicad 7 writes the width in a stroke with a type
+    pub stroke: Option<String>,
+    /// fill, `none` or `solid`
+    pub fill: Option<String>,
     /// timestamp
     pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the circle expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Adjust for GrCircle {
@@ -468,6 +591,69 @@ impl BoundingBox for GrCircle {
     }
 }
 
+/// polygon
+// (gr_poly (pts (xy 100 100) (xy 110 100) (xy 105 90)) (layer "F.Cu") (width 0.1) (fill solid) (tstamp ...))
+#[derive(Clone, Debug, Default)]
+pub struct GrPoly {
+    /// points
+    pub pts: footprint::Pts,
+    /// layer
+    pub layer: footprint::Layer,
+    /// width
+    pub width: f64,
+    /// fill, `none` or `solid`
+    pub fill: Option<String>,
+    /// timestamp
+    pub tstamp: Option<String>,
+}
+
+impl Adjust for GrPoly {
+    fn adjust(&mut self, x: f64, y: f64) {
+        self.pts.adjust(x, y)
+    }
+}
+
+impl BoundingBox for GrPoly {
+    fn bounding_box(&self) -> Bound {
+        self.pts.bounding_box()
+    }
+}
+
+/// rectangle
+// (gr_rect (start 90 90) (end 110 110) (layer "Edge.Cuts") (width 0.1) (fill none) (tstamp ...))
+#[derive(Clone, Debug, Default)]
+pub struct GrRect {
+    /// start corner
+    pub start: footprint::Xy,
+    /// end corner
+    pub end: footprint::Xy,
+    /// layer
+    pub layer: footprint::Layer,
+    /// width
+    pub width: f64,
+    /// fill, `none` or `solid`
+    pub fill: Option<String>,
+    /// timestamp
+    pub tstamp: Option<String>,
+}
+
+impl Adjust for GrRect {
+    fn adjust(&mut self, x: f64, y: f64) {
+        self.start.adjust(x, y);
+        self.end.adjust(x, y)
+    }
+}
+
+impl BoundingBox for GrRect {
+    fn bounding_box(&self) -> Bound {
+        let x1 = self.start.x.min(self.end.x);
+        let y1 = self.start.y.min(self.end.y);
+        let x2 = self.start.x.max(self.end.x);
+        let y2 = self.start.y.max(self.end.y);
+        Bound::new(x1, y1, x2, y2)
+    }
+}
+
 /// dimension
 #[derive(Clone, Debug, Default)]
 pub struct Dimension {
@@ -543,6 +729,9 @@ pub struct Segment {
     pub tstamp: Option<String>,
     /// status
     pub status: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the segment expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Adjust for Segment {
@@ -562,6 +751,44 @@ impl BoundingBox for Segment {
     }
 }
 
+/// arc track
+// (arc (start 101 100) (mid 102 101) (end 103 100) (width 0.25) (layer "F.Cu") (net 1) (tstamp ...))
+#[derive(Clone, Debug, Default)]
+pub struct Arc {
+    /// start point
+    pub start: footprint::Xy,
+    /// middle point
+    pub mid: footprint::Xy,
+    /// end point
+    pub end: footprint::Xy,
+    /// width
+    pub width: f64,
+    /// layer
+    pub layer: footprint::Layer,
+    /// net
+    pub net: i64,
+    /// tstamp
+    pub tstamp: Option<String>,
+}
+
+impl Adjust for Arc {
+    fn adjust(&mut self, x: f64, y: f64) {
+        self.start.adjust(x, y);
+        self.mid.adjust(x, y);
+        self.end.adjust(x, y)
+    }
+}
+
+impl BoundingBox for Arc {
+    fn bounding_box(&self) -> Bound {
+        let x1 = self.start.x.min(self.end.x).min(self.mid.x);
+        let y1 = self.start.y.min(self.end.y).min(self.mid.y);
+        let x2 = self.start.x.max(self.end.x).max(self.mid.x);
+        let y2 = self.start.y.max(self.end.y).max(self.mid.y);
+        Bound::new(x1, y1, x2, y2)
+    }
+}
+
 // TODO: support blind and micro via
 /// via
 // (via [blind] [micro] (at 132.1948 121.2202) (size 0.675) (drill 0.25) (layers F.Cu B.Cu) (net 19))
@@ -581,6 +808,11 @@ pub struct Via {
     pub layers: footprint::Layers,
     /// net
     pub net: i64,
+    /// tstamp
+    pub tstamp: Option<String>,
+    /// other (uninterpreted symbolic-expressions) with their position in
+    /// the via expression
+    pub other: Vec<(usize, Sexp)>,
 }
 
 impl Adjust for Via {
@@ -608,6 +840,7 @@ impl Default for Layout {
             setup: Setup::default(),
             general: General::default(),
             page: String::from("A4"),
+            header: vec![],
             layers: vec![],
         }
     }
@@ -633,6 +866,21 @@ impl BoundingBox for Layout {
 }
 
 impl Layout {
+    /// check if the layout uses the file format of Kicad 6 and later
+    pub fn is_kicad6(&self) -> bool {
+        self.version > KICAD5_VERSION
+    }
+
+    /// get list of copper layers
+    pub fn copper_layers(&self) -> Vec<&Layer> {
+        self.layers.iter().filter(|l| l.is_copper()).collect()
+    }
+
+    /// get the number of copper layers
+    pub fn copper_layer_count(&self) -> i64 {
+        self.copper_layers().len() as i64
+    }
+
     /// get lists of nets
     pub fn nets(&self) -> Vec<&Net> {
         let mut v = vec![];
@@ -708,6 +956,9 @@ impl Layout {
                 Element::GrLine(_) |
                 Element::GrArc(_) |
                 Element::GrCircle(_) |
+                Element::GrPoly(_) |
+                Element::GrRect(_) |
+                Element::Arc(_) |
                 Element::Dimension(_) |
                 Element::Zone(_) |
                 Element::Other(_) => (),
@@ -745,6 +996,9 @@ impl Layout {
                 Element::GrLine(_) |
                 Element::GrArc(_) |
                 Element::GrCircle(_) |
+                Element::GrPoly(_) |
+                Element::GrRect(_) |
+                Element::Arc(_) |
                 Element::Dimension(_) |
                 Element::Zone(_) |
                 Element::Other(_) => (),
@@ -788,6 +1042,7 @@ impl Layout {
             diff_pair_gap: diff_pair_gap,
             diff_pair_width: diff_pair_width,
             nets: nets.into_iter().map(|x| x.into()).collect(),
+            other: vec![],
         }));
     }
 }
@@ -813,6 +1068,7 @@ impl Default for General {
             zones: 0,
             modules: 0,
             nets: 0,
+            other: vec![],
         }
     }
 }
@@ -873,6 +1129,9 @@ impl Adjust for Element {
             Element::GrLine(ref mut e) => e.adjust(x, y),
             Element::GrArc(ref mut e) => e.adjust(x, y),
             Element::GrCircle(ref mut e) => e.adjust(x, y),
+            Element::GrPoly(ref mut e) => e.adjust(x, y),
+            Element::GrRect(ref mut e) => e.adjust(x, y),
+            Element::Arc(ref mut e) => e.adjust(x, y),
             Element::Dimension(ref mut e) => e.adjust(x, y),
             Element::Segment(ref mut e) => e.adjust(x, y),
             Element::Via(ref mut e) => e.adjust(x, y),
@@ -890,6 +1149,9 @@ impl BoundingBox for Element {
             Element::GrLine(ref e) => e.bounding_box(),
             Element::GrArc(ref e) => e.bounding_box(),
             Element::GrCircle(ref e) => e.bounding_box(),
+            Element::GrPoly(ref e) => e.bounding_box(),
+            Element::GrRect(ref e) => e.bounding_box(),
+            Element::Arc(ref e) => e.bounding_box(),
             Element::Dimension(ref e) => e.bounding_box(),
             Element::Segment(ref e) => e.bounding_box(),
             Element::Via(ref e) => e.bounding_box(),
@@ -9,6 +9,7 @@ use wrap;
 use Sexp;
 use symbolic_expressions::iteratom::*;
 use symbolic_expressions::SexpError;
+use sexpr::{name_of, parse_stroke};
 
 use layout::data::*;
 
@@ -24,13 +25,30 @@ impl FromSexp for Version {
     }
 }
 
+// (generator pcbnew), replaces host since Kicad 6
+struct Generator(String);
+
+impl FromSexp for Generator {
+    fn from_sexp(s: &Sexp) -> Result<Generator, SexpError> {
+        let mut i = IterAtom::new(s, "generator")?;
+        let g = Generator(i.s("value")?);
+        i.close(g)
+    }
+}
+
+// (page A4) or since Kicad 6 (paper "A4"), optionally followed by a user
+// size or portrait
 struct Page(String);
 
 impl FromSexp for Page {
     fn from_sexp(s: &Sexp) -> Result<Page, SexpError> {
-        let mut i = IterAtom::new(s, "page")?;
-        let p = Page(i.s("value")?);
-        i.close(p)
+        let name = s.list_name()?;
+        let mut i = IterAtom::new(s, name)?;
+        let mut p = vec![i.s("value")?];
+        while let Some(x) = i.maybe_s() {
+            p.push(x)
+        }
+        i.close(Page(p.join(" ")))
     }
 }
 
@@ -44,16 +62,29 @@ impl FromSexp for Polygon {
     }
 }
 
-struct FilledPolygon(footprint::Pts);
-
 impl FromSexp for FilledPolygon {
     fn from_sexp(s: &Sexp) -> Result<FilledPolygon, SexpError> {
         let mut i = IterAtom::new(s, "filled_polygon")?;
-        let f = FilledPolygon(i.t("pts")?);
+        let f = FilledPolygon {
+            layer: i.maybe_t(),
+            island: i.iter.next_if(|x| name_of(x) == "island").is_some(),
+            pts: i.t("pts")?,
+        };
         i.close(f)
     }
 }
 
+struct Mid(footprint::Xy);
+
+impl FromSexp for Mid {
+    fn from_sexp(s: &Sexp) -> Result<Mid, SexpError> {
+        let mut i = IterAtom::new(s, "mid")?;
+        let x = i.f("x")?;
+        let y = i.f("y")?;
+        i.close(Mid(footprint::Xy::new(x, y, footprint::XyType::Mid)))
+    }
+}
+
 struct FillSegments(footprint::Pts);
 
 impl FromSexp for FillSegments {
@@ -86,18 +117,20 @@ impl FromSexp for Host {
 
 impl FromSexp for General {
     fn from_sexp(s: &Sexp) -> Result<General, SexpError> {
+        // Kicad 6 and later only keep the thickness
         let mut g = General::default();
         let mut i = IterAtom::new(s, "general")?;
-        g.links = i.i_in_list("links")?;
-        g.no_connects = i.i_in_list("no_connects")?;
-        g.area = i.t("area")?;
+        g.links = i.maybe_i_in_list("links").unwrap_or(0);
+        g.no_connects = i.maybe_i_in_list("no_connects").unwrap_or(0);
+        g.area = i.maybe_t().unwrap_or_default();
         g.thickness = i.f_in_list("thickness")?;
-        g.drawings = i.i_in_list("drawings")?;
-        g.tracks = i.i_in_list("tracks")?;
-        g.zones = i.i_in_list("zones")?;
-        g.modules = i.i_in_list("modules")?;
-        g.nets = i.i_in_list("nets")?;
-        i.close(g)
+        g.drawings = i.maybe_i_in_list("drawings").unwrap_or(0);
+        g.tracks = i.maybe_i_in_list("tracks").unwrap_or(0);
+        g.zones = i.maybe_i_in_list("zones").unwrap_or(0);
+        g.modules = i.maybe_i_in_list("modules").unwrap_or(0);
+        g.nets = i.maybe_i_in_list("nets").unwrap_or(0);
+        g.other = i.iter.cloned().collect();
+        Ok(g)
     }
 }
 
@@ -128,18 +161,21 @@ impl FromSexp for LayerVec {
     }
 }
 
+// (32 B.Adhes user hide) or since Kicad 6 (32 "B.Adhes" user "B.Adhesive")
 impl FromSexp for Layer {
     fn from_sexp(s: &Sexp) -> Result<Layer, SexpError> {
         let mut i = IterAtom::new_nameless(s, "layer")?;
         let num = i.i("num")?;
         let layer = footprint::Layer::from_string(&i.s("layer")?)?;
         let layer_type = i.t("layer_type")?;
-        let hide = i.maybe_s().is_some();
+        let hide = i.maybe_literal_s("hide").is_some();
+        let user_name = i.maybe_s();
         i.close(Layer {
             num: num,
             layer: layer,
             layer_type: layer_type,
             hide: hide,
+            user_name,
         })
     }
 }
@@ -170,6 +206,36 @@ impl FromSexp for SetupElement {
     }
 }
 
+impl FromSexp for StackupLayer {
+    fn from_sexp(s: &Sexp) -> Result<StackupLayer, SexpError> {
+        let mut i = IterAtom::new(s, "layer")?;
+        Ok(StackupLayer {
+            name: i.s("name")?,
+            t: i.s_in_list("type")?,
+            thickness: i.maybe_f_in_list("thickness"),
+            material: i.maybe_s_in_list("material"),
+            epsilon_r: i.maybe_f_in_list("epsilon_r"),
+            loss_tangent: i.maybe_f_in_list("loss_tangent"),
+            other: i.iter.cloned().collect(),
+        })
+    }
+}
+
+impl FromSexp for Stackup {
+    fn from_sexp(s: &Sexp) -> Result<Stackup, SexpError> {
+        let i = IterAtom::new(s, "stackup")?;
+        let mut stackup = Stackup::default();
+        for x in i.iter {
+            if x.list_name()? == "layer" {
+                stackup.layers.push(from_sexp(x)?)
+            } else {
+                stackup.other.push(x.clone())
+            }
+        }
+        Ok(stackup)
+    }
+}
+
 impl FromSexp for NetClass {
     fn from_sexp(s: &Sexp) -> Result<NetClass, SexpError> {
         fn parse(e: &Sexp, name: &str) -> Result<f64, SexpError> {
@@ -189,6 +255,7 @@ impl FromSexp for NetClass {
         let mut diff_pair_gap = None;
         let mut diff_pair_width = None;
         let mut nets = vec![];
+        let mut other = vec![];
         for x in i.iter {
             let list_name = x.list_name()?;
             let xn = &list_name[..];
@@ -205,7 +272,7 @@ impl FromSexp for NetClass {
                 "uvia_drill" => uvia_drill = parse(x, xn)?,
                 "diff_pair_gap" => diff_pair_gap = Some(parse(x, xn)?),
                 "diff_pair_width" => diff_pair_width = Some(parse(x, xn)?),
-                _ => return Err(format!("unknown net_class field {}", list_name).into()),
+                _ => other.push(x.clone()),
             }
         }
         let net_class = NetClass {
@@ -220,6 +287,7 @@ impl FromSexp for NetClass {
             diff_pair_width: diff_pair_width,
             nets: nets.into_iter().map(|x| x.into()).collect(),
             trace_width: trace_width,
+            other,
         };
         Ok(net_class)
     }
@@ -227,25 +295,32 @@ impl FromSexp for NetClass {
 
 impl FromSexp for Setup {
     fn from_sexp(s: &Sexp) -> Result<Setup, SexpError> {
+        let mut stackup = None;
         let mut elements = vec![];
         let mut pcbplotparams = vec![];
+        let mut other = vec![];
         let i = IterAtom::new(s, "setup")?;
         for v in i.iter {
-            let n = v.list_name()?;
-            if n == "pcbplotparams" {
+            let n = v.list_name().map(|n| n.as_str()).unwrap_or("");
+            if n == "stackup" {
+                stackup = Some(from_sexp(v)?)
+            } else if n == "pcbplotparams" {
                 let i2 = IterAtom::new(v, "pcbplotparams")?;
                 for y in i2.iter {
                     let p_e = from_sexp(y)?;
                     pcbplotparams.push(p_e)
                 }
-            } else {
-                let setup_element = from_sexp(v)?;
+            } else if let Ok(setup_element) = SetupElement::from_sexp(v) {
                 elements.push(setup_element)
+            } else {
+                other.push(v.clone())
             }
         }
         Ok(Setup {
+            stackup,
             elements: elements,
             pcbplotparams: pcbplotparams,
+            other,
         })
     }
 }
@@ -256,62 +331,160 @@ fn parse_other(e: &Sexp) -> Element {
     Element::Other(e.clone())
 }
 
+fn has_list(e: &Sexp, name: &str) -> Result<bool, SexpError> {
+    Ok(e.list()?.iter().any(|x| match *x {
+        Sexp::List(_) => x.list_name().map(|n| n == name).unwrap_or(false),
+        _ => false,
+    }))
+}
+
 impl FromSexp for GrText {
     fn from_sexp(s: &Sexp) -> Result<GrText, SexpError> {
-        let mut t = GrText::default();
         let mut i = IterAtom::new(s, "gr_text")?;
-        t.value = i.s("value")?;
-        t.at = i.t("at")?;
-        t.layer = i.t("layer")?;
-        t.effects = i.t("effects")?;
-        t.tstamp = i.maybe_s_in_list("tstamp");
-        i.close(t)
+        let value = i.s("value")?;
+        let (mut at, mut layer, mut effects) = (None, None, None);
+        let mut tstamp = None;
+        let mut other = vec![];
+        let first = s.list()?.len() - i.iter.len();
+        for (n, e) in i.iter.enumerate() {
+            match name_of(e) {
+                "at" => at = Some(from_sexp(e)?),
+                "layer" => layer = Some(from_sexp(e)?),
+                "effects" => effects = Some(from_sexp(e)?),
+                "tstamp" => tstamp = Some(e.named_value_s("tstamp")?),
+                _ => other.push((first + n, e.clone())),
+            }
+        }
+        Ok(GrText {
+            value,
+            at: at.ok_or("gr_text: missing at")?,
+            layer: layer.ok_or("gr_text: missing layer")?,
+            effects: effects.ok_or("gr_text: missing effects")?,
+            tstamp,
+            other,
+        })
     }
 }
 
+// (gr_line (start 100 100) (end 110 100) (layer Edge.Cuts) (width 0.1) (tstamp 5A1B2C3D))
+// (gr_line (start 100 100) (end 110 100) (stroke (width 0.1) (type default)) (layer "Edge.Cuts"))
 impl FromSexp for GrLine {
     fn from_sexp(s: &Sexp) -> Result<GrLine, SexpError> {
+        let i = IterAtom::new(s, "gr_line")?;
         let mut l = GrLine::default();
-        let mut i = IterAtom::new(s, "gr_line")?;
-        l.start = i.t("start")?;
-        l.end = i.t("end")?;
-        l.angle = i.maybe_f_in_list("angle").unwrap_or(0.0);
-        l.layer = i.t("layer")?;
-        l.width = i.maybe_f_in_list("width").unwrap_or(0.0);
-        l.tstamp = i.maybe_s_in_list("tstamp");
-        i.close(l)
+        let (mut start, mut end, mut layer) = (None, None, None);
+        for (n, e) in i.iter.enumerate() {
+            match name_of(e) {
+                "start" => start = Some(from_sexp(e)?),
+                "end" => end = Some(from_sexp(e)?),
+                "angle" => l.angle = e.named_value_f("angle")?,
+                "layer" => layer = Some(from_sexp(e)?),
+                "width" => l.width = e.named_value_f("width")?,
+                "stroke" => {
+                    let (width, t) = parse_stroke(e)?;
+                    l.width = width;
+                    l.stroke = Some(t)
+                }
+                "tstamp" => l.tstamp = Some(e.named_value_s("tstamp")?),
+                _ => l.other.push((n + 1, e.clone())),
+            }
+        }
+        l.start = start.ok_or("gr_line: missing start")?;
+        l.end = end.ok_or("gr_line: missing end")?;
+        l.layer = layer.ok_or("gr_line: missing layer")?;
+        Ok(l)
     }
 }
 
 impl FromSexp for GrArc {
     fn from_sexp(s: &Sexp) -> Result<GrArc, SexpError> {
+        let i = IterAtom::new(s, "gr_arc")?;
         let mut a = GrArc::default();
-        let mut i = IterAtom::new(s, "gr_arc")?;
-        a.start = i.t("start")?;
-        a.end = i.t("end")?;
-        a.angle = i.f_in_list("angle")?;
-        a.layer = i.t("layer")?;
-        a.width = i.maybe_f_in_list("width").unwrap_or(0.0);
-        a.tstamp = i.maybe_s_in_list("tstamp");
-        // TODO: maybe status field?
-        i.close(a)
+        let (mut start, mut end, mut angle, mut layer) = (None, None, None, None);
+        for (n, e) in i.iter.enumerate() {
+            match name_of(e) {
+                "start" => start = Some(from_sexp(e)?),
+                "mid" => a.mid = Some(from_sexp(e)?),
+                "end" => end = Some(from_sexp(e)?),
+                "angle" => angle = Some(e.named_value_f("angle")?),
+                "layer" => layer = Some(from_sexp(e)?),
+                "width" => a.width = e.named_value_f("width")?,
+                "stroke" => {
+                    let (width, t) = parse_stroke(e)?;
+                    a.width = width;
+                    a.stroke = Some(t)
+                }
+                "tstamp" => a.tstamp = Some(e.named_value_s("tstamp")?),
+                // TODO: maybe status field?
+                _ => a.other.push((n + 1, e.clone())),
+            }
+        }
+        a.start = start.ok_or("gr_arc: missing start")?;
+        a.end = end.ok_or("gr_arc: missing end")?;
+        a.angle = match a.mid {
+            Some(_) => 0.0,
+            None => angle.ok_or("gr_arc: missing angle")?,
+        };
+        a.layer = layer.ok_or("gr_arc: missing layer")?;
+        Ok(a)
     }
 }
 
 impl FromSexp for GrCircle {
     fn from_sexp(s: &Sexp) -> Result<GrCircle, SexpError> {
+        let i = IterAtom::new(s, "gr_circle")?;
         let mut c = GrCircle::default();
-        let mut i = IterAtom::new(s, "gr_circle")?;
-        c.center = i.t("center")?;
-        c.end = i.t("end")?;
-        c.layer = i.t("layer")?;
-        c.width = i.maybe_f_in_list("width").unwrap_or(0.0);
-        c.tstamp = i.maybe_s_in_list("tstamp");
-        // TODO: status field?
-        i.close(c)
+        let (mut center, mut end, mut layer) = (None, None, None);
+        for (n, e) in i.iter.enumerate() {
+            match name_of(e) {
+                "center" => center = Some(from_sexp(e)?),
+                "end" => end = Some(from_sexp(e)?),
+                "layer" => layer = Some(from_sexp(e)?),
+                "width" => c.width = e.named_value_f("width")?,
+                "stroke" => {
+                    let (width, t) = parse_stroke(e)?;
+                    c.width = width;
+                    c.stroke = Some(t)
+                }
+                "fill" => c.fill = Some(e.named_value_s("fill")?),
+                "tstamp" => c.tstamp = Some(e.named_value_s("tstamp")?),
+                // TODO: status field?
+                _ => c.other.push((n + 1, e.clone())),
+            }
+        }
+        c.center = center.ok_or("gr_circle: missing center")?;
+        c.end = end.ok_or("gr_circle: missing end")?;
+        c.layer = layer.ok_or("gr_circle: missing layer")?;
+        Ok(c)
     }
 }
 
+impl FromSexp for GrPoly {
+    fn from_sexp(s: &Sexp) -> Result<GrPoly, SexpError> {
+        let mut p = GrPoly::default();
+        let mut i = IterAtom::new(s, "gr_poly")?;
+        p.pts = i.t("pts")?;
+        p.layer = i.t("layer")?;
+        p.width = i.maybe_f_in_list("width").unwrap_or(0.0);
+        p.fill = i.maybe_s_in_list("fill");
+        p.tstamp = i.maybe_s_in_list("tstamp");
+        i.close(p)
+    }
+}
+
+impl FromSexp for GrRect {
+    fn from_sexp(s: &Sexp) -> Result<GrRect, SexpError> {
+        let mut r = GrRect::default();
+        let mut i = IterAtom::new(s, "gr_rect")?;
+        r.start = i.t("start")?;
+        r.end = i.t("end")?;
+        r.layer = i.t("layer")?;
+        r.width = i.maybe_f_in_list("width").unwrap_or(0.0);
+        r.fill = i.maybe_s_in_list("fill");
+        r.tstamp = i.maybe_s_in_list("tstamp");
+        i.close(r)
+    }
+}
 
 impl FromSexp for Dimension {
     fn from_sexp(s: &Sexp) -> Result<Dimension, SexpError> {
@@ -335,44 +508,64 @@ impl FromSexp for Dimension {
 
 impl FromSexp for Zone {
     fn from_sexp(s: &Sexp) -> Result<Zone, SexpError> {
-        let mut i = IterAtom::new(s, "zone")?;
-        let net = i.i_in_list("net")?;
-        let net_name = i.s_in_list("net_name")?;
-        let layer = i.t("layer")?;
-        let tstamp = i.s_in_list("tstamp")?;
-        let hatch = i.t("hatch")?;
-        let priority = i.maybe_i_in_list("priority").unwrap_or(0) as u64;
-        let connect_pads = i.t("connect_pads")?;
-        let min_thickness = i.f_in_list("min_thickness")?;
-        let keepout = i.maybe_t();
-        let fill = i.t("fill")?;
+        let i = IterAtom::new(s, "zone")?;
+        let mut net = None;
+        let mut net_name = None;
+        let mut layer = None;
+        let mut tstamp = None;
+        let mut hatch = None;
+        let mut priority = 0;
+        let mut connect_pads = None;
+        let mut min_thickness = None;
+        let mut filled_areas_thickness = None;
+        let mut keepout = None;
+        let mut fill = None;
         let mut polygons = vec![];
         let mut filled_polygons = vec![];
         let mut fill_segments = None;
         let mut others = vec![];
         for x in i.iter {
-            if let Ok(p) = Polygon::from_sexp(x) {
-                polygons.push(p.0)
-            } else if let Ok(p) = FilledPolygon::from_sexp(x) {
-                filled_polygons.push(p.0)
-            } else if let Ok(p) = FillSegments::from_sexp(x) {
-                fill_segments = Some(p.0);
-            } else {
-                others.push(x.clone());
-                debug!("'zone': not parsing {}", x);
+            let name = match *x {
+                Sexp::List(_) => x.list_name()?.as_str(),
+                _ => "",
+            };
+            match name {
+                "net" => net = Some(x.named_value_i("net")?),
+                "net_name" => net_name = Some(x.named_value_s("net_name")?),
+                "layer" => layer = Some(from_sexp(x)?),
+                "tstamp" => tstamp = Some(x.named_value_s("tstamp")?),
+                "hatch" => hatch = Some(from_sexp(x)?),
+                "priority" => priority = x.named_value_i("priority")? as u64,
+                "connect_pads" => connect_pads = Some(from_sexp(x)?),
+                "min_thickness" => min_thickness = Some(x.named_value_f("min_thickness")?),
+                "filled_areas_thickness" => {
+                    let thickness = x.named_value_string("filled_areas_thickness")?;
+                    filled_areas_thickness = Some(thickness == "yes")
+                }
+                "keepout" => keepout = Some(from_sexp(x)?),
+                "fill" => fill = Some(from_sexp(x)?),
+                "polygon" => polygons.push(Polygon::from_sexp(x)?.0),
+                "filled_polygon" => filled_polygons.push(from_sexp(x)?),
+                "fill_segments" => fill_segments = Some(FillSegments::from_sexp(x)?.0),
+                _ => {
+                    others.push(x.clone());
+                    debug!("'zone': not parsing {}", x);
+                }
             }
         }
+        let missing = |field: &str| -> SexpError { format!("zone: missing {}", field).into() };
         Ok(Zone {
-            net: net,
-            net_name: net_name.into(),
-            layer: layer,
-            tstamp: tstamp,
-            hatch: hatch,
+            net: net.ok_or_else(|| missing("net"))?,
+            net_name: net_name.ok_or_else(|| missing("net_name"))?.into(),
+            layer: layer.ok_or_else(|| missing("layer"))?,
+            tstamp: tstamp.ok_or_else(|| missing("tstamp"))?,
+            hatch: hatch.ok_or_else(|| missing("hatch"))?,
             priority: priority,
-            connect_pads: connect_pads,
-            min_thickness: min_thickness,
+            connect_pads: connect_pads.ok_or_else(|| missing("connect_pads"))?,
+            min_thickness: min_thickness.ok_or_else(|| missing("min_thickness"))?,
+            filled_areas_thickness,
             keepout: keepout,
-            fill: fill,
+            fill: fill.ok_or_else(|| missing("fill"))?,
             polygons: polygons,
             filled_polygons: filled_polygons,
             fill_segments: fill_segments,
@@ -400,14 +593,23 @@ impl FromSexp for ConnectPads {
         i.close(connect_pads)
     }
 }
+// (keepout (tracks not_allowed) (vias not_allowed) (copperpour allowed))
 impl FromSexp for Keepout {
     fn from_sexp(s: &Sexp) -> Result<Keepout, SexpError> {
         let mut keepout = Keepout::default();
-        let mut i = IterAtom::new(s, "keepout")?;
-        keepout.tracks = !i.s_in_list("tracks")?.starts_with("not");
-        keepout.vias = !i.s_in_list("vias")?.starts_with("not");
-        keepout.copperpour = !i.s_in_list("copperpour")?.starts_with("not");
-        i.close(keepout)
+        let i = IterAtom::new(s, "keepout")?;
+        let allowed = |x: &Sexp, name: &str| -> Result<bool, SexpError> {
+            Ok(!x.named_value_string(name)?.starts_with("not"))
+        };
+        for x in i.iter {
+            match x.list_name()?.as_str() {
+                "tracks" => keepout.tracks = allowed(x, "tracks")?,
+                "vias" => keepout.vias = allowed(x, "vias")?,
+                "copperpour" => keepout.copperpour = allowed(x, "copperpour")?,
+                _ => keepout.other.push(x.clone()),
+            }
+        }
+        Ok(keepout)
     }
 }
 
@@ -415,47 +617,101 @@ impl FromSexp for Keepout {
 impl FromSexp for Fill {
     fn from_sexp(s: &Sexp) -> Result<Fill, SexpError> {
         let mut fill = Fill::default();
-        let mut i = IterAtom::new(s, "fill")?;
-        fill.filled = i.maybe_literal_s("yes").is_some();
-        fill.segment = i.maybe_s_in_list("mode").is_some();
-        fill.arc_segments = i.i_in_list("arc_segments")?;
-        fill.thermal_gap = i.f_in_list("thermal_gap")?;
-        fill.thermal_bridge_width = i.f_in_list("thermal_bridge_width")?;
-        fill.smoothing = i.maybe_s_in_list("smoothing");
-        fill.corner_radius = i.maybe_f_in_list("radius").unwrap_or(0.0);
-        i.close(fill)
+        let i = IterAtom::new(s, "fill")?;
+        let mut thermal_gap = None;
+        let mut thermal_bridge_width = None;
+        for x in i.iter {
+            let name = match *x {
+                Sexp::List(_) => x.list_name()?.as_str(),
+                _ => "",
+            };
+            match name {
+                "" if x.string()? == "yes" => fill.filled = true,
+                "mode" => fill.mode = Some(x.named_value_s("mode")?),
+                "arc_segments" => fill.arc_segments = Some(x.named_value_i("arc_segments")?),
+                "thermal_gap" => thermal_gap = Some(x.named_value_f("thermal_gap")?),
+                "thermal_bridge_width" => {
+                    thermal_bridge_width = Some(x.named_value_f("thermal_bridge_width")?)
+                }
+                "smoothing" => fill.smoothing = Some(x.named_value_s("smoothing")?),
+                "radius" => fill.corner_radius = x.named_value_f("radius")?,
+                _ => fill.other.push(x.clone()),
+            }
+        }
+        fill.thermal_gap = thermal_gap.ok_or("fill: missing thermal_gap")?;
+        fill.thermal_bridge_width =
+            thermal_bridge_width.ok_or("fill: missing thermal_bridge_width")?;
+        Ok(fill)
     }
 }
 
 // (segment (start 211 61.1) (end 211.1 61) (width 0.2032) (layer F.Cu) (net 20) [(tstamp 55A0DB7E)] [(status foo)])
 impl FromSexp for Segment {
     fn from_sexp(s: &Sexp) -> Result<Segment, SexpError> {
-        let mut i = IterAtom::new(s, "segment")?;
+        let i = IterAtom::new(s, "segment")?;
         let mut segment = Segment::default();
-        segment.start = i.t("start")?;
-        segment.end = i.t("end")?;
-        segment.width = i.f_in_list("width")?;
-        segment.layer = i.t("layer")?;
-        segment.net = i.i_in_list("net")?;
-        segment.tstamp = i.maybe_s_in_list("tstamp");
-        segment.status = i.maybe_s_in_list("status");
-        i.close(segment)
+        let (mut start, mut end, mut width, mut layer, mut net) = (None, None, None, None, None);
+        for (n, e) in i.iter.enumerate() {
+            match name_of(e) {
+                "start" => start = Some(from_sexp(e)?),
+                "end" => end = Some(from_sexp(e)?),
+                "width" => width = Some(e.named_value_f("width")?),
+                "layer" => layer = Some(from_sexp(e)?),
+                "net" => net = Some(e.named_value_i("net")?),
+                "tstamp" => segment.tstamp = Some(e.named_value_s("tstamp")?),
+                "status" => segment.status = Some(e.named_value_s("status")?),
+                _ => segment.other.push((n + 1, e.clone())),
+            }
+        }
+        segment.start = start.ok_or("segment: missing start")?;
+        segment.end = end.ok_or("segment: missing end")?;
+        segment.width = width.ok_or("segment: missing width")?;
+        segment.layer = layer.ok_or("segment: missing layer")?;
+        segment.net = net.ok_or("segment: missing net")?;
+        Ok(segment)
     }
 }
 
-// (via [blind] [micro] (at 132.1948 121.2202) (size 0.675) (drill 0.25) (layers F.Cu B.Cu) (net 19))
+// (arc (start 101 100) (mid 102 101) (end 103 100) (width 0.25) (layer "F.Cu") (net 1) (tstamp ...))
+impl FromSexp for Arc {
+    fn from_sexp(s: &Sexp) -> Result<Arc, SexpError> {
+        let mut i = IterAtom::new(s, "arc")?;
+        let arc = Arc {
+            start: i.t("start")?,
+            mid: i.t::<Mid>("mid")?.0,
+            end: i.t("end")?,
+            width: i.f_in_list("width")?,
+            layer: i.t("layer")?,
+            net: i.i_in_list("net")?,
+            tstamp: i.maybe_s_in_list("tstamp"),
+        };
+        i.close(arc)
+    }
+}
+
+// (via [blind] [micro] (at 132.1948 121.2202) (size 0.675) (drill 0.25) (layers F.Cu B.Cu) [(free)] (net 19))
 impl FromSexp for Via {
     fn from_sexp(s: &Sexp) -> Result<Via, SexpError> {
-        let mut i = IterAtom::new(s, "via")?;
+        let i = IterAtom::new(s, "via")?;
         let mut via = Via::default();
-        via.blind = i.maybe_literal_s("blind").is_some();
-        via.micro = i.maybe_literal_s("micro").is_some();
-        via.at = i.t("at")?;
-        via.size = i.f_in_list("size")?;
-        via.drill = i.maybe_f_in_list("drill").unwrap_or(0.0);
-        via.layers = i.t("layers")?;
-        via.net = i.maybe_i_in_list("net").unwrap_or(0);
-        i.close(via)
+        let (mut at, mut size, mut layers) = (None, None, None);
+        for (n, e) in i.iter.enumerate() {
+            match (e, name_of(e)) {
+                (Sexp::String(t), _) if t == "blind" => via.blind = true,
+                (Sexp::String(t), _) if t == "micro" => via.micro = true,
+                (_, "at") => at = Some(from_sexp(e)?),
+                (_, "size") => size = Some(e.named_value_f("size")?),
+                (_, "drill") => via.drill = e.named_value_f("drill")?,
+                (_, "layers") => layers = Some(from_sexp(e)?),
+                (_, "net") => via.net = e.named_value_i("net")?,
+                (_, "tstamp") => via.tstamp = Some(e.named_value_s("tstamp")?),
+                _ => via.other.push((n + 1, e.clone())),
+            }
+        }
+        via.at = at.ok_or("via: missing at")?;
+        via.size = size.ok_or("via: missing size")?;
+        via.layers = layers.ok_or("via: missing layers")?;
+        Ok(via)
     }
 }
 
@@ -463,64 +719,71 @@ impl FromSexp for Layout {
     fn from_sexp(s: &Sexp) -> Result<Layout, SexpError> {
         let i = IterAtom::new(s, "kicad_pcb")?;
         let mut layout = Layout::default();
+        let mut seen_layers = false;
         // TODO: read in order instead of matching on iter
         for e in i.iter {
-            match (e.list_name()?).as_str() {
-                "version" => layout.version = Version::from_sexp(e)?.0,
-                "host" => layout.host = from_sexp(e)?,
-                "general" => layout.general = from_sexp(e)?,
-                "page" => layout.page = Page::from_sexp(e)?.0,
-                "layers" => layout.layers = LayerVec::from_sexp(e)?.0,
-                "module" => {
-                    let module = wrap(e, from_sexp, Element::Module)?;
-                    layout.elements.push(module)
-                }
-                "net" => {
-                    let net = wrap(e, from_sexp, Element::Net)?;
-                    layout.elements.push(net)
-                }
-                "net_class" => {
-                    let nc = wrap(e, from_sexp, Element::NetClass)?;
-                    layout.elements.push(nc)
+            let name = match *e {
+                Sexp::List(_) => e.list_name()?.as_str(),
+                _ => "",
+            };
+            let element = match name {
+                "version" => {
+                    layout.version = Version::from_sexp(e)?.0;
+                    continue;
                 }
-                "gr_text" => {
-                    let g = wrap(e, from_sexp, Element::GrText)?;
-                    layout.elements.push(g)
+                "host" => {
+                    layout.host = from_sexp(e)?;
+                    continue;
                 }
-                "gr_line" => {
-                    let g = wrap(e, from_sexp, Element::GrLine)?;
-                    layout.elements.push(g)
+                "generator" => {
+                    layout.host = Host {
+                        tool: Generator::from_sexp(e)?.0,
+                        build: String::new(),
+                    };
+                    continue;
                 }
-                "gr_arc" => {
-                    let g = wrap(e, from_sexp, Element::GrArc)?;
-                    layout.elements.push(g)
+                "general" => {
+                    layout.general = from_sexp(e)?;
+                    continue;
                 }
-                "gr_circle" => {
-                    let g = wrap(e, from_sexp, Element::GrCircle)?;
-                    layout.elements.push(g)
+                "page" | "paper" => {
+                    layout.page = Page::from_sexp(e)?.0;
+                    continue;
                 }
-                "dimension" => {
-                    let g = wrap(e, from_sexp, Element::Dimension)?;
-                    layout.elements.push(g)
+                "layers" => {
+                    layout.layers = LayerVec::from_sexp(e)?.0;
+                    seen_layers = true;
+                    continue;
                 }
-                "zone" => {
-                    let g = wrap(e, from_sexp, Element::Zone)?;
-                    layout.elements.push(g)
+                "setup" => {
+                    layout.setup = from_sexp(e)?;
+                    continue;
                 }
-                "segment" => {
-                    let g = wrap(e, from_sexp, Element::Segment)?;
-                    layout.elements.push(g)
+                "module" | "footprint" => wrap(e, from_sexp, Element::Module),
+                "net" => wrap(e, from_sexp, Element::Net),
+                "net_class" => wrap(e, from_sexp, Element::NetClass),
+                "gr_text" => wrap(e, from_sexp, Element::GrText),
+                "gr_line" => wrap(e, from_sexp, Element::GrLine),
+                "gr_arc" => wrap(e, from_sexp, Element::GrArc),
+                "gr_circle" => wrap(e, from_sexp, Element::GrCircle),
+                "gr_poly" => wrap(e, from_sexp, Element::GrPoly),
+                "gr_rect" => wrap(e, from_sexp, Element::GrRect),
+                // the dimensions of Kicad 6 and later and zones on several
+                // layers aren't interpreted and are kept as they are
+                "dimension" if has_list(e, "type")? => Ok(parse_other(e)),
+                "zone" if has_list(e, "layers")? => Ok(parse_other(e)),
+                "dimension" => wrap(e, from_sexp, Element::Dimension),
+                "zone" => wrap(e, from_sexp, Element::Zone),
+                "segment" => wrap(e, from_sexp, Element::Segment),
+                "arc" => wrap(e, from_sexp, Element::Arc),
+                "via" => wrap(e, from_sexp, Element::Via),
+                _ if !seen_layers => {
+                    layout.header.push(e.clone());
+                    continue;
                 }
-                "via" => {
-                    let g = wrap(e, from_sexp, Element::Via)?;
-                    layout.elements.push(g)
-                }
-                "setup" => layout.setup = from_sexp(e)?,
-                _ => {
-                    // println!("unimplemented: {}", e);
-                    layout.elements.push(parse_other(e))
-                }
-            }
+                _ => Ok(parse_other(e)),
+            };
+            layout.elements.push(element?)
         }
         Ok(layout)
     }
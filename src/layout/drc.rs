@@ -0,0 +1,397 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! design rule checking of a layout
+//!
+//! the limits are taken from the `NetClass` of each net, falling back to
+//! the `Default` netclass and the `Setup` of the layout
+
+use std::collections::HashMap;
+use std::fmt;
+
+use checkfix::CheckFixData;
+use geometry::bounds_overlap;
+use layout::connectivity::{Copper, CopperItem, Item, EPSILON};
+use layout::{Element, Layout, NetClass};
+use Bound;
+
+/// DRC section
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    /// clearance between copper items of different nets
+    Clearance,
+    /// sizes of copper items
+    Size,
+}
+
+impl From<Section> for i64 {
+    fn from(section: Section) -> i64 {
+        match section {
+            Section::Clearance => 1,
+            Section::Size => 2,
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Section::Clearance => "Clearance",
+            Section::Size => "Size",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// DRC rule
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    /// track to track clearance
+    TrackTrack,
+    /// track to pad clearance
+    TrackPad,
+    /// via to via clearance
+    ViaVia,
+    /// minimum track width
+    TrackWidth,
+    /// minimum via diameter
+    ViaDiameter,
+    /// minimum via drill
+    ViaDrill,
+}
+
+impl Rule {
+    /// the section of the rule
+    pub fn section(&self) -> Section {
+        match *self {
+            Rule::TrackTrack | Rule::TrackPad | Rule::ViaVia => Section::Clearance,
+            Rule::TrackWidth | Rule::ViaDiameter | Rule::ViaDrill => Section::Size,
+        }
+    }
+}
+
+impl From<Rule> for i64 {
+    fn from(rule: Rule) -> i64 {
+        match rule {
+            Rule::TrackTrack | Rule::TrackWidth => 1,
+            Rule::TrackPad | Rule::ViaDiameter => 2,
+            Rule::ViaVia | Rule::ViaDrill => 3,
+        }
+    }
+}
+
+/// the design rule limits for a net
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    /// minimum clearance to copper of other nets
+    pub clearance: f64,
+    /// minimum track width
+    pub trace_width: f64,
+    /// minimum via diameter
+    pub via_dia: f64,
+    /// minimum via drill
+    pub via_drill: f64,
+    /// minimum micro via diameter
+    pub uvia_dia: f64,
+    /// minimum micro via drill
+    pub uvia_drill: f64,
+}
+
+/// the design rules of a layout
+#[derive(Debug, Clone)]
+pub struct Rules {
+    /// the absolute minimum values from the `Setup`
+    pub setup: Limits,
+    nets: HashMap<i64, Limits>,
+    default: Limits,
+}
+
+impl Rules {
+    /// collect the design rules of a layout
+    pub fn new(layout: &Layout) -> Rules {
+        let get = |name: &str| -> f64 {
+            layout
+                .setup
+                .get(name)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.0)
+        };
+        let setup = Limits {
+            clearance: get("trace_clearance"),
+            trace_width: get("trace_min"),
+            via_dia: get("via_min_size"),
+            via_drill: get("via_min_drill"),
+            uvia_dia: get("uvia_min_size"),
+            uvia_drill: get("uvia_min_drill"),
+        };
+        let netclasses = layout.netclasses();
+        let default = match netclasses.iter().find(|nc| nc.name == "Default") {
+            Some(nc) => setup.with_netclass(nc),
+            None => setup.clone(),
+        };
+        let mut nets = HashMap::new();
+        for net in layout.nets() {
+            if let Some(nc) = netclasses.iter().find(|nc| nc.nets.contains(&net.name)) {
+                nets.insert(net.num, setup.with_netclass(nc));
+            }
+        }
+        Rules {
+            setup,
+            nets,
+            default,
+        }
+    }
+
+    /// get the limits for a net
+    pub fn limits(&self, net: i64) -> &Limits {
+        self.nets.get(&net).unwrap_or(&self.default)
+    }
+
+    fn max_clearance(&self) -> f64 {
+        self.nets
+            .values()
+            .fold(self.default.clearance, |acc, l| acc.max(l.clearance))
+    }
+}
+
+impl Limits {
+    // the limits of a netclass, never below the absolute minimum values
+    fn with_netclass(&self, nc: &NetClass) -> Limits {
+        Limits {
+            clearance: self.clearance.max(nc.clearance),
+            trace_width: self.trace_width.max(nc.trace_width),
+            via_dia: self.via_dia.max(nc.via_dia),
+            via_drill: self.via_drill.max(nc.via_drill),
+            uvia_dia: self.uvia_dia.max(nc.uvia_dia),
+            uvia_drill: self.uvia_drill.max(nc.uvia_drill),
+        }
+    }
+}
+
+/// check the design rules of a layout
+pub fn check(layout: &Layout) -> Vec<CheckFixData> {
+    let rules = Rules::new(layout);
+    let mut v = check_sizes(layout, &rules);
+    v.append(&mut check_clearances(layout, &rules));
+    v
+}
+
+fn report(rule: Rule, item: String, message: String) -> CheckFixData {
+    CheckFixData::new(rule.section().into(), rule.into(), item, message)
+}
+
+fn check_track_width(
+    layout: &Layout,
+    rules: &Rules,
+    element: &Element,
+    net: i64,
+    width: f64,
+) -> Option<CheckFixData> {
+    let limits = rules.limits(net);
+    if width < limits.trace_width - EPSILON {
+        Some(report(
+            Rule::TrackWidth,
+            describe(layout, element),
+            format!("track width {} is below minimum {}", width, limits.trace_width),
+        ))
+    } else {
+        None
+    }
+}
+
+fn check_sizes(layout: &Layout, rules: &Rules) -> Vec<CheckFixData> {
+    let mut v = vec![];
+    for element in &layout.elements {
+        match *element {
+            Element::Segment(ref segment) => {
+                v.extend(check_track_width(layout, rules, element, segment.net, segment.width))
+            }
+            Element::Arc(ref arc) => {
+                v.extend(check_track_width(layout, rules, element, arc.net, arc.width))
+            }
+            Element::Via(ref via) => {
+                let limits = rules.limits(via.net);
+                let (dia, drill) = if via.micro {
+                    (limits.uvia_dia, limits.uvia_drill)
+                } else {
+                    (limits.via_dia, limits.via_drill)
+                };
+                let item = describe(layout, element);
+                if via.size < dia - EPSILON {
+                    v.push(report(
+                        Rule::ViaDiameter,
+                        item.clone(),
+                        format!("via diameter {} is below minimum {}", via.size, dia),
+                    ))
+                }
+                if via.drill < drill - EPSILON {
+                    v.push(report(
+                        Rule::ViaDrill,
+                        item,
+                        format!("via drill {} is below minimum {}", via.drill, drill),
+                    ))
+                }
+            }
+            _ => (),
+        }
+    }
+    v
+}
+
+fn check_clearances(layout: &Layout, rules: &Rules) -> Vec<CheckFixData> {
+    let items = Copper::new(layout).items(layout);
+    let margin = items
+        .iter()
+        .filter_map(|item| item.clearance)
+        .fold(rules.max_clearance(), f64::max);
+    let bounds: Vec<Bound> = items.iter().map(|i| i.shape.bound()).collect();
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|a, b| bounds[*a].x1.partial_cmp(&bounds[*b].x1).unwrap());
+    let mut v = vec![];
+    for (n, &i) in order.iter().enumerate() {
+        for &j in &order[n + 1..] {
+            if bounds[j].x1 > bounds[i].x2 + margin {
+                break;
+            }
+            let (a, b) = (&items[i], &items[j]);
+            if a.layers & b.layers == 0 || (a.net == b.net && a.net != 0) {
+                continue;
+            }
+            let rule = match rule(&a.item, &b.item) {
+                Some(rule) => rule,
+                None => continue,
+            };
+            if !bounds_overlap(&bounds[i], &bounds[j], margin) {
+                continue;
+            }
+            let clearance = clearance(a, rules).max(clearance(b, rules));
+            let distance = a.shape.distance(&b.shape);
+            if distance < clearance - EPSILON {
+                v.push(report(
+                    rule,
+                    format!("{} and {}", name(layout, a), name(layout, b)),
+                    format!("clearance {:.4} is below minimum {}", distance, clearance),
+                ))
+            }
+        }
+    }
+    v
+}
+
+fn rule(a: &Item, b: &Item) -> Option<Rule> {
+    match (a, b) {
+        (Item::Segment(_), Item::Segment(_)) => Some(Rule::TrackTrack),
+        (Item::Segment(_), Item::Pad(_, _)) | (Item::Pad(_, _), Item::Segment(_)) => {
+            Some(Rule::TrackPad)
+        }
+        (Item::Via(_), Item::Via(_)) => Some(Rule::ViaVia),
+        _ => None,
+    }
+}
+
+fn clearance(item: &CopperItem, rules: &Rules) -> f64 {
+    match item.clearance {
+        Some(clearance) if clearance > 0.0 => clearance,
+        _ => rules.limits(item.net).clearance,
+    }
+}
+
+fn name(layout: &Layout, item: &CopperItem) -> String {
+    match item.item {
+        Item::Segment(i) | Item::Via(i) | Item::Zone(i, _) => {
+            describe(layout, &layout.elements[i])
+        }
+        Item::Pad(ref reference, ref pad) => format!("pad {}.{}", reference, pad),
+    }
+}
+
+fn describe(layout: &Layout, element: &Element) -> String {
+    let net_name = |num: i64| {
+        layout
+            .nets()
+            .into_iter()
+            .find(|net| net.num == num)
+            .map(|net| net.name.0.clone())
+            .unwrap_or_default()
+    };
+    match *element {
+        Element::Segment(ref s) => format!(
+            "track ({}, {})-({}, {}) on {} of net '{}'",
+            s.start.x,
+            s.start.y,
+            s.end.x,
+            s.end.y,
+            s.layer,
+            net_name(s.net)
+        ),
+        Element::Arc(ref a) => format!(
+            "arc track ({}, {})-({}, {}) on {} of net '{}'",
+            a.start.x,
+            a.start.y,
+            a.end.x,
+            a.end.y,
+            a.layer,
+            net_name(a.net)
+        ),
+        Element::Via(ref via) => format!(
+            "via ({}, {}) of net '{}'",
+            via.at.x,
+            via.at.y,
+            net_name(via.net)
+        ),
+        Element::Zone(ref zone) => {
+            format!("zone on {} of net '{}'", zone.layer, zone.net_name.0)
+        }
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use checkfix::CheckFixData;
+    use layout;
+
+    const BOARD: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (layers (0 F.Cu signal) (31 B.Cu signal))
+  (setup (trace_clearance 0.2) (trace_min 0.2) (via_min_size 0.6) (via_min_drill 0.3))
+  (net 0 "") (net 1 A) (net 2 B)
+  (net_class Default "" (clearance 0.2) (trace_width 0.25) (via_dia 0.6) (via_drill 0.3)
+    (uvia_dia 0.3) (uvia_drill 0.1) (add_net A))
+  (net_class Power "" (clearance 0.3) (trace_width 0.5) (via_dia 0.8) (via_drill 0.4)
+    (uvia_dia 0.3) (uvia_drill 0.1) (add_net B))
+  (module R (layer F.Cu) (at 10 20)
+    (fp_text reference R1 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (pad 1 smd rect (at 0 0) (size 1 1) (layers F.Cu) (net 2 B)))
+  (segment (start 0 10) (end 10 10) (width 0.25) (layer F.Cu) (net 1))
+  (segment (start 0 10.6) (end 10 10.6) (width 0.5) (layer F.Cu) (net 2))
+  (segment (start 10 20.7) (end 12 20.7) (width 0.2) (layer F.Cu) (net 1))
+  (via (at 20 10) (size 0.8) (drill 0.4) (layers F.Cu B.Cu) (net 2))
+  (via (at 21 10) (size 0.6) (drill 0.2) (layers F.Cu B.Cu) (net 1))
+  (arc (start 50 50) (mid 51 51) (end 52 50) (width 0.4) (layer F.Cu) (net 2))
+)"#;
+
+    fn rules(v: &[CheckFixData]) -> Vec<(i64, i64)> {
+        v.iter()
+            .map(|data| match *data {
+                CheckFixData::Item(ref item) => (item.section, item.rule),
+                CheckFixData::More(_) => (0, 0),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drc() {
+        let layout = layout::parse(BOARD).unwrap();
+        let v = layout::drc::check(&layout);
+        let rules = rules(&v);
+        // track and arc track narrower than their netclass, via drill too small
+        assert_eq!(rules.iter().filter(|r| **r == (2, 1)).count(), 2);
+        assert!(rules.contains(&(2, 3)));
+        // tracks 0.225 apart with a 0.3 netclass clearance
+        assert!(rules.contains(&(1, 1)));
+        // track at 0.1 from a pad
+        assert!(rules.contains(&(1, 2)));
+        // vias 0.3 apart are fine
+        assert!(!rules.contains(&(1, 3)));
+        assert_eq!(v.len(), 5);
+    }
+}
@@ -0,0 +1,223 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! Excellon drill file generation for a layout
+//!
+//! holes are grouped in tools by diameter; oval holes are written as
+//! `G85` slots
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use footprint::{At, PadType};
+use geometry::{module_position, rotate};
+use layout::{Element, Layout};
+use util::write_file;
+use KicadError;
+
+/// a drilled hole, a slot when start and end differ
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hole {
+    /// start of the hole
+    pub start: (f64, f64),
+    /// end of the hole
+    pub end: (f64, f64),
+    /// diameter of the drill
+    pub diameter: f64,
+    /// if the hole is plated
+    pub plated: bool,
+}
+
+impl Hole {
+    /// check if the hole is a slot
+    pub fn is_slot(&self) -> bool {
+        self.start != self.end
+    }
+}
+
+/// get the holes of a layout: vias and pad drills
+pub fn holes(layout: &Layout) -> Vec<Hole> {
+    let mut v = vec![];
+    for element in &layout.elements {
+        match *element {
+            Element::Via(ref via) if via.drill > 0.0 => v.push(Hole {
+                start: (via.at.x, via.at.y),
+                end: (via.at.x, via.at.y),
+                diameter: via.drill,
+                plated: true,
+            }),
+            Element::Module(ref module) => {
+                let (x, y) = module.at();
+                let at = At::new(x, y, module.get_rotation());
+                for pad in module.pads() {
+                    let drill = match pad.drill {
+                        Some(ref drill) if drill.width > 0.0 => drill,
+                        _ => continue,
+                    };
+                    // the drill offset moves the pad shape, not the hole
+                    let center = module_position(&at, pad.at.x, pad.at.y);
+                    let oval = drill.shape.as_ref().map(|s| s == "oval").unwrap_or(false);
+                    let height = if oval && drill.height > 0.0 {
+                        drill.height
+                    } else {
+                        drill.width
+                    };
+                    let diameter = drill.width.min(height);
+                    let half = (drill.width.max(height) - diameter) / 2.0;
+                    let (dx, dy) = if drill.width > height {
+                        rotate(half, 0.0, pad.at.rot)
+                    } else {
+                        rotate(0.0, half, pad.at.rot)
+                    };
+                    v.push(Hole {
+                        start: (center.0 - dx, center.1 - dy),
+                        end: (center.0 + dx, center.1 + dy),
+                        diameter,
+                        plated: pad.t != PadType::NpPth,
+                    })
+                }
+            }
+            _ => (),
+        }
+    }
+    v
+}
+
+/// generate an Excellon drill file for a list of holes
+pub fn holes_to_string(holes: &[Hole], function: &str) -> String {
+    let mut tools: Vec<f64> = vec![];
+    for hole in holes {
+        if !tools.iter().any(|d| same_diameter(*d, hole.diameter)) {
+            tools.push(hole.diameter)
+        }
+    }
+    tools.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut s = String::new();
+    writeln!(s, "M48").unwrap();
+    writeln!(s, "; DRILL file {{kicad_parse_gen {}}}", env!("CARGO_PKG_VERSION")).unwrap();
+    writeln!(s, "; FORMAT={{-:-/ absolute / metric / decimal}}").unwrap();
+    writeln!(s, "; #@! TF.FileFunction,{}", function).unwrap();
+    writeln!(s, "FMAT,2").unwrap();
+    writeln!(s, "METRIC").unwrap();
+    for (i, d) in tools.iter().enumerate() {
+        writeln!(s, "T{}C{:.3}", i + 1, d).unwrap();
+    }
+    writeln!(s, "%").unwrap();
+    writeln!(s, "G90").unwrap();
+    writeln!(s, "G05").unwrap();
+    for (i, d) in tools.iter().enumerate() {
+        writeln!(s, "T{}", i + 1).unwrap();
+        for hole in holes.iter().filter(|h| same_diameter(*d, h.diameter)) {
+            if hole.is_slot() {
+                writeln!(s, "{}G85{}", coord(hole.start), coord(hole.end)).unwrap();
+            } else {
+                writeln!(s, "{}", coord(hole.start)).unwrap();
+            }
+        }
+    }
+    writeln!(s, "T0").unwrap();
+    writeln!(s, "M30").unwrap();
+    s
+}
+
+/// generate an Excellon drill file for a layout, limited to the plated
+/// or non-plated holes if `plated` is given
+pub fn layout_to_string(layout: &Layout, plated: Option<bool>) -> String {
+    let holes: Vec<Hole> = holes(layout)
+        .into_iter()
+        .filter(|h| plated.map(|p| p == h.plated).unwrap_or(true))
+        .collect();
+    // the holes go through all copper layers
+    let layers = layout.copper_layer_count().max(2);
+    let function = match plated {
+        Some(true) => format!("Plated,1,{},PTH", layers),
+        Some(false) => format!("NonPlated,1,{},NPTH", layers),
+        None => format!("MixedPlating,1,{}", layers),
+    };
+    holes_to_string(&holes, &function)
+}
+
+/// write the drill files of a layout in a directory; with `split` the
+/// plated and non-plated holes go in `<base>-PTH.drl` and `<base>-NPTH.drl`,
+/// otherwise all holes go in `<base>.drl`
+pub fn write_drill_files(
+    layout: &Layout,
+    dir: &Path,
+    base: &str,
+    split: bool,
+) -> Result<Vec<PathBuf>, KicadError> {
+    let files = if split {
+        vec![
+            (format!("{}-PTH.drl", base), Some(true)),
+            (format!("{}-NPTH.drl", base), Some(false)),
+        ]
+    } else {
+        vec![(format!("{}.drl", base), None)]
+    };
+    let mut v = vec![];
+    for (name, plated) in files {
+        let path = dir.join(name);
+        write_file(&path, &layout_to_string(layout, plated))?;
+        v.push(path)
+    }
+    Ok(v)
+}
+
+fn same_diameter(a: f64, b: f64) -> bool {
+    (a - b).abs() < 0.0005
+}
+
+// drill files use Y pointing up
+fn coord(p: (f64, f64)) -> String {
+    format!("X{:.3}Y{:.3}", p.0, -p.1)
+}
+
+#[cfg(test)]
+mod test {
+    use layout;
+
+    const BOARD: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (net 0 "") (net 1 A)
+  (module J (layer F.Cu) (at 10 10 90)
+    (fp_text reference J1 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (pad 1 thru_hole circle (at 0 0) (size 2 2) (drill 1) (layers *.Cu *.Mask) (net 1 A))
+    (pad 2 thru_hole oval (at 2 0 90) (size 1.5 3) (drill oval 0.8 2 (offset 0.5 0)) (layers *.Cu *.Mask))
+    (pad "" np_thru_hole circle (at 0 3) (size 3 3) (drill 3) (layers *.Cu *.Mask)))
+  (via (at 20 10) (size 0.6) (drill 0.3) (layers F.Cu B.Cu) (net 1))
+  (via (at 21 10) (size 0.6) (drill 0.3) (layers F.Cu B.Cu) (net 1))
+)"#;
+
+    #[test]
+    fn drill_holes() {
+        let layout = layout::parse(BOARD).unwrap();
+        let holes = layout::drill::holes(&layout);
+        assert_eq!(holes.len(), 5);
+        // slot of pad 2, rotated with the module and the pad
+        assert!(holes[1].is_slot());
+        assert!((holes[1].start.0 - 9.4).abs() < 1e-9);
+        assert!((holes[1].end.0 - 10.6).abs() < 1e-9);
+        assert!(!holes[2].plated);
+    }
+
+    #[test]
+    fn drill_split() {
+        let layout = layout::parse(BOARD).unwrap();
+        let pth = layout::drill::layout_to_string(&layout, Some(true));
+        assert!(pth.contains("T1C0.300\nT2C0.800\nT3C1.000\n"));
+        assert!(pth.contains("T1\nX20.000Y-10.000\nX21.000Y-10.000\n"));
+        assert!(pth.contains("X9.400Y-8.000G85X10.600Y-8.000"));
+        assert!(!pth.contains("C3.000"));
+        let npth = layout::drill::layout_to_string(&layout, Some(false));
+        assert!(npth.contains("T1C3.000\n"));
+        assert!(npth.contains("X13.000Y-10.000"));
+        assert!(npth.contains("TF.FileFunction,NonPlated,1,2,NPTH\n"));
+    }
+
+    #[test]
+    fn drill_layers() {
+        let layers = "(layers (0 F.Cu signal) (1 In1.Cu signal) (2 In2.Cu signal) \
+                      (31 B.Cu signal))\n  (net 0";
+        let layout = layout::parse(&BOARD.replace("(net 0", layers)).unwrap();
+        let drill = layout::drill::layout_to_string(&layout, None);
+        assert!(drill.contains("TF.FileFunction,MixedPlating,1,4\n"));
+    }
+}
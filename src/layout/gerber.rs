@@ -0,0 +1,613 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! Gerber X2 generation for a layout
+//!
+//! one file is generated per copper, solder mask, solder paste, silkscreen
+//! and board outline layer; texts are not rendered
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use footprint;
+use footprint::{LayerSide, LayerType, PadShape, PadType};
+use geometry::{arc_points_through, module_position, rotate, Point, Shape};
+use layout::{Element, Layout, Via};
+use util::write_file;
+use KicadError;
+
+/// get the layers for which a Gerber file is generated
+pub fn layers(layout: &Layout) -> Vec<footprint::Layer> {
+    let mut v: Vec<footprint::Layer> = layout
+        .copper_layers()
+        .into_iter()
+        .map(|l| l.layer.clone())
+        .collect();
+    v.sort_by_key(|l| l.side.copper_num());
+    for t in &[LayerType::Mask, LayerType::Paste, LayerType::SilkS] {
+        for side in &[LayerSide::Front, LayerSide::Back] {
+            v.push(footprint::Layer {
+                side: side.clone(),
+                t: t.clone(),
+            })
+        }
+    }
+    v.push(footprint::Layer {
+        side: LayerSide::Edge,
+        t: LayerType::Cuts,
+    });
+    v
+}
+
+/// generate the Gerber X2 file for a layer of a layout
+pub fn layer_to_string(layout: &Layout, layer: &footprint::Layer) -> Result<String, KicadError> {
+    let function = file_function(layout, layer)?;
+    let mut g = Gerber::new(layer.t == LayerType::Cu);
+    let mask_margin = setup_value(layout, "pad_to_mask_clearance");
+    let paste_margin = setup_value(layout, "pad_to_paste_clearance");
+    for element in &layout.elements {
+        match *element {
+            Element::Segment(ref segment) if same_layer(&segment.layer, layer) => {
+                g.line(
+                    Point::new(segment.start.x, segment.start.y),
+                    Point::new(segment.end.x, segment.end.y),
+                    segment.width,
+                    Some("Conductor"),
+                )
+            }
+            Element::Arc(ref arc) if same_layer(&arc.layer, layer) => {
+                let points = arc_points_through(
+                    &Point::new(arc.start.x, arc.start.y),
+                    &Point::new(arc.mid.x, arc.mid.y),
+                    &Point::new(arc.end.x, arc.end.y),
+                );
+                g.polyline(&points, arc.width, Some("Conductor"))
+            }
+            Element::Via(ref via) if layer.t == LayerType::Cu && via_on(via, layer) => {
+                g.flash(
+                    &Aperture::Circle(via.size),
+                    Point::new(via.at.x, via.at.y),
+                    Some("ViaPad"),
+                )
+            }
+            Element::Zone(ref zone) if same_layer(&zone.layer, layer) => {
+                for polygon in &zone.filled_polygons {
+                    let points = to_points(&polygon.pts);
+                    g.region(&points);
+                    // Kicad 6 and later fill with the exact polygons
+                    if zone.filled_areas_thickness != Some(false) {
+                        g.outline(&points, zone.min_thickness, Some("Conductor"));
+                    }
+                }
+            }
+            Element::GrLine(ref line) if same_layer(&line.layer, layer) => {
+                g.line(
+                    Point::new(line.start.x, line.start.y),
+                    Point::new(line.end.x, line.end.y),
+                    line.width,
+                    g.graphics_function(layer),
+                )
+            }
+            Element::GrArc(ref arc) if same_layer(&arc.layer, layer) => {
+                let start = Point::new(arc.start.x, arc.start.y);
+                let end = Point::new(arc.end.x, arc.end.y);
+                let function = g.graphics_function(layer);
+                match arc.mid {
+                    Some(ref mid) => {
+                        let mid = Point::new(mid.x, mid.y);
+                        g.polyline(&arc_points_through(&start, &mid, &end), arc.width, function)
+                    }
+                    None => g.arc(start, end, arc.angle, arc.width, function),
+                }
+            }
+            Element::GrCircle(ref circle) if same_layer(&circle.layer, layer) => {
+                g.arc(
+                    Point::new(circle.center.x, circle.center.y),
+                    Point::new(circle.end.x, circle.end.y),
+                    360.0,
+                    circle.width,
+                    g.graphics_function(layer),
+                )
+            }
+            Element::GrPoly(ref poly) if same_layer(&poly.layer, layer) => {
+                let points = to_points(&poly.pts);
+                graphic_polygon(&mut g, &points, poly.width, &poly.fill, layer)
+            }
+            Element::GrRect(ref rect) if same_layer(&rect.layer, layer) => {
+                let points = vec![
+                    Point::new(rect.start.x, rect.start.y),
+                    Point::new(rect.end.x, rect.start.y),
+                    Point::new(rect.end.x, rect.end.y),
+                    Point::new(rect.start.x, rect.end.y),
+                ];
+                graphic_polygon(&mut g, &points, rect.width, &rect.fill, layer)
+            }
+            Element::Module(ref module) => {
+                let margin = match layer.t {
+                    LayerType::Mask => module_mask_margin(module).unwrap_or(mask_margin),
+                    LayerType::Paste => paste_margin,
+                    _ => 0.0,
+                };
+                module_to_gerber(&mut g, module, layer, margin)
+            }
+            _ => (),
+        }
+    }
+    Ok(g.finish(&function, layer.t == LayerType::Mask))
+}
+
+/// write the Gerber X2 files of a layout in a directory, naming them
+/// `<base>-<layer>.gbr`; returns the list of written files
+pub fn write_gerbers(layout: &Layout, dir: &Path, base: &str) -> Result<Vec<PathBuf>, KicadError> {
+    let mut v = vec![];
+    for layer in layers(layout) {
+        let s = layer_to_string(layout, &layer)?;
+        let name = format!("{}", layer).replace('.', "_");
+        let path = dir.join(format!("{}-{}.gbr", base, name));
+        write_file(&path, &s)?;
+        v.push(path)
+    }
+    Ok(v)
+}
+
+fn setup_value(layout: &Layout, name: &str) -> f64 {
+    layout
+        .setup
+        .get(name)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.0)
+}
+
+fn module_mask_margin(module: &footprint::Module) -> Option<f64> {
+    for element in &module.elements {
+        if let footprint::Element::SolderMaskMargin(margin) = *element {
+            return Some(margin);
+        }
+    }
+    None
+}
+
+fn file_function(layout: &Layout, layer: &footprint::Layer) -> Result<String, KicadError> {
+    let side = match layer.side {
+        LayerSide::Front => "Top",
+        LayerSide::Back => "Bot",
+        _ => "Inr",
+    };
+    match layer.t {
+        LayerType::Cu => {
+            let mut nums: Vec<i64> = layout
+                .copper_layers()
+                .iter()
+                .filter_map(|l| l.layer.side.copper_num())
+                .collect();
+            nums.sort();
+            let num = layer.side.copper_num();
+            match nums.iter().position(|n| Some(*n) == num) {
+                Some(i) => Ok(format!("Copper,L{},{}", i + 1, side)),
+                None => Err(format!("{} is not a copper layer of the layout", layer).into()),
+            }
+        }
+        LayerType::Mask => Ok(format!("Soldermask,{}", side)),
+        LayerType::Paste => Ok(format!("Paste,{}", side)),
+        LayerType::SilkS => Ok(format!("Legend,{}", side)),
+        LayerType::Cuts => Ok("Profile,NP".into()),
+        _ => Err(format!("no Gerber file function for layer {}", layer).into()),
+    }
+}
+
+// compare side and type, as `Layer` equality only looks at the type
+fn same_layer(a: &footprint::Layer, b: &footprint::Layer) -> bool {
+    a.side == b.side && a.t == b.t
+}
+
+// check if a layer list, possibly containing `*.` wildcards, covers a layer
+fn covers(layers: &footprint::Layers, layer: &footprint::Layer) -> bool {
+    layers
+        .layers
+        .iter()
+        .any(|l| l.t == layer.t && (l.side == layer.side || l.side == LayerSide::Both))
+}
+
+fn via_on(via: &Via, layer: &footprint::Layer) -> bool {
+    let nums: Vec<i64> = via.layers
+        .layers
+        .iter()
+        .filter_map(|l| l.side.copper_num())
+        .collect();
+    match (
+        nums.iter().min(),
+        nums.iter().max(),
+        layer.side.copper_num(),
+    ) {
+        (Some(first), Some(last), Some(num)) => *first <= num && num <= *last,
+        _ => false,
+    }
+}
+
+fn to_points(pts: &footprint::Pts) -> Vec<Point> {
+    pts.elements.iter().map(|p| Point::new(p.x, p.y)).collect()
+}
+
+fn module_to_gerber(g: &mut Gerber, module: &footprint::Module, layer: &footprint::Layer, margin: f64) {
+    let (x, y) = module.at();
+    let at = footprint::At::new(x, y, module.get_rotation());
+    let place = |xy: &footprint::Xy| {
+        let (x, y) = module_position(&at, xy.x, xy.y);
+        Point::new(x, y)
+    };
+    let function = g.graphics_function(layer);
+    for element in &module.elements {
+        match *element {
+            footprint::Element::Pad(ref pad) if covers(&pad.layers, layer) => {
+                let margin = match layer.t {
+                    LayerType::Mask => pad.solder_mask_margin.unwrap_or(margin),
+                    LayerType::Paste => pad.solder_paste_margin.unwrap_or(margin),
+                    _ => 0.0,
+                };
+                pad_to_gerber(g, pad, &at, margin)
+            }
+            footprint::Element::FpLine(ref line) if same_layer(&line.layer, layer) => {
+                g.line(place(&line.start), place(&line.end), line.width, function)
+            }
+            footprint::Element::FpArc(ref arc) if same_layer(&arc.layer, layer) => {
+                match arc.mid {
+                    Some(ref mid) => {
+                        let points = arc_points_through(
+                            &place(&arc.start),
+                            &place(mid),
+                            &place(&arc.end),
+                        );
+                        g.polyline(&points, arc.width, function)
+                    }
+                    None => g.arc(
+                        place(&arc.start),
+                        place(&arc.end),
+                        arc.angle,
+                        arc.width,
+                        function,
+                    ),
+                }
+            }
+            footprint::Element::FpCircle(ref circle) if same_layer(&circle.layer, layer) => {
+                g.arc(
+                    place(&circle.center),
+                    place(&circle.end),
+                    360.0,
+                    circle.width,
+                    function,
+                )
+            }
+            footprint::Element::FpPoly(ref poly) if same_layer(&poly.layer, layer) => {
+                let points: Vec<Point> = poly.pts.elements.iter().map(&place).collect();
+                graphic_polygon(g, &points, poly.width, &poly.fill, layer)
+            }
+            _ => (),
+        }
+    }
+}
+
+// a polygon is filled unless its fill is `none`, the board outline is
+// never filled
+fn graphic_polygon(
+    g: &mut Gerber,
+    points: &[Point],
+    width: f64,
+    fill: &Option<String>,
+    layer: &footprint::Layer,
+) {
+    let function = g.graphics_function(layer);
+    if layer.t != LayerType::Cuts && fill.as_ref().map(|f| &f[..]) != Some("none") {
+        g.region(points)
+    }
+    g.outline(points, width, function)
+}
+
+fn pad_to_gerber(g: &mut Gerber, pad: &footprint::Pad, at: &footprint::At, margin: f64) {
+    // Kicad rounds the corners by a quarter of the smallest side by default
+    let radius = pad.roundrect_rratio.unwrap_or(0.25) * pad.size.x.min(pad.size.y) + margin;
+    let mut pad = pad.clone();
+    pad.size.x += 2.0 * margin;
+    pad.size.y += 2.0 * margin;
+    if pad.size.x <= 0.0 || pad.size.y <= 0.0 {
+        return;
+    }
+    if pad.shape == PadShape::RoundRect {
+        let shape = Shape::rounded_pad(&pad, radius, |x, y| module_position(at, x, y));
+        return g.region(&shape.outline());
+    }
+    let function = match pad.t {
+        PadType::Smd => "SMDPad,CuDef",
+        PadType::Pth => "ComponentPad",
+        PadType::NpPth => "WasherPad",
+    };
+    let rot = pad.at.rot.rem_euclid(180.0);
+    let aligned = rot.abs() < 0.01 || (rot - 90.0).abs() < 0.01;
+    let (w, h) = if (rot - 90.0).abs() < 0.01 {
+        (pad.size.y, pad.size.x)
+    } else {
+        (pad.size.x, pad.size.y)
+    };
+    let shape = Shape::pad(&pad, |x, y| module_position(at, x, y));
+    match (shape, &pad.shape) {
+        (Shape::Round { start, radius, .. }, &PadShape::Circle) => {
+            g.flash(&Aperture::Circle(2.0 * radius), start, Some(function))
+        }
+        (Shape::Round { start, end, .. }, &PadShape::Oval) if aligned => g.flash(
+            &Aperture::Obround(w, h),
+            Point::new((start.x + end.x) / 2.0, (start.y + end.y) / 2.0),
+            Some(function),
+        ),
+        (Shape::Round { start, end, radius }, _) => {
+            g.line(start, end, 2.0 * radius, Some(function))
+        }
+        (Shape::Polygon(ref points), &PadShape::Rect) if aligned => {
+            let n = points.len() as f64;
+            let x = points.iter().map(|p| p.x).sum::<f64>() / n;
+            let y = points.iter().map(|p| p.y).sum::<f64>() / n;
+            g.flash(&Aperture::Rect(w, h), Point::new(x, y), Some(function))
+        }
+        (Shape::Polygon(ref points), _) => g.region(points),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Aperture {
+    Circle(f64),
+    Rect(f64, f64),
+    Obround(f64, f64),
+}
+
+impl Aperture {
+    fn template(&self) -> String {
+        match *self {
+            Aperture::Circle(d) => format!("C,{:.6}", d),
+            Aperture::Rect(w, h) => format!("R,{:.6}X{:.6}", w, h),
+            Aperture::Obround(w, h) => format!("O,{:.6}X{:.6}", w, h),
+        }
+    }
+}
+
+// accumulates the apertures and the drawing commands of a Gerber file
+struct Gerber {
+    copper: bool,
+    apertures: Vec<(String, Option<&'static str>)>,
+    current: Option<usize>,
+    body: String,
+}
+
+// the coordinate format is 4.6 in mm and the Y axis points up
+fn coord(p: &Point) -> String {
+    format!(
+        "X{}Y{}",
+        (p.x * 1e6).round() as i64,
+        (-p.y * 1e6).round() as i64
+    )
+}
+
+impl Gerber {
+    fn new(copper: bool) -> Gerber {
+        Gerber {
+            copper,
+            apertures: vec![],
+            current: None,
+            body: String::new(),
+        }
+    }
+
+    fn graphics_function(&self, layer: &footprint::Layer) -> Option<&'static str> {
+        if layer.t == LayerType::Cuts {
+            Some("Profile")
+        } else if self.copper {
+            Some("Conductor")
+        } else {
+            None
+        }
+    }
+
+    fn select(&mut self, aperture: &Aperture, function: Option<&'static str>) {
+        let key = (aperture.template(), function);
+        let index = match self.apertures.iter().position(|a| *a == key) {
+            Some(index) => index,
+            None => {
+                self.apertures.push(key);
+                self.apertures.len() - 1
+            }
+        };
+        if self.current != Some(index) {
+            self.current = Some(index);
+            writeln!(self.body, "D{}*", index + 10).unwrap();
+        }
+    }
+
+    fn flash(&mut self, aperture: &Aperture, at: Point, function: Option<&'static str>) {
+        self.select(aperture, function);
+        writeln!(self.body, "{}D03*", coord(&at)).unwrap();
+    }
+
+    fn line(&mut self, start: Point, end: Point, width: f64, function: Option<&'static str>) {
+        self.select(&Aperture::Circle(width), function);
+        writeln!(self.body, "{}D02*", coord(&start)).unwrap();
+        writeln!(self.body, "{}D01*", coord(&end)).unwrap();
+    }
+
+    // an arc around `center` starting in `start` over `angle` degrees,
+    // clockwise on the board for a positive angle
+    fn arc(
+        &mut self,
+        center: Point,
+        start: Point,
+        angle: f64,
+        width: f64,
+        function: Option<&'static str>,
+    ) {
+        let (dx, dy) = rotate(start.x - center.x, start.y - center.y, -angle);
+        let end = Point::new(center.x + dx, center.y + dy);
+        self.select(&Aperture::Circle(width), function);
+        writeln!(self.body, "{}D02*", coord(&start)).unwrap();
+        let direction = if angle >= 0.0 { "G02" } else { "G03" };
+        writeln!(
+            self.body,
+            "{}{}I{}J{}D01*",
+            direction,
+            coord(&end),
+            ((center.x - start.x) * 1e6).round() as i64,
+            (-(center.y - start.y) * 1e6).round() as i64
+        ).unwrap();
+        writeln!(self.body, "G01*").unwrap();
+    }
+
+    fn region(&mut self, points: &[Point]) {
+        if points.len() < 3 {
+            return;
+        }
+        writeln!(self.body, "G36*").unwrap();
+        writeln!(self.body, "{}D02*", coord(&points[0])).unwrap();
+        for p in points.iter().skip(1) {
+            writeln!(self.body, "{}D01*", coord(p)).unwrap();
+        }
+        writeln!(self.body, "{}D01*", coord(&points[0])).unwrap();
+        writeln!(self.body, "G37*").unwrap();
+    }
+
+    fn outline(&mut self, points: &[Point], width: f64, function: Option<&'static str>) {
+        let mut closed = points.to_vec();
+        closed.extend(points.first());
+        self.polyline(&closed, width, function)
+    }
+
+    fn polyline(&mut self, points: &[Point], width: f64, function: Option<&'static str>) {
+        if points.len() < 2 || width <= 0.0 {
+            return;
+        }
+        self.select(&Aperture::Circle(width), function);
+        writeln!(self.body, "{}D02*", coord(&points[0])).unwrap();
+        for p in points.iter().skip(1) {
+            writeln!(self.body, "{}D01*", coord(p)).unwrap();
+        }
+    }
+
+    fn finish(self, function: &str, negative: bool) -> String {
+        let mut s = String::new();
+        writeln!(
+            s,
+            "%TF.GenerationSoftware,Productize,kicad_parse_gen,{}*%",
+            env!("CARGO_PKG_VERSION")
+        ).unwrap();
+        writeln!(s, "%TF.FileFunction,{}*%", function).unwrap();
+        let polarity = if negative { "Negative" } else { "Positive" };
+        writeln!(s, "%TF.FilePolarity,{}*%", polarity).unwrap();
+        writeln!(s, "%FSLAX46Y46*%").unwrap();
+        writeln!(s, "%MOMM*%").unwrap();
+        writeln!(s, "%LPD*%").unwrap();
+        writeln!(s, "G01*").unwrap();
+        writeln!(s, "G75*").unwrap();
+        for (i, &(ref template, function)) in self.apertures.iter().enumerate() {
+            if let Some(function) = function {
+                writeln!(s, "%TA.AperFunction,{}*%", function).unwrap();
+            }
+            writeln!(s, "%ADD{}{}*%", i + 10, template).unwrap();
+            if function.is_some() {
+                writeln!(s, "%TD*%").unwrap();
+            }
+        }
+        s.push_str(&self.body);
+        writeln!(s, "M02*").unwrap();
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use footprint;
+    use layout;
+    use layout::test::BOARD as KICAD6_BOARD;
+
+    const BOARD: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (layers (0 F.Cu signal) (31 B.Cu signal) (38 B.Mask user) (39 F.Mask user) (44 Edge.Cuts user))
+  (setup (pad_to_mask_clearance 0.05))
+  (net 0 "") (net 1 A)
+  (module R (layer F.Cu) (at 10 10 90)
+    (fp_text reference R1 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_line (start -1 -1) (end 1 -1) (layer F.SilkS) (width 0.15))
+    (pad 1 smd rect (at -1 0 90) (size 1 0.5) (layers F.Cu F.Paste F.Mask) (net 1 A))
+    (pad 2 thru_hole oval (at 1 0 45) (size 1 2) (drill 0.6) (layers *.Cu *.Mask) (net 1 A)))
+  (gr_line (start 0 0) (end 20 0) (layer Edge.Cuts) (width 0.1))
+  (gr_arc (start 20 5) (end 20 0) (angle 90) (layer Edge.Cuts) (width 0.1))
+  (segment (start 9 10) (end 9 14) (width 0.25) (layer F.Cu) (net 1))
+  (via (at 9 14) (size 0.6) (drill 0.3) (layers F.Cu B.Cu) (net 1))
+)"#;
+
+    #[test]
+    fn gerber_copper() {
+        let layout = layout::parse(BOARD).unwrap();
+        let layer = footprint::Layer::from_string("F.Cu").unwrap();
+        let s = layout::gerber::layer_to_string(&layout, &layer).unwrap();
+        assert!(s.contains("%TF.FileFunction,Copper,L1,Top*%"));
+        // the rect pad is rotated with the module onto the y axis
+        assert!(s.contains("%ADD10R,0.500000X1.000000*%"));
+        assert!(s.contains("X10000000Y-11000000D03*"));
+        assert!(s.contains("%ADD13C,0.600000*%"));
+        assert!(s.ends_with("M02*\n"));
+    }
+
+    #[test]
+    fn gerber_outline() {
+        let layout = layout::parse(BOARD).unwrap();
+        let layer = footprint::Layer::from_string("Edge.Cuts").unwrap();
+        let s = layout::gerber::layer_to_string(&layout, &layer).unwrap();
+        assert!(s.contains("%TF.FileFunction,Profile,NP*%"));
+        assert!(s.contains("G02X25000000Y-5000000I0J-5000000D01*"));
+    }
+
+    #[test]
+    fn gerber_kicad6() {
+        let layout = layout::parse(KICAD6_BOARD).unwrap();
+        let layer = footprint::Layer::from_string("Edge.Cuts").unwrap();
+        let s = layout::gerber::layer_to_string(&layout, &layer).unwrap();
+        // the arc goes through its middle point
+        assert!(s.contains("X90000000Y-95000000D02*"));
+        assert!(s.contains("X91000000Y-94000000D01*"));
+        assert!(s.contains("X92000000Y-95000000D01*"));
+        assert!(!s.contains("G02"));
+        // the rectangle is an outline
+        assert!(s.contains("X90000000Y-90000000D02*\nX110000000Y-90000000D01*\n"));
+        assert!(!s.contains("G36*"));
+
+        let layer = footprint::Layer::from_string("F.Cu").unwrap();
+        let s = layout::gerber::layer_to_string(&layout, &layer).unwrap();
+        // the polygon is filled
+        assert!(s.contains("G36*\nX95000000Y-95000000D02*\nX96000000Y-95000000D01*\n"));
+        // the arc track
+        assert!(s.contains("X98000000Y-100000000D02*"));
+        assert!(s.contains("X97000000Y-101000000D01*"));
+        assert!(s.contains("X98000000Y-102000000D01*"));
+        // the corners of the pad are rounded by a quarter of its width
+        assert!(!s.contains("R,0.800000X0.950000"));
+        assert!(s.contains("X98975000Y-100475000D02*"));
+        assert!(s.contains("X98775000Y-100275000D01*"));
+    }
+
+    #[test]
+    fn gerber_zone() {
+        let board = |thickness: &str| {
+            format!(
+                "(kicad_pcb (version 20211014) (generator pcbnew) (layers (0 F.Cu signal)) \
+                 (zone (net 0) (net_name \"\") (layer \"F.Cu\") (tstamp 1) (hatch edge 0.5) \
+                 (connect_pads (clearance 0.5)) (min_thickness 0.254) \
+                 (filled_areas_thickness {}) (fill yes (thermal_gap 0.5) \
+                 (thermal_bridge_width 0.5)) (polygon (pts (xy 0 0) (xy 10 0) (xy 10 10))) \
+                 (filled_polygon (layer \"F.Cu\") (island) \
+                 (pts (xy 1 1) (xy 9 1) (xy 9 9)))))",
+                thickness
+            )
+        };
+        let layer = footprint::Layer::from_string("F.Cu").unwrap();
+        let layout = layout::parse(&board("no")).unwrap();
+        let s = layout::gerber::layer_to_string(&layout, &layer).unwrap();
+        assert!(s.contains("G36*\nX1000000Y-1000000D02*\nX9000000Y-1000000D01*\n"));
+        assert!(!s.contains("C,0.254000"));
+        let layout = layout::parse(&board("yes")).unwrap();
+        let s = layout::gerber::layer_to_string(&layout, &layer).unwrap();
+        assert!(s.contains("G36*\nX1000000Y-1000000D02*\n"));
+        assert!(s.contains("C,0.254000"));
+    }
+}
@@ -7,6 +7,7 @@
 use symbolic_expressions;
 use symbolic_expressions::IntoSexp;
 use formatter::KicadFormatter;
+use sexpr::requote_board;
 
 pub use layout::data::*;
 
@@ -15,8 +16,11 @@ use {Adjust, Bound, BoundingBox, KicadError};
 /// convert a Kicad layout to a String
 pub fn layout_to_string(layout: &Layout, indent_level: i64) -> Result<String, KicadError> {
     let formatter = KicadFormatter::new(indent_level);
-    let mut s =
-        symbolic_expressions::ser::to_string_with_formatter(&layout.into_sexp(), formatter)?;
+    let mut sexp = layout.into_sexp();
+    if layout.is_kicad6() {
+        sexp = requote_board(&sexp)
+    }
+    let mut s = symbolic_expressions::ser::to_string_with_formatter(&sexp, formatter)?;
     s.push('\n');
     Ok(s)
 }
@@ -30,6 +34,395 @@ pub fn parse(s: &str) -> Result<Layout, KicadError> {
     Ok(x?)
 }
 
+/// forward and back annotation between a layout and its schematic
+pub mod annotate;
+/// copper connectivity of a layout
+pub mod connectivity;
+/// consistency checks between a layout and its schematic
+pub mod consistency;
+/// design rule checking of a layout
+pub mod drc;
+/// Excellon drill file generation for a layout
+pub mod drill;
+/// Gerber X2 generation for a layout
+pub mod gerber;
+/// pick-and-place position files for a layout
+pub mod position;
+/// geographic re-annotation of a layout
+pub mod reannotate;
+
 mod data;
 mod de;
 mod ser;
+
+#[cfg(test)]
+mod test {
+    use symbolic_expressions::parser::parse_str;
+    use symbolic_expressions::{from_sexp, IntoSexp};
+
+    use footprint;
+    use layout;
+
+    pub(super) const BOARD: &str = r#"(kicad_pcb (version 20211014) (generator pcbnew)
+
+  (general
+    (thickness 1.6)
+  )
+
+  (paper "A4")
+  (layers
+    (0 "F.Cu" signal)
+    (31 "B.Cu" signal)
+    (32 "B.Adhes" user "B.Adhesive")
+    (36 "B.SilkS" user "B.Silkscreen")
+    (44 "Edge.Cuts" user)
+  )
+
+  (setup
+    (stackup
+      (layer "F.Cu" (type "copper") (thickness 0.035))
+      (layer "dielectric 1" (type "core") (thickness 1.51) (material "FR4") (epsilon_r 4.5) (loss_tangent 0.02))
+      (layer "B.Cu" (type "copper") (thickness 0.035))
+      (copper_finish "None")
+      (dielectric_constraints no)
+    )
+    (pad_to_mask_clearance 0)
+    (pcbplotparams
+      (layerselection 0x00010fc_ffffffff)
+      (outputdirectory "")
+    )
+  )
+
+  (net 0 "")
+  (net 1 "GND")
+
+  (footprint "Resistor_SMD:R_0603_1608Metric" (layer "F.Cu")
+    (tedit 5F68FEEE) (tstamp 3d7c2b8e-1d5b-4a6b-9b1e-3e5c7b8a9d10)
+    (at 100 100)
+    (property "Sheetfile" "test.kicad_sch")
+    (path "/5c1e6b3a-2f4d-4c7e-8a9b-0d1e2f3a4b5c")
+    (attr smd)
+    (fp_text reference "R1" (at 0 -1.43) (layer "F.SilkS")
+      (effects (font (size 1 1) (thickness 0.15)))
+      (tstamp 0a6c3f7e-5b8d-4e2f-9c1a-7d3e5f9b2c4a)
+    )
+    (fp_line (start -0.2 -0.5) (end 0.2 -0.5) (layer "F.SilkS") (width 0.12) (tstamp 1b7d4f8e-6c9e-4f3a-8d2b-8e4f6a0c3d5b))
+    (fp_arc (start -1 0) (mid 0 -1) (end 1 0) (layer "F.Fab") (width 0.1) (tstamp 2c8e5a9f-7d0f-4a4b-9e3c-9f5a7b1d4e6c))
+    (pad "1" smd roundrect (at -0.825 0) (size 0.8 0.95) (layers "F.Cu" "F.Paste" "F.Mask") (roundrect_rratio 0.25)
+      (net 1 "GND") (pinfunction "A") (pintype "passive") (tstamp 3d9f6b0a-8e1a-4b5c-8f4d-0a6b8c2e5f7d))
+    (model "${KICAD6_3DMODEL_DIR}/Resistor_SMD.3dshapes/R_0603_1608Metric.wrl"
+      (offset (xyz 0 0 0))
+      (scale (xyz 1 1 1))
+      (rotate (xyz 0 0 0))
+    )
+  )
+
+  (gr_rect (start 90 90) (end 110 110) (layer "Edge.Cuts") (width 0.1) (fill none) (tstamp 4e0a7c1b-9f2b-4c6d-9a5e-1b7c9d3f6a8e))
+  (gr_poly
+    (pts
+      (xy 95 95) (xy 96 95) (xy 96 96)
+    ) (layer "F.Cu") (width 0) (fill solid) (tstamp 5f1b8d2c-0a3c-4d7e-8b6f-2c8d0e4a7b9f))
+  (gr_arc (start 90 95) (mid 91 94) (end 92 95) (layer "Edge.Cuts") (width 0.1) (tstamp 6a2c9e3d-1b4d-4e8f-9c7a-3d9e1f5b8c0a))
+  (gr_text "rev 1" (at 100 95) (layer "F.SilkS") (tstamp 7b3d0f4e-2c5e-4f9a-8d8b-4e0f2a6c9d1b)
+    (effects (font (size 1 1) (thickness 0.15)))
+  )
+  (gr_text "bottom" (at 100 105) (layer "B.SilkS") (tstamp 1f7a4d8e-6b9c-4d3e-8f2a-5c0b3e7d9a1f)
+    (effects (font (size 1 1) (thickness 0.15)) (justify left mirror))
+  )
+
+  (segment (start 99.175 100) (end 98 100) (width 0.25) (layer "F.Cu") (net 1) (tstamp 8c4e1a5f-3d6f-4a0b-9e9c-5f1a3b7d0e2c))
+  (arc (start 98 100) (mid 97 101) (end 98 102) (width 0.25) (layer "F.Cu") (net 1) (tstamp 9d5f2b6a-4e7a-4b1c-8f0d-6a2b4c8e1f3d))
+  (via (at 98 102) (size 0.8) (drill 0.4) (layers "F.Cu" "B.Cu") (free) (net 1) (tstamp 0e6a3c7b-5f8b-4c2d-9a1e-7b3c5d9f2a4e))
+
+)
+"#;
+
+    // a Kicad 7 board, which writes the width of lines in a stroke
+    const KICAD7: &str = r#"(kicad_pcb (version 20221018) (generator pcbnew)
+
+  (general
+    (thickness 1.6)
+  )
+
+  (paper "A4")
+  (layers
+    (0 "F.Cu" signal)
+    (31 "B.Cu" signal)
+    (37 "F.SilkS" user "F.Silkscreen")
+    (44 "Edge.Cuts" user)
+  )
+
+  (setup
+    (pad_to_mask_clearance 0)
+    (pcbplotparams
+      (layerselection 0x00010fc_ffffffff)
+      (outputdirectory "")
+    )
+  )
+
+  (net 0 "")
+  (net 1 "GND")
+
+  (gr_line (start 90 90) (end 110 90)
+    (stroke (width 0.1) (type default)) (layer "Edge.Cuts") (tstamp 2a9e6c1f-4b7d-4e0a-9c3f-6d1b8e2a5c7f))
+  (gr_arc (start 90 95) (mid 91 94) (end 92 95)
+    (stroke (width 0.1) (type default)) (layer "Edge.Cuts") (tstamp 3b0f7d2a-5c8e-4f1b-8d4a-7e2c9f3b6d8a))
+  (gr_circle (center 95 95) (end 96 95)
+    (stroke (width 0.15) (type solid)) (fill none) (layer "F.SilkS") (tstamp 4c1a8e3b-6d9f-4a2c-9e5b-8f3d0a4c7e9b))
+  (gr_text "rev 1" (at 100 95) (layer "F.SilkS") (tstamp 5d2b9f4c-7e0a-4b3d-8f6c-9a4e1b5d8f0c)
+    (effects (font (size 1 1) (thickness 0.15)) (justify left bottom))
+  )
+
+  (segment (start 99.175 100) (end 98 100) (width 0.25) (layer "F.Cu") (net 1) (tstamp 6e3c0a5d-8f1b-4c4e-9a7d-0b5f2c6e9a1d))
+  (via (at 98 100) (size 0.8) (drill 0.4) (layers "F.Cu" "B.Cu") (free) (net 1) (tstamp 7f4d1b6e-9a2c-4d5f-8b8e-1c6a3d7f0b2e))
+
+)
+"#;
+
+    // a Kicad 5 board with expressions this library doesn't know
+    const UNKNOWN: &str = r#"(kicad_pcb (version 20171130) (host pcbnew 5.1.9)
+
+  (general
+    (links 1)
+    (no_connects 0)
+    (area 90 90 110 100)
+    (thickness 1.6)
+    (drawings 1)
+    (tracks 1)
+    (zones 0)
+    (modules 1)
+    (nets 2)
+    (future_count 3)
+  )
+
+  (page A4)
+  (title_block
+    (title "Test board")
+    (rev 1)
+  )
+
+  (layers
+    (0 F.Cu signal)
+    (31 B.Cu signal)
+  )
+
+  (setup
+    (last_trace_width 0.25)
+    (user_via 0.6 0.3)
+    (future_setting a b c)
+    (pcbplotparams
+      (layerselection 0x010fc_ffffffff)
+      (outputdirectory ""))
+  )
+
+  (net 0 "")
+  (net 1 GND)
+
+  (net_class Default "This is the default net class."
+    (clearance 0.2)
+    (trace_width 0.25)
+    (via_dia 0.8)
+    (via_drill 0.4)
+    (uvia_dia 0.3)
+    (uvia_drill 0.1)
+    (future_rule 1)
+    (add_net GND)
+  )
+
+  (module Resistor_SMD:R_0603 (layer F.Cu) (tedit 5B301BBD) (tstamp 5C1E6B3A)
+    (at 100 100)
+    (path /5C1E6B3A)
+    (autoplace_cost90 3)
+    (attr smd)
+    (fp_text reference R1 (at 0 -1.43) (layer F.SilkS)
+      (effects (font (size 1 1) (thickness 0.15)))
+    )
+    (fp_curve (pts (xy 0 0) (xy 1 1) (xy 2 1) (xy 3 0)) (layer F.Fab) (width 0.1))
+    (pad 1 smd rect (at -0.825 0) (size 0.8 0.95) (layers F.Cu F.Paste F.Mask)
+      (net 1 GND) (die_length 1.2))
+  )
+
+  (gr_line (start 90 90) (end 110 90) (layer Edge.Cuts) (width 0.1))
+  (target plus (at 95 95) (size 5) (width 0.1) (layer Edge.Cuts))
+
+  (segment (start 99.175 100) (end 98 100) (width 0.25) (layer F.Cu) (net 1))
+
+  (group "" (id 1234) (members 5C1E6B3A))
+)
+"#;
+
+    fn check(layout: &layout::Layout) {
+        assert!(layout.is_kicad6());
+        assert_eq!(layout.host.tool, "pcbnew");
+        assert_eq!(layout.page, "A4");
+        assert_eq!(layout.layers[2].user_name, Some("B.Adhesive".into()));
+        let stackup = layout.setup.stackup.as_ref().unwrap();
+        assert_eq!(stackup.layers.len(), 3);
+        assert_eq!(stackup.layers[1].name, "dielectric 1");
+        assert_eq!(stackup.layers[1].material, Some("FR4".into()));
+        assert_eq!(stackup.other.len(), 2);
+        assert_eq!(layout.setup.get("pad_to_mask_clearance"), Some(&"0".to_string()));
+
+        let module = layout.get_module("R1").unwrap();
+        assert!(module.footprint);
+        assert_eq!(
+            module.get_tstamp(),
+            Some(&"3d7c2b8e-1d5b-4a6b-9b1e-3e5c7b8a9d10".to_string())
+        );
+        let pad = module.pads()[0];
+        assert_eq!(pad.shape, footprint::PadShape::RoundRect);
+        assert_eq!(pad.net.as_ref().unwrap().name.0, "GND");
+        assert_eq!(pad.pintype, Some("passive".into()));
+        assert!(pad.tstamp.is_some());
+
+        let mut elements = layout.elements.iter();
+        elements.find(|e| matches!(**e, layout::Element::GrRect(_))).unwrap();
+        elements.find(|e| matches!(**e, layout::Element::GrPoly(_))).unwrap();
+        match *elements.next().unwrap() {
+            layout::Element::GrArc(ref arc) => assert_eq!(arc.mid.as_ref().unwrap().x, 91.0),
+            ref e => panic!("not an arc: {:?}", e),
+        }
+        match *elements.nth(1).unwrap() {
+            layout::Element::GrText(ref text) => {
+                let justify = vec![footprint::Justify::Left, footprint::Justify::Mirror];
+                assert_eq!(text.effects.justify, justify)
+            }
+            ref e => panic!("not a text: {:?}", e),
+        }
+        match *elements.nth(1).unwrap() {
+            layout::Element::Arc(ref arc) => {
+                assert_eq!(arc.mid.x, 97.0);
+                assert!(arc.tstamp.is_some())
+            }
+            ref e => panic!("not an arc track: {:?}", e),
+        }
+        assert!(
+            layout
+                .elements
+                .iter()
+                .all(|e| !matches!(*e, layout::Element::Other(_)))
+        );
+    }
+
+    #[test]
+    fn kicad6_board() {
+        let layout = layout::parse(BOARD).unwrap();
+        check(&layout);
+        let s = layout::layout_to_string(&layout, 0).unwrap();
+        check(&layout::parse(&s).unwrap());
+        assert_eq!(s, BOARD);
+    }
+
+    #[test]
+    fn kicad7_board() {
+        let layout = layout::parse(KICAD7).unwrap();
+        match layout.elements[2] {
+            layout::Element::GrLine(ref line) => {
+                assert_eq!(line.width, 0.1);
+                assert_eq!(line.stroke, Some("default".into()))
+            }
+            ref e => panic!("not a line: {:?}", e),
+        }
+        match layout.elements[7] {
+            layout::Element::Via(ref via) => assert_eq!(via.other.len(), 1),
+            ref e => panic!("not a via: {:?}", e),
+        }
+        assert_eq!(layout::layout_to_string(&layout, 0).unwrap(), KICAD7);
+    }
+
+    #[test]
+    fn kicad7_footprint_lines() {
+        let s = "(footprint R1 (fp_line (start 0 0) (end 1 0) (stroke (width 0.12) (type solid)) \
+                 (layer F.SilkS) (tstamp 1)) (fp_arc (start 0 0) (mid 1 1) (end 2 0) (stroke \
+                 (width 0.1) (type solid)) (layer F.Fab) (locked yes) (tstamp 2)))";
+        let module: footprint::Module = from_sexp(&parse_str(s).unwrap()).unwrap();
+        match module.elements[0] {
+            footprint::Element::FpLine(ref line) => assert_eq!(line.width, 0.12),
+            ref e => panic!("not a line: {:?}", e),
+        }
+        assert_eq!(module.into_sexp().to_string(), s);
+    }
+
+    #[test]
+    fn unknown_tokens() {
+        let layout = layout::parse(UNKNOWN).unwrap();
+        assert_eq!(layout.general.other.len(), 1);
+        assert_eq!(layout.header.len(), 1);
+        assert_eq!(layout.setup.other.len(), 1);
+        let module = layout.get_module("R1").unwrap();
+        let other = module
+            .elements
+            .iter()
+            .filter(|e| matches!(**e, footprint::Element::Other(_)))
+            .count();
+        assert_eq!(other, 2);
+        assert_eq!(module.pads()[0].other.len(), 1);
+        assert_eq!(module.pads()[0].net.as_ref().unwrap().name.0, "GND");
+        match layout.elements.last() {
+            Some(&layout::Element::Other(_)) => (),
+            e => panic!("not kept: {:?}", e),
+        }
+        assert_eq!(layout::layout_to_string(&layout, 0).unwrap(), UNKNOWN);
+    }
+
+    #[test]
+    fn pad_other_order() {
+        let s = "(pad 1 smd rect (at 0 0) (size 1 1) (layers F.Cu) (die_length 1.2) (net 1 GND))";
+        let pad: footprint::Pad = from_sexp(&parse_str(s).unwrap()).unwrap();
+        assert_eq!(pad.other.len(), 1);
+        assert_eq!(pad.into_sexp().to_string(), s);
+    }
+
+    fn board(element: &str) -> String {
+        format!(
+            "(kicad_pcb (version 20211014) (generator pcbnew) (layers (0 F.Cu signal)) {})",
+            element
+        )
+    }
+
+    #[test]
+    fn broken_known_elements() {
+        assert!(layout::parse(&board("(segment (start 1 2))")).is_err());
+        assert!(layout::parse(&board("(zone (net 1) (net_name GND))")).is_err());
+        assert!(layout::parse(&board("(footprint R1 (fp_line (start a b)))")).is_err());
+        assert!(layout::parse(&board("(footprint R1 (pad 1 smd rect (net x GND)))")).is_err());
+        assert!(layout::parse(&board("(footprint R1 (fp_rect (start a b)))")).is_ok());
+        assert!(layout::parse(&board("(target (start a b))")).is_ok());
+    }
+
+    #[test]
+    fn attr_flags() {
+        let s = "(footprint R1 (attr through_hole exclude_from_bom))";
+        let module: footprint::Module = from_sexp(&parse_str(s).unwrap()).unwrap();
+        assert_eq!(module.into_sexp().to_string(), s);
+    }
+
+    #[test]
+    fn kicad6_zone() {
+        let zone = "(zone (net 1) (net_name GND) (layer F.Cu) (tstamp 1) (hatch edge 0.508) \
+                    (connect_pads (clearance 0.5)) (min_thickness 0.254) \
+                    (filled_areas_thickness no) \
+                    (keepout (tracks allowed) (vias allowed) (copperpour allowed) (pads allowed)) \
+                    (fill yes (mode hatch) (thermal_gap 0.5) (thermal_bridge_width 0.5) \
+                    (island_removal_mode 1)) (polygon (pts (xy 0 0) (xy 1 0) (xy 1 1))) \
+                    (filled_polygon (layer F.Cu) (pts (xy 0 0) (xy 1 0) (xy 1 1))))";
+        let layout = layout::parse(&board(zone)).unwrap();
+        match layout.elements[0] {
+            layout::Element::Zone(ref z) => {
+                assert_eq!(z.fill.arc_segments, None);
+                assert_eq!(z.fill.mode, Some("hatch".into()));
+                assert_eq!(z.fill.other.len(), 1);
+                assert_eq!(z.keepout.as_ref().unwrap().other.len(), 1);
+                assert_eq!(z.polygons.len(), 1);
+                assert_eq!(z.filled_areas_thickness, Some(false));
+                assert!(z.filled_polygons[0].layer.is_some());
+                assert!(z.other.is_empty());
+                assert_eq!(z.into_sexp(), parse_str(zone).unwrap());
+            }
+            ref e => panic!("not a zone: {:?}", e),
+        }
+        let dimension = "(dimension (type aligned) (layer F.Cu) (pts (xy 0 0) (xy 1 0)))";
+        let layout = layout::parse(&board(dimension)).unwrap();
+        assert!(matches!(layout.elements[0], layout::Element::Other(_)));
+    }
+}
@@ -0,0 +1,167 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! pick-and-place position files for a layout
+//!
+//! like Kicad, positions are written with the Y axis pointing up
+
+use std::fmt::Write;
+
+use layout::Layout;
+use reference_ord;
+
+/// the placement of a module
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    /// reference
+    pub reference: String,
+    /// value
+    pub value: String,
+    /// footprint name, without library
+    pub footprint: String,
+    /// x coordinate
+    pub x: f64,
+    /// y coordinate
+    pub y: f64,
+    /// rotation
+    pub rotation: f64,
+    /// if the module is on the front side
+    pub front: bool,
+}
+
+impl Position {
+    fn side(&self) -> &'static str {
+        if self.front {
+            "top"
+        } else {
+            "bottom"
+        }
+    }
+}
+
+/// get the positions of the modules of a layout, ordered by reference;
+/// with `smd_only` only modules with the smd attribute are included
+pub fn positions(layout: &Layout, smd_only: bool) -> Vec<Position> {
+    let mut v: Vec<Position> = layout
+        .get_modules()
+        .into_iter()
+        .filter(|m| !smd_only || m.has_smd_attr())
+        .map(|m| {
+            let (x, y) = m.at();
+            let footprint = match m.name.rfind(':') {
+                Some(i) => m.name[i + 1..].to_string(),
+                None => m.name.clone(),
+            };
+            Position {
+                reference: m.get_reference().cloned().unwrap_or_default(),
+                value: m.get_value_text().map(|t| t.value.clone()).unwrap_or_default(),
+                footprint,
+                x,
+                y,
+                rotation: m.get_rotation(),
+                front: m.is_front(),
+            }
+        })
+        .collect();
+    v.sort_by_key(|p| (reference_ord(&p.reference), p.reference.clone()));
+    v
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// generate a CSV position file
+pub fn to_csv(positions: &[Position]) -> String {
+    let mut s = String::new();
+    writeln!(s, "Ref,Val,Package,PosX,PosY,Rot,Side").unwrap();
+    for p in positions {
+        writeln!(
+            s,
+            "{},{},{},{:.4},{:.4},{:.4},{}",
+            quote(&p.reference),
+            quote(&p.value),
+            quote(&p.footprint),
+            p.x,
+            -p.y,
+            p.rotation,
+            p.side()
+        ).unwrap();
+    }
+    s
+}
+
+/// generate a Kicad style `.pos` position file
+pub fn to_pos(positions: &[Position]) -> String {
+    let w_ref = positions.iter().map(|p| p.reference.len()).fold(8, usize::max);
+    let w_val = positions.iter().map(|p| p.value.len()).fold(8, usize::max);
+    let w_fp = positions.iter().map(|p| p.footprint.len()).fold(16, usize::max);
+    let mut s = String::new();
+    writeln!(s, "### Module positions - created by kicad_parse_gen ###").unwrap();
+    writeln!(s, "## Unit = mm, Angle = deg.").unwrap();
+    writeln!(s, "## Side : All").unwrap();
+    writeln!(
+        s,
+        "# {:<w_ref$}  {:<w_val$}  {:<w_fp$}  {:>10}  {:>10}  {:>8}  Side",
+        "Ref",
+        "Val",
+        "Package",
+        "PosX",
+        "PosY",
+        "Rot",
+        w_ref = w_ref,
+        w_val = w_val,
+        w_fp = w_fp
+    ).unwrap();
+    for p in positions {
+        writeln!(
+            s,
+            "{:<w_ref$}  {:<w_val$}  {:<w_fp$}  {:>10.4}  {:>10.4}  {:>8.4}  {}",
+            p.reference,
+            p.value,
+            p.footprint,
+            p.x,
+            -p.y,
+            p.rotation,
+            p.side(),
+            w_ref = w_ref + 2,
+            w_val = w_val,
+            w_fp = w_fp
+        ).unwrap();
+    }
+    writeln!(s, "## End").unwrap();
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use layout;
+    use layout::position;
+
+    const BOARD: &str = r#"(kicad_pcb (version 4) (host pcbnew 4.0.7)
+  (module Lib:R_0603 (layer F.Cu) (at 10 20 90) (attr smd)
+    (fp_text reference R10 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value 10k (at 0 0) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+  (module Lib:R_0603 (layer B.Cu) (at 30 20 180) (attr smd)
+    (fp_text reference R2 (at 0 0) (layer B.SilkS) (effects (font (size 1 1) (thickness 0.15)) (justify mirror)))
+    (fp_text value 1k (at 0 0) (layer B.Fab) (effects (font (size 1 1) (thickness 0.15)) (justify mirror))))
+  (module Lib:Pin_Header (layer F.Cu) (at 50 20)
+    (fp_text reference J1 (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15))))
+    (fp_text value CONN (at 0 0) (layer F.Fab) (effects (font (size 1 1) (thickness 0.15)))))
+)"#;
+
+    #[test]
+    fn positions() {
+        let layout = layout::parse(BOARD).unwrap();
+        let all = position::positions(&layout, false);
+        let refs: Vec<&str> = all.iter().map(|p| &p.reference[..]).collect();
+        assert_eq!(refs, vec!["J1", "R2", "R10"]);
+        let smd = position::positions(&layout, true);
+        assert_eq!(smd.len(), 2);
+        let csv = position::to_csv(&smd);
+        assert!(csv.contains("\"R2\",\"1k\",\"R_0603\",30.0000,-20.0000,180.0000,bottom\n"));
+        let pos = position::to_pos(&smd);
+        assert!(pos.starts_with("### Module positions"));
+        assert!(pos.contains("R10 "));
+        assert!(pos.ends_with("## End\n"));
+    }
+}
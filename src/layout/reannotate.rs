@@ -0,0 +1,262 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! geographic re-annotation of a layout
+//!
+//! the modules of each side are numbered in rows or columns, every
+//! reference prefix like `R` or `SW` has its own counter. The back side is
+//! numbered like it is seen from the back, so with the X axis mirrored.
+//! Positions are snapped to a grid first so that modules that are almost
+//! aligned end up in the same row or column.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use layout::{Element, Layout};
+use schematic::{self, Schematic};
+
+/// the order in which the modules of a side are numbered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    /// top to bottom in rows, left to right within a row
+    Rows,
+    /// left to right in columns, top to bottom within a column
+    Columns,
+}
+
+/// settings for geographic re-annotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// order on the front side
+    pub front: Order,
+    /// order on the back side
+    pub back: Order,
+    /// first number on the front side
+    pub front_start: i64,
+    /// first number on the back side; when not set the numbering of every
+    /// prefix continues after the front side
+    pub back_start: Option<i64>,
+    /// grid in mm that positions are snapped to before ordering
+    pub grid: f64,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            front: Order::Rows,
+            back: Order::Rows,
+            front_start: 1,
+            back_start: None,
+            grid: 1.0,
+        }
+    }
+}
+
+impl Layout {
+    /// renumber the module references by their position on the board;
+    /// references without a number are left alone. Returns the renamed
+    /// references, from old to new
+    pub fn reannotate(&mut self, options: &Options) -> BTreeMap<String, String> {
+        let mut front = vec![];
+        let mut back = vec![];
+        for (i, element) in self.elements.iter().enumerate() {
+            if let Element::Module(ref module) = *element {
+                let reference = match module.get_reference() {
+                    Some(reference) => reference,
+                    None => continue,
+                };
+                let prefix = match reference_prefix(reference) {
+                    Some(prefix) => prefix.to_string(),
+                    None => continue,
+                };
+                let (x, y) = module.at();
+                let (x, y) = (snap(x, options.grid), snap(y, options.grid));
+                if module.is_front() {
+                    front.push((i, prefix, x, y))
+                } else {
+                    back.push((i, prefix, -x, y))
+                }
+            }
+        }
+        let mut numbers = HashMap::new();
+        let mut counters: HashMap<String, i64> = HashMap::new();
+        let front = number(&mut front, options.front, |prefix| {
+            let next = counters.entry(prefix.to_string()).or_insert(options.front_start);
+            *next += 1;
+            *next - 1
+        });
+        numbers.extend(front);
+        let mut back_counters: HashMap<String, i64> = HashMap::new();
+        let back = number(&mut back, options.back, |prefix| {
+            let start = options
+                .back_start
+                .or_else(|| counters.get(prefix).cloned())
+                .unwrap_or(options.front_start);
+            let next = back_counters.entry(prefix.to_string()).or_insert(start);
+            *next += 1;
+            *next - 1
+        });
+        numbers.extend(back);
+        let mut renames = BTreeMap::new();
+        for (i, element) in self.elements.iter_mut().enumerate() {
+            if let Element::Module(ref mut module) = *element {
+                let n = match numbers.get(&i) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                let old = module.get_reference().cloned().unwrap_or_default();
+                let new = match reference_prefix(&old) {
+                    Some(prefix) => format!("{}{}", prefix, n),
+                    None => continue,
+                };
+                if new != old {
+                    module.set_reference(&old, &new);
+                    renames.insert(old, new);
+                }
+            }
+        }
+        renames
+    }
+}
+
+/// rename the components of a schematic sheet, including the references
+/// of the sheet instances in the `AR` lines; returns true if anything
+/// changed
+pub fn rename_components(schematic: &mut Schematic, renames: &BTreeMap<String, String>) -> bool {
+    let mut changed = false;
+    for element in &mut schematic.elements {
+        if let schematic::Element::Component(ref mut c) = *element {
+            for (path, r) in c.instances() {
+                if let Some(new) = renames.get(&r) {
+                    changed |= c.update_reference_for_path(&path, new)
+                }
+            }
+            if let Some(new) = renames.get(&c.reference).cloned() {
+                c.update_reference(new);
+                changed = true
+            }
+        }
+    }
+    changed
+}
+
+// the prefix of a reference like `SW12`, if it has a number
+fn reference_prefix(reference: &str) -> Option<&str> {
+    let i = reference.find(|c: char| c.is_ascii_digit())?;
+    if i == 0 || reference[i..].parse::<i64>().is_err() {
+        return None;
+    }
+    Some(&reference[..i])
+}
+
+fn snap(v: f64, grid: f64) -> f64 {
+    if grid > 0.0 {
+        (v / grid).round() * grid
+    } else {
+        v
+    }
+}
+
+// sort the modules of a side and give each one the next number of its prefix
+fn number<F>(
+    modules: &mut [(usize, String, f64, f64)],
+    order: Order,
+    mut next: F,
+) -> Vec<(usize, i64)>
+where
+    F: FnMut(&str) -> i64,
+{
+    modules.sort_by(|a, b| {
+        let (a, b) = match order {
+            Order::Rows => ((a.3, a.2), (b.3, b.2)),
+            Order::Columns => ((a.2, a.3), (b.2, b.3)),
+        };
+        a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+    });
+    modules.iter().map(|m| (m.0, next(&m.1))).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use layout;
+    use layout::reannotate::{rename_components, Options, Order};
+    use schematic;
+
+    fn module(reference: &str, x: f64, y: f64, layer: &str) -> String {
+        format!(
+            "  (module Lib:X (layer {}) (at {} {})
+    (fp_text reference {} (at 0 0) (layer F.SilkS) (effects (font (size 1 1) (thickness 0.15)))))\n",
+            layer, x, y, reference
+        )
+    }
+
+    fn layout() -> layout::Layout {
+        let mut s = String::from(
+            "(kicad_pcb (version 4) (host pcbnew 4.0.7)\n  (layers (0 F.Cu signal) (31 B.Cu signal))\n",
+        );
+        s.push_str(&module("R7", 20.0, 10.0, "F.Cu"));
+        s.push_str(&module("R3", 10.0, 10.2, "F.Cu"));
+        s.push_str(&module("R1", 10.0, 20.0, "F.Cu"));
+        s.push_str(&module("C5", 30.0, 20.0, "F.Cu"));
+        s.push_str(&module("R2", 10.0, 10.0, "B.Cu"));
+        s.push_str(&module("R4", 20.0, 10.0, "B.Cu"));
+        s.push_str(&module("MH", 0.0, 0.0, "F.Cu"));
+        s.push(')');
+        layout::parse(&s).unwrap()
+    }
+
+    fn references(layout: &layout::Layout) -> Vec<String> {
+        layout
+            .get_modules()
+            .iter()
+            .map(|m| m.get_reference().cloned().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn reannotate_rows() {
+        let mut layout = layout();
+        let renames = layout.reannotate(&Options::default());
+        // the back side is seen mirrored and continues after the front
+        assert_eq!(references(&layout), vec!["R2", "R1", "R3", "C1", "R5", "R4", "MH"]);
+        assert_eq!(renames["R7"], "R2");
+        assert_eq!(renames["R2"], "R5");
+        assert!(!renames.contains_key("R4"));
+
+        let sch = "EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R7
+U 1 1 5B000002
+P 1000 1000
+AR Path=\"/5A000001/5B000002\" Ref=\"R7\"  Part=\"1\"
+F 0 \"R7\" V 1080 1000 50  0000 C CNN
+\t1    1000 1000
+\t1    0    0    -1
+$EndComp
+$EndSCHEMATC
+";
+        let mut schematic = schematic::parse_str(sch).unwrap();
+        assert!(rename_components(&mut schematic, &renames));
+        let c = &schematic.components()[0];
+        assert_eq!(c.reference, "R2");
+        assert_eq!(c.get_field_value("Reference"), Some("R2".into()));
+        assert_eq!(c.reference_for_path("/5A000001/5B000002"), Some("R2".into()));
+    }
+
+    #[test]
+    fn reannotate_columns() {
+        let mut layout = layout();
+        let options = Options {
+            front: Order::Columns,
+            front_start: 10,
+            back_start: Some(100),
+            ..Options::default()
+        };
+        layout.reannotate(&options);
+        assert_eq!(
+            references(&layout),
+            vec!["R12", "R10", "R11", "C10", "R101", "R100", "MH"]
+        );
+    }
+}
@@ -10,6 +10,7 @@ use std::result;
 use Sexp;
 use symbolic_expressions::IntoSexp;
 use layout::data::*;
+use sexpr::{insert_other, push_width, stroke_sexp};
 
 impl IntoSexp for Layout {
     fn into_sexp(&self) -> Sexp {
@@ -17,14 +18,33 @@ impl IntoSexp for Layout {
 
         v.push(("version", &self.version));
 
-        let mut v2 = Sexp::start("host");
-        v2.push(&self.host.tool);
-        v2.push(&self.host.build);
-        v.push(v2);
+        // Kicad 6 and later only write the tool as generator and the
+        // thickness as general information
+        if self.is_kicad6() {
+            v.push(("generator", &self.host.tool));
+            let mut v2 = Sexp::start("general");
+            v2.push(("thickness", &self.general.thickness));
+            for o in &self.general.other {
+                v2.push(o.clone());
+            }
+            v.push(v2);
+        } else {
+            let mut v2 = Sexp::start("host");
+            v2.push(&self.host.tool);
+            v2.push(&self.host.build);
+            v.push(v2);
+            v.push(self.general.into_sexp());
+        }
 
-        v.push(self.general.into_sexp());
+        let mut v2 = Sexp::start(if self.is_kicad6() { "paper" } else { "page" });
+        for x in self.page.split(' ') {
+            v2.push(x);
+        }
+        v.push(v2);
 
-        v.push(("page", &self.page));
+        for o in &self.header {
+            v.push(o.clone());
+        }
 
 
         let mut v2 = Sexp::start("layers");
@@ -54,6 +74,9 @@ impl IntoSexp for General {
         v.push(("zones", &self.zones));
         v.push(("modules", &self.modules));
         v.push(("nets", &self.nets));
+        for o in &self.other {
+            v.push(o.clone());
+        }
         v
     }
 }
@@ -80,9 +103,12 @@ impl IntoSexp for Element {
             Element::GrLine(ref s) => s.into_sexp(),
             Element::GrArc(ref s) => s.into_sexp(),
             Element::GrCircle(ref s) => s.into_sexp(),
+            Element::GrPoly(ref s) => s.into_sexp(),
+            Element::GrRect(ref s) => s.into_sexp(),
             Element::Dimension(ref s) => s.into_sexp(),
             Element::Zone(ref s) => s.into_sexp(),
             Element::Segment(ref s) => s.into_sexp(),
+            Element::Arc(ref s) => s.into_sexp(),
             Element::Via(ref s) => s.into_sexp(),
         }
     }
@@ -101,6 +127,10 @@ impl IntoSexp for Zone {
         }
         v.push(self.connect_pads.into_sexp());
         v.push(("min_thickness", &self.min_thickness));
+        if let Some(thickness) = self.filled_areas_thickness {
+            let thickness: String = if thickness { "yes" } else { "no" }.into();
+            v.push(("filled_areas_thickness", &thickness))
+        }
         if let Some(ref keepout) = self.keepout {
             v.push(keepout.into_sexp());
         }
@@ -109,7 +139,7 @@ impl IntoSexp for Zone {
             v.push(("polygon", p.into_sexp()))
         }
         for p in &self.filled_polygons {
-            v.push(("filled_polygon", p.into_sexp()))
+            v.push(p.into_sexp())
         }
         if let Some(ref s) = self.fill_segments {
             v.push(("fill_segments", s.into_sexp()))
@@ -121,6 +151,20 @@ impl IntoSexp for Zone {
     }
 }
 
+impl IntoSexp for FilledPolygon {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("filled_polygon");
+        if let Some(ref layer) = self.layer {
+            v.push(("layer", layer))
+        }
+        if self.island {
+            v.push(Sexp::start("island"))
+        }
+        v.push(self.pts.into_sexp());
+        v
+    }
+}
+
 impl IntoSexp for Hatch {
     fn into_sexp(&self) -> Sexp {
         let mut v = Sexp::start("hatch");
@@ -155,6 +199,9 @@ impl IntoSexp for Keepout {
         v.push(("tracks", &allowed(self.tracks)));
         v.push(("vias", &allowed(self.vias)));
         v.push(("copperpour", &allowed(self.copperpour)));
+        for o in &self.other {
+            v.push(o.clone());
+        }
         v
     }
 }
@@ -164,11 +211,12 @@ impl IntoSexp for Fill {
         if self.filled {
             v.push("yes")
         }
-        if self.segment {
-            let s: String = "segment".into();
-            v.push(("mode", &s))
+        if let Some(ref mode) = self.mode {
+            v.push(("mode", mode))
+        }
+        if let Some(ref arc_segments) = self.arc_segments {
+            v.push(("arc_segments", arc_segments))
         }
-        v.push(("arc_segments", &self.arc_segments));
         v.push(("thermal_gap", &self.thermal_gap));
         v.push(("thermal_bridge_width", &self.thermal_bridge_width));
         if let Some(ref smoothing) = self.smoothing {
@@ -177,6 +225,9 @@ impl IntoSexp for Fill {
         if self.corner_radius != 0.0 {
             v.push(("radius", &self.corner_radius))
         }
+        for o in &self.other {
+            v.push(o.clone());
+        }
         v
     }
 }
@@ -207,6 +258,9 @@ impl IntoSexp for NetClass {
         if let Some(diff_pair_width) = self.diff_pair_width {
             v.push(("diff_pair_width", &diff_pair_width));
         }
+        for o in &self.other {
+            v.push(o.clone());
+        }
         for net in &self.nets {
             v.push(("add_net", net));
         }
@@ -223,6 +277,9 @@ impl IntoSexp for Layer {
         if self.hide {
             v.push("hide".into());
         }
+        if let Some(ref user_name) = self.user_name {
+            v.push(user_name.clone().into());
+        }
         v.into()
     }
 }
@@ -252,12 +309,55 @@ impl IntoSexp for SetupElement {
     }
 }
 
+impl IntoSexp for StackupLayer {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("layer");
+        v.push(&self.name);
+        v.push(("type", &self.t));
+        if let Some(ref thickness) = self.thickness {
+            v.push(("thickness", thickness));
+        }
+        if let Some(ref material) = self.material {
+            v.push(("material", material));
+        }
+        if let Some(ref epsilon_r) = self.epsilon_r {
+            v.push(("epsilon_r", epsilon_r));
+        }
+        if let Some(ref loss_tangent) = self.loss_tangent {
+            v.push(("loss_tangent", loss_tangent));
+        }
+        for o in &self.other {
+            v.push(o.clone());
+        }
+        v
+    }
+}
+
+impl IntoSexp for Stackup {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("stackup");
+        for layer in &self.layers {
+            v.push(layer.into_sexp());
+        }
+        for o in &self.other {
+            v.push(o.clone());
+        }
+        v
+    }
+}
+
 impl IntoSexp for Setup {
     fn into_sexp(&self) -> Sexp {
         let mut v = Sexp::start("setup");
+        if let Some(ref stackup) = self.stackup {
+            v.push(stackup.into_sexp())
+        }
         for k in &self.elements {
             v.push(k.into_sexp())
         }
+        for o in &self.other {
+            v.push(o.clone());
+        }
         let mut v2 = Sexp::start("pcbplotparams");
         for k in &self.pcbplotparams {
             v2.push(k.into_sexp())
@@ -273,10 +373,11 @@ impl IntoSexp for GrText {
         v.push(&self.value);
         v.push(self.at.into_sexp());
         v.push(("layer", &self.layer));
-        v.push(self.effects.into_sexp());
         if let Some(ref tstamp) = self.tstamp {
             v.push(("tstamp", tstamp));
         }
+        v.push(self.effects.into_sexp());
+        insert_other(&mut v, &self.other);
         v
     }
 }
@@ -286,12 +387,15 @@ impl IntoSexp for GrLine {
         let mut v = Sexp::start("gr_line");
         v.push(self.start.into_sexp());
         v.push(self.end.into_sexp());
-        v.push(("angle", &self.angle));
-        v.push(("layer", &self.layer));
-        v.push(("width", &self.width));
+        // Kicad only writes the angle of a line if it isn't 0
+        if self.angle != 0.0 {
+            v.push(("angle", &self.angle));
+        }
+        push_width(&mut v, self.width, &self.stroke, ("layer", &self.layer).into());
         if let Some(ref tstamp) = self.tstamp {
             v.push(("tstamp", tstamp));
         }
+        insert_other(&mut v, &self.other);
         v
     }
 }
@@ -300,13 +404,21 @@ impl IntoSexp for GrArc {
     fn into_sexp(&self) -> Sexp {
         let mut v = Sexp::start("gr_arc");
         v.push(self.start.into_sexp());
-        v.push(self.end.into_sexp());
-        v.push(("angle", &self.angle));
-        v.push(("layer", &self.layer));
-        v.push(("width", &self.width));
+        match self.mid {
+            Some(ref mid) => {
+                v.push(mid.into_sexp());
+                v.push(self.end.into_sexp());
+            }
+            None => {
+                v.push(self.end.into_sexp());
+                v.push(("angle", &self.angle));
+            }
+        }
+        push_width(&mut v, self.width, &self.stroke, ("layer", &self.layer).into());
         if let Some(ref tstamp) = self.tstamp {
             v.push(("tstamp", tstamp));
         }
+        insert_other(&mut v, &self.other);
         v
     }
 }
@@ -316,8 +428,54 @@ impl IntoSexp for GrCircle {
         let mut v = Sexp::start("gr_circle");
         v.push(self.center.into_sexp());
         v.push(self.end.into_sexp());
+        // Kicad 7 writes the stroke and fill before the layer
+        if let Some(ref t) = self.stroke {
+            v.push(stroke_sexp(self.width, t));
+            if let Some(ref fill) = self.fill {
+                v.push(("fill", fill));
+            }
+            v.push(("layer", &self.layer));
+        } else {
+            v.push(("layer", &self.layer));
+            v.push(("width", &self.width));
+            if let Some(ref fill) = self.fill {
+                v.push(("fill", fill));
+            }
+        }
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp));
+        }
+        insert_other(&mut v, &self.other);
+        v
+    }
+}
+
+impl IntoSexp for GrPoly {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("gr_poly");
+        v.push(self.pts.into_sexp());
         v.push(("layer", &self.layer));
         v.push(("width", &self.width));
+        if let Some(ref fill) = self.fill {
+            v.push(("fill", fill));
+        }
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp));
+        }
+        v
+    }
+}
+
+impl IntoSexp for GrRect {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("gr_rect");
+        v.push(self.start.into_sexp());
+        v.push(self.end.into_sexp());
+        v.push(("layer", &self.layer));
+        v.push(("width", &self.width));
+        if let Some(ref fill) = self.fill {
+            v.push(("fill", fill));
+        }
         if let Some(ref tstamp) = self.tstamp {
             v.push(("tstamp", tstamp));
         }
@@ -362,10 +520,27 @@ impl IntoSexp for Segment {
         if let Some(ref status) = self.status {
             v.push(("status", status))
         }
+        insert_other(&mut v, &self.other);
         v
     }
 }
 
+// (arc (start 101 100) (mid 102 101) (end 103 100) (width 0.25) (layer F.Cu) (net 1) (tstamp ...))
+impl IntoSexp for Arc {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("arc");
+        v.push(self.start.into_sexp());
+        v.push(self.mid.into_sexp());
+        v.push(self.end.into_sexp());
+        v.push(("width", &self.width));
+        v.push(("layer", &self.layer));
+        v.push(("net", &self.net));
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp))
+        }
+        v
+    }
+}
 
 // (via (at 132.1948 121.2202) (size 0.675) (drill 0.25) (layers F.Cu B.Cu) (net 19))
 
@@ -385,6 +560,10 @@ impl IntoSexp for Via {
         }
         v.push(self.layers.into_sexp());
         v.push(("net", &self.net));
+        if let Some(ref tstamp) = self.tstamp {
+            v.push(("tstamp", tstamp))
+        }
+        insert_other(&mut v, &self.other);
         v
     }
 }
@@ -96,6 +96,10 @@ pub enum KicadFile {
     Project(project::Project),
     /// a Kicad fp-lib-table file
     FpLibTable(fp_lib_table::FpLibTable),
+    /// a Kicad sym-lib-table file
+    SymLibTable(sym_lib_table::SymLibTable),
+    /// a Kicad netlist file
+    Netlist(netlist::Netlist),
 }
 
 /// types of Kicad files that we expect to read
@@ -113,6 +117,10 @@ pub enum Expected {
     Project,
     /// an fp-lib-table file
     FpLibTable,
+    /// a sym-lib-table file
+    SymLibTable,
+    /// a Kicad netlist file
+    Netlist,
     /// any Kicad file
     Any,
 }
@@ -128,6 +136,8 @@ impl fmt::Display for KicadFile {
             KicadFile::SymbolLib(_) => write!(f, "symbollib"),
             KicadFile::Project(_) => write!(f, "project"),
             KicadFile::FpLibTable(_) => write!(f, "fp-lib-table"),
+            KicadFile::SymLibTable(_) => write!(f, "sym-lib-table"),
+            KicadFile::Netlist(_) => write!(f, "netlist"),
         }
     }
 }
@@ -143,7 +153,12 @@ pub fn read_kicad_file(name: &Path, expected: Expected) -> Result<KicadFile, Kic
             return Err(x);
         },
     }
-    match schematic::parse(Some(PathBuf::from(name)), &data) {
+    let sch = if schematic::kicad_sch::is_kicad_sch(&data) {
+        schematic::kicad_sch::parse(Some(PathBuf::from(name)), &data)
+    } else {
+        schematic::parse(Some(PathBuf::from(name)), &data)
+    };
+    match sch {
         Ok(sch) => return Ok(KicadFile::Schematic(sch)),
         Err(x) => if expected == Expected::Schematic {
             return Err(x);
@@ -155,7 +170,12 @@ pub fn read_kicad_file(name: &Path, expected: Expected) -> Result<KicadFile, Kic
             return Err(x);
         },
     }
-    match symbol_lib::parse_str(&data) {
+    let sl = if symbol_lib::kicad_sym::is_kicad_sym(&data) {
+        symbol_lib::kicad_sym::parse(&data)
+    } else {
+        symbol_lib::parse_str(&data)
+    };
+    match sl {
         Ok(sl) => return Ok(KicadFile::SymbolLib(sl)),
         Err(x) => if expected == Expected::SymbolLib {
             return Err(x);
@@ -173,6 +193,18 @@ pub fn read_kicad_file(name: &Path, expected: Expected) -> Result<KicadFile, Kic
             return Err(x.into());
         },
     }
+    match sym_lib_table::parse(&data) {
+        Ok(p) => return Ok(KicadFile::SymLibTable(p)),
+        Err(x) => if expected == Expected::SymLibTable {
+            return Err(x.into());
+        },
+    }
+    match netlist::parse(&data) {
+        Ok(n) => return Ok(KicadFile::Netlist(n)),
+        Err(x) => if expected == Expected::Netlist {
+            return Err(x);
+        },
+    }
     Ok(KicadFile::Unknown(PathBuf::from(name)))
 }
 
@@ -192,6 +224,16 @@ pub fn read_schematic(name: &Path) -> Result<schematic::Schematic, KicadError> {
     }
 }
 
+/// write out a kicad `Schematic` to a file, in the s-expression format if
+/// the file has the `.kicad_sch` extension
+pub fn write_schematic(schematic: &schematic::Schematic, name: &Path) -> Result<(), KicadError> {
+    if name.extension() == Some("kicad_sch".as_ref()) {
+        let s = schematic::kicad_sch::to_string(schematic, 0)?;
+        return write_file(name, &s);
+    }
+    write_file(name, &format!("{}", schematic))
+}
+
 /// read a file, expecting it to be a Kicad layout file
 pub fn read_layout(name: &Path) -> Result<layout::Layout, KicadError> {
     match read_kicad_file(name, Expected::Layout)? {
@@ -220,6 +262,16 @@ pub fn read_symbol_lib(name: &Path) -> Result<symbol_lib::SymbolLib, KicadError>
     }
 }
 
+/// write out a kicad `SymbolLib` to a file, in the s-expression format if
+/// the file has the `.kicad_sym` extension
+pub fn write_symbol_lib(lib: &symbol_lib::SymbolLib, name: &Path) -> Result<(), KicadError> {
+    if name.extension() == Some("kicad_sym".as_ref()) {
+        let s = symbol_lib::kicad_sym::to_string(lib, 0)?;
+        return write_file(name, &s);
+    }
+    write_file(name, &format!("{}", lib))
+}
+
 /// read a file, expecting it to be a Kicad project file
 pub fn read_project(name: &Path) -> Result<project::Project, KicadError> {
     match read_kicad_file(name, Expected::Project)? {
@@ -236,6 +288,28 @@ pub fn read_fp_lib_table(name: &Path) -> Result<fp_lib_table::FpLibTable, KicadE
     }
 }
 
+/// read a file, expecting it to be a sym-lib-table
+pub fn read_sym_lib_table(name: &Path) -> Result<sym_lib_table::SymLibTable, KicadError> {
+    match read_kicad_file(name, Expected::SymLibTable)? {
+        KicadFile::SymLibTable(mo) => Ok(mo),
+        x => str_error(format!("unexpected {} in {}", x, name.display())),
+    }
+}
+
+/// read a file, expecting it to be a Kicad netlist
+pub fn read_netlist(name: &Path) -> Result<netlist::Netlist, KicadError> {
+    match read_kicad_file(name, Expected::Netlist)? {
+        KicadFile::Netlist(mo) => Ok(mo),
+        x => str_error(format!("unexpected {} in {}", x, name.display())),
+    }
+}
+
+/// write out a kicad `Netlist` to a file
+pub fn write_netlist(netlist: &netlist::Netlist, name: &Path) -> Result<(), KicadError> {
+    let s = netlist::to_string(netlist, 0)?;
+    write_file(name, &s)
+}
+
 fn wrap<X, Y, F, G>(s: &Sexp, make: F, wrapper: G) -> result::Result<Y,SexpError>
 where
     F: Fn(&Sexp) -> result::Result<X,SexpError>,
@@ -347,7 +421,7 @@ pub trait Adjust {
 /// ordering used for ordering by component reference
 /// to e.g. avoid U1 U101 U2 and get U1 U2 U101
 pub fn reference_ord(r: &str) -> (char, i64) {
-    let c = r.chars().nth(0).unwrap();
+    let c = r.chars().next().unwrap_or(' ');
     let mut s = String::new();
     for c in r.chars() {
         if c >= '0' && c <= '9' {
@@ -381,8 +455,20 @@ pub mod symbol_lib;
 pub mod project;
 /// Kicad fp-lib-table format handling
 pub mod fp_lib_table;
+/// Kicad sym-lib-table format handling
+pub mod sym_lib_table;
+/// expansion of the variables in library tables
+pub mod expand;
+/// Kicad netlist format handling
+pub mod netlist;
 /// checking and fixing related to the Kicad Library Convention
 pub mod checkfix;
+/// bill of materials generation from a schematic
+pub mod bom;
 
 mod util;
 mod formatter;
+mod sexpr;
+mod geometry;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,822 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+// extension: .net
+// format: new-style
+
+use symbolic_expressions;
+use symbolic_expressions::{IntoSexp, Sexp, SexpError};
+use symbolic_expressions::iteratom::*;
+use formatter::KicadFormatter;
+
+use schematic::Schematic;
+use symbol_lib::{Draw, PinType, Symbol, SymbolLib};
+use KicadError;
+
+/// a Kicad netlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct Netlist {
+    /// version of the netlist format, `D` for current Kicad
+    pub version: String,
+    /// information about the design the netlist was made from
+    pub design: Option<Design>,
+    /// the components
+    pub components: Vec<Component>,
+    /// the library parts used by the components
+    pub libparts: Vec<LibPart>,
+    /// the libraries the library parts come from
+    pub libraries: Vec<Library>,
+    /// the nets
+    pub nets: Vec<Net>,
+}
+
+impl Default for Netlist {
+    fn default() -> Netlist {
+        Netlist {
+            version: "D".into(),
+            design: None,
+            components: vec![],
+            libparts: vec![],
+            libraries: vec![],
+            nets: vec![],
+        }
+    }
+}
+
+/// information about the design
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Design {
+    /// the source schematic file
+    pub source: Option<String>,
+    /// the date of the export
+    pub date: Option<String>,
+    /// the tool that made the netlist
+    pub tool: Option<String>,
+    /// other elements like the sheets, kept as is
+    pub other: Vec<Sexp>,
+}
+
+/// a component of a netlist
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Component {
+    /// reference
+    pub reference: String,
+    /// value
+    pub value: String,
+    /// footprint
+    pub footprint: Option<String>,
+    /// datasheet
+    pub datasheet: Option<String>,
+    /// custom fields
+    pub fields: Vec<Field>,
+    /// the library part of the component
+    pub libsource: Option<LibSource>,
+    /// the sheet of the component as `(names, tstamps)`
+    pub sheetpath: Option<(String, String)>,
+    /// timestamp
+    pub tstamp: Option<String>,
+    /// other elements, kept as is
+    pub other: Vec<Sexp>,
+}
+
+/// a field of a component or a library part
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Field {
+    /// name
+    pub name: String,
+    /// value
+    pub value: String,
+}
+
+/// the library part of a component
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibSource {
+    /// library name
+    pub lib: String,
+    /// part name
+    pub part: String,
+    /// description
+    pub description: Option<String>,
+}
+
+/// a library part
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibPart {
+    /// library name
+    pub lib: String,
+    /// part name
+    pub part: String,
+    /// aliases of the part
+    pub aliases: Vec<String>,
+    /// description
+    pub description: Option<String>,
+    /// documentation
+    pub docs: Option<String>,
+    /// footprint filters
+    pub footprints: Vec<String>,
+    /// fields
+    pub fields: Vec<Field>,
+    /// pins
+    pub pins: Vec<Pin>,
+    /// other elements, kept as is
+    pub other: Vec<Sexp>,
+}
+
+/// a pin of a library part
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pin {
+    /// number
+    pub num: String,
+    /// name
+    pub name: String,
+    /// electrical type like `passive` or `power_in`
+    pub type_: String,
+}
+
+/// a library
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Library {
+    /// logical name
+    pub logical: String,
+    /// uri
+    pub uri: String,
+}
+
+/// a net
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Net {
+    /// net code
+    pub code: i64,
+    /// net name
+    pub name: String,
+    /// the pins on the net
+    pub nodes: Vec<Node>,
+}
+
+/// a pin on a net
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Node {
+    /// reference of the component
+    pub reference: String,
+    /// pin number
+    pub pin: String,
+    /// pin function, the name of the pin
+    pub pinfunction: Option<String>,
+}
+
+impl Component {
+    /// the path of sheet timestamps and component timestamp, as found in
+    /// the `path` of the module of the component in a layout
+    pub fn path(&self) -> Option<String> {
+        let tstamp = self.tstamp.as_ref()?;
+        let sheet = match self.sheetpath {
+            Some((_, ref tstamps)) => tstamps.trim_end_matches('/'),
+            None => "",
+        };
+        Some(format!("{}/{}", sheet, tstamp))
+    }
+}
+
+impl Netlist {
+    /// create a netlist for a schematic including its sub-sheets; the
+    /// symbols of the components are looked up in the libraries
+    pub fn from_schematic(
+        schematic: &Schematic,
+        libs: &[&SymbolLib],
+    ) -> Result<Netlist, KicadError> {
+        let connectivity = schematic.connectivity(libs)?;
+        let mut netlist = Netlist {
+            design: Some(Design {
+                source: schematic
+                    .filename
+                    .as_ref()
+                    .map(|f| f.display().to_string()),
+                date: None,
+                tool: Some(format!("kicad_parse_gen {}", env!("CARGO_PKG_VERSION"))),
+                other: vec![],
+            }),
+            ..Netlist::default()
+        };
+        for instance in schematic.component_instances()? {
+            let reference = instance.reference;
+            if reference.starts_with('#') || netlist.component(&reference).is_some() {
+                continue;
+            }
+            let c = instance.component;
+            let (lib, part) = match c.name.find(':') {
+                Some(i) => (c.name[..i].to_string(), c.name[i + 1..].to_string()),
+                None => (String::new(), c.name.clone()),
+            };
+            let non_empty = |name: &str| {
+                c.get_field_value(name)
+                    .filter(|v| !v.is_empty() && v != "~")
+            };
+            netlist.components.push(Component {
+                reference,
+                value: c.get_field_value("Value").unwrap_or_default(),
+                footprint: non_empty("Footprint"),
+                datasheet: non_empty("UserDocLink"),
+                fields: c.fields
+                    .iter()
+                    .filter(|f| f.i > 3)
+                    .map(|f| Field {
+                        name: f.name.clone(),
+                        value: f.value.clone(),
+                    })
+                    .collect(),
+                libsource: Some(LibSource {
+                    lib: lib.clone(),
+                    part: part.clone(),
+                    description: None,
+                }),
+                sheetpath: Some((instance.name_path, format!("{}/", instance.stamp_path))),
+                tstamp: c.timestamp().map(|t| t.to_string()),
+                other: vec![],
+            });
+            if netlist.libparts.iter().any(|p| p.lib == lib && p.part == part) {
+                continue;
+            }
+            let cache_name = c.name.replace(':', "_");
+            let matches = |s: &Symbol| s.name == c.name || s.name == cache_name || s.name == part;
+            let symbol = libs.iter().filter_map(|l| l.find(matches)).next();
+            if let Some(symbol) = symbol {
+                let mut pins: Vec<Pin> = vec![];
+                for draw in &symbol.draw {
+                    if let Draw::Pin(ref pin) = *draw {
+                        if !pins.iter().any(|p| p.num == pin.number) {
+                            pins.push(Pin {
+                                num: pin.number.clone(),
+                                name: pin.name.clone(),
+                                type_: pin_type_name(&pin.pin_type).into(),
+                            })
+                        }
+                    }
+                }
+                netlist.libparts.push(LibPart {
+                    lib,
+                    part,
+                    aliases: symbol.aliases.clone(),
+                    fields: symbol
+                        .fields
+                        .iter()
+                        .filter(|f| f.i < 2)
+                        .map(|f| Field {
+                            name: if f.i == 0 { "Reference" } else { "Value" }.into(),
+                            value: f.value.clone(),
+                        })
+                        .collect(),
+                    pins,
+                    ..LibPart::default()
+                })
+            }
+        }
+        for (i, (name, nodes)) in connectivity.nets.into_iter().enumerate() {
+            netlist.nets.push(Net {
+                code: i as i64 + 1,
+                name,
+                nodes: nodes
+                    .into_iter()
+                    .map(|n| Node {
+                        reference: n.reference,
+                        pin: n.pin,
+                        pinfunction: None,
+                    })
+                    .collect(),
+            })
+        }
+        Ok(netlist)
+    }
+
+    /// get a component by reference
+    pub fn component(&self, reference: &str) -> Option<&Component> {
+        self.components.iter().find(|c| c.reference == reference)
+    }
+
+    /// get the net a pin is on
+    pub fn net_for_pin(&self, reference: &str, pin: &str) -> Option<&Net> {
+        self.nets.iter().find(|net| {
+            net.nodes
+                .iter()
+                .any(|n| n.reference == reference && n.pin == pin)
+        })
+    }
+}
+
+// the pin types as named in a netlist
+fn pin_type_name(t: &PinType) -> &'static str {
+    match *t {
+        PinType::Input => "input",
+        PinType::Output => "output",
+        PinType::Bidi => "BiDi",
+        PinType::Tristate => "3state",
+        PinType::Passive => "passive",
+        PinType::Unspecified => "unspc",
+        PinType::PowerInput => "power_in",
+        PinType::PowerOutput => "power_out",
+        PinType::OpenCollector => "openCol",
+        PinType::OpenEmitter => "openEm",
+        PinType::NotConnected => "NotConnected",
+    }
+}
+
+fn push_option(v: &mut Sexp, name: &str, value: &Option<String>) {
+    if let Some(ref value) = *value {
+        v.push((name, value))
+    }
+}
+
+fn push_fields(v: &mut Sexp, fields: &[Field]) {
+    if fields.is_empty() {
+        return;
+    }
+    let mut l = Sexp::start("fields");
+    for field in fields {
+        l.push(field.into_sexp())
+    }
+    v.push(l)
+}
+
+impl IntoSexp for Netlist {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("export");
+        v.push(("version", &self.version));
+        if let Some(ref design) = self.design {
+            v.push(design.into_sexp())
+        }
+        let mut l = Sexp::start("components");
+        for c in &self.components {
+            l.push(c.into_sexp())
+        }
+        v.push(l);
+        let mut l = Sexp::start("libparts");
+        for p in &self.libparts {
+            l.push(p.into_sexp())
+        }
+        v.push(l);
+        let mut l = Sexp::start("libraries");
+        for library in &self.libraries {
+            l.push(library.into_sexp())
+        }
+        v.push(l);
+        let mut l = Sexp::start("nets");
+        for net in &self.nets {
+            l.push(net.into_sexp())
+        }
+        v.push(l);
+        v
+    }
+}
+
+impl IntoSexp for Design {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("design");
+        push_option(&mut v, "source", &self.source);
+        push_option(&mut v, "date", &self.date);
+        push_option(&mut v, "tool", &self.tool);
+        for other in &self.other {
+            v.push(other.clone())
+        }
+        v
+    }
+}
+
+impl IntoSexp for Component {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("comp");
+        v.push(("ref", &self.reference));
+        v.push(("value", &self.value));
+        push_option(&mut v, "footprint", &self.footprint);
+        push_option(&mut v, "datasheet", &self.datasheet);
+        push_fields(&mut v, &self.fields);
+        if let Some(ref libsource) = self.libsource {
+            let mut l = Sexp::start("libsource");
+            l.push(("lib", &libsource.lib));
+            l.push(("part", &libsource.part));
+            push_option(&mut l, "description", &libsource.description);
+            v.push(l)
+        }
+        if let Some((names, tstamps)) = &self.sheetpath {
+            let mut l = Sexp::start("sheetpath");
+            l.push(("names", names));
+            l.push(("tstamps", tstamps));
+            v.push(l)
+        }
+        push_option(&mut v, "tstamp", &self.tstamp);
+        for other in &self.other {
+            v.push(other.clone())
+        }
+        v
+    }
+}
+
+impl IntoSexp for Field {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("field");
+        v.push(("name", &self.name));
+        v.push(&self.value);
+        v
+    }
+}
+
+impl IntoSexp for LibPart {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("libpart");
+        v.push(("lib", &self.lib));
+        v.push(("part", &self.part));
+        if !self.aliases.is_empty() {
+            let mut l = Sexp::start("aliases");
+            for alias in &self.aliases {
+                l.push(("alias", alias))
+            }
+            v.push(l)
+        }
+        push_option(&mut v, "description", &self.description);
+        push_option(&mut v, "docs", &self.docs);
+        if !self.footprints.is_empty() {
+            let mut l = Sexp::start("footprints");
+            for fp in &self.footprints {
+                l.push(("fp", fp))
+            }
+            v.push(l)
+        }
+        push_fields(&mut v, &self.fields);
+        if !self.pins.is_empty() {
+            let mut l = Sexp::start("pins");
+            for pin in &self.pins {
+                let mut p = Sexp::start("pin");
+                p.push(("num", &pin.num));
+                p.push(("name", &pin.name));
+                p.push(("type", &pin.type_));
+                l.push(p)
+            }
+            v.push(l)
+        }
+        for other in &self.other {
+            v.push(other.clone())
+        }
+        v
+    }
+}
+
+impl IntoSexp for Library {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("library");
+        v.push(("logical", &self.logical));
+        v.push(("uri", &self.uri));
+        v
+    }
+}
+
+impl IntoSexp for Net {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("net");
+        v.push(("code", &self.code));
+        v.push(("name", &self.name));
+        for node in &self.nodes {
+            let mut n = Sexp::start("node");
+            n.push(("ref", &node.reference));
+            n.push(("pin", &node.pin));
+            push_option(&mut n, "pinfunction", &node.pinfunction);
+            v.push(n)
+        }
+        v
+    }
+}
+
+// the elements of a list after its name
+fn elements<'a>(s: &'a Sexp, name: &str) -> Result<Vec<&'a Sexp>, SexpError> {
+    let i = IterAtom::new(s, name)?;
+    Ok(i.iter.collect())
+}
+
+// the name of a list element; empty for a string
+fn name_of(s: &Sexp) -> &str {
+    match *s {
+        Sexp::List(ref l) => match l.first() {
+            Some(Sexp::String(name)) => name,
+            _ => "",
+        },
+        _ => "",
+    }
+}
+
+// a list with a name and an optional value, like `(datasheet)`
+fn value_of(s: &Sexp) -> Result<String, SexpError> {
+    let l = s.list()?;
+    match l.get(1) {
+        Some(x) => x.s(),
+        None => Ok(String::new()),
+    }
+}
+
+fn vec_of<T: FromSexp>(s: &Sexp, name: &str) -> Result<Vec<T>, SexpError> {
+    let mut i = IterAtom::new(s, name)?;
+    i.vec()
+}
+
+impl FromSexp for Netlist {
+    fn from_sexp(s: &Sexp) -> Result<Netlist, SexpError> {
+        let mut n = Netlist::default();
+        for e in elements(s, "export")? {
+            match name_of(e) {
+                "version" => n.version = value_of(e)?,
+                "design" => n.design = Some(Design::from_sexp(e)?),
+                "components" => n.components = vec_of(e, "components")?,
+                "libparts" => n.libparts = vec_of(e, "libparts")?,
+                "libraries" => n.libraries = vec_of(e, "libraries")?,
+                "nets" => n.nets = vec_of(e, "nets")?,
+                x => return Err(format!("unknown element {} in netlist", x).into()),
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl FromSexp for Design {
+    fn from_sexp(s: &Sexp) -> Result<Design, SexpError> {
+        let mut d = Design::default();
+        for e in elements(s, "design")? {
+            match name_of(e) {
+                "source" => d.source = Some(value_of(e)?),
+                "date" => d.date = Some(value_of(e)?),
+                "tool" => d.tool = Some(value_of(e)?),
+                _ => d.other.push(e.clone()),
+            }
+        }
+        Ok(d)
+    }
+}
+
+impl FromSexp for Component {
+    fn from_sexp(s: &Sexp) -> Result<Component, SexpError> {
+        let mut c = Component::default();
+        for e in elements(s, "comp")? {
+            match name_of(e) {
+                "ref" => c.reference = value_of(e)?,
+                "value" => c.value = value_of(e)?,
+                "footprint" => c.footprint = Some(value_of(e)?),
+                "datasheet" => c.datasheet = Some(value_of(e)?),
+                "fields" => c.fields = vec_of(e, "fields")?,
+                "libsource" => {
+                    let mut l = LibSource::default();
+                    for e in elements(e, "libsource")? {
+                        match name_of(e) {
+                            "lib" => l.lib = value_of(e)?,
+                            "part" => l.part = value_of(e)?,
+                            "description" => l.description = Some(value_of(e)?),
+                            x => return Err(format!("unknown element {} in libsource", x).into()),
+                        }
+                    }
+                    c.libsource = Some(l)
+                }
+                "sheetpath" => {
+                    let mut i = IterAtom::new(e, "sheetpath")?;
+                    let names = i.s_in_list("names")?;
+                    let tstamps = i.s_in_list("tstamps")?;
+                    c.sheetpath = i.close(Some((names, tstamps)))?
+                }
+                "tstamp" => c.tstamp = Some(value_of(e)?),
+                _ => c.other.push(e.clone()),
+            }
+        }
+        Ok(c)
+    }
+}
+
+impl FromSexp for Field {
+    fn from_sexp(s: &Sexp) -> Result<Field, SexpError> {
+        let mut i = IterAtom::new(s, "field")?;
+        let name = i.s_in_list("name")?;
+        let value = i.maybe_s().unwrap_or_default();
+        i.close(Field { name, value })
+    }
+}
+
+impl FromSexp for LibPart {
+    fn from_sexp(s: &Sexp) -> Result<LibPart, SexpError> {
+        let mut p = LibPart::default();
+        for e in elements(s, "libpart")? {
+            match name_of(e) {
+                "lib" => p.lib = value_of(e)?,
+                "part" => p.part = value_of(e)?,
+                "aliases" => {
+                    for a in elements(e, "aliases")? {
+                        p.aliases.push(a.named_value_s("alias")?)
+                    }
+                }
+                "description" => p.description = Some(value_of(e)?),
+                "docs" => p.docs = Some(value_of(e)?),
+                "footprints" => {
+                    for fp in elements(e, "footprints")? {
+                        p.footprints.push(fp.named_value_s("fp")?)
+                    }
+                }
+                "fields" => p.fields = vec_of(e, "fields")?,
+                "pins" => p.pins = vec_of(e, "pins")?,
+                _ => p.other.push(e.clone()),
+            }
+        }
+        Ok(p)
+    }
+}
+
+impl FromSexp for Pin {
+    fn from_sexp(s: &Sexp) -> Result<Pin, SexpError> {
+        let mut i = IterAtom::new(s, "pin")?;
+        let num = i.s_in_list("num")?;
+        let name = i.s_in_list("name")?;
+        let type_ = i.s_in_list("type")?;
+        i.close(Pin { num, name, type_ })
+    }
+}
+
+impl FromSexp for Library {
+    fn from_sexp(s: &Sexp) -> Result<Library, SexpError> {
+        let mut i = IterAtom::new(s, "library")?;
+        let logical = i.s_in_list("logical")?;
+        let uri = i.s_in_list("uri")?;
+        i.close(Library { logical, uri })
+    }
+}
+
+impl FromSexp for Net {
+    fn from_sexp(s: &Sexp) -> Result<Net, SexpError> {
+        let mut i = IterAtom::new(s, "net")?;
+        let code = i.i_in_list("code")?;
+        let name = i.s_in_list("name")?;
+        let nodes = i.vec()?;
+        Ok(Net { code, name, nodes })
+    }
+}
+
+impl FromSexp for Node {
+    fn from_sexp(s: &Sexp) -> Result<Node, SexpError> {
+        let mut i = IterAtom::new(s, "node")?;
+        let reference = i.s_in_list("ref")?;
+        let pin = i.s_in_list("pin")?;
+        let pinfunction = i.maybe_s_in_list("pinfunction");
+        i.close(Node {
+            reference,
+            pin,
+            pinfunction,
+        })
+    }
+}
+
+/// parse a &str to a `Netlist`
+pub fn parse(s: &str) -> Result<Netlist, KicadError> {
+    let t = symbolic_expressions::parser::parse_str(s)?;
+    let s = symbolic_expressions::from_sexp(&t)?;
+    Ok(s)
+}
+
+/// convert a `Netlist` to a formatted symbolic-expressions String
+pub fn to_string(netlist: &Netlist, indent_level: i64) -> Result<String, KicadError> {
+    let formatter = KicadFormatter::new(indent_level);
+    symbolic_expressions::ser::to_string_with_formatter(&netlist.into_sexp(), formatter)
+        .map_err(From::from)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use netlist;
+    use schematic;
+    use symbol_lib;
+    use test_util;
+
+    const LIB: &str = r##"EESchema-LIBRARY Version 2.3
+#encoding utf-8
+#
+# R
+#
+DEF R R 0 0 N Y 1 F N
+F0 "R" 80 0 50 V V C CNN
+F1 "R" 0 0 50 V V C CNN
+DRAW
+X ~ 1 0 150 50 D 50 50 1 1 P
+X ~ 2 0 -150 50 U 50 50 1 1 P
+ENDDRAW
+ENDDEF
+#
+#End Library
+"##;
+
+    const SCHEMATIC: &str = r##"EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R1
+U 1 1 5B000001
+P 1000 1000
+F 0 "R1" V 1080 1000 50  0000 C CNN
+F 1 "10k" V 1000 1000 50  0000 C CNN
+F 2 "Resistors_SMD:R_0603" V 930 1000 50  0001 C CNN
+F 3 "" H 1000 1000 50  0001 C CNN
+	1    1000 1000
+	1    0    0    -1  
+$EndComp
+$Comp
+L R R2
+U 1 1 5B000002
+P 1000 1500
+F 0 "R2" V 1080 1500 50  0000 C CNN
+F 1 "1k" V 1000 1500 50  0000 C CNN
+	1    1000 1500
+	1    0    0    -1  
+$EndComp
+Wire Wire Line
+	1000 1150 1000 1350
+$EndSCHEMATC
+"##;
+
+    const NETLIST: &str = r#"(export (version D)
+  (design
+    (source /home/user/test.sch)
+    (date "Fri 06 Apr 2018 10:00:00 AM CEST")
+    (tool "Eeschema 4.0.7")
+    (sheet (number 1) (name /) (tstamps /)
+      (title_block
+        (title)
+        (company)
+        (rev)
+        (date)
+        (source test.sch)
+        (comment (number 1) (value "")))))
+  (components
+    (comp (ref R1)
+      (value 10k)
+      (footprint Resistors_SMD:R_0603)
+      (fields
+        (field (name MPN) RC0603))
+      (libsource (lib device) (part R))
+      (sheetpath (names /) (tstamps /))
+      (tstamp 5B000001))
+    (comp (ref R2)
+      (value 1k)
+      (libsource (lib device) (part R))
+      (sheetpath (names /Sub/) (tstamps /5A000001/))
+      (tstamp 5B000002)))
+  (libparts
+    (libpart (lib device) (part R)
+      (description Resistor)
+      (footprints
+        (fp R_*))
+      (fields
+        (field (name Reference) R)
+        (field (name Value) R))
+      (pins
+        (pin (num 1) (name ~) (type passive))
+        (pin (num 2) (name ~) (type passive)))))
+  (libraries
+    (library (logical device)
+      (uri /usr/share/kicad/library/device.lib)))
+  (nets
+    (net (code 1) (name /SIG)
+      (node (ref R1) (pin 1))
+      (node (ref R2) (pin 1)))
+    (net (code 2) (name "Net-(R1-Pad2)")
+      (node (ref R1) (pin 2)))))"#;
+
+    #[test]
+    fn netlist_round_trip() {
+        let n = netlist::parse(NETLIST).unwrap();
+        assert_eq!(n.components.len(), 2);
+        assert_eq!(n.components[0].fields[0].value, "RC0603");
+        assert_eq!(n.libparts[0].pins.len(), 2);
+        assert_eq!(n.libraries[0].logical, "device");
+        assert_eq!(n.net_for_pin("R2", "1").map(|net| &net.name[..]), Some("/SIG"));
+        let s = netlist::to_string(&n, 0).unwrap();
+        assert_eq!(s, NETLIST);
+    }
+
+    #[test]
+    fn netlist_from_schematic() {
+        let lib = symbol_lib::parse_str(LIB).unwrap();
+        let sch = schematic::parse_str(SCHEMATIC).unwrap();
+        let n = netlist::Netlist::from_schematic(&sch, &[&lib]).unwrap();
+        assert_eq!(n.components.len(), 2);
+        assert_eq!(n.components[0].footprint, Some("Resistors_SMD:R_0603".into()));
+        assert_eq!(n.components[0].datasheet, None);
+        assert_eq!(n.libparts.len(), 1);
+        assert_eq!(n.nets.len(), 3);
+        assert_eq!(n.net_for_pin("R1", "2"), n.net_for_pin("R2", "1"));
+        let s = netlist::to_string(&n, 0).unwrap();
+        assert_eq!(netlist::parse(&s).unwrap(), n);
+    }
+
+    #[test]
+    fn netlist_from_hierarchy() {
+        let dir = test_util::legacy_hierarchy("netlist_from_hierarchy");
+        let lib = symbol_lib::parse_str(LIB).unwrap();
+        let sch = schematic::parse_file(&dir.join("root.sch")).unwrap();
+        let n = netlist::Netlist::from_schematic(&sch, &[&lib]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let references: Vec<&str> = n.components.iter().map(|c| &c.reference[..]).collect();
+        assert_eq!(references, vec!["R1", "R2", "R3"]);
+        let r3 = n.component("R3").unwrap();
+        assert_eq!(r3.sheetpath, Some(("/Right/".into(), "/5A000002/".into())));
+        assert_eq!(r3.path(), Some("/5A000002/5B000001".into()));
+        assert!(n.net_for_pin("R3", "1").is_some());
+    }
+}
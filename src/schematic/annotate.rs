@@ -0,0 +1,277 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! annotation of the components of a schematic
+//!
+//! components with a reference ending in `?` get the first free number of
+//! their prefix, counting from 1 or from the sheet number times an offset.
+//! Existing references are kept. A unit of a multi-unit component joins a
+//! component with the same symbol and value on the same sheet that doesn't
+//! use that unit yet. Sheets are annotated in hierarchy order, the
+//! components of a sheet in the order they appear in the file.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use schematic::{kicad_sch, load_hierarchy, Element, Schematic, SheetInstance};
+use {write_schematic, KicadError};
+
+/// how new references are numbered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Numbering {
+    /// the first free number of the prefix
+    Free,
+    /// the first free number after the sheet number times the offset, with
+    /// an offset of 100 the first resistor on the root sheet is `R101`
+    Sheet(i64),
+}
+
+/// annotate the schematic in `filename` and its sub-sheets; the changed
+/// sheet files are written back. Returns the path and the new reference of
+/// every annotated component
+pub fn annotate(
+    filename: &Path,
+    numbering: Numbering,
+) -> Result<Vec<(String, String)>, KicadError> {
+    let (mut sheets, instances) = load_hierarchy(filename)?;
+    let (annotated, mut touched) = annotate_sheets(&mut sheets, &instances, numbering);
+    if kicad_sch::update_symbol_instances(&mut sheets)? && !touched.contains(&0) {
+        touched.push(0)
+    }
+    for i in touched {
+        if let Some(ref name) = sheets[i].filename {
+            write_schematic(&sheets[i], name)?
+        }
+    }
+    Ok(annotated)
+}
+
+// a component in an instance of a sheet
+struct Entry {
+    instance: usize,
+    file: usize,
+    element: usize,
+    path: String,
+    reference: String,
+    name: String,
+    value: String,
+    unit: i64,
+}
+
+// the units of a reference in use on a sheet instance
+struct Package<'a> {
+    instance: usize,
+    name: &'a str,
+    value: &'a str,
+    units: Vec<i64>,
+}
+
+// split a reference like `U12` in prefix and number, `U?` has no number
+fn split_reference(reference: &str) -> (&str, Option<i64>) {
+    if reference.ends_with('?') {
+        return (reference.trim_end_matches('?'), None);
+    }
+    let prefix = reference.trim_end_matches(|c: char| c.is_ascii_digit());
+    (prefix, reference[prefix.len()..].parse().ok())
+}
+
+fn add_unit<'a>(packages: &mut HashMap<String, Package<'a>>, reference: &str, entry: &'a Entry) {
+    packages
+        .entry(reference.to_string())
+        .or_insert_with(|| Package {
+            instance: entry.instance,
+            name: &entry.name,
+            value: &entry.value,
+            units: vec![],
+        })
+        .units
+        .push(entry.unit)
+}
+
+fn entries(sheets: &[Schematic], instances: &[SheetInstance]) -> Vec<Entry> {
+    let mut v = vec![];
+    for (instance, &(ref stamp_path, file)) in instances.iter().enumerate() {
+        for (element, e) in sheets[file].elements.iter().enumerate() {
+            if let Element::Component(ref c) = *e {
+                let path = format!("{}/{}", stamp_path, c.timestamp().unwrap_or(""));
+                v.push(Entry {
+                    instance,
+                    file,
+                    element,
+                    reference: c.reference_for_path(&path)
+                        .unwrap_or_else(|| c.reference.clone()),
+                    path,
+                    name: c.name.clone(),
+                    value: c.get_field_value("Value").unwrap_or_default(),
+                    unit: c.unit(),
+                })
+            }
+        }
+    }
+    v
+}
+
+// give every unannotated component a reference; returns the paths and new
+// references and the indices of the changed sheet files
+fn annotate_sheets(
+    sheets: &mut [Schematic],
+    instances: &[SheetInstance],
+    numbering: Numbering,
+) -> (Vec<(String, String)>, Vec<usize>) {
+    let entries = entries(sheets, instances);
+    let mut used: HashMap<&str, BTreeSet<i64>> = HashMap::new();
+    let mut packages: HashMap<String, Package> = HashMap::new();
+    for entry in &entries {
+        let (prefix, number) = split_reference(&entry.reference);
+        if let Some(number) = number {
+            used.entry(prefix).or_default().insert(number);
+            add_unit(&mut packages, &entry.reference, entry)
+        }
+    }
+    let mut assigned = vec![];
+    for entry in &entries {
+        let (prefix, number) = split_reference(&entry.reference);
+        if number.is_some() {
+            continue;
+        }
+        let package = packages
+            .iter()
+            .filter(|&(r, p)| {
+                split_reference(r).0 == prefix && p.instance == entry.instance &&
+                    p.name == entry.name && p.value == entry.value &&
+                    !p.units.contains(&entry.unit)
+            })
+            .map(|(r, _)| r.clone())
+            .min_by_key(|r| split_reference(r).1);
+        let reference = match package {
+            Some(reference) => reference,
+            None => {
+                let numbers = used.entry(prefix).or_default();
+                let mut n = match numbering {
+                    Numbering::Free => 1,
+                    Numbering::Sheet(offset) => (entry.instance as i64 + 1) * offset + 1,
+                };
+                while numbers.contains(&n) {
+                    n += 1
+                }
+                numbers.insert(n);
+                format!("{}{}", prefix, n)
+            }
+        };
+        add_unit(&mut packages, &reference, entry);
+        assigned.push((entry, reference))
+    }
+    let mut annotated = vec![];
+    let mut touched = vec![];
+    for (entry, reference) in assigned {
+        let shared = instances.iter().filter(|i| i.1 == entry.file).count() > 1;
+        if let Element::Component(ref mut c) = sheets[entry.file].elements[entry.element] {
+            if shared || !c.ar_path.is_empty() {
+                c.set_reference_for_path(&entry.path, &reference)
+            }
+            if !shared || c.reference.ends_with('?') {
+                c.update_reference(reference.clone())
+            }
+        }
+        if !touched.contains(&entry.file) {
+            touched.push(entry.file)
+        }
+        annotated.push((entry.path.clone(), reference))
+    }
+    (annotated, touched)
+}
+
+#[cfg(test)]
+mod test {
+    use schematic;
+    use schematic::annotate::{annotate_sheets, Numbering};
+
+    fn component(name: &str, reference: &str, unit: i64, stamp: &str, value: &str) -> String {
+        format!(
+            "$Comp
+L {name} {reference}
+U {unit} 1 {stamp}
+P 1000 1000
+F 0 \"{reference}\" V 1080 1000 50  0000 C CNN
+F 1 \"{value}\" V 1000 1000 50  0000 C CNN
+\t{unit}    1000 1000
+\t1    0    0    -1
+$EndComp
+",
+            name = name,
+            reference = reference,
+            unit = unit,
+            stamp = stamp,
+            value = value
+        )
+    }
+
+    fn sheets() -> Vec<schematic::Schematic> {
+        let header = "EESchema Schematic File Version 2\nEELAYER 25 0\nEELAYER END\n";
+        let mut root = String::from(header);
+        root.push_str(&component("R", "R?", 1, "5B000001", "1k"));
+        root.push_str(&component("R", "R5", 1, "5B000002", "1k"));
+        root.push_str(&component("R", "R?", 1, "5B000003", "1k"));
+        root.push_str(&component("OPAMP", "U?", 1, "5B000004", "LM358"));
+        root.push_str(&component("OPAMP", "U?", 2, "5B000005", "LM358"));
+        root.push_str(&component("OPAMP", "U?", 1, "5B000006", "LM358"));
+        root.push_str(&component("GND", "#PWR?", 1, "5B000007", "GND"));
+        root.push_str("$EndSCHEMATC\n");
+        let mut sub = String::from(header);
+        sub.push_str(&component("R", "R?", 1, "5B000008", "10k"));
+        sub.push_str("$EndSCHEMATC\n");
+        vec![
+            schematic::parse_str(&root).unwrap(),
+            schematic::parse_str(&sub).unwrap(),
+        ]
+    }
+
+    fn instances() -> Vec<(String, usize)> {
+        vec![
+            (String::new(), 0),
+            ("/5A000001".into(), 1),
+            ("/5A000002".into(), 1),
+        ]
+    }
+
+    fn references(sheet: &schematic::Schematic) -> Vec<String> {
+        sheet.components().iter().map(|c| c.reference.clone()).collect()
+    }
+
+    #[test]
+    fn annotate_free() {
+        let mut sheets = sheets();
+        let (annotated, touched) = annotate_sheets(&mut sheets, &instances(), Numbering::Free);
+        assert_eq!(annotated.len(), 8);
+        assert_eq!(touched, vec![0, 1]);
+        // existing numbers are kept, units of the same part share a reference
+        assert_eq!(
+            references(&sheets[0]),
+            vec!["R1", "R5", "R2", "U1", "U1", "U2", "#PWR1"]
+        );
+        assert_eq!(sheets[0].components()[0].get_field_value("Reference"), Some("R1".into()));
+        // every instance of a sheet gets its own reference
+        let sub = &sheets[1].components()[0];
+        assert_eq!(sub.reference, "R3");
+        assert_eq!(sub.reference_for_path("/5A000001/5B000008"), Some("R3".into()));
+        assert_eq!(sub.reference_for_path("/5A000002/5B000008"), Some("R4".into()));
+        assert_eq!(annotated[7], ("/5A000002/5B000008".to_string(), "R4".to_string()));
+    }
+
+    #[test]
+    fn annotate_per_sheet() {
+        let mut sheets = sheets();
+        annotate_sheets(&mut sheets, &instances(), Numbering::Sheet(100));
+        assert_eq!(
+            references(&sheets[0]),
+            vec!["R101", "R5", "R102", "U101", "U101", "U102", "#PWR101"]
+        );
+        let sub = &sheets[1].components()[0];
+        assert_eq!(sub.reference_for_path("/5A000001/5B000008"), Some("R201".into()));
+        assert_eq!(sub.reference_for_path("/5A000002/5B000008"), Some("R301".into()));
+        // a second run changes nothing
+        let numbering = Numbering::Sheet(100);
+        let (annotated, touched) = annotate_sheets(&mut sheets, &instances(), numbering);
+        assert!(annotated.is_empty());
+        assert!(touched.is_empty());
+    }
+}
@@ -0,0 +1,479 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+//! electrical connectivity of a schematic
+//!
+//! wires, junctions, labels and component pins that touch are merged into
+//! nets. Local labels connect within a sheet, global labels and hidden
+//! power pins connect across the hierarchy and hierarchical labels connect
+//! to the matching sheet pin in the parent sheet. A wire end or a pin on
+//! the middle of a wire connects to it, crossing wires need a junction.
+
+use std::collections::{BTreeMap, HashMap};
+
+use schematic::{load_sheets, sheet_paths, Element, Schematic, TextType, WireType};
+use symbol_lib::{Draw, PinType, Symbol, SymbolLib};
+use util::DisjointSet;
+use KicadError;
+
+/// a pin of a component
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Node {
+    /// reference of the component
+    pub reference: String,
+    /// number of the pin
+    pub pin: String,
+}
+
+/// the nets of a schematic including its sub-sheets
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Connectivity {
+    /// the nets by name, with their ordered pins
+    pub nets: BTreeMap<String, Vec<Node>>,
+    /// the pins marked with a no-connect that are not connected to any
+    /// other pin
+    pub no_connects: Vec<Node>,
+}
+
+impl Connectivity {
+    /// resolve the nets of a schematic, loading its sub-sheets from disk;
+    /// the symbols of the components are looked up in the libraries
+    pub fn new(schematic: &Schematic, libs: &[&SymbolLib]) -> Result<Connectivity, KicadError> {
+        resolve(schematic, libs)
+    }
+
+    /// get the name of the net a pin is on
+    pub fn net(&self, reference: &str, pin: &str) -> Option<&str> {
+        self.nets
+            .iter()
+            .find(|&(_, nodes)| {
+                nodes
+                    .iter()
+                    .any(|n| n.reference == reference && n.pin == pin)
+            })
+            .map(|(name, _)| &name[..])
+    }
+}
+
+impl Schematic {
+    /// resolve the nets of the schematic including its sub-sheets
+    pub fn connectivity(&self, libs: &[&SymbolLib]) -> Result<Connectivity, KicadError> {
+        Connectivity::new(self, libs)
+    }
+}
+
+// an instance of a sheet in the hierarchy
+struct Instance<'a> {
+    schematic: &'a Schematic,
+    // sheet names, like `/` or `/power/`
+    name_path: String,
+    // sheet timestamps, like `` or `/5A1B2C3D`
+    stamp_path: String,
+    depth: usize,
+    // the parent instance and the index of the sheet in it
+    parent: Option<(usize, usize)>,
+}
+
+#[derive(Debug)]
+enum Kind {
+    // a wire with its second point
+    Wire(i64, i64),
+    Junction,
+    NoConnect,
+    Label(String),
+    Global(String),
+    Hierarchical(String),
+    // a pin of a sheet symbol, with the index of the sheet
+    SheetPin(usize, String),
+    // a component pin; power symbols don't have a node
+    Pin(Option<Node>),
+}
+
+struct Item {
+    instance: usize,
+    x: i64,
+    y: i64,
+    kind: Kind,
+}
+
+impl Item {
+    fn points(&self) -> Vec<(i64, i64)> {
+        match self.kind {
+            Kind::Wire(x2, y2) => vec![(self.x, self.y), (x2, y2)],
+            _ => vec![(self.x, self.y)],
+        }
+    }
+}
+
+fn resolve(schematic: &Schematic, libs: &[&SymbolLib]) -> Result<Connectivity, KicadError> {
+    let (sheets, sheet_instances) = load_sheets(schematic)?;
+    let paths = sheet_paths(schematic, &sheets, &sheet_instances);
+    let instances: Vec<Instance> = sheet_instances
+        .into_iter()
+        .zip(paths)
+        .map(|((stamp_path, file), path)| Instance {
+            schematic: if file == 0 { schematic } else { &sheets[file - 1] },
+            name_path: path.name_path,
+            depth: stamp_path.matches('/').count(),
+            stamp_path,
+            parent: path.parent,
+        })
+        .collect();
+    let mut items = vec![];
+    for (i, instance) in instances.iter().enumerate() {
+        collect_items(instance, i, libs, &mut items)?;
+    }
+    let mut sets = DisjointSet::new(items.len());
+    connect_sheets(&instances, &items, &mut sets);
+    connect_by_name(&instances, &items, &mut sets);
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..items.len() {
+        groups.entry(sets.find(i)).or_default().push(i)
+    }
+    let mut connectivity = Connectivity::default();
+    for group in groups.values() {
+        let mut nodes: Vec<Node> = group
+            .iter()
+            .filter_map(|&i| match items[i].kind {
+                Kind::Pin(Some(ref node)) => Some(node.clone()),
+                _ => None,
+            })
+            .collect();
+        nodes.sort();
+        nodes.dedup();
+        if nodes.is_empty() {
+            continue;
+        }
+        let no_connect = group
+            .iter()
+            .any(|&i| matches!(items[i].kind, Kind::NoConnect));
+        if no_connect && nodes.len() == 1 {
+            connectivity.no_connects.append(&mut nodes);
+            continue;
+        }
+        let name = net_name(&instances, &items, group, &nodes[0]);
+        connectivity.nets.entry(name).or_default().append(&mut nodes);
+    }
+    for nodes in connectivity.nets.values_mut() {
+        nodes.sort();
+        nodes.dedup();
+    }
+    connectivity.no_connects.sort();
+    Ok(connectivity)
+}
+
+fn collect_items(
+    instance: &Instance,
+    i: usize,
+    libs: &[&SymbolLib],
+    items: &mut Vec<Item>,
+) -> Result<(), KicadError> {
+    let item = |x: i64, y: i64, kind: Kind| Item {
+        instance: i,
+        x,
+        y,
+        kind,
+    };
+    let schematic = instance.schematic;
+    let stamp_path = &instance.stamp_path;
+    for element in &schematic.elements {
+        match *element {
+            Element::Wire(ref w) => if let WireType::Wire = w.type_ {
+                items.push(item(w.x1, w.y1, Kind::Wire(w.x2, w.y2)))
+            },
+            Element::Connection(ref c) => items.push(item(c.x, c.y, Kind::Junction)),
+            Element::NoConnect(ref n) => items.push(item(n.x, n.y, Kind::NoConnect)),
+            Element::Text(ref t) => {
+                let name = t.text.clone();
+                let kind = match t.t {
+                    TextType::Note => continue,
+                    TextType::Label => Kind::Label(name),
+                    TextType::Global => Kind::Global(name),
+                    TextType::Hierarchical => Kind::Hierarchical(name),
+                };
+                items.push(item(t.x, t.y, kind))
+            }
+            Element::Component(ref c) => {
+                let symbol = match find_symbol(libs, &c.name) {
+                    Some(symbol) => symbol,
+                    None => {
+                        return Err(format!(
+                            "symbol {} for component {} not found",
+                            c.name, c.reference
+                        ).into())
+                    }
+                };
+                let reference = c.timestamp()
+                    .and_then(|t| c.reference_for_path(&format!("{}/{}", stamp_path, t)))
+                    .unwrap_or_else(|| c.reference.clone());
+                let (unit, convert) = (c.unit(), c.convert());
+                for draw in &symbol.draw {
+                    let pin = match *draw {
+                        Draw::Pin(ref pin) => pin,
+                        _ => continue,
+                    };
+                    if (pin.unit != 0 && pin.unit != unit) ||
+                        (pin.convert != 0 && pin.convert != convert)
+                    {
+                        continue;
+                    }
+                    let (dx, dy) = c.rotation.transform(pin.x, pin.y);
+                    let (x, y) = (c.x + dx, c.y + dy);
+                    let node = if reference.starts_with('#') {
+                        None
+                    } else {
+                        Some(Node {
+                            reference: reference.clone(),
+                            pin: pin.number.clone(),
+                        })
+                    };
+                    items.push(item(x, y, Kind::Pin(node)));
+                    // hidden power inputs connect to the net with their name
+                    if pin.pin_type == PinType::PowerInput && !pin.pin_visible {
+                        items.push(item(x, y, Kind::Global(pin.name.clone())))
+                    }
+                }
+            }
+            Element::Other(_) => (),
+        }
+    }
+    for (j, sheet) in schematic.sheets.iter().enumerate() {
+        for label in &sheet.labels {
+            items.push(item(label.x, label.y, Kind::SheetPin(j, label.name.clone())))
+        }
+    }
+    Ok(())
+}
+
+fn find_symbol<'a>(libs: &[&'a SymbolLib], name: &str) -> Option<&'a Symbol> {
+    // cache libraries replace the library prefix separator by an underscore
+    let cache_name = name.replace(':', "_");
+    let short_name = match name.rfind(':') {
+        Some(i) => &name[i + 1..],
+        None => name,
+    };
+    let names = [name, &cache_name[..], short_name];
+    libs.iter()
+        .filter_map(|lib| {
+            lib.find(|s| names.iter().any(|n| s.name == *n || s.aliases.iter().any(|a| a == n)))
+        })
+        .next()
+}
+
+fn on_segment(p: (i64, i64), a: (i64, i64), b: (i64, i64)) -> bool {
+    let cross = (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+    cross == 0 && p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1)
+        && p.1 <= a.1.max(b.1)
+}
+
+// connect the items within a sheet by position
+fn connect_sheets(instances: &[Instance], items: &[Item], sets: &mut DisjointSet) {
+    for instance in 0..instances.len() {
+        let indices: Vec<usize> = (0..items.len())
+            .filter(|&i| items[i].instance == instance)
+            .collect();
+        let mut positions: HashMap<(i64, i64), usize> = HashMap::new();
+        for &i in &indices {
+            for p in items[i].points() {
+                let j = *positions.entry(p).or_insert(i);
+                sets.join(i, j)
+            }
+        }
+        for &w in &indices {
+            let (a, b) = match items[w].kind {
+                Kind::Wire(x2, y2) => ((items[w].x, items[w].y), (x2, y2)),
+                _ => continue,
+            };
+            for &i in &indices {
+                if i != w && items[i].points().into_iter().any(|p| on_segment(p, a, b)) {
+                    sets.join(i, w)
+                }
+            }
+        }
+    }
+}
+
+// connect labels, sheet pins and units of the same component by name
+fn connect_by_name(instances: &[Instance], items: &[Item], sets: &mut DisjointSet) {
+    let mut locals: HashMap<(usize, &str), usize> = HashMap::new();
+    let mut globals: HashMap<&str, usize> = HashMap::new();
+    let mut sheet_pins: HashMap<(usize, usize, &str), usize> = HashMap::new();
+    let mut nodes: HashMap<&Node, usize> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        let j = match item.kind {
+            Kind::Label(ref name) => *locals.entry((item.instance, name)).or_insert(i),
+            Kind::Global(ref name) => *globals.entry(name).or_insert(i),
+            Kind::SheetPin(sheet, ref name) => {
+                *sheet_pins.entry((item.instance, sheet, name)).or_insert(i)
+            }
+            Kind::Pin(Some(ref node)) => *nodes.entry(node).or_insert(i),
+            _ => continue,
+        };
+        sets.join(i, j)
+    }
+    for (i, item) in items.iter().enumerate() {
+        if let Kind::Hierarchical(ref name) = item.kind {
+            let (parent, sheet) = match instances[item.instance].parent {
+                Some(parent) => parent,
+                None => continue,
+            };
+            if let Some(&j) = sheet_pins.get(&(parent, sheet, &name[..])) {
+                sets.join(i, j)
+            }
+        }
+    }
+}
+
+// global names win, then local and hierarchical labels closest to the
+// root; unnamed nets are named after their first pin
+fn net_name(instances: &[Instance], items: &[Item], group: &[usize], first: &Node) -> String {
+    group
+        .iter()
+        .filter_map(|&i| {
+            let instance = &instances[items[i].instance];
+            let local = |name: &str| format!("{}{}", instance.name_path, name);
+            match items[i].kind {
+                Kind::Global(ref name) => Some((0, 0, 0, name.clone())),
+                Kind::Label(ref name) => Some((1, instance.depth, 0, local(name))),
+                Kind::Hierarchical(ref name) => Some((1, instance.depth, 1, local(name))),
+                _ => None,
+            }
+        })
+        .min()
+        .map(|(_, _, _, name)| name)
+        .unwrap_or_else(|| format!("Net-({}-Pad{})", first.reference, first.pin))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::Node;
+    use schematic;
+    use symbol_lib;
+    use test_util;
+
+    const LIB: &str = r##"EESchema-LIBRARY Version 2.3
+#encoding utf-8
+#
+# R
+#
+DEF R R 0 0 N Y 1 F N
+F0 "R" 80 0 50 V V C CNN
+F1 "R" 0 0 50 V V C CNN
+DRAW
+X ~ 1 0 150 50 D 50 50 1 1 P
+X ~ 2 0 -150 50 U 50 50 1 1 P
+ENDDRAW
+ENDDEF
+#
+# GND
+#
+DEF GND #PWR 0 0 Y Y 1 F P
+F0 "#PWR" 0 -250 50 H I C CNN
+F1 "GND" 0 -150 50 H V C CNN
+DRAW
+X GND 1 0 0 0 D 50 50 1 1 W N
+ENDDRAW
+ENDDEF
+#
+#End Library
+"##;
+
+    const ROOT: &str = r##"EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R1
+U 1 1 5B000001
+P 1000 1000
+F 0 "R1" V 1080 1000 50  0000 C CNN
+	1    1000 1000
+	1    0    0    -1  
+$EndComp
+$Comp
+L GND #PWR01
+U 1 1 5B000003
+P 1000 1300
+F 0 "#PWR01" H 1000 1050 50  0001 C CNN
+	1    1000 1300
+	1    0    0    -1  
+$EndComp
+$Comp
+L R R3
+U 1 1 5B000004
+P 1500 550
+F 0 "R3" V 1580 550 50  0000 C CNN
+	1    1500 550
+	1    0    0    -1  
+$EndComp
+$Sheet
+S 2000 700 500 500
+U 5A000001
+F0 "Sub" 60
+F1 "sub.sch" 60
+F2 "IN" I L 2000 850 60 
+$EndSheet
+Wire Wire Line
+	1000 1150 1000 1300
+Wire Wire Line
+	1000 850 2000 850
+Wire Wire Line
+	1500 700 1500 1000
+NoConn ~ 1500 400
+Text Label 1200 850 0    60   ~ 0
+SIG
+$EndSCHEMATC
+"##;
+
+    const SUB: &str = r##"EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R?
+U 1 1 5B000002
+P 800 650
+AR Path="/5A000001/5B000002" Ref="R2"  Part="1" 
+F 0 "R?" V 880 650 50  0000 C CNN
+	1    800 650
+	1    0    0    -1  
+$EndComp
+$Comp
+L GND #PWR02
+U 1 1 5B000005
+P 800 800
+F 0 "#PWR02" H 800 550 50  0001 C CNN
+	1    800 800
+	1    0    0    -1  
+$EndComp
+Wire Wire Line
+	500 500 800 500
+Text HLabel 500 500 0    60   Input ~ 0
+IN
+$EndSCHEMATC
+"##;
+
+    fn node(reference: &str, pin: &str) -> Node {
+        Node {
+            reference: reference.into(),
+            pin: pin.into(),
+        }
+    }
+
+    #[test]
+    fn schematic_nets() {
+        let lib = symbol_lib::parse_str(LIB).unwrap();
+        let dir = test_util::temp_dir("schematic_nets");
+        fs::write(dir.join("root.sch"), ROOT).unwrap();
+        fs::write(dir.join("sub.sch"), SUB).unwrap();
+        let root = schematic::parse_file(&dir.join("root.sch")).unwrap();
+        let c = root.connectivity(&[&lib]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(c.nets.len(), 3);
+        assert_eq!(c.nets["/SIG"], vec![node("R1", "1"), node("R2", "1")]);
+        assert_eq!(c.nets["GND"], vec![node("R1", "2"), node("R2", "2")]);
+        // crossing wires without a junction don't connect
+        assert_eq!(c.nets["Net-(R3-Pad2)"], vec![node("R3", "2")]);
+        assert_eq!(c.no_connects, vec![node("R3", "1")]);
+        assert_eq!(c.net("R2", "1"), Some("/SIG"));
+    }
+}
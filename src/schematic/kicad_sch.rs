@@ -0,0 +1,1102 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+// extension: .kicad_sch
+// format: new-style
+
+//! Kicad 6 and later schematics
+//!
+//! the s-expression schematic is mapped on the same `Schematic` as the
+//! legacy format, with the coordinates converted from mm to mils. What has
+//! no place in the legacy model, like the embedded symbols and the uuids of
+//! the pins, is kept as is and written back. The elements also keep the
+//! expression they were read from, what didn't change, like a position
+//! that is off the mil grid, is written back from it. The references of the
+//! symbol instances become `AR` lines so that annotation works the same for
+//! both formats.
+
+use std::path::PathBuf;
+
+use symbolic_expressions;
+use symbolic_expressions::{Sexp, SexpError};
+use formatter::KicadFormatter;
+use sexpr::{at, at_angle, child, effects_sexp, elements, flag, legacy_field_name, maybe_number,
+            name_of, number, parse_effects, property_id, property_name, quoted, requote,
+            requote_at, string, to_mils, to_mm, write_back, xy, Effects};
+
+use schematic::{Component, ComponentField, ComponentRotation, Connection, Description, Element,
+                LabelForm, LabelSide, NoConnect, Orientation, Schematic, Sheet, SheetLabel, Text,
+                TextType, Wire, WireType};
+use KicadError;
+
+// the file format version that is written when the schematic has none
+const VERSION: &str = "20211123";
+
+// paper sizes in mils
+const PAPER: &[(&str, i64, i64)] = &[
+    ("A5", 8268, 5846),
+    ("A4", 11693, 8268),
+    ("A3", 16535, 11693),
+    ("A2", 23386, 16535),
+    ("A1", 33110, 23386),
+    ("A0", 46811, 33110),
+    ("A", 11000, 8500),
+    ("B", 17000, 11000),
+    ("C", 22000, 17000),
+    ("D", 34000, 22000),
+    ("E", 44000, 34000),
+    ("USLetter", 11000, 8500),
+    ("USLegal", 14000, 8500),
+    ("USLedger", 17000, 11000),
+];
+
+/// is `s` a schematic in the s-expression format
+pub fn is_kicad_sch(s: &str) -> bool {
+    s.trim_start().starts_with("(kicad_sch")
+}
+
+/// parse a &str in the s-expression format to a Kicad schematic,
+/// optionally setting the filename
+pub fn parse(filename: Option<PathBuf>, s: &str) -> Result<Schematic, KicadError> {
+    let t = symbolic_expressions::parser::parse_str(s)?;
+    let mut sch = parse_schematic(&t)?;
+    sch.filename = filename;
+    Ok(sch)
+}
+
+/// convert a `Schematic` to a formatted s-expression String
+pub fn to_string(schematic: &Schematic, indent_level: i64) -> Result<String, KicadError> {
+    let formatter = KicadFormatter::new(indent_level);
+    let mut s =
+        symbolic_expressions::ser::to_string_with_formatter(&schematic_sexp(schematic), formatter)?;
+    s.push('\n');
+    Ok(s)
+}
+
+fn uuid(v: &mut Sexp, uuid: &str) {
+    if !uuid.is_empty() {
+        v.push(("uuid", &uuid.to_string()))
+    }
+}
+
+// the rotation matrix of a symbol from its angle and mirroring
+fn rotation(angle: i64, mirror: Option<&str>) -> ComponentRotation {
+    let (a, b, c, d) = match angle.rem_euclid(360) {
+        90 => (0, -1, -1, 0),
+        180 => (-1, 0, 0, 1),
+        270 => (0, 1, 1, 0),
+        _ => (1, 0, 0, -1),
+    };
+    let (a, b, c, d) = match mirror {
+        Some("x") => (a, b, -c, -d),
+        Some("y") => (-a, -b, c, d),
+        _ => (a, b, c, d),
+    };
+    ComponentRotation { a, b, c, d }
+}
+
+fn angle_and_mirror(r: &ComponentRotation) -> (i64, Option<&'static str>) {
+    for angle in &[0, 90, 180, 270] {
+        for mirror in &[None, Some("x"), Some("y")] {
+            if rotation(*angle, *mirror) == *r {
+                return (*angle, *mirror);
+            }
+        }
+    }
+    (0, None)
+}
+
+// the `(path ..)` elements in `(instances (project .. (path ..)))` and in
+// `(symbol_instances (path ..))`
+fn instance_paths(s: &Sexp) -> Vec<&Sexp> {
+    let mut v = vec![];
+    if let Sexp::List(ref l) = *s {
+        for e in l {
+            match name_of(e) {
+                "path" => v.push(e),
+                "project" => v.append(&mut instance_paths(e)),
+                _ => (),
+            }
+        }
+    }
+    v
+}
+
+// replace the references of the instance paths
+fn update_instances<F>(s: &Sexp, reference: &F) -> Sexp
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match *s {
+        Sexp::List(ref l) => {
+            let path = if name_of(s) == "path" {
+                string(s, 1).ok().and_then(|p| reference(&p))
+            } else {
+                None
+            };
+            let v: Vec<Sexp> = l.iter()
+                .map(|e| match path {
+                    Some(ref r) if name_of(e) == "reference" => ("reference", quoted(r)).into(),
+                    _ => update_instances(e, reference),
+                })
+                .collect();
+            v.into()
+        }
+        _ => s.clone(),
+    }
+}
+
+// the legacy path of a symbol instance; the instance paths of Kicad 7 start
+// with the uuid of the root sheet and don't end with the symbol
+fn legacy_path(path: &str, uuid: &str) -> String {
+    let sheets = match path.get(1..).and_then(|p| p.find('/')) {
+        Some(i) => &path[i + 1..],
+        None => "",
+    };
+    format!("{}/{}", sheets, uuid)
+}
+
+fn ar_line(path: &str, reference: &str, unit: i64) -> String {
+    format!("AR Path=\"{}\" Ref=\"{}\"  Part=\"{}\" ", path, reference, unit)
+}
+
+fn parse_paper(s: &Sexp, d: &mut Description) -> Result<(), SexpError> {
+    d.size = string(s, 1)?;
+    if d.size == "User" {
+        d.dimx = to_mils(number(s, 2)?);
+        d.dimy = to_mils(number(s, 3)?);
+    } else if let Some(&(_, x, y)) = PAPER.iter().find(|p| p.0 == d.size) {
+        d.dimx = x;
+        d.dimy = y;
+    }
+    if flag(s, "portrait") {
+        ::std::mem::swap(&mut d.dimx, &mut d.dimy)
+    }
+    Ok(())
+}
+
+fn parse_title_block(s: &Sexp, d: &mut Description) -> Result<(), SexpError> {
+    for e in elements(s, "title_block")? {
+        match name_of(e) {
+            "title" => d.title = string(e, 1)?,
+            "date" => d.date = string(e, 1)?,
+            "rev" => d.rev = string(e, 1)?,
+            "company" => d.comp = string(e, 1)?,
+            "comment" => {
+                let comment = string(e, 2)?;
+                match number(e, 1)? as i64 {
+                    1 => d.comment1 = comment,
+                    2 => d.comment2 = comment,
+                    3 => d.comment3 = comment,
+                    4 => d.comment4 = comment,
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn parse_field(s: &Sexp, index: i64) -> Result<ComponentField, SexpError> {
+    let name = string(s, 1)?;
+    let value = string(s, 2)?;
+    let i = property_id(s, &name, index)?;
+    let (x, y, angle) = at(s)?;
+    let effects = parse_effects(s)?;
+    let name = legacy_field_name(i, name);
+    let mut f = ComponentField::new_from(i, name, value, x as f64, y as f64);
+    f.size = effects.size;
+    f.visible = !effects.hide;
+    f.italic = effects.italic;
+    f.bold = effects.bold;
+    if angle == 90 || angle == 270 {
+        f.orientation = Orientation::Vertical
+    }
+    let (hjustify, vjustify) = effects.justification();
+    f.hjustify = hjustify;
+    f.vjustify = vjustify;
+    Ok(f)
+}
+
+fn field_sexp(f: &ComponentField) -> Sexp {
+    let mut v = Sexp::start("property");
+    v.push(quoted(property_name(f.i, &f.name)));
+    v.push(quoted(&f.value));
+    v.push(("id", &f.i));
+    let angle = match f.orientation {
+        Orientation::Horizontal => 0,
+        Orientation::Vertical => 90,
+    };
+    v.push(at_angle(f.x as i64, f.y as i64, angle));
+    let mut effects = Effects {
+        size: f.size,
+        italic: f.italic,
+        bold: f.bold,
+        hide: !f.visible,
+        ..Effects::default()
+    };
+    effects.set_justification(&f.hjustify, &f.vjustify);
+    v.push(effects_sexp(&effects));
+    v
+}
+
+fn parse_symbol(s: &Sexp) -> Result<Component, SexpError> {
+    let mut c = Component::default();
+    let (mut unit, mut convert, mut uuid) = (1, 1, String::new());
+    let (mut angle, mut mirror) = (0, None);
+    let mut index = 0;
+    for e in elements(s, "symbol")? {
+        match name_of(e) {
+            "lib_id" => c.name = string(e, 1)?,
+            "at" => {
+                let (x, y, a) = at(s)?;
+                c.x = x;
+                c.y = y;
+                angle = a
+            }
+            "mirror" => mirror = Some(string(e, 1)?),
+            "unit" => unit = number(e, 1)? as i64,
+            "convert" => convert = number(e, 1)? as i64,
+            "uuid" => uuid = string(e, 1)?,
+            "property" => {
+                let f = parse_field(e, index)?;
+                if f.i == 0 {
+                    c.reference = f.value.clone()
+                }
+                c.fields.push(f);
+                index += 1
+            }
+            _ => c.other.push(e.clone()),
+        }
+    }
+    for instances in c.other.iter().filter(|e| name_of(e) == "instances") {
+        for path in instance_paths(instances) {
+            if let Some(reference) = child(path, "reference") {
+                let unit = child(path, "unit").and_then(|u| maybe_number(u, 1)).unwrap_or(1.0);
+                let path = legacy_path(&string(path, 1)?, &uuid);
+                c.ar_path.push(ar_line(&path, &string(reference, 1)?, unit as i64))
+            }
+        }
+    }
+    c.u = format!("U {} {} {}", unit, convert, uuid);
+    c.rotation = rotation(angle, mirror.as_ref().map(|m| &m[..]));
+    c.sexp = Some(s.clone());
+    Ok(c)
+}
+
+// the elements kept as is that Kicad writes after the properties
+fn is_tail(s: &Sexp) -> bool {
+    matches!(name_of(s), "property" | "pin" | "instances")
+}
+
+fn symbol_sexp(c: &Component) -> Sexp {
+    let mut v = Sexp::start("symbol");
+    v.push(("lib_id", quoted(&c.name)));
+    let (angle, mirror) = angle_and_mirror(&c.rotation);
+    v.push(at_angle(c.x, c.y, angle));
+    if let Some(mirror) = mirror {
+        v.push(("mirror", &mirror))
+    }
+    v.push(("unit", &c.unit()));
+    if c.convert() != 1 {
+        v.push(("convert", &c.convert()))
+    }
+    for e in c.other.iter().filter(|e| !is_tail(e)) {
+        v.push(requote(e))
+    }
+    uuid(&mut v, c.timestamp().unwrap_or(""));
+    for f in &c.fields {
+        v.push(field_sexp(f))
+    }
+    let uuid = c.timestamp().unwrap_or("");
+    let reference = |path: &str| c.reference_for_path(&legacy_path(path, uuid));
+    for e in c.other.iter().filter(|e| is_tail(e)) {
+        match name_of(e) {
+            "pin" => v.push(requote_at(e, &[1])),
+            "instances" => v.push(requote(&update_instances(e, &reference))),
+            _ => v.push(requote(e)),
+        }
+    }
+    v
+}
+
+fn parse_wire(s: &Sexp) -> Result<Wire, SexpError> {
+    let type_ = match name_of(s) {
+        "bus" => WireType::Bus,
+        "polyline" => WireType::Notes,
+        _ => WireType::Wire,
+    };
+    let pts = match child(s, "pts") {
+        Some(pts) => elements(pts, "pts")?,
+        None => vec![],
+    };
+    if pts.len() != 2 {
+        return Err(format!("expecting 2 points in {}", s).into());
+    }
+    Ok(Wire {
+        type_,
+        x1: to_mils(number(pts[0], 1)?),
+        y1: to_mils(number(pts[0], 2)?),
+        x2: to_mils(number(pts[1], 1)?),
+        y2: to_mils(number(pts[1], 2)?),
+        sexp: Some(s.clone()),
+    })
+}
+
+fn wire_sexp(w: &Wire) -> Sexp {
+    let (name, type_) = match w.type_ {
+        WireType::Wire => ("wire", "default"),
+        WireType::Bus => ("bus", "default"),
+        WireType::Notes => ("polyline", "dash"),
+    };
+    let mut v = Sexp::start(name);
+    let mut pts = Sexp::start("pts");
+    pts.push(xy("xy", w.x1, w.y1));
+    pts.push(xy("xy", w.x2, w.y2));
+    v.push(pts);
+    let mut stroke = Sexp::start("stroke");
+    stroke.push(("width", &0));
+    stroke.push(("type", &type_));
+    v.push(stroke);
+    v
+}
+
+fn parse_junction(s: &Sexp) -> Result<Connection, SexpError> {
+    let (x, y, _) = at(s)?;
+    Ok(Connection {
+        x,
+        y,
+        sexp: Some(s.clone()),
+    })
+}
+
+fn junction_sexp(c: &Connection) -> Sexp {
+    let mut v = Sexp::start("junction");
+    v.push(xy("at", c.x, c.y));
+    v.push(("diameter", &0));
+    let mut color = Sexp::start("color");
+    for _ in 0..4 {
+        color.push(0)
+    }
+    v.push(color);
+    v
+}
+
+fn parse_no_connect(s: &Sexp) -> Result<NoConnect, SexpError> {
+    let (x, y, _) = at(s)?;
+    Ok(NoConnect {
+        x,
+        y,
+        sexp: Some(s.clone()),
+    })
+}
+
+fn no_connect_sexp(n: &NoConnect) -> Sexp {
+    let mut v = Sexp::start("no_connect");
+    v.push(xy("at", n.x, n.y));
+    v
+}
+
+fn label_shape(shape: &str) -> &str {
+    match shape {
+        "input" => "Input",
+        "output" => "Output",
+        "bidirectional" => "BiDi",
+        "tri_state" => "3State",
+        "passive" => "UnSpc",
+        x => x,
+    }
+}
+
+fn shape_name(shape: &str) -> &str {
+    match shape {
+        "Input" => "input",
+        "Output" => "output",
+        "BiDi" => "bidirectional",
+        "3State" => "tri_state",
+        _ => "passive",
+    }
+}
+
+fn parse_text(s: &Sexp) -> Result<Text, SexpError> {
+    let t = match name_of(s) {
+        "label" => TextType::Label,
+        "global_label" => TextType::Global,
+        "hierarchical_label" => TextType::Hierarchical,
+        _ => TextType::Note,
+    };
+    let (x, y, angle) = at(s)?;
+    let effects = parse_effects(s)?;
+    let shape = match child(s, "shape") {
+        Some(shape) => Some(label_shape(&string(shape, 1)?).to_string()),
+        None if !t.is_local() => Some("UnSpc".to_string()),
+        None => None,
+    };
+    Ok(Text {
+        t,
+        x,
+        y,
+        orientation: angle / 90,
+        size: effects.size,
+        shape,
+        italic: effects.italic,
+        thickness: effects.thickness,
+        text: string(s, 1)?,
+        sexp: Some(s.clone()),
+    })
+}
+
+fn text_sexp(t: &Text) -> Sexp {
+    let name = match t.t {
+        TextType::Note => "text",
+        TextType::Label => "label",
+        TextType::Global => "global_label",
+        TextType::Hierarchical => "hierarchical_label",
+    };
+    let mut v = Sexp::start(name);
+    v.push(quoted(&t.text));
+    if let Some(ref shape) = t.shape {
+        v.push(("shape", &shape_name(shape)))
+    }
+    v.push(at_angle(t.x, t.y, t.orientation * 90));
+    let mut justify = vec![];
+    if t.orientation < 2 {
+        justify.push("left".into())
+    } else {
+        justify.push("right".into())
+    }
+    if t.t.is_local() {
+        justify.push("bottom".into())
+    }
+    v.push(effects_sexp(&Effects {
+        size: t.size,
+        thickness: t.thickness,
+        italic: t.italic,
+        bold: false,
+        justify,
+        hide: false,
+    }));
+    v
+}
+
+fn parse_sheet_pin(s: &Sexp) -> Result<SheetLabel, SexpError> {
+    let (x, y, angle) = at(s)?;
+    let form = match &string(s, 2)?[..] {
+        "input" => LabelForm::Input,
+        "output" => LabelForm::Output,
+        "bidirectional" => LabelForm::BiDi,
+        "tri_state" => LabelForm::TriState,
+        _ => LabelForm::Unspecified,
+    };
+    let side = match angle {
+        0 => LabelSide::Right,
+        90 => LabelSide::Top,
+        270 => LabelSide::Bottom,
+        _ => LabelSide::Left,
+    };
+    Ok(SheetLabel {
+        name: string(s, 1)?,
+        form,
+        side,
+        x,
+        y,
+        size: parse_effects(s)?.size,
+    })
+}
+
+fn sheet_pin_sexp(l: &SheetLabel) -> Sexp {
+    let mut v = Sexp::start("pin");
+    v.push(quoted(&l.name));
+    v.push(match l.form {
+        LabelForm::Input => "input",
+        LabelForm::Output => "output",
+        LabelForm::BiDi => "bidirectional",
+        LabelForm::TriState => "tri_state",
+        LabelForm::Unspecified => "passive",
+    });
+    let (angle, justify) = match l.side {
+        LabelSide::Left => (180, "left"),
+        LabelSide::Right => (0, "right"),
+        LabelSide::Top => (90, "right"),
+        LabelSide::Bottom => (270, "left"),
+    };
+    v.push(at_angle(l.x, l.y, angle));
+    v.push(effects_sexp(&Effects {
+        size: l.size,
+        justify: vec![justify.into()],
+        ..Effects::default()
+    }));
+    v
+}
+
+fn parse_sheet(s: &Sexp) -> Result<Sheet, SexpError> {
+    let mut sheet = Sheet::default();
+    for e in elements(s, "sheet")? {
+        match name_of(e) {
+            "at" => {
+                sheet.x = to_mils(number(e, 1)?);
+                sheet.y = to_mils(number(e, 2)?);
+            }
+            "size" => {
+                sheet.dimx = to_mils(number(e, 1)?);
+                sheet.dimy = to_mils(number(e, 2)?);
+            }
+            "uuid" => sheet.unique = string(e, 1)?,
+            "property" => match &string(e, 1)?[..] {
+                "Sheet name" | "Sheetname" => {
+                    sheet.name = string(e, 2)?;
+                    sheet.name_size = parse_effects(e)?.size
+                }
+                "Sheet file" | "Sheetfile" => {
+                    sheet.filename = string(e, 2)?;
+                    sheet.filename_size = parse_effects(e)?.size
+                }
+                _ => sheet.other.push(e.clone()),
+            },
+            "pin" => sheet.labels.push(parse_sheet_pin(e)?),
+            _ => sheet.other.push(e.clone()),
+        }
+    }
+    sheet.sexp = Some(s.clone());
+    Ok(sheet)
+}
+
+fn sheet_property(name: &str, value: &str, id: i64, y: i64, sheet: &Sheet) -> Sexp {
+    let (size, vjustify) = if id == 0 {
+        (sheet.name_size, "bottom")
+    } else {
+        (sheet.filename_size, "top")
+    };
+    let mut v = Sexp::start("property");
+    v.push(quoted(name));
+    v.push(quoted(value));
+    v.push(("id", &id));
+    v.push(at_angle(sheet.x, y, 0));
+    v.push(effects_sexp(&Effects {
+        size,
+        justify: vec!["left".into(), vjustify.into()],
+        ..Effects::default()
+    }));
+    v
+}
+
+fn sheet_sexp(sheet: &Sheet) -> Sexp {
+    let mut v = Sexp::start("sheet");
+    v.push(xy("at", sheet.x, sheet.y));
+    v.push(xy("size", sheet.dimx, sheet.dimy));
+    for e in sheet.other.iter().filter(|e| !is_tail(e)) {
+        v.push(requote(e))
+    }
+    uuid(&mut v, &sheet.unique);
+    v.push(sheet_property("Sheet name", &sheet.name, 0, sheet.y, sheet));
+    let y = sheet.y + sheet.dimy;
+    v.push(sheet_property("Sheet file", &sheet.filename, 1, y, sheet));
+    for e in sheet.other.iter().filter(|e| is_tail(e)) {
+        v.push(requote(e))
+    }
+    for l in &sheet.labels {
+        v.push(sheet_pin_sexp(l))
+    }
+    v
+}
+
+// the symbols, wires, junctions, no-connects and texts
+fn parse_element(s: &Sexp) -> Result<Option<Element>, SexpError> {
+    let element = match name_of(s) {
+        "symbol" => Element::Component(parse_symbol(s)?),
+        "wire" | "bus" => Element::Wire(parse_wire(s)?),
+        // only the lines with two points fit a legacy note
+        "polyline" => match parse_wire(s) {
+            Ok(w) => Element::Wire(w),
+            Err(_) => return Ok(None),
+        },
+        "junction" => Element::Connection(parse_junction(s)?),
+        "no_connect" => Element::NoConnect(parse_no_connect(s)?),
+        "label" | "global_label" | "hierarchical_label" | "text" => {
+            Element::Text(parse_text(s)?)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(element))
+}
+
+fn parse_schematic(s: &Sexp) -> Result<Schematic, SexpError> {
+    let mut sch = Schematic::default();
+    sch.description.title.clear();
+    sch.description.date.clear();
+    // the elements Kicad writes between the embedded symbols and the sheets
+    // that aren't parsed are kept in between the parsed ones
+    let (mut in_body, mut after_body) = (false, false);
+    for e in elements(s, "kicad_sch")? {
+        match name_of(e) {
+            "paper" => parse_paper(e, &mut sch.description)?,
+            "title_block" => parse_title_block(e, &mut sch.description)?,
+            "lib_symbols" => {
+                sch.lib_symbols = elements(e, "lib_symbols")?.into_iter().cloned().collect();
+                in_body = true
+            }
+            "sheet" => {
+                sch.sheets.push(parse_sheet(e)?);
+                after_body = true
+            }
+            _ => match parse_element(e)? {
+                Some(element) => {
+                    sch.elements.push(element);
+                    in_body = true
+                }
+                None if in_body && !after_body && !is_tail_of_schematic(e) => {
+                    sch.elements.push(Element::Other(e.to_string()))
+                }
+                None => {
+                    after_body |= in_body;
+                    sch.other.push(e.clone())
+                }
+            },
+        }
+    }
+    let lines = symbol_instances(&sch)?;
+    add_ar_lines(&mut sch, &lines);
+    Ok(sch)
+}
+
+// the elements Kicad writes after the sheets
+fn is_tail_of_schematic(s: &Sexp) -> bool {
+    matches!(name_of(s), "sheet_instances" | "symbol_instances")
+}
+
+// the path, reference and unit of the instances in `(symbol_instances ..)`;
+// Kicad 6 keeps the references of all instances in the root sheet
+fn symbol_instances(sch: &Schematic) -> Result<Vec<(String, String, i64)>, SexpError> {
+    let mut lines = vec![];
+    for instances in sch.other.iter().filter(|e| name_of(e) == "symbol_instances") {
+        for path in instance_paths(instances) {
+            let p = string(path, 1)?;
+            if let Some(reference) = child(path, "reference") {
+                let unit = child(path, "unit").and_then(|u| maybe_number(u, 1)).unwrap_or(1.0);
+                lines.push((p, string(reference, 1)?, unit as i64))
+            }
+        }
+    }
+    Ok(lines)
+}
+
+fn add_ar_lines(sch: &mut Schematic, lines: &[(String, String, i64)]) {
+    for element in &mut sch.elements {
+        if let Element::Component(ref mut c) = *element {
+            for &(ref path, ref reference, unit) in lines {
+                let uuid = c.timestamp().unwrap_or("").to_string();
+                if path.ends_with(&format!("/{}", uuid)) && c.reference_for_path(path).is_none() {
+                    c.ar_path.push(ar_line(path, reference, unit))
+                }
+            }
+        }
+    }
+}
+
+// the reference of the instance at `path` of one of the components
+fn instance_reference(components: &[Component], path: &str) -> Option<String> {
+    let uuid = path.rsplit('/').next().unwrap_or("");
+    components
+        .iter()
+        .find(|c| c.timestamp() == Some(uuid))
+        .and_then(|c| c.reference_for_path(path))
+}
+
+// give the components of the sub-sheets the `AR` lines of their instances
+// in the `symbol_instances` of the root sheet
+pub(crate) fn share_symbol_instances(
+    root: &Schematic,
+    sheets: &mut [Schematic],
+) -> Result<(), KicadError> {
+    let lines = symbol_instances(root)?;
+    for sheet in sheets {
+        add_ar_lines(sheet, &lines)
+    }
+    Ok(())
+}
+
+// write the references of the components of all sheets in the
+// `symbol_instances` of the root sheet `sheets[0]`; returns if any changed
+pub(crate) fn update_symbol_instances(sheets: &mut [Schematic]) -> Result<bool, KicadError> {
+    let components: Vec<Component> = sheets.iter().flat_map(|s| s.components()).collect();
+    let reference = |path: &str| instance_reference(&components, path);
+    let lines = symbol_instances(&sheets[0])?;
+    let changed = lines
+        .iter()
+        .any(|(path, r, _)| reference(path).is_some_and(|n| n != *r));
+    if changed {
+        let root = &mut sheets[0];
+        root.other = root.other
+            .iter()
+            .map(|e| match name_of(e) {
+                "symbol_instances" => update_instances(e, &reference),
+                _ => e.clone(),
+            })
+            .collect();
+    }
+    Ok(changed)
+}
+
+fn paper_sexp(d: &Description) -> Sexp {
+    let mut v = Sexp::start("paper");
+    v.push(quoted(&d.size));
+    if d.size == "User" {
+        v.push(to_mm(d.dimx as f64));
+        v.push(to_mm(d.dimy as f64));
+    } else if d.dimy > d.dimx {
+        v.push("portrait")
+    }
+    v
+}
+
+fn title_block_sexp(d: &Description) -> Option<Sexp> {
+    let mut v = Sexp::start("title_block");
+    for &(name, value) in &[
+        ("title", &d.title),
+        ("date", &d.date),
+        ("rev", &d.rev),
+        ("company", &d.comp),
+    ] {
+        if !value.is_empty() {
+            v.push((name, quoted(value)))
+        }
+    }
+    for (i, comment) in [&d.comment1, &d.comment2, &d.comment3, &d.comment4].iter().enumerate() {
+        if !comment.is_empty() {
+            let mut c = Sexp::start("comment");
+            c.push(i as i64 + 1);
+            c.push(quoted(comment));
+            v.push(c)
+        }
+    }
+    match v.list().map(|l| l.len()) {
+        Ok(1) => None,
+        _ => Some(v),
+    }
+}
+
+// `t` written over the expression `original` it was read from, see
+// `sexpr::write_back`
+fn written_back<T, P, W>(original: &Option<Sexp>, t: &T, parse: P, write: W) -> Sexp
+where
+    P: Fn(&Sexp) -> Result<T, SexpError>,
+    W: Fn(&T) -> Sexp,
+{
+    let after = write(t);
+    match original.as_ref().map(|o| (o, parse(o))) {
+        Some((o, Ok(before))) => write_back(o, &write(&before), &after),
+        _ => after,
+    }
+}
+
+fn element_sexp(element: &Element) -> Option<Sexp> {
+    Some(match *element {
+        Element::Component(ref c) => written_back(&c.sexp, c, parse_symbol, symbol_sexp),
+        Element::Wire(ref w) => written_back(&w.sexp, w, parse_wire, wire_sexp),
+        Element::Connection(ref c) => written_back(&c.sexp, c, parse_junction, junction_sexp),
+        Element::NoConnect(ref n) => written_back(&n.sexp, n, parse_no_connect, no_connect_sexp),
+        Element::Text(ref t) => written_back(&t.sexp, t, parse_text, text_sexp),
+        Element::Other(ref s) => match symbolic_expressions::parser::parse_str(s) {
+            Ok(e @ Sexp::List(_)) => requote(&e),
+            _ => return None,
+        },
+    })
+}
+
+fn schematic_sexp(sch: &Schematic) -> Sexp {
+    let mut v = Sexp::start("kicad_sch");
+    let tail = sch.other.iter().position(is_tail_of_schematic).unwrap_or(sch.other.len());
+    let (header, tail) = sch.other.split_at(tail);
+    if !header.iter().any(|e| name_of(e) == "version") {
+        v.push(("version", &VERSION));
+        v.push(("generator", &"kicad_parse_gen"));
+    }
+    for e in header {
+        v.push(requote(e))
+    }
+    if !sch.description.size.is_empty() {
+        v.push(paper_sexp(&sch.description))
+    }
+    if let Some(title_block) = title_block_sexp(&sch.description) {
+        v.push(title_block)
+    }
+    let mut lib_symbols = Sexp::start("lib_symbols");
+    for symbol in &sch.lib_symbols {
+        lib_symbols.push(requote(symbol))
+    }
+    v.push(lib_symbols);
+    for element in &sch.elements {
+        if let Some(e) = element_sexp(element) {
+            v.push(e)
+        }
+    }
+    for sheet in &sch.sheets {
+        v.push(written_back(&sheet.sexp, sheet, parse_sheet, sheet_sexp))
+    }
+    let components = sch.components();
+    let reference = |path: &str| instance_reference(&components, path);
+    for e in tail {
+        match name_of(e) {
+            "symbol_instances" => v.push(requote(&update_instances(e, &reference))),
+            _ => v.push(requote(e)),
+        }
+    }
+    v
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use schematic;
+    use schematic::kicad_sch;
+    use schematic::{Element, LabelSide, TextType, WireType};
+    use test_util;
+
+    const SCHEMATIC: &str = r#"(kicad_sch (version 20211123) (generator eeschema)
+
+  (uuid 8f1d2c3e-0000-4000-8000-000000000001)
+
+  (paper "A4")
+
+  (title_block
+    (title "Test")
+    (rev "1")
+    (comment 1 "first")
+  )
+
+  (lib_symbols
+    (symbol "Device:R" (pin_numbers hide) (pin_names (offset 0)) (in_bom yes) (on_board yes)
+      (property "Reference" "R" (id 0) (at 2.032 0 90)
+        (effects (font (size 1.27 1.27)))
+      )
+      (symbol "R_1_1"
+        (pin passive line (at 0 3.81 270) (length 1.27)
+          (name "~" (effects (font (size 1.27 1.27))))
+          (number "1" (effects (font (size 1.27 1.27))))
+        )
+      )
+    )
+  )
+
+  (junction (at 127 50.8) (diameter 0) (color 0 0 0 0)
+    (uuid 8f1d2c3e-0000-4000-8000-000000000010)
+  )
+
+  (no_connect (at 152.4 50.8) (uuid 8f1d2c3e-0000-4000-8000-000000000011))
+
+  (bus_entry (at 100.5 45.72) (size 2.54 2.54)
+    (stroke (width 0) (type default) (color 0 0 0 0))
+    (uuid 8f1d2c3e-0000-4000-8000-000000000015)
+  )
+
+  (wire (pts (xy 100.5 50.8) (xy 127 50.8))
+    (stroke (width 0) (type default) (color 0 0 0 0))
+    (uuid 8f1d2c3e-0000-4000-8000-000000000012)
+  )
+
+  (label "SIG" (at 106.68 50.8 0)
+    (effects (font (size 1.27 1.27)) (justify left bottom))
+    (uuid 8f1d2c3e-0000-4000-8000-000000000013)
+  )
+
+  (global_label "VIN" (shape input) (at 101.6 50.8 180) (fields_autoplaced)
+    (effects (font (size 1.27 1.27)) (justify right))
+    (uuid 8f1d2c3e-0000-4000-8000-000000000014)
+    (property "Intersheet References" "${INTERSHEET_REFS}" (id 0) (at 94.2367 50.8 0)
+      (effects (font (size 1.27 1.27)) (justify right) hide)
+    )
+  )
+
+  (symbol (lib_id "Device:R") (at 127 63.5 90) (mirror x) (unit 1)
+    (in_bom yes) (on_board yes)
+    (uuid 8f1d2c3e-0000-4000-8000-000000000020)
+    (property "Reference" "R1" (id 0) (at 130.8 62.2 0)
+      (effects (font (size 1.27 1.27)) (justify left))
+    )
+    (property "Value" "10k" (id 1) (at 130.81 64.77 0)
+      (effects (font (size 1.27 1.27)) (justify left))
+    )
+    (property "Footprint" "" (id 2) (at 127 63.5 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (pin "1" (uuid 8f1d2c3e-0000-4000-8000-000000000021))
+  )
+
+  (sheet (at 152.4 76.2) (size 25.4 12.7) (fields_autoplaced)
+    (stroke (width 0.1524) (type solid) (color 0 0 0 0))
+    (fill (color 0 0 0 0.0000))
+    (uuid 8f1d2c3e-0000-4000-8000-000000000030)
+    (property "Sheet name" "sub" (id 0) (at 152.4 75.4884 0)
+      (effects (font (size 1.27 1.27)) (justify left bottom))
+    )
+    (property "Sheet file" "sub.kicad_sch" (id 1) (at 152.4 89.4846 0)
+      (effects (font (size 1.27 1.27)) (justify left top))
+    )
+    (pin "IN" input (at 152.4 81.3 180)
+      (effects (font (size 1.27 1.27)) (justify left))
+      (uuid 8f1d2c3e-0000-4000-8000-000000000031)
+    )
+  )
+
+  (sheet_instances
+    (path "/" (page "1"))
+  )
+
+  (symbol_instances
+    (path "/8f1d2c3e-0000-4000-8000-000000000020"
+      (reference "R1") (unit 1) (value "10k") (footprint "")
+    )
+  )
+)
+"#;
+
+    #[test]
+    fn kicad_sch_parse() {
+        let sch = kicad_sch::parse(None, SCHEMATIC).unwrap();
+        assert_eq!(sch.description.size, "A4");
+        assert_eq!(sch.description.dimx, 11693);
+        assert_eq!(sch.description.title, "Test");
+        assert_eq!(sch.description.comment1, "first");
+        assert_eq!(sch.lib_symbols.len(), 1);
+        assert_eq!(sch.elements.len(), 7);
+        assert!(matches!(sch.elements[2], Element::Other(ref s) if s.starts_with("(bus_entry")));
+        match sch.elements[3] {
+            Element::Wire(ref w) => {
+                assert!(matches!(w.type_, WireType::Wire));
+                assert_eq!((w.x1, w.y1, w.x2, w.y2), (3957, 2000, 5000, 2000));
+            }
+            _ => panic!("expecting a wire"),
+        }
+        match sch.elements[5] {
+            Element::Text(ref t) => {
+                assert!(matches!(t.t, TextType::Global));
+                assert_eq!(t.shape, Some("Input".to_string()));
+                assert_eq!(t.orientation, 2);
+            }
+            _ => panic!("expecting a global label"),
+        }
+        let c = &sch.components()[0];
+        assert_eq!(c.name, "Device:R");
+        assert_eq!(c.reference, "R1");
+        assert_eq!((c.x, c.y), (5000, 2500));
+        assert_eq!(c.unit(), 1);
+        assert_eq!(c.timestamp(), Some("8f1d2c3e-0000-4000-8000-000000000020"));
+        assert_eq!(c.get_field_value("Value"), Some("10k".into()));
+        assert!(!c.get_field("Footprint").unwrap().visible);
+        assert_eq!(c.rotation.transform(100, 0), (0, 100));
+        let path = "/8f1d2c3e-0000-4000-8000-000000000020";
+        assert_eq!(c.reference_for_path(path), Some("R1".into()));
+        let sheet = &sch.sheets[0];
+        assert_eq!(sheet.name, "sub");
+        assert_eq!(sheet.filename, "sub.kicad_sch");
+        assert_eq!((sheet.x, sheet.y, sheet.dimx, sheet.dimy), (6000, 3000, 1000, 500));
+        assert_eq!(sheet.unique, "8f1d2c3e-0000-4000-8000-000000000030");
+        assert_eq!(sheet.labels[0].name, "IN");
+        assert!(matches!(sheet.labels[0].side, LabelSide::Left));
+    }
+
+    #[test]
+    fn kicad_sch_round_trip() {
+        let mut sch = kicad_sch::parse(None, SCHEMATIC).unwrap();
+        assert_eq!(kicad_sch::to_string(&sch, 0).unwrap(), SCHEMATIC);
+        sch.modify_component("R1", |c| {
+            c.update_reference("R7".into());
+            c.update_reference_for_path("/8f1d2c3e-0000-4000-8000-000000000020", "R7");
+        });
+        let s = kicad_sch::to_string(&sch, 0).unwrap();
+        assert_eq!(s, SCHEMATIC.replace("\"R1\"", "\"R7\""));
+        let sch2 = kicad_sch::parse(None, &s).unwrap();
+        let c = &sch2.components()[0];
+        assert_eq!(c.reference, "R7");
+        assert_eq!(c.rotation, sch.components()[0].rotation);
+        assert_eq!(sch2.sheets[0].labels[0].y, 3201);
+        // what moves is written from the mils, the rest is kept
+        if let Element::Wire(ref mut w) = sch.elements[3] {
+            w.x2 = 5100
+        }
+        let s = kicad_sch::to_string(&sch, 0).unwrap();
+        assert!(s.contains("(wire (pts (xy 100.5 50.8) (xy 129.54 50.8))\n    \
+                            (stroke (width 0) (type default) (color 0 0 0 0))\n    \
+                            (uuid 8f1d2c3e-0000-4000-8000-000000000012)"));
+    }
+
+    #[test]
+    fn kicad_sch_datasheet() {
+        let footprint = "(property \"Footprint\" \"\" (id 2)";
+        let datasheet = "(property \"Datasheet\" \"http://r\" (id 3) (at 127 63.5 0)\n      \
+                         (effects (font (size 1.27 1.27)) hide)\n    )\n    ";
+        let s = SCHEMATIC.replace(footprint, &format!("{}{}", datasheet, footprint));
+        let sch = kicad_sch::parse(None, &s).unwrap();
+        let c = &sch.components()[0];
+        assert_eq!(c.get_field_value("UserDocLink"), Some("http://r".into()));
+        let s = kicad_sch::to_string(&sch, 0).unwrap();
+        assert!(s.contains("(property \"Datasheet\" \"http://r\" (id 3)"));
+    }
+
+    #[test]
+    fn kicad_sch_instances() {
+        // Kicad 7 keeps the instances in the symbol, with the root sheet
+        let s = r#"(kicad_sch (version 20230121) (generator eeschema)
+  (uuid 8f1d2c3e-0000-4000-8000-000000000001)
+  (paper "A4")
+  (lib_symbols)
+  (symbol (lib_id "Device:R") (at 127 63.5 0) (unit 2)
+    (uuid 8f1d2c3e-0000-4000-8000-000000000040)
+    (property "Reference" "R2" (at 130.81 62.23 0)
+      (effects (font (size 1.27 1.27)))
+    )
+    (instances
+      (project "test"
+        (path "/8f1d2c3e-0000-4000-8000-000000000001/8f1d2c3e-0000-4000-8000-000000000030"
+          (reference "R2") (unit 2)
+        )
+      )
+    )
+  )
+)
+"#;
+        let sch = kicad_sch::parse(None, s).unwrap();
+        let c = &sch.components()[0];
+        assert_eq!(c.unit(), 2);
+        let path = "/8f1d2c3e-0000-4000-8000-000000000030/8f1d2c3e-0000-4000-8000-000000000040";
+        assert_eq!(c.reference_for_path(path), Some("R2".into()));
+    }
+
+    #[test]
+    fn kicad_sch_sub_sheet_instances() {
+        let dir = test_util::temp_dir("kicad_sch_sub_sheet_instances");
+        let path = "/8f1d2c3e-0000-4000-8000-000000000030/8f1d2c3e-0000-4000-8000-000000000050";
+        let root = SCHEMATIC.replace(
+            "  (symbol_instances\n",
+            &format!("  (symbol_instances\n    (path \"{}\" (reference \"R5\") (unit 1))\n", path),
+        );
+        let sub = r#"(kicad_sch (version 20211123) (generator eeschema)
+  (uuid 8f1d2c3e-0000-4000-8000-000000000002)
+  (paper "A4")
+  (lib_symbols)
+  (symbol (lib_id "Device:R") (at 127 63.5 0) (unit 1)
+    (uuid 8f1d2c3e-0000-4000-8000-000000000050)
+    (property "Reference" "R?" (id 0) (at 130.81 62.23 0)
+      (effects (font (size 1.27 1.27)))
+    )
+  )
+)
+"#;
+        fs::write(dir.join("root.kicad_sch"), root).unwrap();
+        fs::write(dir.join("sub.kicad_sch"), sub).unwrap();
+        let (mut sheets, _) = schematic::load_hierarchy(&dir.join("root.kicad_sch")).unwrap();
+        let instances = sheets[0].component_instances().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(instances[1].reference, "R5");
+        assert!(!kicad_sch::update_symbol_instances(&mut sheets).unwrap());
+        sheets[1].modify_components(|c| {
+            c.update_reference_for_path(path, "R9");
+        });
+        assert!(kicad_sch::update_symbol_instances(&mut sheets).unwrap());
+        let s = kicad_sch::to_string(&sheets[0], 0).unwrap();
+        assert!(s.contains("(reference \"R9\")"));
+        assert!(s.contains("(reference \"R1\")"));
+        assert!(!kicad_sch::to_string(&sheets[1], 0).unwrap().contains("R9"));
+    }
+}
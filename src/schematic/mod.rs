@@ -9,12 +9,19 @@ use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 
 // get from parent
-use {Bound, BoundingBox, KicadError};
+use {Bound, BoundingBox, KicadError, Sexp};
 use util::read_file;
 use str_error;
 use parse_split_quote_aware;
 use parse_split_quote_aware_n;
 
+/// annotation of the components of a schematic
+pub mod annotate;
+/// electrical connectivity of a schematic
+pub mod connectivity;
+/// Kicad 6 and later s-expression schematics
+pub mod kicad_sch;
+
 /// a Kicad schematic
 #[derive(Debug, Default)]
 pub struct Schematic {
@@ -30,6 +37,10 @@ pub struct Schematic {
     pub sheets: Vec<Sheet>,
     /// eelayer is transparently copied
     pub eelayer: String,
+    /// the symbols embedded in an s-expression schematic, kept as is
+    pub lib_symbols: Vec<Sexp>,
+    /// other elements of an s-expression schematic, kept as is
+    pub other: Vec<Sexp>,
 }
 
 impl BoundingBox for Schematic {
@@ -408,6 +419,11 @@ pub struct Component {
     pub fields: Vec<ComponentField>,
     /// rotation
     pub rotation: ComponentRotation,
+    /// other elements of an s-expression schematic like the pins, kept as is
+    pub other: Vec<Sexp>,
+    /// the expression of an s-expression schematic it was read from; what
+    /// didn't change is written back from it
+    pub sexp: Option<Sexp>,
 }
 
 impl Default for Component {
@@ -426,6 +442,8 @@ impl Default for Component {
                 c: 0,
                 d: 0,
             },
+            other: vec![],
+            sexp: None,
         }
     }
 }
@@ -579,6 +597,82 @@ impl Component {
         c.visible = false;
         self.fields.push(c)
     }
+
+    // U unit convert timestamp
+    fn u_part(&self, i: usize) -> Option<&str> {
+        self.u.split_whitespace().nth(i)
+    }
+
+    /// the unit of a multi-unit component, starting at 1
+    pub fn unit(&self) -> i64 {
+        self.u_part(1).and_then(|x| x.parse().ok()).unwrap_or(1)
+    }
+
+    /// the body style of the component, 2 for the De Morgan conversion
+    pub fn convert(&self) -> i64 {
+        self.u_part(2).and_then(|x| x.parse().ok()).unwrap_or(1)
+    }
+
+    /// the timestamp of the component
+    pub fn timestamp(&self) -> Option<&str> {
+        self.u_part(3)
+    }
+
+    /// get the reference of the component for an instance of its sheet
+    /// from the `AR` lines; `path` is the path of sheet timestamps followed
+    /// by the timestamp of the component, like `/5A1B2C3D/5A1B2C4E`
+    pub fn reference_for_path(&self, path: &str) -> Option<String> {
+        self.ar_path
+            .iter()
+            .find(|ar| ar_attribute(ar, "Path").as_ref().map(|p| &p[..]) == Some(path))
+            .and_then(|ar| ar_attribute(ar, "Ref"))
+    }
+
+    /// set the reference of the component in the `AR` line for an instance
+    /// of its sheet, returns false if there is no `AR` line for the path
+    pub fn update_reference_for_path(&mut self, path: &str, r: &str) -> bool {
+        for ar in &mut self.ar_path {
+            if ar_attribute(ar, "Path").as_ref().map(|p| &p[..]) != Some(path) {
+                continue;
+            }
+            if let Some(start) = ar.find("Ref=\"").map(|i| i + 5) {
+                if let Some(len) = ar[start..].find('"') {
+                    ar.replace_range(start..start + len, r);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// set the reference of the component for an instance of its sheet,
+    /// adding an `AR` line if there is none for the path yet
+    pub fn set_reference_for_path(&mut self, path: &str, r: &str) {
+        if !self.update_reference_for_path(path, r) {
+            let ar = format!("AR Path=\"{}\" Ref=\"{}\"  Part=\"{}\" ", path, r, self.unit());
+            self.ar_path.push(ar)
+        }
+    }
+
+    /// get the path and reference of every instance of the component from
+    /// the `AR` lines
+    pub fn instances(&self) -> Vec<(String, String)> {
+        self.ar_path
+            .iter()
+            .filter_map(|ar| match (ar_attribute(ar, "Path"), ar_attribute(ar, "Ref")) {
+                (Some(path), Some(r)) => Some((path, r)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// AR Path="/5A1B2C3D/5A1B2C4E" Ref="R1"  Part="1"
+fn ar_attribute(ar: &str, name: &str) -> Option<String> {
+    let key = format!("{}=\"", name);
+    let start = ar.find(&key)? + key.len();
+    let len = ar[start..].find('"')?;
+    Some(ar[start..start + len].to_string())
 }
 
 impl fmt::Display for Component {
@@ -613,7 +707,7 @@ impl fmt::Display for Component {
 }
 
 /// a component rotation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ComponentRotation {
     a: i64,
     b: i64,
@@ -621,6 +715,14 @@ pub struct ComponentRotation {
     d: i64,
 }
 
+impl ComponentRotation {
+    /// transform a symbol library coordinate to a coordinate relative to
+    /// the position of the component; this includes the flip of the Y axis
+    pub fn transform(&self, x: i64, y: i64) -> (i64, i64) {
+        (self.a * x + self.b * y, self.c * x + self.d * y)
+    }
+}
+
 /// a component orientation
 #[derive(Debug, Clone)]
 pub enum Orientation {
@@ -823,6 +925,11 @@ pub struct Sheet {
     pub filename_size: i64,
     /// sheet labels
     pub labels: Vec<SheetLabel>, // starting at F2
+    /// other elements of an s-expression schematic, kept as is
+    pub other: Vec<Sexp>,
+    /// the expression of an s-expression schematic it was read from; what
+    /// didn't change is written back from it
+    pub sexp: Option<Sexp>,
 }
 
 impl BoundingBox for Sheet {
@@ -849,6 +956,8 @@ impl Default for Sheet {
             filename: String::from(""),
             filename_size: 60,
             labels: vec![],
+            other: vec![],
+            sexp: None,
         }
     }
 }
@@ -1005,6 +1114,9 @@ pub struct Wire {
     pub x2: i64,
     /// y-coordinate of second point of wire
     pub y2: i64,
+    /// the expression of an s-expression schematic it was read from; what
+    /// didn't change is written back from it
+    pub sexp: Option<Sexp>,
 }
 
 impl fmt::Display for Wire {
@@ -1021,6 +1133,9 @@ pub struct Connection {
     pub x: i64,
     /// connection y-coordinate
     pub y: i64,
+    /// the expression of an s-expression schematic it was read from; what
+    /// didn't change is written back from it
+    pub sexp: Option<Sexp>,
 }
 
 impl fmt::Display for Connection {
@@ -1036,6 +1151,9 @@ pub struct NoConnect {
     pub x: i64,
     /// no-connect y-coordinate
     pub y: i64,
+    /// the expression of an s-expression schematic it was read from; what
+    /// didn't change is written back from it
+    pub sexp: Option<Sexp>,
 }
 
 impl fmt::Display for NoConnect {
@@ -1104,6 +1222,9 @@ pub struct Text {
     pub thickness: i64,
     /// the contained text
     pub text: String,
+    /// the expression of an s-expression schematic it was read from; what
+    /// didn't change is written back from it
+    pub sexp: Option<Sexp>,
 }
 
 impl fmt::Display for Text {
@@ -1288,8 +1409,9 @@ fn parse_component(p: &mut ParseState) -> Result<Component, KicadError> {
             Some("U") => parse_component_u(p, &mut d)?,
             Some("P") => parse_component_p(p, &mut d)?,
             Some("F") => parse_component_f(p, &mut d)?,
-            Some("1") | Some("0") => parse_component_rotation(p, &mut d)?,
             Some("AR") => parse_component_ar(p, &mut d)?,
+            // the redundant position line starts with the unit
+            Some(x) if x.parse::<i64>().is_ok() => parse_component_rotation(p, &mut d)?,
             _ => println!("skipping unknown component line {}", s),
         }
         p.next()
@@ -1422,6 +1544,7 @@ fn parse_wire(p: &mut ParseState) -> Result<Wire, KicadError> {
         y1: y1,
         x2: x2,
         y2: y2,
+        sexp: None,
     })
 }
 
@@ -1434,7 +1557,11 @@ fn parse_connection(p: &mut ParseState) -> Result<Connection, KicadError> {
     }
     let x1 = i64_from_string(p, &String::from(v[2]))?;
     let y1 = i64_from_string(p, &String::from(v[3]))?;
-    Ok(Connection { x: x1, y: y1 })
+    Ok(Connection {
+        x: x1,
+        y: y1,
+        sexp: None,
+    })
 }
 
 // NoConnect ~ 5250 3050
@@ -1446,7 +1573,11 @@ fn parse_no_connect(p: &mut ParseState) -> Result<NoConnect, KicadError> {
     }
     let x1 = i64_from_string(p, &String::from(v[2]))?;
     let y1 = i64_from_string(p, &String::from(v[3]))?;
-    Ok(NoConnect { x: x1, y: y1 })
+    Ok(NoConnect {
+        x: x1,
+        y: y1,
+        sexp: None,
+    })
 }
 
 //Text Label 9300 2175 0    60   Italic 12
@@ -1494,6 +1625,7 @@ fn parse_text(p: &mut ParseState) -> Result<Text, KicadError> {
         italic: italic,
         thickness: thickness,
         text: text,
+        sexp: None,
     })
 }
 
@@ -1570,6 +1702,9 @@ pub fn parse_str(s: &str) -> Result<Schematic, KicadError> {
 /// parse a file as a Kicad schematic
 pub fn parse_file(filename: &Path) -> Result<Schematic, KicadError> {
     let s = read_file(filename)?;
+    if kicad_sch::is_kicad_sch(&s) {
+        return kicad_sch::parse(Some(PathBuf::from(filename)), &s);
+    }
     parse(Some(PathBuf::from(filename)), &s[..])
 }
 
@@ -1592,3 +1727,140 @@ pub fn parse_file_for_sheet(schematic: &Schematic, sheet: &Sheet) -> Result<Sche
     let f = filename_for_sheet(schematic, sheet)?;
     parse_file(&f)
 }
+
+// an instance of a sheet as its path of sheet timestamps and the index of its file
+pub(crate) type SheetInstance = (String, usize);
+
+// load a schematic and its sub-sheets, each file once; returns the files and
+// the instances of the sheets in hierarchy order, so the index of an instance
+// is its sheet number minus one
+pub(crate) fn load_hierarchy(
+    filename: &Path,
+) -> Result<(Vec<Schematic>, Vec<SheetInstance>), KicadError> {
+    let root = parse_file(filename)?;
+    let (mut sheets, instances) = load_sheets(&root)?;
+    sheets.insert(0, root);
+    Ok((sheets, instances))
+}
+
+// like `load_hierarchy` for a schematic that is already loaded; the returned
+// files are the sub-sheets only, file `i` of an instance is `sheets[i - 1]`
+fn load_sheets(root: &Schematic) -> Result<(Vec<Schematic>, Vec<SheetInstance>), KicadError> {
+    let mut sheets = vec![];
+    let mut instances = vec![];
+    add_instances(root, &mut sheets, &mut instances, String::new(), 0)?;
+    kicad_sch::share_symbol_instances(root, &mut sheets)?;
+    Ok((sheets, instances))
+}
+
+fn add_instances(
+    root: &Schematic,
+    sheets: &mut Vec<Schematic>,
+    instances: &mut Vec<SheetInstance>,
+    stamp_path: String,
+    file: usize,
+) -> Result<(), KicadError> {
+    let mut children = vec![];
+    {
+        let schematic = if file == 0 { root } else { &sheets[file - 1] };
+        for sheet in &schematic.sheets {
+            let name = filename_for_sheet(schematic, sheet)?;
+            children.push((format!("{}/{}", stamp_path, sheet.unique), name))
+        }
+    }
+    instances.push((stamp_path, file));
+    for (stamp_path, name) in children {
+        let child = if root.filename.as_ref() == Some(&name) {
+            0
+        } else {
+            match sheets.iter().position(|s| s.filename.as_ref() == Some(&name)) {
+                Some(child) => child + 1,
+                None => {
+                    sheets.push(parse_file(&name)?);
+                    sheets.len()
+                }
+            }
+        };
+        add_instances(root, sheets, instances, stamp_path, child)?
+    }
+    Ok(())
+}
+
+// a component in an instance of its sheet
+#[derive(Debug, Clone)]
+pub(crate) struct ComponentInstance {
+    // sheet names, like `/` or `/power/`
+    pub name_path: String,
+    // sheet timestamps, like `` or `/5A1B2C3D`
+    pub stamp_path: String,
+    // the reference of the component in this instance
+    pub reference: String,
+    pub component: Component,
+}
+
+// the place of a sheet instance in the hierarchy
+pub(crate) struct SheetPath {
+    // sheet names, like `/` or `/power/`
+    pub name_path: String,
+    // the parent instance and the index of the sheet in the file of the parent
+    pub parent: Option<(usize, usize)>,
+}
+
+// the sheet paths of the instances returned by `load_sheets`
+pub(crate) fn sheet_paths(
+    root: &Schematic,
+    sheets: &[Schematic],
+    instances: &[SheetInstance],
+) -> Vec<SheetPath> {
+    let file = |i: usize| if i == 0 { root } else { &sheets[i - 1] };
+    // the instances by their sheet timestamps
+    let mut parents: HashMap<&str, usize> = HashMap::new();
+    let mut v: Vec<SheetPath> = vec![];
+    for (n, (stamp_path, _)) in instances.iter().enumerate() {
+        let path = match stamp_path.rfind('/') {
+            None => SheetPath {
+                name_path: "/".into(),
+                parent: None,
+            },
+            Some(j) => {
+                let parent = parents[&stamp_path[..j]];
+                let unique = &stamp_path[j + 1..];
+                let parent_sheets = &file(instances[parent].1).sheets;
+                let sheet = parent_sheets.iter().position(|s| s.unique == unique);
+                let name = sheet.map(|k| &parent_sheets[k].name[..]).unwrap_or(unique);
+                SheetPath {
+                    name_path: format!("{}{}/", v[parent].name_path, name),
+                    parent: sheet.map(|k| (parent, k)),
+                }
+            }
+        };
+        parents.insert(stamp_path, n);
+        v.push(path)
+    }
+    v
+}
+
+impl Schematic {
+    // every component once per instance of its sheet, in hierarchy order and
+    // with the reference from the `AR` line of the instance
+    pub(crate) fn component_instances(&self) -> Result<Vec<ComponentInstance>, KicadError> {
+        let (sheets, instances) = load_sheets(self)?;
+        let paths = sheet_paths(self, &sheets, &instances);
+        let file = |i: usize| if i == 0 { self } else { &sheets[i - 1] };
+        let mut v = vec![];
+        for (&(ref stamp_path, i), path) in instances.iter().zip(&paths) {
+            for c in file(i).components() {
+                let reference = c.timestamp()
+                    .and_then(|t| c.reference_for_path(&format!("{}/{}", stamp_path, t)))
+                    .unwrap_or_else(|| c.reference.clone());
+                v.push(ComponentInstance {
+                    name_path: path.name_path.clone(),
+                    stamp_path: stamp_path.clone(),
+                    reference,
+                    component: c,
+                })
+            }
+        }
+        Ok(v)
+    }
+}
@@ -0,0 +1,441 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+// helpers for the s-expression formats of Kicad 6 and later, which use mm
+// where the legacy formats use mils
+
+use symbolic_expressions::{encode_string, Sexp, SexpError};
+use symbolic_expressions::iteratom::*;
+
+use schematic::Justify;
+
+const MM_PER_MIL: f64 = 0.0254;
+
+// the names of the mandatory fields
+pub const FIELDS: &[&str] = &["Reference", "Value", "Footprint", "Datasheet"];
+
+pub fn to_mils(mm: f64) -> i64 {
+    (mm / MM_PER_MIL).round() as i64
+}
+
+pub fn to_mm(mils: f64) -> f64 {
+    (mils * MM_PER_MIL * 10_000.0).round() / 10_000.0
+}
+
+// the elements of a list after its name
+pub fn elements<'a>(s: &'a Sexp, name: &str) -> Result<Vec<&'a Sexp>, SexpError> {
+    let i = IterAtom::new(s, name)?;
+    Ok(i.iter.collect())
+}
+
+// the name of a list element; empty for a string
+pub fn name_of(s: &Sexp) -> &str {
+    match *s {
+        Sexp::List(ref l) => match l.first() {
+            Some(Sexp::String(name)) => name,
+            _ => "",
+        },
+        _ => "",
+    }
+}
+
+// the first element with a name in a list
+pub fn child<'a>(s: &'a Sexp, name: &str) -> Option<&'a Sexp> {
+    match *s {
+        Sexp::List(ref l) => l.iter().find(|e| name_of(e) == name),
+        _ => None,
+    }
+}
+
+// the string at position `i` of a list like `(label "SIG" ...)`
+pub fn string(s: &Sexp, i: usize) -> Result<String, SexpError> {
+    match s.list()?.get(i) {
+        Some(x) => x.s(),
+        None => Err(format!("missing value {} in {}", i, s).into()),
+    }
+}
+
+// the number at position `i` of a list like `(at 10 20 90)`
+pub fn number(s: &Sexp, i: usize) -> Result<f64, SexpError> {
+    match s.list()?.get(i) {
+        Some(x) => x.f(),
+        None => Err(format!("missing value {} in {}", i, s).into()),
+    }
+}
+
+pub fn maybe_number(s: &Sexp, i: usize) -> Option<f64> {
+    s.list().ok()?.get(i)?.f().ok()
+}
+
+// a flag like `hide` in Kicad 6 or `(hide yes)` in later versions
+pub fn flag(s: &Sexp, name: &str) -> bool {
+    match *s {
+        Sexp::List(ref l) => l.iter().skip(1).any(|e| match *e {
+            Sexp::String(ref x) => x == name,
+            Sexp::List(_) => {
+                name_of(e) == name && string(e, 1).map(|v| v != "no").unwrap_or(true)
+            }
+            Sexp::Empty => false,
+        }),
+        _ => false,
+    }
+}
+
+// the name of a field as in the legacy formats, which call the `Datasheet`
+// field `UserDocLink`
+pub fn legacy_field_name(i: i64, name: String) -> String {
+    match i {
+        3 => "UserDocLink".into(),
+        _ => name,
+    }
+}
+
+// the name of a property, the mandatory fields have the names of the
+// s-expression formats
+pub fn property_name(i: i64, name: &str) -> &str {
+    match FIELDS.get(i as usize) {
+        Some(name) => name,
+        None => name,
+    }
+}
+
+// the id of a property, Kicad 7 and later leave it out and number the
+// properties in order after the mandatory ones
+pub fn property_id(s: &Sexp, name: &str, index: i64) -> Result<i64, SexpError> {
+    Ok(match child(s, "id") {
+        Some(id) => number(id, 1)? as i64,
+        None => match name {
+            "Reference" => 0,
+            "Value" => 1,
+            "Footprint" => 2,
+            "Datasheet" => 3,
+            _ => index,
+        },
+    })
+}
+
+// position in mils and angle in degrees from `(at x y angle)`
+pub fn at(s: &Sexp) -> Result<(i64, i64, i64), SexpError> {
+    match child(s, "at") {
+        Some(at) => Ok((
+            to_mils(number(at, 1)?),
+            to_mils(number(at, 2)?),
+            maybe_number(at, 3).unwrap_or(0.0).round() as i64,
+        )),
+        None => Err(format!("missing position in {}", s).into()),
+    }
+}
+
+// put expressions that weren't interpreted back at their position in the
+// list they were read from
+pub fn insert_other(v: &mut Sexp, other: &[(usize, Sexp)]) {
+    if let Sexp::List(ref mut l) = *v {
+        for &(i, ref o) in other {
+            let i = i.min(l.len());
+            l.insert(i, o.clone())
+        }
+    }
+}
+
+// the width and type of a line in Kicad 7, like
+// `(stroke (width 0.12) (type solid))`
+pub fn parse_stroke(s: &Sexp) -> Result<(f64, String), SexpError> {
+    let mut i = IterAtom::new(s, "stroke")?;
+    let width = i.f_in_list("width")?;
+    let t = i.s_in_list("type")?;
+    i.close((width, t))
+}
+
+pub fn stroke_sexp(width: f64, t: &str) -> Sexp {
+    let mut v = Sexp::start("stroke");
+    v.push(("width", &width));
+    v.push(("type", &t));
+    v
+}
+
+// the width of a line and its layer; Kicad 7 writes the width in a stroke
+// with a type, before the layer
+pub fn push_width(v: &mut Sexp, width: f64, stroke: &Option<String>, layer: Sexp) {
+    match *stroke {
+        Some(ref t) => {
+            v.push(stroke_sexp(width, t));
+            v.push(layer)
+        }
+        None => {
+            v.push(layer);
+            v.push(("width", &width))
+        }
+    }
+}
+
+// a string that Kicad expects quoted even if it is a number like a pin
+pub fn quoted(s: &str) -> Sexp {
+    let is_quoted = s.len() > 1 && s.starts_with('"') && s.ends_with('"');
+    if !is_quoted && encode_string(s) == s {
+        Sexp::String(format!("\"{}\"", s))
+    } else {
+        s.into()
+    }
+}
+
+pub fn xy(name: &str, x: i64, y: i64) -> Sexp {
+    let mut v = Sexp::start(name);
+    v.push(to_mm(x as f64));
+    v.push(to_mm(y as f64));
+    v
+}
+
+pub fn at_angle(x: i64, y: i64, angle: i64) -> Sexp {
+    let mut v = xy("at", x, y);
+    v.push(angle);
+    v
+}
+
+// the positions of strings that Kicad expects quoted in elements that are
+// kept as is, the lists are parsed without quotes
+pub fn quoted_positions(name: &str) -> &'static [usize] {
+    match name {
+        "property" => &[1, 2],
+        "comment" => &[2],
+        "symbol" | "text" | "name" | "number" | "path" | "reference" | "page" | "project" |
+        "value" | "footprint" | "lib_name" | "alternate" => &[1],
+        _ => &[],
+    }
+}
+
+pub fn requote_at(s: &Sexp, positions: &[usize]) -> Sexp {
+    match *s {
+        Sexp::List(ref l) => {
+            let positions = if positions.is_empty() {
+                quoted_positions(name_of(s))
+            } else {
+                positions
+            };
+            let v: Vec<Sexp> = l.iter()
+                .enumerate()
+                .map(|(i, e)| match *e {
+                    Sexp::String(ref x) if positions.contains(&i) => quoted(x),
+                    _ => requote_at(e, &[]),
+                })
+                .collect();
+            v.into()
+        }
+        _ => s.clone(),
+    }
+}
+
+pub fn requote(s: &Sexp) -> Sexp {
+    requote_at(s, &[])
+}
+
+// the lists that are named themselves like properties, sub-symbols and
+// sheet pins, which Kicad writes with a quoted string after their name
+fn named_lists(l: &[Sexp]) -> Vec<&str> {
+    l.iter()
+        .filter(|e| match e.list().ok().and_then(|v| v.get(1)) {
+            Some(Sexp::String(s)) => s.starts_with('"'),
+            _ => false,
+        })
+        .map(name_of)
+        .collect()
+}
+
+// the keys that match the elements of the lists in `write_back`: a list by
+// its name, the string after it for the `named` lists and how many lists
+// with the same name and string came before it; a string by its position
+// among the strings
+fn keys(l: &[Sexp], named: &[&str]) -> Vec<(String, String, usize)> {
+    let mut keys: Vec<(String, String, usize)> = vec![];
+    for e in l {
+        let name = name_of(e).to_string();
+        let what = match e.list().ok().and_then(|v| v.get(1)) {
+            Some(Sexp::String(s)) if named.contains(&&name[..]) => s.trim_matches('"'),
+            _ => "",
+        };
+        let n = keys.iter().filter(|k| k.0 == name && k.1 == what).count();
+        keys.push((name, what.to_string(), n))
+    }
+    keys
+}
+
+// `after` written over the expression `original` it was read from, where
+// `before` is what `original` becomes when it is parsed and written again:
+// the parts of `after` that are the same as in `before` are taken from
+// `original`. This keeps the order of the elements, the elements that
+// aren't parsed and the numbers that don't survive the conversion to mils.
+pub fn write_back(original: &Sexp, before: &Sexp, after: &Sexp) -> Sexp {
+    match (original, before, after) {
+        (Sexp::List(o), Sexp::List(b), Sexp::List(a))
+            if name_of(original) == name_of(after) =>
+        {
+            let mut named = named_lists(b);
+            named.append(&mut named_lists(a));
+            let (ko, kb, ka) = (keys(o, &named), keys(b, &named), keys(a, &named));
+            // the elements with the position of the element of `after` they
+            // come from
+            let mut v: Vec<(Option<usize>, Sexp)> = vec![];
+            for (e, k) in o.iter().zip(&ko) {
+                match (kb.iter().position(|x| x == k), ka.iter().position(|x| x == k)) {
+                    (Some(i), Some(j)) => v.push((Some(j), write_back(e, &b[i], &a[j]))),
+                    (Some(_), None) => (),
+                    (None, _) => v.push((None, requote(e))),
+                }
+            }
+            for (j, (e, k)) in a.iter().zip(&ka).enumerate() {
+                if !kb.contains(k) {
+                    let i = v.iter()
+                        .rposition(|x| x.0.is_some_and(|x| x < j))
+                        .map_or(v.len().min(1), |i| i + 1);
+                    v.insert(i, (Some(j), e.clone()))
+                }
+            }
+            let v: Vec<Sexp> = v.into_iter().map(|x| x.1).collect();
+            v.into()
+        }
+        // the strings that are written quoted are taken from `after`, the
+        // original lost its quotes when it was parsed
+        (Sexp::String(_), _, Sexp::String(s)) if before == after && !s.starts_with('"') => {
+            original.clone()
+        }
+        _ => after.clone(),
+    }
+}
+
+// Kicad 6 boards and footprints quote names, layers and texts but not
+// keywords, numbers and time stamps; `name` is the name of the list the
+// string at position `i` is in and `parent` the name of the list around it
+fn board_quoted(parent: &str, name: &str, i: usize, value: &str) -> bool {
+    match name {
+        // layer wildcards like `*.Cu` are not quoted
+        "layers" => i > 0 && !value.contains('*'),
+        // the layer definitions like `(0 "F.Cu" signal "Front")`
+        _ if parent == "layers" => i == 1 || i == 3,
+        "net" | "fp_text" | "comment" => i == 2,
+        "property" => i == 1 || i == 2,
+        // the type of a stackup layer, but not of a dimension
+        "type" => parent == "layer" && i == 1,
+        "paper" | "layer" | "footprint" | "gr_text" | "pad" | "pinfunction" | "pintype" |
+        "model" | "descr" | "tags" | "path" | "material" | "copper_finish" | "color" |
+        "net_name" | "title" | "company" | "rev" | "date" | "group" | "name" | "face" |
+        "prefix" | "suffix" | "outputdirectory" => i == 1,
+        _ => false,
+    }
+}
+
+fn requote_board_in(s: &Sexp, parent: &str) -> Sexp {
+    match *s {
+        Sexp::List(ref l) => {
+            let name = name_of(s);
+            let v: Vec<Sexp> = l.iter()
+                .enumerate()
+                .map(|(i, e)| match *e {
+                    Sexp::String(ref x) if board_quoted(parent, name, i, x) => quoted(x),
+                    _ => requote_board_in(e, name),
+                })
+                .collect();
+            v.into()
+        }
+        _ => s.clone(),
+    }
+}
+
+// quote the strings of a board or footprint like Kicad 6 and later do
+pub fn requote_board(s: &Sexp) -> Sexp {
+    requote_board_in(s, "")
+}
+
+/// text effects with the sizes in mils
+#[derive(Debug, Default)]
+pub struct Effects {
+    pub size: i64,
+    pub thickness: i64,
+    pub italic: bool,
+    pub bold: bool,
+    pub justify: Vec<String>,
+    pub hide: bool,
+}
+
+impl Effects {
+    // the horizontal and vertical justification, centered by default
+    pub fn justification(&self) -> (Justify, Justify) {
+        let (mut hjustify, mut vjustify) = (Justify::Center, Justify::Center);
+        for j in &self.justify {
+            match &j[..] {
+                "left" => hjustify = Justify::Left,
+                "right" => hjustify = Justify::Right,
+                "top" => vjustify = Justify::Top,
+                "bottom" => vjustify = Justify::Bottom,
+                _ => (),
+            }
+        }
+        (hjustify, vjustify)
+    }
+
+    pub fn set_justification(&mut self, hjustify: &Justify, vjustify: &Justify) {
+        self.justify.clear();
+        match *hjustify {
+            Justify::Left => self.justify.push("left".into()),
+            Justify::Right => self.justify.push("right".into()),
+            _ => (),
+        }
+        match *vjustify {
+            Justify::Top => self.justify.push("top".into()),
+            Justify::Bottom => self.justify.push("bottom".into()),
+            _ => (),
+        }
+    }
+}
+
+pub fn parse_effects(s: &Sexp) -> Result<Effects, SexpError> {
+    let mut effects = Effects::default();
+    let e = match child(s, "effects") {
+        Some(e) => e,
+        None => return Ok(effects),
+    };
+    if let Some(font) = child(e, "font") {
+        if let Some(size) = child(font, "size") {
+            effects.size = to_mils(number(size, 1)?)
+        }
+        if let Some(thickness) = child(font, "thickness") {
+            effects.thickness = to_mils(number(thickness, 1)?)
+        }
+        effects.italic = flag(font, "italic");
+        effects.bold = flag(font, "bold");
+    }
+    if let Some(justify) = child(e, "justify") {
+        for j in &justify.list()?[1..] {
+            effects.justify.push(j.s()?)
+        }
+    }
+    effects.hide = flag(e, "hide") || flag(s, "hide");
+    Ok(effects)
+}
+
+pub fn effects_sexp(effects: &Effects) -> Sexp {
+    let mut v = Sexp::start("effects");
+    let mut font = Sexp::start("font");
+    let mut size = Sexp::start("size");
+    size.push(to_mm(effects.size as f64));
+    size.push(to_mm(effects.size as f64));
+    font.push(size);
+    if effects.thickness > 0 {
+        font.push(("thickness", &to_mm(effects.thickness as f64)))
+    }
+    if effects.bold {
+        font.push("bold")
+    }
+    if effects.italic {
+        font.push("italic")
+    }
+    v.push(font);
+    if !effects.justify.is_empty() {
+        let mut justify = Sexp::start("justify");
+        for j in &effects.justify {
+            justify.push(j)
+        }
+        v.push(justify)
+    }
+    if effects.hide {
+        v.push("hide")
+    }
+    v
+}
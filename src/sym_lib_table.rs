@@ -0,0 +1,150 @@
+// (c) 2017 Productize SPRL <joost@productize.be>
+
+// filename: sym-lib-table
+// format: new-style
+
+use std::path::Path;
+
+use symbolic_expressions;
+use symbolic_expressions::{IntoSexp, Sexp, SexpError};
+use formatter::KicadFormatter;
+use symbolic_expressions::iteratom::*;
+use error::KicadError;
+use expand::ExpandContext;
+use symbol_lib::{Symbol, SymbolLib};
+use read_symbol_lib;
+
+/// a library entry, the same as in an fp-lib-table
+pub use fp_lib_table::Lib;
+
+/// a sym-lib-table
+#[derive(Debug, Clone)]
+pub struct SymLibTable {
+    /// the library references contained in the sym-lib-table
+    pub libs: Vec<Lib>,
+}
+
+impl SymLibTable {
+    /// chain the table over another one like a project table over the global
+    /// table, the libraries of this table take precedence
+    pub fn chain(&self, global: &SymLibTable) -> SymLibTable {
+        let mut libs = self.libs.clone();
+        for lib in &global.libs {
+            if self.get(&lib.name).is_none() {
+                libs.push(lib.clone())
+            }
+        }
+        SymLibTable { libs }
+    }
+
+    /// get a library entry by its nickname
+    pub fn get(&self, nickname: &str) -> Option<&Lib> {
+        self.libs.iter().find(|l| l.name == nickname)
+    }
+
+    /// load the symbol library of a nickname, expanding its URI with the
+    /// environment
+    pub fn library(&self, nickname: &str) -> Result<SymbolLib, KicadError> {
+        self.library_with(nickname, &ExpandContext::default())
+    }
+
+    /// load the symbol library of a nickname, expanding its URI in a context
+    pub fn library_with(
+        &self,
+        nickname: &str,
+        context: &ExpandContext,
+    ) -> Result<SymbolLib, KicadError> {
+        let lib = match self.get(nickname) {
+            Some(lib) => lib,
+            None => {
+                let e = format!("library {} not found in sym-lib-table", nickname);
+                return Err(e.into());
+            }
+        };
+        // legacy `.lib` files and `.kicad_sym` files since Kicad 6
+        if !lib.type_.eq_ignore_ascii_case("legacy") && !lib.type_.eq_ignore_ascii_case("kicad") {
+            let e = format!("library {} of type {} is not supported", nickname, lib.type_);
+            return Err(e.into());
+        }
+        read_symbol_lib(Path::new(&lib.expand_uri(context)?))
+    }
+
+    /// load a symbol from a `lib:symbol` identifier like the name of a
+    /// component in a schematic
+    pub fn resolve(&self, id: &str) -> Result<Symbol, KicadError> {
+        self.resolve_with(id, &ExpandContext::default())
+    }
+
+    /// load a symbol from a `lib:symbol` identifier, expanding the URI of the
+    /// library in a context
+    pub fn resolve_with(&self, id: &str, context: &ExpandContext) -> Result<Symbol, KicadError> {
+        let (nickname, name) = match id.find(':') {
+            Some(i) => (&id[..i], &id[i + 1..]),
+            None => return Err(format!("symbol {} has no library", id).into()),
+        };
+        let lib = self.library_with(nickname, context)?;
+        match lib.find(|s| s.name == name || s.aliases.iter().any(|a| a == name)) {
+            Some(symbol) => Ok(symbol.clone()),
+            None => Err(format!("no symbol {} in library {}", name, nickname).into()),
+        }
+    }
+}
+
+impl IntoSexp for SymLibTable {
+    fn into_sexp(&self) -> Sexp {
+        let mut v = Sexp::start("sym_lib_table");
+        for e in &self.libs {
+            v.push(e.into_sexp())
+        }
+        v
+    }
+}
+
+impl FromSexp for SymLibTable {
+    fn from_sexp(s: &Sexp) -> Result<SymLibTable, SexpError> {
+        let mut i = IterAtom::new(s, "sym_lib_table")?;
+        let libs = i.vec()?;
+        Ok(SymLibTable { libs })
+    }
+}
+
+/// parse a &str to a `SymLibTable`
+pub fn parse(s: &str) -> Result<SymLibTable, SexpError> {
+    let t = symbolic_expressions::parser::parse_str(s)?;
+    let s = symbolic_expressions::from_sexp(&t)?;
+    Ok(s)
+}
+
+/// convert a `SymLibTable` to a formatted symbolic-expressions String
+pub fn to_string(sym_lib_table: &SymLibTable, indent_level: i64) -> Result<String, KicadError> {
+    let formatter = KicadFormatter::new(indent_level);
+    symbolic_expressions::ser::to_string_with_formatter(&sym_lib_table.into_sexp(), formatter)
+        .map_err(From::from)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use expand::ExpandContext;
+    use sym_lib_table::parse;
+
+    #[test]
+    fn resolve() {
+        let table = parse(
+            "(sym_lib_table
+  (lib (name project)(type Legacy)(uri ${KIPRJMOD}/symbol_lib1-cache.lib)(options \"\")(descr \"\"))
+  (lib (name eeschema)(type Eeschema)(uri ${KIPRJMOD}/symbol_lib1-cache.lib)(options \"\")(descr \"\"))
+)",
+        ).unwrap();
+        let context = ExpandContext::for_project(Path::new("tests/data"));
+        assert_eq!(table.resolve_with("project:R", &context).unwrap().name, "R");
+        // an alias resolves to the symbol that has it
+        let symbol = table.resolve_with("project:BARNEY", &context).unwrap();
+        assert_eq!(symbol.name, "ST-USBLC6-2");
+        assert!(table.resolve_with("project:C", &context).is_err());
+        assert!(table.resolve_with("missing:R", &context).is_err());
+        assert!(table.resolve_with("eeschema:R", &context).is_err());
+        assert!(table.resolve_with("R", &context).is_err());
+    }
+}
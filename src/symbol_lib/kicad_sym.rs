@@ -0,0 +1,836 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+// extension: .kicad_sym
+// format: new-style
+
+//! Kicad 6 and later symbol libraries
+//!
+//! the s-expression library is mapped on the same `SymbolLib` as the legacy
+//! format, with the coordinates converted from mm to mils, so that a library
+//! can be converted between both formats and the KLC checks work on either.
+//! The drawing of every unit and body style is a sub-symbol named like
+//! `R_1_1`. A derived symbol gets a copy of the drawing of the symbol it
+//! extends, legacy aliases are written as derived symbols. The keywords and
+//! description properties and other elements without a place in the legacy
+//! model are kept as is.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use symbolic_expressions;
+use symbolic_expressions::{Sexp, SexpError};
+use formatter::KicadFormatter;
+use sexpr::{at, at_angle, child, effects_sexp, elements, flag, legacy_field_name, name_of, number,
+            parse_effects, property_id, property_name, quoted, requote, string, to_mils, to_mm,
+            write_back, xy, Effects};
+
+use schematic::Orientation;
+use symbol_lib::{Arc, Circle, Draw, Field, Fill, Pin, PinOrientation, PinShape, PinType,
+                 Polyline, Rectangle, Symbol, SymbolLib, Text};
+use {str_error, KicadError};
+
+// the file format version that is written
+const VERSION: &str = "20211014";
+
+// the offset of the pin names in mils when the library doesn't specify it
+const PIN_NAME_OFFSET: f64 = 40.0;
+
+const PIN_TYPES: &[(PinType, &str)] = &[
+    (PinType::Input, "input"),
+    (PinType::Output, "output"),
+    (PinType::Bidi, "bidirectional"),
+    (PinType::Tristate, "tri_state"),
+    (PinType::Passive, "passive"),
+    (PinType::Unspecified, "unspecified"),
+    (PinType::PowerInput, "power_in"),
+    (PinType::PowerOutput, "power_out"),
+    (PinType::OpenCollector, "open_collector"),
+    (PinType::OpenEmitter, "open_emitter"),
+    (PinType::NotConnected, "no_connect"),
+    // a free pin has no legacy equivalent
+    (PinType::Unspecified, "free"),
+];
+
+const PIN_SHAPES: &[(PinShape, &str)] = &[
+    (PinShape::Line, "line"),
+    (PinShape::Inverted, "inverted"),
+    (PinShape::Clock, "clock"),
+    (PinShape::InvertedClock, "inverted_clock"),
+    (PinShape::InputLow, "input_low"),
+    (PinShape::ClockLow, "clock_low"),
+    (PinShape::OutputLow, "output_low"),
+    (PinShape::FallingEdgeClock, "edge_clock_high"),
+    (PinShape::NonLogic, "non_logic"),
+];
+
+/// is `s` a symbol library in the s-expression format
+pub fn is_kicad_sym(s: &str) -> bool {
+    s.trim_start().starts_with("(kicad_symbol_lib")
+}
+
+/// parse a &str in the s-expression format to a symbol lib
+pub fn parse(s: &str) -> Result<SymbolLib, KicadError> {
+    let t = symbolic_expressions::parser::parse_str(s)?;
+    let symbols: Vec<Sexp> = elements(&t, "kicad_symbol_lib")?
+        .into_iter()
+        .filter(|e| name_of(e) == "symbol")
+        .cloned()
+        .collect();
+    parse_symbols(&symbols)
+}
+
+/// parse a list of `symbol` elements, like the `lib_symbols` embedded in a
+/// schematic, to a symbol lib
+pub fn parse_symbols(symbols: &[Sexp]) -> Result<SymbolLib, KicadError> {
+    let mut lib = SymbolLib::default();
+    for s in symbols {
+        lib.symbols.push(parse_symbol(s)?)
+    }
+    for i in 0..lib.symbols.len() {
+        let parent = match lib.symbols[i].extends {
+            Some(ref name) => match lib.symbols.iter().find(|s| &s.name == name) {
+                Some(parent) => parent.clone(),
+                None => return str_error(format!("unknown symbol {} to extend", name)),
+            },
+            None => continue,
+        };
+        let symbol = &mut lib.symbols[i];
+        symbol.text_offset = parent.text_offset;
+        symbol.draw_pinnumber = parent.draw_pinnumber;
+        symbol.draw_pinname = parent.draw_pinname;
+        symbol.unit_count = parent.unit_count;
+        symbol.unit_locked = parent.unit_locked;
+        symbol.is_power = parent.is_power;
+        symbol.draw = parent.draw;
+    }
+    Ok(lib)
+}
+
+/// convert a `SymbolLib` to a formatted s-expression String
+pub fn to_string(lib: &SymbolLib, indent_level: i64) -> Result<String, KicadError> {
+    let formatter = KicadFormatter::new(indent_level);
+    let mut s = symbolic_expressions::ser::to_string_with_formatter(&lib_sexp(lib), formatter)?;
+    s.push('\n');
+    Ok(s)
+}
+
+fn parse_field(s: &Sexp, index: i64) -> Result<Field, SexpError> {
+    let name = string(s, 1)?;
+    let (x, y, angle) = at(s)?;
+    let effects = parse_effects(s)?;
+    let (hjustify, vjustify) = effects.justification();
+    let i = property_id(s, &name, index)?;
+    Ok(Field {
+        i,
+        value: string(s, 2)?,
+        x: x as f64,
+        y: y as f64,
+        dimension: effects.size,
+        orientation: if angle == 90 || angle == 270 {
+            Orientation::Vertical
+        } else {
+            Orientation::Horizontal
+        },
+        visible: !effects.hide,
+        hjustify,
+        vjustify,
+        italic: effects.italic,
+        bold: effects.bold,
+        name: legacy_field_name(i, name),
+    })
+}
+
+fn parse_symbol(s: &Sexp) -> Result<Symbol, SexpError> {
+    let mut symbol = Symbol::new(string(s, 1)?, String::new());
+    symbol.text_offset = PIN_NAME_OFFSET;
+    symbol.draw_pinnumber = true;
+    symbol.draw_pinname = true;
+    let mut index = 0;
+    for e in elements(s, "symbol")? {
+        match name_of(e) {
+            "" => (),
+            "extends" => symbol.extends = Some(string(e, 1)?),
+            "power" => symbol.is_power = true,
+            "unit_locked" => symbol.unit_locked = true,
+            "pin_numbers" => symbol.draw_pinnumber = !flag(e, "hide"),
+            "pin_names" => {
+                if let Some(offset) = child(e, "offset") {
+                    symbol.text_offset = to_mils(number(offset, 1)?) as f64
+                }
+                symbol.draw_pinname = !flag(e, "hide")
+            }
+            "property" if string(e, 1)?.starts_with("ki_") => symbol.other.push(e.clone()),
+            "property" => {
+                let field = parse_field(e, index)?;
+                if field.i == 0 {
+                    symbol.reference = field.value.clone()
+                }
+                symbol.fields.push(field);
+                index += 1
+            }
+            "symbol" => parse_unit(e, &mut symbol)?,
+            _ => symbol.other.push(e.clone()),
+        }
+    }
+    symbol.fields.sort_by_key(|f| f.i);
+    let units = symbol.draw.iter().filter_map(unit_of).map(|(unit, _)| unit).max();
+    symbol.unit_count = units.unwrap_or(1).max(1);
+    symbol.sexp = Some(s.clone());
+    Ok(symbol)
+}
+
+// a sub-symbol like `R_1_1` with the drawing of a unit and body style
+fn parse_unit(s: &Sexp, symbol: &mut Symbol) -> Result<(), SexpError> {
+    let name = string(s, 1)?;
+    let mut parts = name.rsplitn(3, '_').map(|p| p.parse::<i64>());
+    let (unit, convert) = match (parts.next(), parts.next()) {
+        (Some(Ok(convert)), Some(Ok(unit))) => (unit, convert),
+        _ => return Err(format!("unexpected unit name {}", name).into()),
+    };
+    for e in elements(s, "symbol")? {
+        let draw = match name_of(e) {
+            "" => continue,
+            "pin" => Draw::Pin(parse_pin(e, unit, convert)?),
+            "rectangle" => Draw::Rectangle(Rectangle {
+                x1: point(e, "start")?.0,
+                y1: point(e, "start")?.1,
+                x2: point(e, "end")?.0,
+                y2: point(e, "end")?.1,
+                unit,
+                convert,
+                thickness: stroke_width(e)?,
+                fill: parse_fill(e)?,
+            }),
+            "polyline" => {
+                let mut points = vec![];
+                if let Some(pts) = child(e, "pts") {
+                    for xy in elements(pts, "pts")? {
+                        points.push((to_mils(number(xy, 1)?), to_mils(number(xy, 2)?)))
+                    }
+                }
+                Draw::Polyline(Polyline {
+                    points,
+                    unit,
+                    convert,
+                    thickness: stroke_width(e)?,
+                    fill: parse_fill(e)?,
+                })
+            }
+            "circle" => Draw::Circle(Circle {
+                x: point(e, "center")?.0,
+                y: point(e, "center")?.1,
+                radius: to_mils(number(child(e, "radius").unwrap_or(e), 1)?),
+                unit,
+                convert,
+                thickness: stroke_width(e)?,
+                fill: parse_fill(e)?,
+            }),
+            "arc" => Draw::Arc(parse_arc(e, unit, convert)?),
+            "text" => Draw::Text(parse_text(e, unit, convert)?),
+            // kept as is, in the sub-symbol of the item before it
+            _ => Draw::Other(format!("{}", requote(e))),
+        };
+        symbol.draw.push(draw)
+    }
+    Ok(())
+}
+
+// a point like `(start 1.27 2.54)` in mils
+fn point(s: &Sexp, name: &str) -> Result<(i64, i64), SexpError> {
+    match child(s, name) {
+        Some(p) => Ok((to_mils(number(p, 1)?), to_mils(number(p, 2)?))),
+        None => Err(format!("missing {} in {}", name, s).into()),
+    }
+}
+
+fn stroke_width(s: &Sexp) -> Result<i64, SexpError> {
+    match child(s, "stroke").and_then(|stroke| child(stroke, "width")) {
+        Some(width) => Ok(to_mils(number(width, 1)?)),
+        None => Ok(0),
+    }
+}
+
+fn parse_fill(s: &Sexp) -> Result<Fill, SexpError> {
+    let fill = match child(s, "fill").and_then(|fill| child(fill, "type")) {
+        Some(t) => string(t, 1)?,
+        None => return Ok(Fill::Transparent),
+    };
+    match &fill[..] {
+        "outline" => Ok(Fill::FilledForeground),
+        "background" => Ok(Fill::FilledBackground),
+        _ => Ok(Fill::Transparent),
+    }
+}
+
+fn parse_pin(s: &Sexp, unit: i64, convert: i64) -> Result<Pin, SexpError> {
+    let pin_type = string(s, 1)?;
+    let pin_shape = string(s, 2)?;
+    let (x, y, angle) = at(s)?;
+    let (name, number_) = match (child(s, "name"), child(s, "number")) {
+        (Some(name), Some(number_)) => (name, number_),
+        _ => return Err(format!("missing name or number in {}", s).into()),
+    };
+    Ok(Pin {
+        name: string(name, 1)?,
+        number: string(number_, 1)?,
+        x,
+        y,
+        len: match child(s, "length") {
+            Some(len) => to_mils(number(len, 1)?),
+            None => 0,
+        },
+        orientation: match angle {
+            90 => PinOrientation::Up,
+            180 => PinOrientation::Left,
+            270 => PinOrientation::Down,
+            _ => PinOrientation::Right,
+        },
+        num_size: parse_effects(number_)?.size,
+        name_size: parse_effects(name)?.size,
+        unit,
+        convert,
+        pin_type: match PIN_TYPES.iter().find(|t| t.1 == pin_type) {
+            Some(t) => t.0.clone(),
+            None => return Err(format!("unknown pin type {}", pin_type).into()),
+        },
+        pin_visible: !flag(s, "hide"),
+        pin_shape: match PIN_SHAPES.iter().find(|t| t.1 == pin_shape) {
+            Some(t) => t.0.clone(),
+            None => return Err(format!("unknown pin shape {}", pin_shape).into()),
+        },
+    })
+}
+
+// angle of a point seen from a center in tenths of a degree
+fn decidegrees(center: (f64, f64), p: (f64, f64)) -> f64 {
+    (p.1 - center.1).atan2(p.0 - center.0) * 1800.0 / PI
+}
+
+// an arc through a start, mid and end point; the legacy format has the
+// center, radius and angles of the start and end point
+fn parse_arc(s: &Sexp, unit: i64, convert: i64) -> Result<Arc, SexpError> {
+    let start = point(s, "start")?;
+    let end = point(s, "end")?;
+    let (center, radius, start_angle, end_angle) = if child(s, "mid").is_some() {
+        let (a, b, c) = (start, point(s, "mid")?, end);
+        let (ax, ay, bx, by, cx, cy) = (
+            a.0 as f64,
+            a.1 as f64,
+            b.0 as f64,
+            b.1 as f64,
+            c.0 as f64,
+            c.1 as f64,
+        );
+        let d = 2.0 * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+        if d == 0.0 {
+            return Err(format!("arc without curve in {}", s).into());
+        }
+        let (a2, b2, c2) = (ax * ax + ay * ay, bx * bx + by * by, cx * cx + cy * cy);
+        let center = (
+            (a2 * (by - cy) + b2 * (cy - ay) + c2 * (ay - by)) / d,
+            (a2 * (cx - bx) + b2 * (ax - cx) + c2 * (bx - ax)) / d,
+        );
+        let radius = (ax - center.0).hypot(ay - center.1);
+        let t1 = decidegrees(center, (ax, ay));
+        let tm = (decidegrees(center, (bx, by)) - t1).rem_euclid(3600.0);
+        let t2 = (decidegrees(center, (cx, cy)) - t1).rem_euclid(3600.0);
+        // the angles run counterclockwise if the mid point comes first that way
+        let t2 = if tm < t2 { t1 + t2 } else { t1 - (3600.0 - t2) };
+        (center, radius, t1, t2)
+    } else {
+        // the format before Kicad 6 has `(radius (at x y) (length r) (angles a b))`
+        let r = match child(s, "radius") {
+            Some(r) => r,
+            None => return Err(format!("missing mid point in {}", s).into()),
+        };
+        let c = point(r, "at")?;
+        let angles = match child(r, "angles") {
+            Some(angles) => (number(angles, 1)? * 10.0, number(angles, 2)? * 10.0),
+            None => return Err(format!("missing angles in {}", s).into()),
+        };
+        let length = match child(r, "length") {
+            Some(length) => to_mils(number(length, 1)?) as f64,
+            None => return Err(format!("missing length in {}", s).into()),
+        };
+        ((c.0 as f64, c.1 as f64), length, angles.0, angles.1)
+    };
+    Ok(Arc {
+        x: center.0.round() as i64,
+        y: center.1.round() as i64,
+        radius: radius.round() as i64,
+        start_angle: start_angle.round() as i64,
+        end_angle: end_angle.round() as i64,
+        unit,
+        convert,
+        thickness: stroke_width(s)?,
+        fill: parse_fill(s)?,
+        start_x: start.0,
+        start_y: start.1,
+        end_x: end.0,
+        end_y: end.1,
+    })
+}
+
+fn parse_text(s: &Sexp, unit: i64, convert: i64) -> Result<Text, SexpError> {
+    // the angle of a text in a symbol is in tenths of a degree
+    let (x, y, orientation) = at(s)?;
+    let effects = parse_effects(s)?;
+    let (hjustify, vjustify) = effects.justification();
+    Ok(Text {
+        orientation,
+        x,
+        y,
+        dimension: effects.size,
+        visible: !effects.hide,
+        unit,
+        convert,
+        text: string(s, 1)?,
+        italic: effects.italic,
+        bold: effects.bold,
+        hjustify,
+        vjustify,
+    })
+}
+
+fn unit_of(draw: &Draw) -> Option<(i64, i64)> {
+    match *draw {
+        Draw::Pin(ref p) => Some((p.unit, p.convert)),
+        Draw::Rectangle(ref r) => Some((r.unit, r.convert)),
+        Draw::Polyline(ref p) => Some((p.unit, p.convert)),
+        Draw::Circle(ref c) => Some((c.unit, c.convert)),
+        Draw::Arc(ref a) => Some((a.unit, a.convert)),
+        Draw::Text(ref t) => Some((t.unit, t.convert)),
+        Draw::Other(_) => None,
+    }
+}
+
+fn field_sexp(field: &Field) -> Sexp {
+    let mut v = Sexp::start("property");
+    v.push(quoted(property_name(field.i, &field.name)));
+    v.push(quoted(&field.value));
+    v.push(("id", &field.i));
+    let angle = match field.orientation {
+        Orientation::Horizontal => 0,
+        Orientation::Vertical => 90,
+    };
+    v.push(at_angle(field.x.round() as i64, field.y.round() as i64, angle));
+    let mut effects = Effects {
+        size: field.dimension,
+        italic: field.italic,
+        bold: field.bold,
+        hide: !field.visible,
+        ..Effects::default()
+    };
+    effects.set_justification(&field.hjustify, &field.vjustify);
+    v.push(effects_sexp(&effects));
+    v
+}
+
+fn stroke_sexp(thickness: i64) -> Sexp {
+    let mut v = Sexp::start("stroke");
+    v.push(("width", &to_mm(thickness as f64)));
+    v.push(("type", &"default"));
+    let mut color = Sexp::start("color");
+    for _ in 0..4 {
+        color.push(0)
+    }
+    v.push(color);
+    v
+}
+
+fn fill_sexp(fill: &Fill) -> Sexp {
+    let t = match *fill {
+        Fill::FilledForeground => "outline",
+        Fill::FilledBackground => "background",
+        Fill::Transparent => "none",
+    };
+    let mut v = Sexp::start("fill");
+    v.push(("type", &t));
+    v
+}
+
+fn pin_sexp(pin: &Pin) -> Sexp {
+    let mut v = Sexp::start("pin");
+    v.push(PIN_TYPES.iter().find(|t| t.0 == pin.pin_type).map_or("passive", |t| t.1));
+    v.push(PIN_SHAPES.iter().find(|t| t.0 == pin.pin_shape).map_or("line", |t| t.1));
+    let angle = match pin.orientation {
+        PinOrientation::Right => 0,
+        PinOrientation::Up => 90,
+        PinOrientation::Left => 180,
+        PinOrientation::Down => 270,
+    };
+    v.push(at_angle(pin.x, pin.y, angle));
+    v.push(("length", &to_mm(pin.len as f64)));
+    if !pin.pin_visible {
+        v.push("hide")
+    }
+    for &(name, text, size) in &[
+        ("name", &pin.name, pin.name_size),
+        ("number", &pin.number, pin.num_size),
+    ] {
+        let mut e = Sexp::start(name);
+        e.push(quoted(text));
+        e.push(effects_sexp(&Effects {
+            size,
+            ..Effects::default()
+        }));
+        v.push(e)
+    }
+    v
+}
+
+fn arc_sexp(arc: &Arc) -> Sexp {
+    let mid = (arc.start_angle + arc.end_angle) as f64 / 2.0 * PI / 1800.0;
+    let mid_x = arc.x as f64 + arc.radius as f64 * mid.cos();
+    let mid_y = arc.y as f64 + arc.radius as f64 * mid.sin();
+    let mut v = Sexp::start("arc");
+    v.push(xy("start", arc.start_x, arc.start_y));
+    v.push(xy("mid", mid_x.round() as i64, mid_y.round() as i64));
+    v.push(xy("end", arc.end_x, arc.end_y));
+    v.push(stroke_sexp(arc.thickness));
+    v.push(fill_sexp(&arc.fill));
+    v
+}
+
+fn text_sexp(text: &Text) -> Sexp {
+    let mut v = Sexp::start("text");
+    v.push(quoted(&text.text));
+    v.push(at_angle(text.x, text.y, text.orientation));
+    let mut effects = Effects {
+        size: text.dimension,
+        italic: text.italic,
+        bold: text.bold,
+        hide: !text.visible,
+        ..Effects::default()
+    };
+    effects.set_justification(&text.hjustify, &text.vjustify);
+    v.push(effects_sexp(&effects));
+    v
+}
+
+fn draw_sexp(draw: &Draw) -> Option<Sexp> {
+    let v = match *draw {
+        Draw::Pin(ref pin) => pin_sexp(pin),
+        Draw::Rectangle(ref rect) => {
+            let mut v = Sexp::start("rectangle");
+            v.push(xy("start", rect.x1, rect.y1));
+            v.push(xy("end", rect.x2, rect.y2));
+            v.push(stroke_sexp(rect.thickness));
+            v.push(fill_sexp(&rect.fill));
+            v
+        }
+        Draw::Polyline(ref poly) => {
+            let mut v = Sexp::start("polyline");
+            let mut pts = Sexp::start("pts");
+            for &(x, y) in &poly.points {
+                pts.push(xy("xy", x, y))
+            }
+            v.push(pts);
+            v.push(stroke_sexp(poly.thickness));
+            v.push(fill_sexp(&poly.fill));
+            v
+        }
+        Draw::Circle(ref circle) => {
+            let mut v = Sexp::start("circle");
+            v.push(xy("center", circle.x, circle.y));
+            v.push(("radius", &to_mm(circle.radius as f64)));
+            v.push(stroke_sexp(circle.thickness));
+            v.push(fill_sexp(&circle.fill));
+            v
+        }
+        Draw::Arc(ref arc) => arc_sexp(arc),
+        Draw::Text(ref text) => text_sexp(text),
+        // only the elements kept from an s-expression library, the legacy
+        // drawing parts that aren't parsed can't be converted
+        Draw::Other(ref s) if s.starts_with('(') => {
+            return symbolic_expressions::parser::parse_str(s).ok()
+        }
+        Draw::Other(_) => return None,
+    };
+    Some(v)
+}
+
+fn symbol_sexp(symbol: &Symbol) -> Sexp {
+    let mut v = Sexp::start("symbol");
+    v.push(quoted(&symbol.name));
+    let derived = symbol.extends.is_some();
+    if let Some(ref parent) = symbol.extends {
+        let mut e = Sexp::start("extends");
+        e.push(quoted(parent));
+        v.push(e)
+    } else {
+        if symbol.is_power {
+            v.push(Sexp::start("power"))
+        }
+        if symbol.unit_locked {
+            v.push(Sexp::start("unit_locked"))
+        }
+        if !symbol.draw_pinnumber {
+            let mut e = Sexp::start("pin_numbers");
+            e.push("hide");
+            v.push(e)
+        }
+        if symbol.text_offset != PIN_NAME_OFFSET || !symbol.draw_pinname {
+            let mut e = Sexp::start("pin_names");
+            if symbol.text_offset != PIN_NAME_OFFSET {
+                e.push(("offset", &to_mm(symbol.text_offset)))
+            }
+            if !symbol.draw_pinname {
+                e.push("hide")
+            }
+            v.push(e)
+        }
+    }
+    let (properties, other): (Vec<&Sexp>, Vec<&Sexp>) =
+        symbol.other.iter().partition(|e| name_of(e) == "property");
+    if other.is_empty() && !derived {
+        v.push(("in_bom", &"yes"));
+        v.push(("on_board", &"yes"));
+    }
+    for e in other {
+        v.push(requote(e))
+    }
+    for field in &symbol.fields {
+        v.push(field_sexp(field))
+    }
+    for e in properties {
+        v.push(requote(e))
+    }
+    if derived {
+        return v;
+    }
+    // the sub-symbols are named without the library of the symbol
+    let name = symbol.name.rsplit(':').next().unwrap_or("");
+    let mut units: BTreeMap<(i64, i64), Vec<Sexp>> = BTreeMap::new();
+    let mut unit = (0, 1);
+    for draw in &symbol.draw {
+        unit = unit_of(draw).unwrap_or(unit);
+        if let Some(e) = draw_sexp(draw) {
+            units.entry(unit).or_default().push(e)
+        }
+    }
+    for ((u, c), draws) in units {
+        let mut e = Sexp::start("symbol");
+        e.push(quoted(&format!("{}_{}_{}", name, u, c)));
+        for draw in draws {
+            e.push(draw)
+        }
+        v.push(e)
+    }
+    v
+}
+
+// a legacy alias as a symbol derived from the symbol that has it
+fn alias_sexp(symbol: &Symbol, alias: &str) -> Sexp {
+    let mut derived = Symbol::new(alias.to_string(), symbol.reference.clone());
+    derived.extends = Some(symbol.name.clone());
+    derived.fields = symbol.fields.clone();
+    for field in &mut derived.fields {
+        if field.i == 1 {
+            field.value = alias.to_string()
+        }
+    }
+    symbol_sexp(&derived)
+}
+
+fn lib_sexp(lib: &SymbolLib) -> Sexp {
+    let mut v = Sexp::start("kicad_symbol_lib");
+    v.push(("version", &VERSION));
+    v.push(("generator", &"kicad_symbol_editor"));
+    for symbol in &lib.symbols {
+        // written over the expression the symbol was read from, which keeps
+        // what has no place in the legacy model, like the positions that are
+        // off the mil grid and the alternate functions of the pins
+        let after = symbol_sexp(symbol);
+        match symbol.sexp.as_ref().map(|o| (o, parse_symbol(o))) {
+            Some((o, Ok(before))) => v.push(write_back(o, &symbol_sexp(&before), &after)),
+            _ => v.push(after),
+        }
+        for alias in &symbol.aliases {
+            v.push(alias_sexp(symbol, alias))
+        }
+    }
+    v
+}
+
+#[cfg(test)]
+mod test {
+    use checkfix::{CheckFix, Config};
+    use symbol_lib;
+    use symbol_lib::kicad_sym::{parse, to_string};
+    use symbol_lib::{Draw, Fill, PinOrientation, PinType};
+
+    const LIB: &str = r#"(kicad_symbol_lib (version 20211014) (generator kicad_symbol_editor)
+  (symbol "OPAMP" (pin_names (offset 0.254)) (in_bom yes) (on_board yes)
+    (property "Reference" "U" (id 0) (at 0 5.08 0)
+      (effects (font (size 1.27 1.27)) (justify left))
+    )
+    (property "Value" "OPAMP" (id 1) (at 0 -5.1 0)
+      (effects (font (size 1.27 1.27)) (justify left))
+    )
+    (property "Footprint" "" (id 2) (at 0 0 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (property "Datasheet" "~" (id 3) (at 0 0 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (property "ki_keywords" "dual opamp" (id 4) (at 0 0 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (symbol "OPAMP_0_1"
+      (polyline
+        (pts (xy -5.08 5.08) (xy 5.08 0) (xy -5.08 -5.08) (xy -5.08 5.08))
+        (stroke (width 0.254) (type default) (color 0 0 0 0))
+        (fill (type background))
+      )
+      (circle (center 0 0) (radius 0.508)
+        (stroke (width 0.1) (type dash) (color 0 0 0 0))
+        (fill (type outline))
+      )
+      (arc (start -1.27 0) (mid 0 1.27) (end 1.27 0)
+        (stroke (width 0) (type default) (color 0 0 0 0))
+        (fill (type none))
+      )
+      (text "A B" (at 0 2.54 900)
+        (effects (font (size 1.27 1.27)))
+      )
+    )
+    (symbol "OPAMP_1_1"
+      (pin input line (at -7.62 2.54 0) (length 2.54)
+        (name "+" (effects (font (size 1.27 1.27))))
+        (number "3" (effects (font (size 1.27 1.27))))
+        (alternate "IN+" input line)
+      )
+      (pin output line (at 7.6 0 180) (length 2.5)
+        (name "~" (effects (font (size 1.27 1.27) bold)))
+        (number "1" (effects (font (size 1 1))))
+      )
+      (pin free line (at 0 -7.62 90) (length 2.54)
+        (name "~" (effects (font (size 1.27 1.27))))
+        (number "8" (effects (font (size 1.27 1.27))))
+      )
+    )
+    (symbol "OPAMP_2_1"
+      (pin input inverted (at -7.62 -2.54 0) (length 2.54) hide
+        (name "-" (effects (font (size 1.27 1.27))))
+        (number "6" (effects (font (size 1.27 1.27))))
+      )
+    )
+  )
+  (symbol "LM358" (extends "OPAMP")
+    (property "Reference" "U" (id 0) (at 0 5.08 0)
+      (effects (font (size 1.27 1.27)) (justify left))
+    )
+    (property "Value" "LM358" (id 1) (at 0 -5.08 0)
+      (effects (font (size 1.27 1.27)) (justify left))
+    )
+  )
+)
+"#;
+
+    const LEGACY: &str = r#"EESchema-LIBRARY Version 2.3
+#encoding utf-8
+#
+# R
+#
+DEF R R 0 0 N Y 1 F N
+F0 "R" 80 0 50 V V C CNN
+F1 "R" 0 0 50 V V C CNN
+F2 "" -70 0 50 V I C CNN
+F3 "" 0 0 50 H I C CNN
+DRAW
+S -40 -100 40 100 0 1 10 N
+P 2 0 1 0  0 100  0 150 N
+C 0 0 20 0 1 0 F
+A 0 -50 50 1800 0 0 1 10 N -50 -50 50 -50
+T 900 0 -80 50 0 0 1 R Normal 0 C C
+X ~ 1 0 150 50 D 50 50 1 1 P
+X ~ 2 0 -150 50 U 50 50 1 1 P
+ENDDRAW
+ENDDEF
+#
+#End Library
+"#;
+
+    #[test]
+    fn kicad_sym_parse() {
+        let lib = parse(LIB).unwrap();
+        assert_eq!(lib.symbols.len(), 2);
+        let opamp = &lib.symbols[0];
+        assert_eq!(opamp.reference, "U");
+        assert_eq!(opamp.unit_count, 2);
+        assert_eq!(opamp.text_offset, 10.0);
+        assert!(opamp.draw_pinname && opamp.draw_pinnumber);
+        assert_eq!(opamp.fields.len(), 4);
+        assert_eq!(opamp.fields[1].value, "OPAMP");
+        assert_eq!(opamp.fields[0].y, 200.0);
+        assert!(!opamp.fields[2].visible);
+        assert_eq!(opamp.other.len(), 3);
+        let pins = opamp.pins();
+        assert_eq!(pins.len(), 4);
+        assert_eq!(pins[0].number, "3");
+        assert_eq!((pins[0].x, pins[0].y, pins[0].len), (-300, 100, 100));
+        assert_eq!(pins[0].orientation, PinOrientation::Right);
+        assert_eq!(pins[1].pin_type, PinType::Output);
+        assert_eq!(pins[1].orientation, PinOrientation::Left);
+        assert_eq!(pins[2].pin_type, PinType::Unspecified);
+        assert_eq!((pins[3].unit, pins[3].convert), (2, 1));
+        assert!(!pins[3].pin_visible);
+        match opamp.draw[1] {
+            Draw::Circle(ref c) => {
+                assert_eq!((c.x, c.y, c.radius), (0, 0, 20));
+                assert_eq!(c.fill, Fill::FilledForeground)
+            }
+            ref d => panic!("unexpected {:?}", d),
+        }
+        match opamp.draw[2] {
+            Draw::Arc(ref a) => {
+                assert_eq!((a.x, a.y, a.radius), (0, 0, 50));
+                assert_eq!((a.start_angle, a.end_angle), (1800, 0));
+            }
+            ref d => panic!("unexpected {:?}", d),
+        }
+        // a derived symbol has the drawing of its parent
+        let lm358 = &lib.symbols[1];
+        assert_eq!(lm358.extends, Some("OPAMP".into()));
+        assert_eq!(lm358.fields[1].value, "LM358");
+        assert_eq!(lm358.draw.len(), opamp.draw.len());
+        assert_eq!(lm358.unit_count, 2);
+    }
+
+    #[test]
+    fn kicad_sym_round_trip() {
+        let lib = parse(LIB).unwrap();
+        assert_eq!(to_string(&lib, 0).unwrap(), LIB);
+    }
+
+    #[test]
+    fn kicad_sym_legacy() {
+        let legacy = symbol_lib::parse_str(LEGACY).unwrap();
+        let s = to_string(&legacy, 0).unwrap();
+        assert!(s.contains("(symbol \"R_0_1\""));
+        assert!(s.contains("(pin_numbers hide) (pin_names (offset 0))"));
+        let lib = parse(&s).unwrap();
+        assert_eq!(format!("{}", lib), LEGACY);
+        // aliases become derived symbols
+        let mut aliased = symbol_lib::parse_str(LEGACY).unwrap();
+        aliased.symbols[0].aliases.push("R_Small".into());
+        let lib = parse(&to_string(&aliased, 0).unwrap()).unwrap();
+        assert_eq!(lib.symbols.len(), 2);
+        assert_eq!(lib.symbols[1].name, "R_Small");
+        assert_eq!(lib.symbols[1].fields[1].value, "R_Small");
+        assert_eq!(lib.symbols[1].pins().len(), 2);
+    }
+
+    #[test]
+    fn kicad_sym_klc() {
+        let lib = parse(LIB).unwrap();
+        let config = Config::klc();
+        // only NC pins should be hidden; the pin that is off the grid and has
+        // a small number is reported too
+        let pins = lib.symbols[0].pins();
+        let checks: Vec<usize> = pins.iter().map(|p| p.check(&config).len()).collect();
+        assert_eq!(checks, vec![0, 4, 0, 1]);
+        assert!(!lib.symbols[1].check(&config).is_empty());
+    }
+}
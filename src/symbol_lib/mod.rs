@@ -15,6 +15,10 @@ use schematic;
 use str_error;
 use checkfix::{self, CheckFix, CheckFixData, Config};
 use KicadError;
+use Sexp;
+
+/// Kicad 6 and later s-expression symbol libraries
+pub mod kicad_sym;
 
 /// a Kicad symbolic file
 #[derive(Debug, Default)]
@@ -49,6 +53,14 @@ pub struct Symbol {
     pub aliases: Vec<String>,
     /// draw
     pub draw: Vec<Draw>,
+    /// the symbol this one is derived from in an s-expression library;
+    /// the drawing is copied from it
+    pub extends: Option<String>,
+    /// other elements of an s-expression library like the keywords, kept as is
+    pub other: Vec<Sexp>,
+    /// the expression of an s-expression library it was read from; what
+    /// didn't change is written back from it
+    pub sexp: Option<Sexp>,
 }
 // F n “text” posx posy dimension orientation visibility hjustify vjustify/italic/bold “name”
 // F0 "#PWR" 0 0 30 H I C CNN
@@ -89,6 +101,14 @@ pub enum Draw {
     Pin(Pin),
     /// a rectangle
     Rectangle(Rectangle),
+    /// a polyline
+    Polyline(Polyline),
+    /// a circle
+    Circle(Circle),
+    /// an arc
+    Arc(Arc),
+    /// a text
+    Text(Text),
     /// a non-parsed drawing part
     Other(String),
 }
@@ -215,6 +235,108 @@ pub struct Rectangle {
     pub fill: Fill,
 }
 
+// P 2 0 1 0  -50 0  50 0 N
+// P count unit convert thickness (posx posy)* cc
+/// draw a polyline
+#[derive(Debug, Clone, Default)]
+pub struct Polyline {
+    /// the points of the polyline
+    pub points: Vec<(i64, i64)>,
+    /// unit ??
+    pub unit: i64,
+    /// convert ???
+    pub convert: i64,
+    /// thickness of the line
+    pub thickness: i64,
+    /// `Fill` of the polyline
+    pub fill: Fill,
+}
+
+// C 0 0 100 0 1 10 N
+// C posx posy radius unit convert thickness cc
+/// draw a circle
+#[derive(Debug, Clone, Default)]
+pub struct Circle {
+    /// x-coordinate of the center
+    pub x: i64,
+    /// y-coordinate of the center
+    pub y: i64,
+    /// radius
+    pub radius: i64,
+    /// unit ??
+    pub unit: i64,
+    /// convert ???
+    pub convert: i64,
+    /// thickness of the line
+    pub thickness: i64,
+    /// `Fill` of the circle
+    pub fill: Fill,
+}
+
+// A 12 0 12 -1799 -1 0 1 0 N 0 0 25 0
+// A posx posy radius start_angle end_angle unit convert thickness cc startx starty endx endy
+/// draw an arc, the angles are in tenths of a degree
+#[derive(Debug, Clone, Default)]
+pub struct Arc {
+    /// x-coordinate of the center
+    pub x: i64,
+    /// y-coordinate of the center
+    pub y: i64,
+    /// radius
+    pub radius: i64,
+    /// angle of the start point
+    pub start_angle: i64,
+    /// angle of the end point
+    pub end_angle: i64,
+    /// unit ??
+    pub unit: i64,
+    /// convert ???
+    pub convert: i64,
+    /// thickness of the line
+    pub thickness: i64,
+    /// `Fill` of the arc
+    pub fill: Fill,
+    /// x-coordinate of the start point
+    pub start_x: i64,
+    /// y-coordinate of the start point
+    pub start_y: i64,
+    /// x-coordinate of the end point
+    pub end_x: i64,
+    /// y-coordinate of the end point
+    pub end_y: i64,
+}
+
+// T 0 0 100 50 0 0 0 text Normal 0 C C
+// T orientation posx posy dimension hidden unit convert text italic bold hjustify vjustify
+/// draw a text, the orientation is in tenths of a degree
+#[derive(Debug, Clone)]
+pub struct Text {
+    /// orientation
+    pub orientation: i64,
+    /// x-coordinate
+    pub x: i64,
+    /// y-coordinate
+    pub y: i64,
+    /// dimension
+    pub dimension: i64,
+    /// if the text is visible
+    pub visible: bool,
+    /// unit ??
+    pub unit: i64,
+    /// convert ???
+    pub convert: i64,
+    /// the text
+    pub text: String,
+    /// italic
+    pub italic: bool,
+    /// bold
+    pub bold: bool,
+    /// horizontal justification
+    pub hjustify: schematic::Justify,
+    /// vertical justification
+    pub vjustify: schematic::Justify,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 /// fill for a rectangle
 pub enum Fill {
@@ -285,6 +407,9 @@ impl Symbol {
             fields: vec![],
             aliases: vec![],
             draw: vec![],
+            extends: None,
+            other: vec![],
+            sexp: None,
         }
     }
 
@@ -414,6 +539,10 @@ impl fmt::Display for Draw {
             Draw::Other(ref s) => write!(f, "{}", s),
             Draw::Pin(ref p) => write!(f, "{}", p),
             Draw::Rectangle(ref p) => write!(f, "{}", p),
+            Draw::Polyline(ref p) => write!(f, "{}", p),
+            Draw::Circle(ref p) => write!(f, "{}", p),
+            Draw::Arc(ref p) => write!(f, "{}", p),
+            Draw::Text(ref p) => write!(f, "{}", p),
         }
     }
 }
@@ -427,6 +556,50 @@ impl fmt::Display for Rectangle {
     }
 }
 
+impl fmt::Display for Polyline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        write!(f, "P {} {} {} {}", self.points.len(), self.unit, self.convert, self.thickness)?;
+        for &(x, y) in &self.points {
+            write!(f, "  {} {}", x, y)?;
+        }
+        write!(f, " {}", self.fill)
+    }
+}
+
+impl fmt::Display for Circle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        write!(f, "C {} {} {} ", self.x, self.y, self.radius)?;
+        write!(f, "{} {} ", self.unit, self.convert)?;
+        write!(f, "{} {}", self.thickness, self.fill)
+    }
+}
+
+impl fmt::Display for Arc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        write!(f, "A {} {} {} ", self.x, self.y, self.radius)?;
+        write!(f, "{} {} ", self.start_angle, self.end_angle)?;
+        write!(f, "{} {} ", self.unit, self.convert)?;
+        write!(f, "{} {} ", self.thickness, self.fill)?;
+        write!(f, "{} {} ", self.start_x, self.start_y)?;
+        write!(f, "{} {}", self.end_x, self.end_y)
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        write!(f, "T {} {} {} ", self.orientation, self.x, self.y)?;
+        write!(f, "{} {} ", self.dimension, if self.visible { 0 } else { 1 })?;
+        write!(f, "{} {} ", self.unit, self.convert)?;
+        if self.text.contains(' ') {
+            write!(f, "\"{}\" ", self.text)?
+        } else {
+            write!(f, "{} ", self.text)?
+        }
+        write!(f, "{} ", if self.italic { "Italic" } else { "Normal" })?;
+        write!(f, "{} {} {}", if self.bold { 1 } else { 0 }, self.hjustify, self.vjustify)
+    }
+}
+
 impl fmt::Display for Fill {
     fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
         match *self {
@@ -717,6 +890,14 @@ fn parse_symbol(p: &mut ParseState) -> Result<Symbol, KicadError> {
         } else if s2.starts_with("S ") {
             let rect = parse_rect(p, &s2)?;
             s.draw.push(Draw::Rectangle(rect));
+        } else if s2.starts_with("P ") {
+            s.draw.push(Draw::Polyline(parse_polyline(p, &s2)?));
+        } else if s2.starts_with("C ") {
+            s.draw.push(Draw::Circle(parse_circle(p, &s2)?));
+        } else if s2.starts_with("A ") {
+            s.draw.push(Draw::Arc(parse_arc(p, &s2)?));
+        } else if s2.starts_with("T ") {
+            s.draw.push(Draw::Text(parse_text(p, &s2)?));
         } else {
             s.draw.push(Draw::Other(s2.clone()));
         }
@@ -816,6 +997,100 @@ fn parse_rect(p: &mut ParseState, line: &str) -> Result<Rectangle, KicadError> {
     Ok(rect)
 }
 
+// P 2 0 1 0  -50 0  50 0 N
+fn parse_polyline(p: &mut ParseState, line: &str) -> Result<Polyline, KicadError> {
+    let mut poly = Polyline::default();
+    let v: Vec<&str> = line.split_whitespace().collect();
+    if v.len() < 5 {
+        return str_error(format!("unexpected elements in {}", line));
+    }
+    let n = i64_from_string(p, v[1])? as usize;
+    if v.len() != 5 + 2 * n && v.len() != 6 + 2 * n {
+        return str_error(format!("unexpected elements in {}", line));
+    }
+    poly.unit = i64_from_string(p, v[2])?;
+    poly.convert = i64_from_string(p, v[3])?;
+    poly.thickness = i64_from_string(p, v[4])?;
+    for i in 0..n {
+        let x = i64_from_string(p, v[5 + 2 * i])?;
+        let y = i64_from_string(p, v[6 + 2 * i])?;
+        poly.points.push((x, y))
+    }
+    if let Some(fill) = v.get(5 + 2 * n) {
+        poly.fill = Fill::make(fill)?
+    }
+    Ok(poly)
+}
+
+// C 0 0 100 0 1 10 N
+fn parse_circle(p: &mut ParseState, line: &str) -> Result<Circle, KicadError> {
+    let v: Vec<&str> = line.split_whitespace().collect();
+    if v.len() != 8 {
+        return str_error(format!("unexpected elements in {}", line));
+    }
+    Ok(Circle {
+        x: i64_from_string(p, v[1])?,
+        y: i64_from_string(p, v[2])?,
+        radius: i64_from_string(p, v[3])?,
+        unit: i64_from_string(p, v[4])?,
+        convert: i64_from_string(p, v[5])?,
+        thickness: i64_from_string(p, v[6])?,
+        fill: Fill::make(v[7])?,
+    })
+}
+
+// A 12 0 12 -1799 -1 0 1 0 N 0 0 25 0
+fn parse_arc(p: &mut ParseState, line: &str) -> Result<Arc, KicadError> {
+    let v: Vec<&str> = line.split_whitespace().collect();
+    if v.len() != 14 {
+        return str_error(format!("unexpected elements in {}", line));
+    }
+    Ok(Arc {
+        x: i64_from_string(p, v[1])?,
+        y: i64_from_string(p, v[2])?,
+        radius: i64_from_string(p, v[3])?,
+        start_angle: i64_from_string(p, v[4])?,
+        end_angle: i64_from_string(p, v[5])?,
+        unit: i64_from_string(p, v[6])?,
+        convert: i64_from_string(p, v[7])?,
+        thickness: i64_from_string(p, v[8])?,
+        fill: Fill::make(v[9])?,
+        start_x: i64_from_string(p, v[10])?,
+        start_y: i64_from_string(p, v[11])?,
+        end_x: i64_from_string(p, v[12])?,
+        end_y: i64_from_string(p, v[13])?,
+    })
+}
+
+// T 0 0 100 50 0 0 0 text Normal 0 C C
+fn parse_text(p: &mut ParseState, line: &str) -> Result<Text, KicadError> {
+    let v = &parse_split_quote_aware(line)?;
+    if v.len() != 9 && v.len() != 13 {
+        return str_error(format!("unexpected elements in {}", line));
+    }
+    let mut text = Text {
+        orientation: f64_from_string(p, &v[1])?.round() as i64,
+        x: i64_from_string(p, &v[2])?,
+        y: i64_from_string(p, &v[3])?,
+        dimension: i64_from_string(p, &v[4])?,
+        visible: bool_from_string(&v[5], "0", "1")?,
+        unit: i64_from_string(p, &v[6])?,
+        convert: i64_from_string(p, &v[7])?,
+        text: v[8].clone(),
+        italic: false,
+        bold: false,
+        hjustify: schematic::Justify::Center,
+        vjustify: schematic::Justify::Center,
+    };
+    if v.len() == 13 {
+        text.italic = v[9] == "Italic";
+        text.bold = v[10] != "0";
+        text.hjustify = schematic::Justify::new(char_at(&v[11], 0))?;
+        text.vjustify = schematic::Justify::new(char_at(&v[12], 0))?;
+    }
+    Ok(text)
+}
+
 fn parse(s: &str) -> Result<SymbolLib, KicadError> {
     let mut lib = SymbolLib::default();
     let v: Vec<&str> = s.lines().collect();
@@ -840,10 +1115,13 @@ pub fn parse_str(s: &str) -> Result<SymbolLib, KicadError> {
     parse(s)
 }
 
-/// parse a file to a symbol lib
+/// parse a file to a symbol lib, in the legacy or the s-expression format
 pub fn parse_file(filename: &PathBuf) -> Result<SymbolLib, KicadError> {
     let name = filename.to_str().unwrap();
     let s = read_file(name)?;
+    if kicad_sym::is_kicad_sym(&s) {
+        return kicad_sym::parse(&s);
+    }
     parse(&s[..])
 }
 
@@ -1075,7 +1353,7 @@ impl CheckFix for Draw {
                     v.push(i)
                 }
             }
-            Draw::Other(_) => (),
+            Draw::Polyline(_) | Draw::Circle(_) | Draw::Arc(_) | Draw::Text(_) | Draw::Other(_) => (),
         }
         v
     }
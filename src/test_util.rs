@@ -0,0 +1,76 @@
+// (c) 2016-2017 Productize SPRL <joost@productize.be>
+
+// fixtures shared by the tests
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+// a legacy root sheet with resistor R1 and two instances, Left and Right, of
+// `sub.sch`
+const ROOT: &str = r##"EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R1
+U 1 1 5B000002
+P 1000 1000
+F 0 "R1" V 1080 1000 50  0000 C CNN
+F 1 "10k" V 1000 1000 50  0000 C CNN
+F 2 "Lib:R_0603" V 930 1000 50  0001 C CNN
+	1    1000 1000
+	1    0    0    -1
+$EndComp
+$Sheet
+S 2000 700 500 500
+U 5A000001
+F0 "Left" 60
+F1 "sub.sch" 60
+$EndSheet
+$Sheet
+S 3000 700 500 500
+U 5A000002
+F0 "Right" 60
+F1 "sub.sch" 60
+$EndSheet
+$EndSCHEMATC
+"##;
+
+// the sub-sheet with a resistor that is R2 in Left and R3 in Right
+const SUB: &str = r##"EESchema Schematic File Version 2
+EELAYER 25 0
+EELAYER END
+$Comp
+L R R?
+U 1 1 5B000001
+P 1000 1000
+AR Path="/5A000001/5B000001" Ref="R2"  Part="1"
+AR Path="/5A000002/5B000001" Ref="R3"  Part="1"
+F 0 "R?" V 1080 1000 50  0000 C CNN
+F 1 "10k" V 1000 1000 50  0000 C CNN
+F 2 "Lib:R_0603" V 930 1000 50  0001 C CNN
+	1    1000 1000
+	1    0    0    -1
+$EndComp
+$EndSCHEMATC
+"##;
+
+// a new empty directory for the test `name`, unique for every call
+pub fn temp_dir(name: &str) -> PathBuf {
+    let n = NEXT_DIR.fetch_add(1, Ordering::SeqCst);
+    let dir = env::temp_dir().join(format!("kicad_parse_gen_{}_{}_{}", name, process::id(), n));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// a directory with the legacy hierarchy `root.sch` and `sub.sch`
+pub fn legacy_hierarchy(name: &str) -> PathBuf {
+    let dir = temp_dir(name);
+    fs::write(dir.join("root.sch"), ROOT).unwrap();
+    fs::write(dir.join("sub.sch"), SUB).unwrap();
+    dir
+}
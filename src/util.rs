@@ -42,3 +42,38 @@ where
 
     Ok(())
 }
+
+/// union-find over indices
+pub(crate) struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    pub fn new(n: usize) -> DisjointSet {
+        DisjointSet {
+            parent: (0..n).collect(),
+        }
+    }
+
+    pub fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    pub fn join(&mut self, a: usize, b: usize) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.parent[a.max(b)] = a.min(b)
+        }
+    }
+}
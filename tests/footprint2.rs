@@ -22,8 +22,8 @@ fn parse_and_compare() {
     let s = kicad::footprint::module_to_string(&module, 0).unwrap();
 
     let changeset = Changeset::new(&content, &s, "\n");
-    if changeset.distance > 1 {
+    if changeset.distance > 0 {
         println!("{}", changeset);
-        assert_eq!(changeset.distance, 1);
+        assert_eq!(changeset.distance, 0);
     }
 }
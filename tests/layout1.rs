@@ -24,8 +24,8 @@ fn parse_and_compare() {
     kicad::write_file("/tmp/dump.kicad_pcb", &s).unwrap();
 
     let changeset = Changeset::new(&content, &s, "\n");
-    if changeset.distance > 1 {
+    if changeset.distance > 0 {
         println!("{}", changeset);
-        assert_eq!(changeset.distance, 1);
+        assert_eq!(changeset.distance, 0);
     }
 }